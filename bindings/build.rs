@@ -1,61 +1,259 @@
 fn main() {
     windows::build! {
-        Windows::Win32::{
+        Windows::{
             Foundation::{
-                CloseHandle,
-                HANDLE,
-                HINSTANCE,
-                HWND,
-                PSTR,
-                WPARAM,
-                LPARAM,
-                LRESULT
+                Collections::IVectorView,
+                IAsyncOperation
             },
-            System::{
-                Console::AttachConsole,
-                Com::{
-                    IPersistFile,
-                    CoInitializeEx,
-                    CoCreateInstance,
-                    CoTaskMemFree,
-                    COINIT,
-                    CLSCTX
-                },
-                DataExchange::{
-                    AddClipboardFormatListener,
-                    EnumClipboardFormats,
-                    GetClipboardData,
-                    GetClipboardFormatNameA,
-                    GetClipboardOwner,
-                    GetPriorityClipboardFormat,
-                    OpenClipboard,
-                    CloseClipboard
-                },
-                LibraryLoader::GetModuleHandleA,
-                Threading::{
-                    OpenProcess,
-                    PROCESS_ACCESS_RIGHTS
-                },
-                ProcessStatus::K32GetProcessImageFileNameA,
-                SystemServices::{CLIPBOARD_FORMATS, CHAR}
+            Graphics::Imaging::{
+                BitmapPixelFormat,
+                SoftwareBitmap
             },
-            Graphics::Gdi::{BITMAPINFO, BITMAPINFOHEADER, BI_BITFIELDS},
-            UI::Shell::{
-                IKnownFolder,
-                IKnownFolderManager,
-                IShellLinkA,
-                KnownFolderManager,
-                ShellLink,
-                ShellExecuteA,
-                Shell_NotifyIconA,
-                FOLDERID_Startup,
-                NOTIFYICONDATAA,
-                NOTIFY_ICON_DATA_FLAGS,
-                NOTIFYICON_VERSION_4,
-                NOTIFY_ICON_MESSAGE
+            Media::Ocr::{
+                OcrEngine,
+                OcrLine,
+                OcrResult
             },
-            UI::Controls::*,
-            UI::WindowsAndMessaging::*
+            Security::Cryptography::CryptographicBuffer,
+            Win32::{
+                Foundation::{
+                    CloseHandle,
+                    HANDLE,
+                    HINSTANCE,
+                    HWND,
+                    PSTR,
+                    PWSTR,
+                    SYSTEMTIME,
+                    WPARAM,
+                    LPARAM,
+                    LRESULT
+                },
+                Globalization::{
+                    GetDateFormatEx,
+                    ENUM_DATE_FORMATS_FLAGS
+                },
+                Networking::WinHttp::{
+                    WinHttpCloseHandle,
+                    WinHttpConnect,
+                    WinHttpCrackUrl,
+                    WinHttpOpen,
+                    WinHttpOpenRequest,
+                    WinHttpQueryHeaders,
+                    WinHttpReadData,
+                    WinHttpReceiveResponse,
+                    WinHttpSendRequest,
+                    URL_COMPONENTS,
+                    WINHTTP_ACCESS_TYPE,
+                    WINHTTP_OPEN_REQUEST_FLAGS,
+                    WINHTTP_QUERY_STATUS_CODE,
+                    WINHTTP_QUERY_FLAG_NUMBER
+                },
+                Security::{
+                    CryptProtectData,
+                    CryptUnprotectData,
+                    CRYPTPROTECT_UI_FORBIDDEN,
+                    Cryptography::Core::{
+                        BCryptDestroyKey,
+                        BCryptHash,
+                        BCryptImportKeyPair,
+                        BCryptVerifySignature,
+                        BCRYPT_ECCKEY_BLOB,
+                        BCRYPT_ECDSA_PUBLIC_P256_MAGIC,
+                        CryptBinaryToStringA,
+                        CryptStringToBinaryW,
+                        CRYPTOAPI_BLOB,
+                        CRYPT_STRING
+                    }
+                },
+                Storage::FileSystem::{
+                    FindChangeNotificationHandle,
+                    FindCloseChangeNotification,
+                    FindFirstChangeNotificationW,
+                    FindNextChangeNotification,
+                    GetFileTime,
+                    MoveFileExW,
+                    SetFileTime,
+                    FILE_NOTIFY_CHANGE,
+                    MOVE_FILE_FLAGS
+                },
+                System::{
+                    Console::{
+                        AllocConsole,
+                        AttachConsole,
+                        FreeConsole,
+                        GetConsoleWindow,
+                        SetConsoleTitleW
+                    },
+                    Com::{
+                        IPersistFile,
+                        CoInitializeEx,
+                        CoCreateInstance,
+                        CoTaskMemFree,
+                        COINIT,
+                        CLSCTX
+                    },
+                    DataExchange::{
+                        AddClipboardFormatListener,
+                        EnumClipboardFormats,
+                        GetClipboardData,
+                        GetClipboardFormatNameW,
+                        GetClipboardOwner,
+                        GetClipboardSequenceNumber,
+                        GetPriorityClipboardFormat,
+                        IsClipboardFormatAvailable,
+                        RegisterClipboardFormatW,
+                        RemoveClipboardFormatListener,
+                        OpenClipboard,
+                        CloseClipboard,
+                        EmptyClipboard,
+                        SetClipboardData,
+                        COPYDATASTRUCT
+                    },
+                    LibraryLoader::GetModuleHandleW,
+                    Memory::{
+                        GlobalAlloc,
+                        GlobalFree,
+                        GlobalLock,
+                        GlobalSize,
+                        GlobalUnlock,
+                        LocalFree,
+                        GLOBAL_ALLOC_FLAGS
+                    },
+                    Registry::{
+                        RegDeleteKeyValueW,
+                        RegDeleteTreeW,
+                        RegGetValueW,
+                        RegSetKeyValueW,
+                        HKEY,
+                        HKEY_CURRENT_USER,
+                        HKEY_LOCAL_MACHINE,
+                        RRF_RT,
+                        REG_VALUE_TYPE
+                    },
+                    RemoteDesktop::ProcessIdToSessionId,
+                    Threading::{
+                        CreateMutexW,
+                        GetCurrentProcessId,
+                        GetCurrentThreadId,
+                        OpenProcess,
+                        WaitForSingleObject,
+                        PROCESS_ACCESS_RIGHTS,
+                        WAIT_RETURN_CAUSE
+                    },
+                    ProcessStatus::K32GetProcessImageFileNameW,
+                    Shutdown::{
+                        ShutdownBlockReasonCreate,
+                        ShutdownBlockReasonDestroy
+                    },
+                    SystemInformation::{GetVersionExW, OSVERSIONINFOW},
+                    SystemServices::CLIPBOARD_FORMATS
+                },
+                Graphics::Gdi::{
+                    BeginPaint,
+                    BitBlt,
+                    CreateCompatibleBitmap,
+                    CreateCompatibleDC,
+                    CreateFontW,
+                    CreatePen,
+                    DeleteDC,
+                    DeleteObject,
+                    DrawFocusRect,
+                    DrawTextW,
+                    EndPaint,
+                    EnumDisplayMonitors,
+                    GetDC,
+                    GetDIBits,
+                    GetMonitorInfoW,
+                    GetSysColorBrush,
+                    InvalidateRect,
+                    LineTo,
+                    MonitorFromRect,
+                    MonitorFromWindow,
+                    MoveToEx,
+                    ReleaseDC,
+                    SelectObject,
+                    SetBkMode,
+                    SetStretchBltMode,
+                    SetTextColor,
+                    StretchDIBits,
+                    TextOutW,
+                    BITMAPINFO,
+                    BITMAPINFOHEADER,
+                    BI_BITFIELDS,
+                    BI_RGB,
+                    DIB_USAGE,
+                    DRAW_TEXT_FORMAT,
+                    HBRUSH,
+                    HMONITOR,
+                    MONITORINFO,
+                    MONITOR_FROM_FLAGS,
+                    PAINTSTRUCT,
+                    PEN_STYLE,
+                    ROP_CODE,
+                    STRETCH_BLT_MODE
+                },
+                UI::HiDpi::{
+                    GetDpiForMonitor,
+                    MONITOR_DPI_TYPE
+                },
+                UI::Accessibility::{
+                    CUIAutomation,
+                    IUIAutomation,
+                    IUIAutomationElement,
+                    IUIAutomationElementArray,
+                    IUIAutomationCondition,
+                    IUIAutomationValuePattern,
+                    UIA_EditControlTypeId,
+                    UIA_ValuePatternId,
+                    TreeScope
+                },
+                UI::KeyboardAndMouseInput::{
+                    EnableWindow,
+                    RegisterHotKey,
+                    ReleaseCapture,
+                    SetCapture,
+                    UnregisterHotKey,
+                    HOT_KEY_MODIFIERS
+                },
+                UI::Shell::{
+                    DragFinish,
+                    DragQueryFileW,
+                    DROPFILES,
+                    HDROP,
+                    ILCreateFromPathW,
+                    ILFree,
+                    SHOpenFolderAndSelectItems,
+                    IKnownFolder,
+                    IKnownFolderManager,
+                    IShellLinkW,
+                    KnownFolderManager,
+                    ShellLink,
+                    ShellExecuteW,
+                    SHFileOperationW,
+                    SHFILEOPSTRUCTW,
+                    FOF_ALLOWUNDO,
+                    FOF_NOCONFIRMATION,
+                    FOF_NOERRORUI,
+                    FOF_SILENT,
+                    FO_DELETE,
+                    SHQueryUserNotificationState,
+                    QUERY_USER_NOTIFICATION_STATE,
+                    Shell_NotifyIconW,
+                    FOLDERID_Startup,
+                    NOTIFYICONDATAW,
+                    NOTIFY_ICON_DATA_FLAGS,
+                    NOTIFYICON_VERSION_4,
+                    NIIF_ERROR,
+                    NIIF_INFO,
+                    NIIF_WARNING,
+                    NINF_KEY,
+                    NIN_BALLOONUSERCLICK,
+                    NIN_SELECT,
+                    NOTIFY_ICON_MESSAGE
+                },
+                UI::Controls::*,
+                UI::WindowsAndMessaging::*
+            }
         }
     };
 }
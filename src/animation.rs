@@ -0,0 +1,100 @@
+//! Animated image passthrough.
+//!
+//! Saving an animated GIF or APNG through the normal pipeline would flatten it
+//! into its first frame. Instead, when the clipboard (or a dropped file)
+//! contains an animated image, its original bytes are saved as they are, and
+//! the first frame is only used for things like thumbnails.
+
+use crate::windows::{get_clipboard_bytes, Clipboard};
+use image::codecs::gif::GifDecoder;
+use image::AnimationDecoder;
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+/// The registered clipboard formats that may contain an animated image, in
+/// order of preference.
+const CLIPBOARD_FORMATS: [&str; 3] = ["GIF", "image/gif", "PNG"];
+
+/// The file formats of animated images.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+impl AnimationFormat {
+    /// The file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "png",
+        }
+    }
+}
+
+/// An encoded animated image.
+pub struct Animation {
+    pub format: AnimationFormat,
+    pub data: Vec<u8>,
+}
+
+impl Animation {
+    /// Returns the animation encoded in `data`, or `None` if `data` isn't an
+    /// animated GIF or APNG, with more than one frame.
+    pub fn detect(data: Vec<u8>) -> Option<Self> {
+        let format = if data.starts_with(b"GIF8") && gif_is_animated(&data) {
+            AnimationFormat::Gif
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") && png_is_animated(&data) {
+            AnimationFormat::Apng
+        } else {
+            return None;
+        };
+
+        Some(Self { format, data })
+    }
+}
+
+/// Returns the animated image on the clipboard, if there is one.
+pub fn read_clipboard(clipboard: &Clipboard) -> Option<Animation> {
+    CLIPBOARD_FORMATS
+        .iter()
+        .filter_map(|format| get_clipboard_bytes(clipboard, format))
+        .find_map(Animation::detect)
+}
+
+/// Returns whether or not a GIF has more than one frame.
+fn gif_is_animated(data: &[u8]) -> bool {
+    match GifDecoder::new(data) {
+        Ok(decoder) => decoder.into_frames().take(2).count() > 1,
+        Err(_) => false,
+    }
+}
+
+/// Returns whether or not a PNG has an animation control chunk, declaring more
+/// than one frame, before its image data.
+fn png_is_animated(data: &[u8]) -> bool {
+    let mut position = 8;
+
+    while position + 8 <= data.len() {
+        let length = match data[position..position + 4].try_into() {
+            Ok(length) => u32::from_be_bytes(length) as usize,
+            Err(_) => return false,
+        };
+
+        match &data[position + 4..position + 8] {
+            b"acTL" => {
+                let frames = data
+                    .get(position + 8..position + 12)
+                    .and_then(|frames| frames.try_into().ok());
+
+                return matches!(frames, Some(frames) if u32::from_be_bytes(frames) > 1);
+            }
+            b"IDAT" | b"IEND" => return false,
+            // Data, then CRC
+            _ => position += 8 + length + 4,
+        }
+    }
+
+    false
+}
@@ -0,0 +1,130 @@
+//! Starting the program automatically when the user logs in.
+//!
+//! Auto-start is registered either with a value in the user's `Run` registry
+//! key, or with a shortcut in their Startup folder, as chosen by
+//! [`auto_start_method`]. Corporate policies often block the Startup folder, so
+//! the `Run` key is used by default.
+//!
+//! The registration is refreshed each time the program starts, so that it
+//! follows the executable if it is moved, and so that a registration made with
+//! the other method (e.g. a shortcut made by an older version) is migrated.
+//!
+//! [`auto_start_method`]: crate::settings::Program::auto_start_method
+
+use crate::settings::{self, AutoStartMethod, Settings};
+use crate::windows::{
+    create_link, delete_user_registry_value, get_known_folder_path, get_registry_string,
+    set_user_registry_string,
+};
+use bindings::Windows::Win32::{System::Registry::HKEY_CURRENT_USER, UI::Shell::FOLDERID_Startup};
+use log::{info, warn};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// The user's `Run` registry key.
+const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
+/// The name of the program's value in the `Run` key.
+const RUN_VALUE: &str = "Snip & AutoSave";
+
+/// The name of the program's shortcut in the Startup folder.
+const SHORTCUT_NAME: &str = "Snip & AutoSave.lnk";
+
+/// Registers the program to start automatically, with `method`, removing any
+/// registration made with the other method.
+pub fn enable(method: AutoStartMethod) -> windows::Result<()> {
+    match method {
+        AutoStartMethod::Registry => {
+            set_user_registry_string(RUN_KEY, Some(RUN_VALUE), &command())?;
+            remove_shortcut();
+        }
+        AutoStartMethod::Shortcut => {
+            create_link(
+                &shortcut_path()?,
+                &exe_path(),
+                settings::portable_flag().unwrap_or_default(),
+            )?;
+            remove_run_value();
+        }
+    }
+
+    Ok(())
+}
+
+/// Stops the program from starting automatically, however it was registered.
+pub fn disable() {
+    remove_run_value();
+    remove_shortcut();
+}
+
+/// Refreshes the auto-start registration, if auto-start is enabled, migrating
+/// it to the configured method, and updating the path to the executable.
+pub fn refresh() {
+    let mut auto_start = false;
+    let mut method = AutoStartMethod::Registry;
+
+    Settings::read(|s| {
+        auto_start = s.program.auto_start;
+        method = s.program.auto_start_method;
+    });
+
+    if !auto_start {
+        return;
+    }
+
+    let has_shortcut = matches!(shortcut_path(), Ok(path) if path.exists());
+    let run_value = get_registry_string(HKEY_CURRENT_USER, RUN_KEY, RUN_VALUE);
+
+    let up_to_date = match method {
+        AutoStartMethod::Registry => !has_shortcut && run_value == Some(command()),
+        AutoStartMethod::Shortcut => has_shortcut && run_value.is_none(),
+    };
+
+    if up_to_date {
+        return;
+    }
+
+    match enable(method) {
+        Ok(()) => info!("Updated auto-start registration"),
+        Err(e) => warn!("Failed to update auto-start registration: {:#?}", e),
+    }
+}
+
+/// Returns the path to the running executable.
+fn exe_path() -> PathBuf {
+    dunce::simplified(&env::current_exe().unwrap()).to_path_buf()
+}
+
+/// Returns the command line that is written to the `Run` key.
+fn command() -> String {
+    match settings::portable_flag() {
+        Some(flag) => format!("\"{}\" {}", exe_path().display(), flag),
+        None => format!("\"{}\"", exe_path().display()),
+    }
+}
+
+/// Returns the path of the program's shortcut in the Startup folder.
+fn shortcut_path() -> windows::Result<PathBuf> {
+    Ok(get_known_folder_path(FOLDERID_Startup)?.join(SHORTCUT_NAME))
+}
+
+/// Removes the program's value from the `Run` key, if it exists.
+fn remove_run_value() {
+    if get_registry_string(HKEY_CURRENT_USER, RUN_KEY, RUN_VALUE).is_some() {
+        if let Err(e) = delete_user_registry_value(RUN_KEY, RUN_VALUE) {
+            warn!("Failed to remove auto-start registry value: {:#?}", e);
+        }
+    }
+}
+
+/// Removes the program's shortcut from the Startup folder, if it exists.
+fn remove_shortcut() {
+    if let Ok(path) = shortcut_path() {
+        if path.exists() {
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Failed to remove auto-start shortcut: {:#?}", e);
+            }
+        }
+    }
+}
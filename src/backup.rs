@@ -0,0 +1,139 @@
+//! Backing up and restoring the program's state, e.g. when moving to a new
+//! machine.
+//!
+//! A backup is a zip file containing the settings file, the history (which
+//! holds the hashes that duplicate detection and statistics rely on), and the
+//! history's thumbnails. The archive encryption key isn't included, as it is
+//! protected with DPAPI, so can only be unwrapped by the same Windows user.
+
+use crate::history::{self, HISTORY_FILE, THUMBNAIL_DIR};
+use crate::ipc;
+use crate::settings::{self, SETTINGS_FILE};
+use crate::zip::{self, Entry, ZipError};
+use log::info;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+/// The name of the file within the config directory that the existing state is
+/// backed up to, before a backup is restored over it.
+const PRE_RESTORE_BACKUP: &str = "pre-restore-backup.zip";
+
+/// The files within the config directory that are backed up, along with every
+/// file in [`THUMBNAIL_DIR`].
+///
+/// [`THUMBNAIL_DIR`]: THUMBNAIL_DIR
+const STATE_FILES: [&str; 2] = [SETTINGS_FILE, HISTORY_FILE];
+
+/// Errors that can occur whilst backing up or restoring the program's state.
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("Unable to read or write the program's state: {0}")]
+    Io(#[from] io::Error),
+    #[error("Unable to read or write the backup: {0}")]
+    Zip(#[from] ZipError),
+    #[error("The backup contains an unexpected file: {0}")]
+    UnexpectedFile(String),
+    #[error("The program is running - exit it before restoring a backup")]
+    Running,
+}
+
+/// Writes the program's state to a backup at `path`, returning how many files
+/// it contains.
+pub fn backup(path: &Path) -> Result<usize, BackupError> {
+    let config_dir = settings::config_dir();
+    let mut entries = Vec::new();
+
+    let mut add = |name: String, file: &Path| -> io::Result<()> {
+        entries.push(Entry {
+            name,
+            data: fs::read(file)?,
+            modified: fs::metadata(file)?.modified()?.into(),
+        });
+
+        Ok(())
+    };
+
+    for name in STATE_FILES.iter() {
+        let file = config_dir.join(name);
+
+        if file.is_file() {
+            add(name.to_string(), &file)?;
+        }
+    }
+
+    if let Ok(thumbnails) = fs::read_dir(config_dir.join(THUMBNAIL_DIR)) {
+        for thumbnail in thumbnails {
+            let thumbnail = thumbnail?;
+
+            add(
+                format!(
+                    "{}/{}",
+                    THUMBNAIL_DIR,
+                    thumbnail.file_name().to_string_lossy()
+                ),
+                &thumbnail.path(),
+            )?;
+        }
+    }
+
+    zip::write(path, &entries)?;
+
+    info!("Backed up {} files to {}", entries.len(), path.display());
+
+    Ok(entries.len())
+}
+
+/// Replaces the program's state with the backup at `path`, after backing up
+/// the existing state to [`PRE_RESTORE_BACKUP`], returning how many files were
+/// restored.
+///
+/// [`PRE_RESTORE_BACKUP`]: PRE_RESTORE_BACKUP
+pub fn restore(path: &Path) -> Result<usize, BackupError> {
+    // The running instance would overwrite the restored settings and history
+    if ipc::is_running() {
+        return Err(BackupError::Running);
+    }
+
+    let entries = zip::read(path)?;
+
+    // Nothing is restored unless every file is one that a backup contains
+    if let Some((name, _)) = entries.iter().find(|(name, _)| !is_state_file(name)) {
+        return Err(BackupError::UnexpectedFile(name.clone()));
+    }
+
+    let config_dir = settings::config_dir();
+    fs::create_dir_all(config_dir.join(THUMBNAIL_DIR))?;
+
+    backup(&config_dir.join(PRE_RESTORE_BACKUP))?;
+
+    for (name, data) in &entries {
+        fs::write(config_dir.join(name), data)?;
+    }
+
+    // Thumbnail paths in the history are absolute, so must be moved from the
+    // backed up machine's config directory to this one's
+    history::relocate_thumbnails();
+
+    info!("Restored {} files from {}", entries.len(), path.display());
+
+    Ok(entries.len())
+}
+
+/// Returns whether or not `name` is the name of a file that a backup contains.
+fn is_state_file(name: &str) -> bool {
+    if STATE_FILES.contains(&name) {
+        return true;
+    }
+
+    match name.split_once('/') {
+        Some((dir, file)) => {
+            dir == THUMBNAIL_DIR
+                && !file.is_empty()
+                && !file.contains(&['/', '\\', ':'][..])
+                && file != ".."
+        }
+        None => false,
+    }
+}
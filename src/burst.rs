@@ -0,0 +1,97 @@
+//! Collapsing of bursts of near-identical screenshots, e.g. when the same
+//! window is snipped again and again whilst trying to catch a tooltip, so that
+//! only the last screenshot of the burst is kept.
+//!
+//! Unlike duplicate detection, which skips a new screenshot, collapsing keeps
+//! the new screenshot, and moves the previous one to the trash, as the last
+//! attempt is usually the one that was wanted.
+
+use crate::perceptual_hash::PerceptualHash;
+use crate::save;
+use crate::settings::Settings;
+use crate::trash;
+use crate::windows::get_foreground_window;
+use image::RgbImage;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The number of bits (out of 256) that the perceptual hashes of two
+/// screenshots in a burst may differ by, i.e. under 1%.
+const MAX_DISTANCE: u32 = 2;
+
+/// The last saved screenshot, which the next one may collapse.
+struct LastSave {
+    /// Where the screenshot was saved to.
+    path: PathBuf,
+
+    /// The foreground window when the screenshot was saved.
+    window: isize,
+
+    /// When the screenshot was saved.
+    saved_at: Instant,
+
+    /// The perceptual hash of the screenshot.
+    perceptual_hash: PerceptualHash,
+}
+
+lazy_static! {
+    /// The last saved screenshot, if bursts are being collapsed.
+    static ref LAST_SAVE: Mutex<Option<LastSave>> = Mutex::new(None);
+}
+
+/// Records `image`, which was just saved to `path`, moving the screenshot
+/// saved before it to the trash, if both are part of the same burst.
+pub fn collapse(path: &Path, image: &RgbImage) {
+    let mut enabled = false;
+    let mut burst_length = Duration::default();
+
+    Settings::read(|s| {
+        enabled = s.dedup.collapse_bursts;
+        burst_length = Duration::from_secs(s.dedup.burst_seconds);
+    });
+
+    let mut last_save = LAST_SAVE.lock().unwrap();
+
+    if !enabled {
+        last_save.take();
+        return;
+    }
+
+    let window = get_foreground_window().0;
+    let perceptual_hash = PerceptualHash::of(image);
+
+    let previous = last_save.replace(LastSave {
+        path: path.to_owned(),
+        window,
+        saved_at: Instant::now(),
+        perceptual_hash,
+    });
+
+    if let Some(previous) = previous {
+        let is_burst = previous.window == window
+            && previous.saved_at.elapsed() <= burst_length
+            && previous.perceptual_hash.distance(&perceptual_hash) <= MAX_DISTANCE
+            // Encrypted screenshots are all saved to the same archive, which can't be trashed
+            && previous.path != path;
+
+        if is_burst {
+            info!(
+                "{} is a retry of {} - moving the earlier screenshot to the trash",
+                path.display(),
+                previous.path.display()
+            );
+
+            for file in save::existing_copies(&previous.path)
+                .iter()
+                .chain(Some(&previous.path))
+            {
+                if let Err(e) = trash::discard(file) {
+                    warn!("Failed to move {} to the trash: {:#?}", file.display(), e);
+                }
+            }
+        }
+    }
+}
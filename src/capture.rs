@@ -0,0 +1,395 @@
+//! The capture worker thread.
+//!
+//! Reading the clipboard, converting its image data, and saving it can all
+//! take a while, so none of it is done on the window message loop thread,
+//! which would make the notification area icon unresponsive. Instead,
+//! `WM_CLIPBOARDUPDATE` messages are forwarded to a dedicated worker thread,
+//! which handles each clipboard update in turn, then hands each screenshot to
+//! the [`save_queue`] to be saved.
+//!
+//! Failures to read a screenshot are published as [`Event::Failed`], so that
+//! the user is told why it wasn't saved. As they are usually caused by another
+//! program holding the clipboard open, reading is retried once, shortly after.
+//!
+//! [`Event::Failed`]: Event::Failed
+//! [`save_queue`]: crate::save_queue
+
+use crate::animation::{self, Animation};
+use crate::clipboard_history;
+use crate::colour_picker;
+use crate::convert::{dib_to_image, ConversionError};
+use crate::crop;
+use crate::detection_test;
+use crate::events::{self, Event, FailureKind, SkipReason};
+use crate::heuristics::{
+    clipboard_has_image, clipboard_has_remote_snip, clipboard_owned_by_snip_and_sketch,
+    clipboard_source, CaptureSource,
+};
+use crate::metrics;
+use crate::notification_area::{self, BalloonLevel};
+use crate::region;
+use crate::save::{self, Snapshot};
+use crate::session;
+use crate::settings::{RemoteSessionAction, Settings};
+use crate::windows::{
+    com_initialize, get_clipboard_dib, get_foreground_window, get_window_thread_and_process_id,
+    open_clipboard, Clipboard,
+};
+use bindings::Windows::Win32::System::Com::COINIT_MULTITHREADED;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use image::RgbImage;
+use lazy_static::lazy_static;
+use log::{debug, error, info, warn};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::{process, thread};
+use thiserror::Error;
+
+/// How long to wait before retrying a failed capture.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Errors that can occur whilst reading a screenshot from the clipboard.
+#[derive(Error, Debug)]
+pub enum CaptureError {
+    #[error("Unable to open the clipboard: {0}")]
+    OpenClipboard(#[source] windows::Error),
+    #[error("Unable to read the screenshot from the clipboard: {0}")]
+    ReadClipboard(#[source] windows::Error),
+    #[error("Unable to convert the screenshot: {0}")]
+    Convert(#[from] ConversionError),
+}
+
+impl CaptureError {
+    /// Returns whether or not the capture might succeed if it is retried, i.e.
+    /// whether the error was caused by the clipboard, rather than its contents.
+    fn is_transient(&self) -> bool {
+        matches!(self, Self::OpenClipboard(_) | Self::ReadClipboard(_))
+    }
+
+    /// Returns the stage of the capture pipeline that failed.
+    fn kind(&self) -> FailureKind {
+        match self {
+            Self::OpenClipboard(_) | Self::ReadClipboard(_) => FailureKind::Clipboard,
+            Self::Convert(_) => FailureKind::Conversion,
+        }
+    }
+}
+
+/// A request for the worker thread.
+enum Job {
+    /// The clipboard contents changed.
+    ClipboardUpdate,
+
+    /// The user asked for the clipboard image to be saved, whatever it is.
+    SaveNow,
+}
+
+lazy_static! {
+    /// The sending half of the worker thread's job channel, once it has been
+    /// started.
+    static ref JOBS: Mutex<Option<Sender<Job>>> = Mutex::new(None);
+
+    /// The worker thread, once it has been started.
+    static ref WORKER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+}
+
+/// Starts the worker thread. Clipboard updates are ignored until this is
+/// called.
+pub fn start() {
+    let (sender, receiver) = unbounded();
+    *JOBS.lock().unwrap() = Some(sender);
+
+    let worker = thread::Builder::new()
+        .name("capture".into())
+        .spawn(move || run(receiver))
+        .expect("Failed to start capture worker thread");
+
+    *WORKER.lock().unwrap() = Some(worker);
+}
+
+/// Stops the worker thread, once it has finished the jobs that are already
+/// queued. Clipboard updates are ignored after this is called.
+pub fn stop() {
+    JOBS.lock().unwrap().take();
+
+    if let Some(worker) = WORKER.lock().unwrap().take() {
+        if worker.join().is_err() {
+            warn!("Capture worker thread panicked");
+        }
+    }
+}
+
+/// Queues a clipboard update for the worker thread. This returns immediately.
+pub fn on_clipboard_update() {
+    queue(Job::ClipboardUpdate);
+}
+
+/// Queues the image on the clipboard to be saved, bypassing the heuristics, so
+/// that images that weren't recognised as screenshots can still be saved. This
+/// returns immediately.
+pub fn save_now() {
+    queue(Job::SaveNow);
+}
+
+/// Sends `job` to the worker thread.
+fn queue(job: Job) {
+    match &*JOBS.lock().unwrap() {
+        Some(jobs) => {
+            let _ = jobs.send(job);
+        }
+        None => warn!("Capture worker thread isn't running - job ignored"),
+    }
+}
+
+/// Returns the number of jobs waiting for the worker thread.
+pub fn queue_depth() -> usize {
+    JOBS.lock().unwrap().as_ref().map_or(0, |jobs| jobs.len())
+}
+
+/// The worker thread's main loop, which exits when the job channel closes.
+fn run(jobs: Receiver<Job>) {
+    // Needed to read browser address bars with UI Automation
+    if let Err(e) = com_initialize(COINIT_MULTITHREADED) {
+        warn!(
+            "Failed to initialise COM on the capture worker thread: {:#?}",
+            e
+        );
+    }
+
+    for job in jobs {
+        match job {
+            Job::ClipboardUpdate => process_clipboard_update(),
+            Job::SaveNow => save_clipboard_now(),
+        }
+    }
+}
+
+/// Returns whether or not the clipboard contains an image that should be saved,
+/// i.e. a Snip & Sketch screenshot (taken on this machine, or on the `source`
+/// machine), or any image, if the user has chosen to save all clipboard images.
+fn is_capture(clipboard: &Clipboard, source: CaptureSource) -> bool {
+    let mut save_all_clipboard_images = false;
+    Settings::read(|s| save_all_clipboard_images = s.program.save_all_clipboard_images);
+
+    if save_all_clipboard_images && clipboard_has_image(clipboard) {
+        info!("Clipboard contains an image - saving it to disk");
+        return true;
+    }
+
+    // Redirected data is owned by the redirecting process, so the allowlist doesn't apply to it
+    if source == CaptureSource::Remote {
+        if clipboard_has_remote_snip(clipboard) {
+            info!("Clipboard contains a screenshot from a remote machine - saving it to disk");
+            return true;
+        }
+
+        return false;
+    }
+
+    if clipboard_owned_by_snip_and_sketch(clipboard).unwrap_or_else(|e| {
+        warn!("Heuristics failed: {:#?}", e);
+        false
+    }) {
+        info!("Clipboard is owned by Snip & Sketch - saving screenshot to disk");
+        return true;
+    }
+
+    false
+}
+
+/// Returns whether or not the foreground window belongs to this process (e.g.
+/// the folder picker, or another dialog).
+///
+/// Screenshots of the program's own windows are very likely to have been taken
+/// whilst configuring it, and the folder picker's preview pane can put images
+/// on the clipboard, so neither should be saved.
+fn own_window_is_foreground() -> bool {
+    let (process_id, _) = get_window_thread_and_process_id(get_foreground_window());
+
+    process_id == process::id()
+}
+
+/// Reads a screenshot from the clipboard, if it contains one, and saves it.
+fn process_clipboard_update() {
+    // Give the Snip & Sketch screenshot overlay a chance to
+    // disappear before we block the clipboard to copy image data
+    thread::sleep(Duration::from_millis(100));
+
+    if clipboard_history::is_unchanged() {
+        debug!("Clipboard sequence number unchanged - update ignored");
+        metrics::record_unchanged_clipboard();
+        return;
+    }
+
+    let mut retry = false;
+    let mut dedup = true;
+
+    Settings::read(|s| {
+        retry = s.program.retry_failed_captures;
+        dedup = s.dedup.enabled;
+    });
+
+    let image = match read_capture() {
+        Err(e) if retry && e.is_transient() => {
+            warn!("{} - retrying", e);
+            thread::sleep(RETRY_DELAY);
+
+            read_capture()
+        }
+        result => result,
+    };
+
+    let (image, animation, source) = match image {
+        Ok(Some(capture)) => capture,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Failed to capture screenshot: {}", e);
+            events::publish(Event::Failed {
+                kind: e.kind(),
+                error: e.to_string(),
+            });
+
+            return;
+        }
+    };
+
+    // With deduplication disabled, repeated identical snips are all kept, and
+    // can't be told apart from re-announcements by their content
+    if dedup && clipboard_history::is_reannouncement(&image) {
+        info!("Screenshot was re-announced by clipboard history - ignoring");
+        events::publish(Event::Skipped {
+            reason: SkipReason::Reannouncement,
+        });
+
+        return;
+    }
+
+    if colour_picker::take_armed() {
+        colour_picker::pick(&image);
+        return;
+    }
+
+    // The foreground window and the time are recorded before anything else can
+    // take a while, or cover the screen (e.g. the quick crop window)
+    let snapshot = Snapshot::take();
+
+    // The region is found first, as the quick crop window may cover it
+    let region = region::locate(&image);
+
+    let (image, region) = match animation {
+        None if crop::is_enabled() => {
+            let (width, height) = image.dimensions();
+
+            match crop::crop(image) {
+                Some(cropped) if cropped.dimensions() == (width, height) => (cropped, region),
+                // The cropped screenshot can't be snipped again from the region it was found in
+                Some(cropped) => (cropped, None),
+                None => return,
+            }
+        }
+        _ => (image, region),
+    };
+
+    match animation {
+        Some(animation) => save::archive_animation(image, region, source, animation, snapshot),
+        None => save::archive(image, region, source, snapshot),
+    }
+}
+
+/// Reads the screenshot on the clipboard, along with the original animation, if
+/// it is animated, and where it came from. Returns `None` if the clipboard
+/// doesn't contain a screenshot, if saving is paused (including in Remote
+/// Desktop sessions), or if one of our own windows is in the foreground.
+fn read_capture() -> Result<Option<(RgbImage, Option<Animation>, CaptureSource)>, CaptureError> {
+    let clipboard = open_clipboard(None).map_err(CaptureError::OpenClipboard)?;
+
+    detection_test::on_clipboard_update(&clipboard);
+
+    let source = clipboard_source();
+
+    if !is_capture(&clipboard, source) {
+        debug!("Clipboard does not contain a capture");
+        metrics::record_heuristic_rejection();
+        return Ok(None);
+    }
+
+    events::publish(Event::CaptureDetected);
+
+    let mut paused = false;
+    Settings::read(|s| paused = s.program.paused);
+
+    if paused {
+        info!("Saving is paused - ignoring");
+        events::publish(Event::Skipped {
+            reason: SkipReason::Paused,
+        });
+
+        return Ok(None);
+    }
+
+    if session::remote_action() == RemoteSessionAction::Pause {
+        info!("Saving is paused in Remote Desktop sessions - ignoring");
+        events::publish(Event::Skipped {
+            reason: SkipReason::RemoteSession,
+        });
+
+        return Ok(None);
+    }
+
+    if own_window_is_foreground() {
+        info!("One of our own windows is in the foreground - ignoring");
+        events::publish(Event::Skipped {
+            reason: SkipReason::OwnWindow,
+        });
+
+        return Ok(None);
+    }
+
+    let image = read_image(&clipboard)?;
+
+    Ok(Some((image, animation::read_clipboard(&clipboard), source)))
+}
+
+/// Saves the image on the clipboard, regardless of which program put it there,
+/// or whether saving is paused.
+fn save_clipboard_now() {
+    let image = open_clipboard(None)
+        .map_err(CaptureError::OpenClipboard)
+        .and_then(|clipboard| {
+            if clipboard_has_image(&clipboard) {
+                read_image(&clipboard).map(Some)
+            } else {
+                Ok(None)
+            }
+        });
+
+    match image {
+        Ok(Some(image)) => {
+            info!("Saving clipboard image on request");
+            events::publish(Event::CaptureDetected);
+            save::archive(image, None, clipboard_source(), Snapshot::take());
+        }
+        Ok(None) => {
+            let _ = notification_area::show_balloon(
+                "Nothing to save",
+                "The clipboard doesn't contain an image",
+                BalloonLevel::Info,
+            );
+        }
+        Err(e) => {
+            error!("Failed to save clipboard image: {}", e);
+            events::publish(Event::Failed {
+                kind: e.kind(),
+                error: e.to_string(),
+            });
+        }
+    }
+}
+
+/// Reads the image on the clipboard.
+fn read_image(clipboard: &Clipboard) -> Result<RgbImage, CaptureError> {
+    let bitmap = get_clipboard_dib(clipboard).map_err(CaptureError::ReadClipboard)?;
+
+    Ok(dib_to_image(bitmap, clipboard)?)
+}
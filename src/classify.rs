@@ -0,0 +1,180 @@
+//! Lightweight classification of screenshot content.
+//!
+//! Each screenshot is tagged with a [`Category`], using cheap colour histogram
+//! and edge density heuristics over a sample of its pixels, rather than any
+//! kind of machine learning. The category is available as the `{category}`
+//! filename placeholder, and can route screenshots into per-category
+//! subdirectories (see [`Routing`]).
+//!
+//! [`Category`]: Category
+//! [`Routing`]: crate::settings::Routing
+
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The approximate number of pixels sampled along each axis.
+const SAMPLES_PER_AXIS: u32 = 200;
+
+/// The luminance difference between neighbouring pixels that counts as an edge.
+const EDGE_THRESHOLD: f32 = 48.0;
+
+/// The kind of content that a screenshot shows.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// Application windows, dialogs, web pages, etc.
+    Ui,
+
+    /// Source code, or a terminal, usually with syntax highlighting.
+    Code,
+
+    /// Photographs, video frames, and other natural images.
+    Photo,
+
+    /// Mostly text on a plain, light background.
+    Document,
+}
+
+impl Category {
+    /// A short name for the category, as used in filenames.
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::Ui => "ui",
+            Category::Code => "code",
+            Category::Photo => "photo",
+            Category::Document => "document",
+        }
+    }
+}
+
+/// Measurements of a sample of a screenshot's pixels.
+struct Features {
+    /// The fraction of pixels that are (roughly) the most common colour.
+    background_fraction: f32,
+
+    /// The luminance (from 0 to 255) of the most common colour.
+    background_luminance: f32,
+
+    /// The number of distinct (quantised) colours, relative to the number of
+    /// sampled pixels.
+    colour_diversity: f32,
+
+    /// The fraction of non-background pixels that are strongly saturated.
+    saturated_ink_fraction: f32,
+
+    /// The fraction of pixels that differ sharply from their right neighbour.
+    edge_density: f32,
+}
+
+/// Classifies the content of `image`.
+pub fn classify(image: &RgbImage) -> Category {
+    let features = measure(image);
+
+    if features.colour_diversity > 0.25 && features.background_fraction < 0.2 {
+        Category::Photo
+    } else if features.background_fraction > 0.4 && features.background_luminance < 80.0 {
+        // Dark themed editors and terminals
+        Category::Code
+    } else if features.background_fraction > 0.6
+        && features.background_luminance > 200.0
+        && features.edge_density > 0.02
+    {
+        if features.saturated_ink_fraction > 0.15 {
+            Category::Code
+        } else {
+            Category::Document
+        }
+    } else {
+        Category::Ui
+    }
+}
+
+/// Measures the features of a grid of pixels sampled from `image`.
+fn measure(image: &RgbImage) -> Features {
+    let step_x = (image.width() / SAMPLES_PER_AXIS).max(1);
+    let step_y = (image.height() / SAMPLES_PER_AXIS).max(1);
+
+    let mut histogram: HashMap<(u8, u8, u8), u32> = HashMap::new();
+    let mut samples = 0u32;
+    let mut edges = 0u32;
+
+    for y in (0..image.height()).step_by(step_y as usize) {
+        for x in (0..image.width()).step_by(step_x as usize) {
+            let pixel = image.get_pixel(x, y);
+
+            *histogram.entry(quantise(pixel)).or_default() += 1;
+            samples += 1;
+
+            if x + 1 < image.width()
+                && (luminance(pixel) - luminance(image.get_pixel(x + 1, y))).abs() > EDGE_THRESHOLD
+            {
+                edges += 1;
+            }
+        }
+    }
+
+    if samples == 0 {
+        return Features {
+            background_fraction: 1.0,
+            background_luminance: 255.0,
+            colour_diversity: 0.0,
+            saturated_ink_fraction: 0.0,
+            edge_density: 0.0,
+        };
+    }
+
+    let (background, background_count) = histogram
+        .iter()
+        .max_by_key(|(_, count)| **count)
+        .map(|(colour, count)| (*colour, *count))
+        .unwrap();
+
+    let (saturated, ink) = histogram
+        .iter()
+        .filter(|(colour, _)| **colour != background)
+        .fold((0, 0), |(saturated, ink), (colour, count)| {
+            if saturation(*colour) > 0.4 {
+                (saturated + count, ink + count)
+            } else {
+                (saturated, ink + count)
+            }
+        });
+
+    let (r, g, b) = background;
+
+    Features {
+        background_fraction: background_count as f32 / samples as f32,
+        background_luminance: luminance(&Rgb([r << 3, g << 3, b << 3])),
+        colour_diversity: histogram.len() as f32 / samples as f32,
+        saturated_ink_fraction: if ink == 0 {
+            0.0
+        } else {
+            saturated as f32 / ink as f32
+        },
+        edge_density: edges as f32 / samples as f32,
+    }
+}
+
+/// Reduces `pixel` to 5 bits per channel, so that near-identical colours (e.g.
+/// from anti-aliasing or JPEG artifacts) are counted together.
+fn quantise(pixel: &Rgb<u8>) -> (u8, u8, u8) {
+    (pixel[0] >> 3, pixel[1] >> 3, pixel[2] >> 3)
+}
+
+/// The perceived brightness of `pixel`, from 0 to 255.
+fn luminance(pixel: &Rgb<u8>) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+/// The HSV saturation, from 0 to 1, of a quantised colour.
+fn saturation((r, g, b): (u8, u8, u8)) -> f32 {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+
+    if max == 0 {
+        0.0
+    } else {
+        (max - min) as f32 / max as f32
+    }
+}
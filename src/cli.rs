@@ -0,0 +1,485 @@
+//! Command line interface.
+//!
+//! When the program is started with a subcommand, the subcommand is run, and
+//! the program exits, instead of starting the clipboard listener. The program
+//! exits with an [`ExitCode`], so that scripts can tell why a command failed.
+//!
+//! [`ExitCode`]: ExitCode
+
+use crate::backup::{self, BackupError};
+use crate::contact_sheet;
+use crate::encryption;
+use crate::history_viewer;
+use crate::ipc::{self, IpcCommand, IpcError};
+use crate::migration;
+use crate::reencode;
+use crate::save;
+use crate::scan;
+use crate::settings::{self, Output, Settings};
+use crate::stats;
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+
+/// Usage information, printed when the command line can't be parsed.
+pub const USAGE: &str = "\
+Usage:
+    snip-and-autosave.exe [--portable] [command]
+
+    snip-and-autosave.exe
+        Runs the program in the background, saving screenshots as they are captured.
+
+    snip-and-autosave.exe backup <archive>
+        Saves the settings, history and thumbnails to a zip file, e.g. to move them to a new
+        machine.
+
+    snip-and-autosave.exe contact-sheet <date> [end date] <output file>
+        Lays out the screenshots saved on a date (YYYY-MM-DD), or from the date until the end
+        date, in a single image, or a PDF, if the output file ends in .pdf.
+
+    snip-and-autosave.exe decrypt <archive> <output directory>
+        Decrypts every screenshot in an encrypted archive into a directory.
+
+    snip-and-autosave.exe set-folder <directory>
+        Changes the directory that screenshots are saved to, and offers to move the existing
+        screenshots there.
+
+    snip-and-autosave.exe restore <archive>
+        Replaces the settings, history and thumbnails with those in a backup. The program must
+        not be running.
+
+    snip-and-autosave.exe reencode [directory]
+        Converts the screenshots in a directory (by default, the screenshot directory) to the
+        configured image format, keeping their timestamps.
+
+    snip-and-autosave.exe scan [--quarantine] [directory]
+        Checks that the screenshots in a directory (by default, the screenshot directory) aren't
+        corrupt, optionally moving corrupt screenshots into a Quarantine subdirectory.
+
+    snip-and-autosave.exe view [archive] [screenshot directory]
+        Opens the history viewer, without starting the program, for a backup or history file
+        (by default, this machine's history). Screenshots that aren't where they were saved are
+        looked for by name in the screenshot directory, if given.
+
+    snip-and-autosave.exe save-now
+        Asks the running program to save the image on the clipboard, whatever it is.
+
+    snip-and-autosave.exe stats [directory]
+        Shows the total and per-month size of the screenshots in a directory (by default, the
+        screenshot directory), and lists the largest screenshots.
+
+Options:
+    --portable
+        Stores the settings, history and logs in a snip-and-autosave folder beside the
+        executable, instead of in %APPDATA%, as if a portable.toml file existed beside it.
+
+Exit codes:
+    0   Success
+    1   The command failed (e.g. a file couldn't be read, or corrupt screenshots were found)
+    2   The command line couldn't be parsed
+    3   settings.toml couldn't be read
+    4   The command needs the program to be running, but it isn't
+    5   The running program didn't respond in time
+    6   A file couldn't be saved
+    7   The command needs the program to not be running, but it is";
+
+/// The status that the program exits with after running a subcommand. These
+/// values are stable, so that scripts can rely on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    /// The command succeeded.
+    Success = 0,
+
+    /// The command failed for a reason without a more specific exit code.
+    Failure = 1,
+
+    /// The command line couldn't be parsed.
+    Usage = 2,
+
+    /// The settings file couldn't be read or parsed.
+    ConfigError = 3,
+
+    /// The command needs the program to be running, but it isn't.
+    NotRunning = 4,
+
+    /// The running program didn't respond to the command in time.
+    IpcTimeout = 5,
+
+    /// A file that the command writes couldn't be saved.
+    SaveFailed = 6,
+
+    /// The command needs the program to not be running, but it is.
+    Running = 7,
+}
+
+impl From<IpcError> for ExitCode {
+    fn from(error: IpcError) -> Self {
+        match error {
+            IpcError::NotRunning => ExitCode::NotRunning,
+            IpcError::Timeout => ExitCode::IpcTimeout,
+            IpcError::Rejected => ExitCode::Failure,
+        }
+    }
+}
+
+/// A subcommand, specified on the command line.
+pub enum Command {
+    /// Backs up the program's state.
+    Backup { archive: PathBuf },
+
+    /// Generates a contact sheet of the screenshots saved within a range of
+    /// days.
+    ContactSheet {
+        from: NaiveDate,
+        to: NaiveDate,
+        output: PathBuf,
+    },
+
+    /// Decrypts the entries of an encrypted screenshot archive.
+    Decrypt {
+        archive: PathBuf,
+        output_dir: PathBuf,
+    },
+
+    /// Changes the screenshot directory of the running instance, or the stored
+    /// settings, if the program isn't running.
+    SetFolder { directory: PathBuf },
+
+    /// Replaces the program's state with a backup.
+    Restore { archive: PathBuf },
+
+    /// Re-encodes existing screenshots into the configured image format.
+    Reencode { directory: Option<PathBuf> },
+
+    /// Asks the running instance to save the image on the clipboard.
+    SaveNow,
+
+    /// Shows statistics about existing screenshots.
+    Stats { directory: Option<PathBuf> },
+
+    /// Opens the history viewer, without starting the program.
+    View {
+        archive: Option<PathBuf>,
+        screenshot_dir: Option<PathBuf>,
+    },
+
+    /// Checks existing screenshots for corruption.
+    Scan {
+        directory: Option<PathBuf>,
+        quarantine: bool,
+    },
+}
+
+/// Parses the program's command line arguments (excluding the executable path).
+/// Portable mode is turned on straight away, if requested.
+///
+/// Returns `Ok(None)` if no subcommand was given, meaning that the program
+/// should run normally.
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Command>, String> {
+    let mut command = args.next();
+
+    if command.as_deref() == Some(settings::PORTABLE_FLAG) {
+        settings::enable_portable_mode();
+        command = args.next();
+    }
+
+    let command = match command {
+        Some(command) => command,
+        None => return Ok(None),
+    };
+
+    let command = match command.as_str() {
+        "backup" => Command::Backup {
+            archive: args.next().ok_or("Missing archive path")?.into(),
+        },
+        "contact-sheet" => {
+            let from = parse_date(&args.next().ok_or("Missing date")?)?;
+            let argument = args.next().ok_or("Missing output file")?;
+
+            match args.next() {
+                Some(output) => Command::ContactSheet {
+                    from,
+                    to: parse_date(&argument)?,
+                    output: output.into(),
+                },
+                None => Command::ContactSheet {
+                    from,
+                    to: from,
+                    output: argument.into(),
+                },
+            }
+        }
+        "decrypt" => {
+            let archive = args.next().ok_or("Missing archive path")?;
+            let output_dir = args.next().ok_or("Missing output directory")?;
+
+            Command::Decrypt {
+                archive: archive.into(),
+                output_dir: output_dir.into(),
+            }
+        }
+        "set-folder" => {
+            let directory = args.next().ok_or("Missing directory")?;
+
+            Command::SetFolder {
+                directory: directory.into(),
+            }
+        }
+        "restore" => Command::Restore {
+            archive: args.next().ok_or("Missing archive path")?.into(),
+        },
+        "reencode" => Command::Reencode {
+            directory: args.next().map(PathBuf::from),
+        },
+        "save-now" => Command::SaveNow,
+        "stats" => Command::Stats {
+            directory: args.next().map(PathBuf::from),
+        },
+        "view" => Command::View {
+            archive: args.next().map(PathBuf::from),
+            screenshot_dir: args.next().map(PathBuf::from),
+        },
+        "scan" => {
+            let mut argument = args.next();
+            let quarantine = argument.as_deref() == Some("--quarantine");
+
+            if quarantine {
+                argument = args.next();
+            }
+
+            Command::Scan {
+                directory: argument.map(PathBuf::from),
+                quarantine,
+            }
+        }
+        _ => return Err(format!("Unknown command \"{}\"", command)),
+    };
+
+    if let Some(argument) = args.next() {
+        return Err(format!("Unexpected argument \"{}\"", argument));
+    }
+
+    Ok(Some(command))
+}
+
+/// Parses a `YYYY-MM-DD` date.
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("\"{}\" is not a date (YYYY-MM-DD)", date))
+}
+
+/// Returns the configured screenshot directory.
+fn screenshot_dir() -> PathBuf {
+    let mut screenshot_dir = PathBuf::new();
+    Settings::read(|s| screenshot_dir = s.paths.screenshots.clone());
+
+    screenshot_dir
+}
+
+/// Runs a subcommand, printing its results to the console, and returning the
+/// status that the program should exit with.
+pub fn run(command: Command) -> ExitCode {
+    // Restoring and decrypting don't need the settings, so must work even if they're broken
+    if !matches!(
+        command,
+        Command::Backup { .. } | Command::Restore { .. } | Command::Decrypt { .. }
+    ) {
+        if let Err(e) = settings::check_settings_file() {
+            println!("Unable to read settings.toml: {}", e);
+            return ExitCode::ConfigError;
+        }
+    }
+
+    match command {
+        Command::Backup { archive } => match backup::backup(&archive) {
+            Ok(count) => {
+                println!("Backed up {} files to {}", count, archive.display());
+                ExitCode::Success
+            }
+            Err(e) => {
+                println!("Failed to back up to {}: {}", archive.display(), e);
+                ExitCode::SaveFailed
+            }
+        },
+        Command::Restore { archive } => match backup::restore(&archive) {
+            Ok(count) => {
+                println!("Restored {} files from {}", count, archive.display());
+                ExitCode::Success
+            }
+            Err(e) => {
+                println!("Failed to restore {}: {}", archive.display(), e);
+
+                match e {
+                    BackupError::Running => ExitCode::Running,
+                    _ => ExitCode::Failure,
+                }
+            }
+        },
+        Command::ContactSheet { from, to, output } => {
+            match contact_sheet::generate(from, to, &output) {
+                Ok(count) => {
+                    println!(
+                        "Saved a contact sheet of {} screenshots to {}",
+                        count,
+                        output.display()
+                    );
+                    ExitCode::Success
+                }
+                Err(e) => {
+                    println!("Failed to generate a contact sheet: {}", e);
+                    ExitCode::SaveFailed
+                }
+            }
+        }
+        Command::Decrypt {
+            archive,
+            output_dir,
+        } => match encryption::extract_archive(&archive, &output_dir) {
+            Ok(count) => {
+                println!(
+                    "Extracted {} screenshots to {}",
+                    count,
+                    output_dir.display()
+                );
+                ExitCode::Success
+            }
+            Err(e) => {
+                println!("Failed to decrypt {}: {}", archive.display(), e);
+                ExitCode::Failure
+            }
+        },
+        Command::Reencode { directory } => {
+            let mut output = Output::default();
+            Settings::read(|s| output = s.output.clone());
+
+            let directory = directory.unwrap_or_else(screenshot_dir);
+
+            match reencode::reencode_dir(&directory, &output) {
+                Ok(report) => {
+                    for (screenshot, e) in &report.failures {
+                        println!("Failed to re-encode {}: {}", screenshot.display(), e);
+                    }
+
+                    println!(
+                        "Re-encoded {} screenshots ({} already converted, {} animated, {} failed)",
+                        report.converted,
+                        report.skipped,
+                        report.animated,
+                        report.failures.len()
+                    );
+
+                    if report.failures.is_empty() {
+                        ExitCode::Success
+                    } else {
+                        ExitCode::SaveFailed
+                    }
+                }
+                Err(e) => {
+                    println!("Failed to read {}: {}", directory.display(), e);
+                    ExitCode::Failure
+                }
+            }
+        }
+        Command::SaveNow => match ipc::try_send(&IpcCommand::SaveNow) {
+            Ok(()) => ExitCode::Success,
+            Err(e) => {
+                println!("Failed to save the clipboard: {}", e);
+                e.into()
+            }
+        },
+        Command::Stats { directory } => {
+            let directory = directory.unwrap_or_else(screenshot_dir);
+
+            match stats::collect(&directory) {
+                Ok(stats) => {
+                    print!("{}", stats);
+                    ExitCode::Success
+                }
+                Err(e) => {
+                    println!("Failed to read {}: {}", directory.display(), e);
+                    ExitCode::Failure
+                }
+            }
+        }
+        Command::Scan {
+            directory,
+            quarantine,
+        } => {
+            let directory = directory.unwrap_or_else(screenshot_dir);
+
+            match scan::scan_dir(&directory, quarantine) {
+                Ok(report) => {
+                    for (screenshot, reason) in &report.corrupt {
+                        println!("{} is corrupt: {}", screenshot.display(), reason);
+                    }
+
+                    println!(
+                        "Checked {} screenshots, {} corrupt{}",
+                        report.checked,
+                        report.corrupt.len(),
+                        if quarantine && !report.corrupt.is_empty() {
+                            format!(" (moved to {})", scan::QUARANTINE_DIR)
+                        } else {
+                            String::new()
+                        }
+                    );
+
+                    if report.corrupt.is_empty() {
+                        ExitCode::Success
+                    } else {
+                        ExitCode::Failure
+                    }
+                }
+                Err(e) => {
+                    println!("Failed to scan {}: {}", directory.display(), e);
+                    ExitCode::Failure
+                }
+            }
+        }
+        Command::View {
+            archive,
+            screenshot_dir,
+        } => match history_viewer::view_archive(archive.as_deref(), screenshot_dir.as_deref()) {
+            Ok(()) => ExitCode::Success,
+            Err(e) => {
+                println!("Failed to view the history: {}", e);
+                ExitCode::Failure
+            }
+        },
+        Command::SetFolder { directory } => {
+            match ipc::try_send(&IpcCommand::SetScreenshotDir(directory.clone())) {
+                Ok(()) => ExitCode::Success,
+                // No instance is running to offer to migrate the screenshots,
+                // and this process exits as soon as the command returns, so
+                // everything is done before returning
+                Err(IpcError::NotRunning) => set_folder(&directory),
+                Err(e) => {
+                    println!("Failed to change the screenshot folder: {}", e);
+                    e.into()
+                }
+            }
+        }
+    }
+}
+
+/// Changes the screenshot directory to `directory`, verifies that it is
+/// writable, then offers to migrate the screenshots in the previous directory
+/// over to it, all before returning.
+fn set_folder(directory: &Path) -> ExitCode {
+    let old_dir = match migration::set_screenshot_dir(directory) {
+        Some(old_dir) => old_dir,
+        None => {
+            println!("The screenshot folder is set by your administrator, so can't be changed");
+            return ExitCode::Failure;
+        }
+    };
+
+    if let Err(e) = save::check_screenshot_dir() {
+        println!("{} is not writable: {}", directory.display(), e);
+    }
+
+    if old_dir != directory {
+        migration::offer_migration(&old_dir, directory);
+    }
+
+    ExitCode::Success
+}
@@ -0,0 +1,80 @@
+//! Detection of clipboard update re-announcements.
+//!
+//! When clipboard history (Win+V) is enabled, Windows can put an item that is
+//! already on the clipboard back onto it (e.g. when the item is pinned, or
+//! synced from another device), which fires another clipboard update. These
+//! re-announcements are detected by the clipboard sequence number, and by the
+//! owner and content of the clipboard data, so that they aren't saved again.
+//! The content is only compared whilst deduplication is enabled, as otherwise
+//! repeated identical snips must all be kept.
+
+use crate::windows::{get_clipboard_owner, get_clipboard_sequence_number};
+use image::RgbImage;
+use lazy_static::lazy_static;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+/// Identifies a single clipboard update.
+struct Announcement {
+    /// The clipboard sequence number at the time of the update.
+    sequence_number: u32,
+
+    /// The window that owned the clipboard data.
+    owner: isize,
+}
+
+lazy_static! {
+    /// The last clipboard update that was processed.
+    static ref LAST_ANNOUNCEMENT: Mutex<Option<Announcement>> = Mutex::new(None);
+
+    /// The owner and content hash of the last image that was read from the
+    /// clipboard.
+    static ref LAST_IMAGE: Mutex<Option<(isize, u64)>> = Mutex::new(None);
+}
+
+/// Returns whether or not the clipboard contents are unchanged since the last
+/// update was processed. This is cheap, as it doesn't read the clipboard data,
+/// so should be checked first.
+pub fn is_unchanged() -> bool {
+    let sequence_number = get_clipboard_sequence_number();
+    let mut last_announcement = LAST_ANNOUNCEMENT.lock().unwrap();
+
+    if matches!(&*last_announcement, Some(last) if last.sequence_number == sequence_number) {
+        return true;
+    }
+
+    *last_announcement = Some(Announcement {
+        sequence_number,
+        owner: get_clipboard_owner().0,
+    });
+
+    false
+}
+
+/// Returns whether or not `image`, read from the clipboard, is a
+/// re-announcement of the last image that was read, from the same owner.
+///
+/// [`is_unchanged`] must be called before this function, for each clipboard
+/// update.
+///
+/// [`is_unchanged`]: is_unchanged
+pub fn is_reannouncement(image: &RgbImage) -> bool {
+    let mut hasher = DefaultHasher::new();
+    image.dimensions().hash(&mut hasher);
+    image.as_raw().hash(&mut hasher);
+
+    let content_hash = hasher.finish();
+
+    let owner = match &*LAST_ANNOUNCEMENT.lock().unwrap() {
+        Some(announcement) => announcement.owner,
+        None => return false,
+    };
+
+    let mut last_image = LAST_IMAGE.lock().unwrap();
+    let is_reannouncement = *last_image == Some((owner, content_hash));
+
+    *last_image = Some((owner, content_hash));
+
+    is_reannouncement
+}
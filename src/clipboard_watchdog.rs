@@ -0,0 +1,119 @@
+//! The clipboard listener watchdog.
+//!
+//! After resuming from sleep, or reconnecting to a Remote Desktop session,
+//! Windows sometimes stops sending `WM_CLIPBOARDUPDATE` to the main window, so
+//! screenshots silently stop being saved. The clipboard listener is therefore
+//! re-registered whenever the system resumes, and periodically checked: if the
+//! clipboard sequence number has changed without an update being received,
+//! the listener is re-registered, and the missed update is handled.
+
+use crate::capture;
+use crate::windows::{
+    add_clipboard_listener, get_clipboard_sequence_number, remove_clipboard_listener,
+    send_notify_message,
+};
+use bindings::Windows::Win32::{
+    Foundation::{HWND, LPARAM, LRESULT, WPARAM},
+    UI::WindowsAndMessaging::{PBT_APMRESUMEAUTOMATIC, WM_APP},
+};
+use log::{error, info, warn};
+use std::sync::atomic::{AtomicIsize, AtomicU32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Sent to the main window when the watchdog finds that clipboard updates are
+/// being missed, so that the listener is re-registered on the window's own
+/// thread.
+pub const WMAPP_CLIPBOARD_LISTENER_STALLED: u32 = WM_APP + 3;
+
+/// How often the clipboard sequence number is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The main window, once the watchdog has been started.
+static WINDOW: AtomicIsize = AtomicIsize::new(0);
+
+/// The clipboard sequence number when the last clipboard update was received.
+static LAST_SEEN_SEQUENCE: AtomicU32 = AtomicU32::new(0);
+
+/// Starts the watchdog thread, which checks `window`'s clipboard listener.
+pub fn start(window: HWND) {
+    WINDOW.store(window.0, Ordering::SeqCst);
+    LAST_SEEN_SEQUENCE.store(get_clipboard_sequence_number(), Ordering::SeqCst);
+
+    thread::Builder::new()
+        .name("clipboard-watchdog".into())
+        .spawn(run)
+        .expect("Failed to start clipboard watchdog thread");
+}
+
+/// Records that a clipboard update was received.
+pub fn on_clipboard_update() {
+    LAST_SEEN_SEQUENCE.store(get_clipboard_sequence_number(), Ordering::SeqCst);
+}
+
+/// `WM_POWERBROADCAST` message processor, which re-registers the clipboard
+/// listener when the system resumes from sleep.
+pub fn on_power_broadcast(window: HWND, w_param: WPARAM) -> LRESULT {
+    if w_param.0 as u32 == PBT_APMRESUMEAUTOMATIC {
+        info!("Resumed from sleep - re-registering the clipboard listener");
+        reregister(window);
+    }
+
+    LRESULT(1)
+}
+
+/// [`WMAPP_CLIPBOARD_LISTENER_STALLED`] message processor, which re-registers
+/// the clipboard listener, then handles the update that was missed.
+///
+/// [`WMAPP_CLIPBOARD_LISTENER_STALLED`]: WMAPP_CLIPBOARD_LISTENER_STALLED
+pub fn on_listener_stalled(window: HWND) -> LRESULT {
+    reregister(window);
+
+    on_clipboard_update();
+    capture::on_clipboard_update();
+
+    LRESULT(0)
+}
+
+/// Replaces `window`'s clipboard listener registration.
+fn reregister(window: HWND) {
+    // Fails if the registration has already been lost, which is fine
+    let _ = remove_clipboard_listener(window);
+
+    if let Err(e) = add_clipboard_listener(window) {
+        error!("Failed to re-register the clipboard listener: {:#?}", e);
+    }
+}
+
+/// The watchdog thread's main loop.
+fn run() {
+    let mut previous_sequence = get_clipboard_sequence_number();
+
+    loop {
+        thread::sleep(CHECK_INTERVAL);
+
+        let sequence = get_clipboard_sequence_number();
+
+        // The update may still be on its way, unless the clipboard has been left alone since the
+        // last check
+        let is_stalled =
+            sequence == previous_sequence && sequence != LAST_SEEN_SEQUENCE.load(Ordering::SeqCst);
+
+        if is_stalled {
+            warn!("Clipboard changed without an update being received - re-registering listener");
+
+            let window = HWND(WINDOW.load(Ordering::SeqCst));
+
+            if let Err(e) = send_notify_message(
+                window,
+                WMAPP_CLIPBOARD_LISTENER_STALLED,
+                WPARAM(0),
+                LPARAM(0),
+            ) {
+                error!("Failed to re-register the clipboard listener: {:#?}", e);
+            }
+        }
+
+        previous_sequence = sequence;
+    }
+}
@@ -0,0 +1,103 @@
+//! Colour picking, for snipping purely to find out what colour something is.
+//!
+//! When the colour picker hotkey is pressed, the next snip isn't saved.
+//! Instead, the colour of the pixel under the mouse cursor (or, if the snip
+//! can't be found on the screen, the pixel in its centre) is copied to the
+//! clipboard as a hex colour, e.g. `#1F6FEB`, and recorded in the history.
+
+use crate::history::{self, Entry};
+use crate::notification_area::{self, BalloonLevel};
+use crate::region;
+use crate::settings::{NotificationVerbosity, Settings};
+use crate::windows::{open_clipboard, set_clipboard_text};
+use bindings::Windows::Win32::{Foundation::POINT, UI::WindowsAndMessaging::GetCursorPos};
+use chrono::Utc;
+use image::RgbImage;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long the colour picker stays armed for, so that a cancelled snip doesn't
+/// turn a much later one into a colour pick.
+const ARMED_TIMEOUT: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    /// When the colour picker was armed, if it is waiting for a snip.
+    static ref ARMED_AT: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+/// Makes the next snip pick a colour, rather than being saved.
+pub fn arm() {
+    info!("Colour picker armed - the next snip will pick a colour");
+    *ARMED_AT.lock().unwrap() = Some(Instant::now());
+}
+
+/// Makes the next snip be saved as usual again.
+pub fn disarm() {
+    ARMED_AT.lock().unwrap().take();
+}
+
+/// Returns whether or not the colour picker was armed (and not too long ago),
+/// disarming it.
+pub fn take_armed() -> bool {
+    match ARMED_AT.lock().unwrap().take() {
+        Some(armed_at) => armed_at.elapsed() <= ARMED_TIMEOUT,
+        None => false,
+    }
+}
+
+/// Copies the colour of the pixel under the mouse cursor in `image` to the
+/// clipboard, and records it in the history.
+pub fn pick(image: &RgbImage) {
+    let (x, y) = pixel_under_cursor(image);
+    let [r, g, b] = image.get_pixel(x, y).0;
+    let colour = format!("#{:02X}{:02X}{:02X}", r, g, b);
+
+    info!("Picked colour {} at ({}, {})", colour, x, y);
+
+    if let Err(e) =
+        open_clipboard(None).and_then(|clipboard| set_clipboard_text(&clipboard, &colour))
+    {
+        warn!("Failed to copy colour to clipboard: {:#?}", e);
+    }
+
+    history::record(Entry::colour_pick(colour.clone(), image, Utc::now()));
+
+    let mut verbosity = NotificationVerbosity::EverySave;
+    Settings::read(|s| verbosity = s.notifications.verbosity);
+
+    if verbosity >= NotificationVerbosity::EverySave {
+        let _ = notification_area::show_balloon(
+            "Colour copied",
+            &format!("{} was copied to the clipboard", colour),
+            BalloonLevel::Info,
+        );
+    }
+}
+
+/// Returns the coordinates within `image` of the pixel under the mouse cursor,
+/// or of its centre pixel, if the cursor isn't over the snip.
+fn pixel_under_cursor(image: &RgbImage) -> (u32, u32) {
+    let centre = (image.width() / 2, image.height() / 2);
+
+    let mut cursor = POINT::default();
+
+    if unsafe { GetCursorPos(&mut cursor).0 == 0 } {
+        warn!("Failed to get the cursor position");
+        return centre;
+    }
+
+    let region = match region::find_on_screen(image) {
+        Some(region) => region,
+        None => return centre,
+    };
+
+    let (x, y) = (cursor.x - region.x, cursor.y - region.y);
+
+    if x < 0 || y < 0 || x as u32 >= image.width() || y as u32 >= image.height() {
+        return centre;
+    }
+
+    (x as u32, y as u32)
+}
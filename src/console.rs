@@ -0,0 +1,86 @@
+//! Runtime debug console.
+//!
+//! The program runs without a console, so its output is normally only visible
+//! when it is launched from a terminal. The debug console allocates a console
+//! window on demand instead, which all subsequent output is written to.
+
+use crate::placement;
+use crate::settings::Settings;
+use crate::windows::{alloc_console, free_console, get_console_window, remove_close_button};
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The title of the debug console window.
+const CONSOLE_TITLE: &str = "Snip & AutoSave Debug Console";
+
+/// The name that the debug console's position is saved under.
+const PLACEMENT_NAME: &str = "debug_console";
+
+/// Whether or not the debug console is currently shown.
+static IS_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Returns whether or not the debug console is currently shown.
+pub fn is_shown() -> bool {
+    IS_SHOWN.load(Ordering::SeqCst)
+}
+
+/// Shows the debug console, if it is enabled in the settings. This should be
+/// called once, at start-up.
+pub fn restore() -> windows::Result<()> {
+    let mut debug_console = false;
+    Settings::read(|s| debug_console = s.program.debug_console);
+
+    if debug_console {
+        show()?;
+    }
+
+    Ok(())
+}
+
+/// Shows the debug console if it is hidden, otherwise hides it, and remembers
+/// the choice for the next time that the program starts.
+pub fn toggle() -> windows::Result<()> {
+    if is_shown() {
+        hide();
+        Settings::write(|s| s.program.debug_console = false);
+    } else {
+        show()?;
+        Settings::write(|s| s.program.debug_console = true);
+    }
+
+    Ok(())
+}
+
+/// Allocates the debug console, and redirects output to it.
+fn show() -> windows::Result<()> {
+    // We may still be attached to the console of the terminal that launched us,
+    // which would prevent us from allocating our own
+    free_console();
+
+    let window = alloc_console(CONSOLE_TITLE)?;
+
+    // Closing a console window terminates every process attached to it, so
+    // the console must be closed via the tray menu instead
+    remove_close_button(window);
+
+    if let Err(e) = placement::restore(window, PLACEMENT_NAME) {
+        warn!("Failed to restore debug console position: {:#?}", e);
+    }
+
+    IS_SHOWN.store(true, Ordering::SeqCst);
+    info!("Debug console opened");
+
+    Ok(())
+}
+
+/// Closes the debug console. Output is discarded until it is shown again.
+fn hide() {
+    if let Some(window) = get_console_window() {
+        if let Err(e) = placement::save(window, PLACEMENT_NAME) {
+            warn!("Failed to save debug console position: {:#?}", e);
+        }
+    }
+
+    free_console();
+    IS_SHOWN.store(false, Ordering::SeqCst);
+}
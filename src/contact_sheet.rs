@@ -0,0 +1,214 @@
+//! Contact sheets, which lay out the screenshots saved within a range of days
+//! in a single image (or PDF), for quickly reviewing or sharing a day's
+//! captures.
+//!
+//! Screenshots are drawn from their history thumbnails where possible, so that
+//! large screenshots don't have to be decoded.
+
+use crate::history::{self, Entry};
+use crate::output;
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use image::codecs::jpeg::JpegEncoder;
+use image::{imageops, ColorType, ImageError, Rgb, RgbImage};
+use log::warn;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+/// The maximum width and height of each screenshot on the sheet, in pixels.
+const CELL_SIZE: u32 = 256;
+
+/// The space around each screenshot on the sheet, in pixels.
+const PADDING: u32 = 16;
+
+/// How many screenshots are laid out in each row.
+const COLUMNS: u32 = 6;
+
+/// The colour of the sheet behind the screenshots.
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+
+/// The quality of the JPEG image embedded in PDF contact sheets.
+const PDF_JPEG_QUALITY: u8 = 90;
+
+/// Errors that can occur whilst generating a contact sheet.
+#[derive(Error, Debug)]
+pub enum ContactSheetError {
+    #[error("No screenshots were saved between {0} and {1}")]
+    NoScreenshots(NaiveDate, NaiveDate),
+    #[error("Unable to create the contact sheet: {0}")]
+    Image(#[from] ImageError),
+    #[error("Unable to write the contact sheet: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Generates a contact sheet of the screenshots saved from the start of `from`
+/// to the end of `to` (in local time), and saves it to `output`. If `output`
+/// has a `.pdf` extension, the sheet is saved as a single page PDF, otherwise
+/// its format is chosen from its extension.
+///
+/// Returns how many screenshots are on the sheet.
+pub fn generate(from: NaiveDate, to: NaiveDate, output: &Path) -> Result<usize, ContactSheetError> {
+    let entries = history::saved_between(start_of_day(from), start_of_day(to.succ()));
+
+    let thumbnails: Vec<RgbImage> = entries.iter().filter_map(load_thumbnail).collect();
+
+    if thumbnails.is_empty() {
+        return Err(ContactSheetError::NoScreenshots(from, to));
+    }
+
+    let sheet = lay_out(&thumbnails);
+
+    let is_pdf = match output.extension().and_then(|e| e.to_str()) {
+        Some(extension) => extension.eq_ignore_ascii_case("pdf"),
+        None => false,
+    };
+
+    if is_pdf {
+        write_pdf(&sheet, output)?;
+    } else {
+        sheet.save(output)?;
+    }
+
+    Ok(thumbnails.len())
+}
+
+/// Returns the time that `date` starts at, in the local time zone.
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms(0, 0, 0);
+
+    match Local.from_local_datetime(&midnight).earliest() {
+        Some(start) => start.with_timezone(&Utc),
+        // Midnight was skipped by a daylight saving time change
+        None => Utc.from_utc_datetime(&midnight),
+    }
+}
+
+/// Loads a downscaled copy of the screenshot recorded by `entry`, from its
+/// thumbnail, if it has one, or from the screenshot itself.
+fn load_thumbnail(entry: &Entry) -> Option<RgbImage> {
+    let source = match &entry.thumbnail {
+        Some(thumbnail) if thumbnail.is_file() => thumbnail,
+        _ => &entry.path,
+    };
+
+    let image = match output::open(source) {
+        Ok(image) => image.to_rgb8(),
+        Err(e) => {
+            warn!(
+                "Leaving {} off the contact sheet: {}",
+                entry.path.display(),
+                e
+            );
+            return None;
+        }
+    };
+
+    let scale = (CELL_SIZE as f32 / image.width().max(image.height()) as f32).min(1.0);
+
+    Some(imageops::thumbnail(
+        &image,
+        ((image.width() as f32 * scale) as u32).max(1),
+        ((image.height() as f32 * scale) as u32).max(1),
+    ))
+}
+
+/// Lays `thumbnails` out in a grid, in order, centring each within its cell.
+fn lay_out(thumbnails: &[RgbImage]) -> RgbImage {
+    let columns = COLUMNS.min(thumbnails.len() as u32);
+    let rows = (thumbnails.len() as u32 - 1) / columns + 1;
+    let cell = CELL_SIZE + PADDING;
+
+    let mut sheet =
+        RgbImage::from_pixel(columns * cell + PADDING, rows * cell + PADDING, BACKGROUND);
+
+    for (i, thumbnail) in thumbnails.iter().enumerate() {
+        let column = i as u32 % columns;
+        let row = i as u32 / columns;
+
+        let x = PADDING + column * cell + (CELL_SIZE - thumbnail.width()) / 2;
+        let y = PADDING + row * cell + (CELL_SIZE - thumbnail.height()) / 2;
+
+        imageops::replace(&mut sheet, thumbnail, x, y);
+    }
+
+    sheet
+}
+
+/// Saves `sheet` to `path` as a single page PDF, with the sheet embedded as a
+/// JPEG image filling the page, at one point per pixel.
+fn write_pdf(sheet: &RgbImage, path: &Path) -> Result<(), ContactSheetError> {
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, PDF_JPEG_QUALITY).encode(
+        sheet,
+        sheet.width(),
+        sheet.height(),
+        ColorType::Rgb8,
+    )?;
+
+    let (width, height) = sheet.dimensions();
+    let contents = format!("q {} 0 0 {} 0 0 cm /Sheet Do Q", width, height);
+
+    let objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /XObject << /Sheet 4 0 R >> >> /Contents 5 0 R >>",
+            width, height
+        )
+        .into_bytes(),
+        [
+            format!(
+                "<< /Type /XObject /Subtype /Image /Width {} /Height {} \
+                 /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode \
+                 /Length {} >>\nstream\n",
+                width,
+                height,
+                jpeg.len()
+            )
+            .as_bytes(),
+            &jpeg,
+            b"\nendstream",
+        ]
+        .concat(),
+        format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            contents.len(),
+            contents
+        )
+        .into_bytes(),
+    ];
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+
+    fs::write(path, pdf)?;
+
+    Ok(())
+}
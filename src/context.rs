@@ -0,0 +1,137 @@
+//! Snapshots of the context that screenshots are taken in.
+//!
+//! When a screenshot is taken, the foreground process, the title of its
+//! window, and, where available, the web page or document that it is showing,
+//! are recorded in the screenshot's history, so that screenshots stay traceable
+//! to their source. Window titles and addresses can be sensitive, so this is
+//! disabled by default.
+//!
+//! The address of a web page is read from the address bar of known browsers,
+//! using UI Automation. Document paths are found in window titles, which many
+//! editors show the full path of the open document in.
+
+use crate::settings::Settings;
+use crate::windows::{
+    get_first_edit_value, get_foreground_window, get_process_image_file_name, get_window_text,
+    get_window_thread_and_process_id, open_process,
+};
+use bindings::Windows::Win32::Foundation::HWND;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The separators that window titles are split into parts with, when searching
+/// them for document paths.
+const TITLE_SEPARATORS: [&str; 3] = [" - ", " \u{2014} ", " | "];
+
+/// What was in the foreground when a screenshot was taken.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Context {
+    /// The image name of the foreground process, e.g. `chrome.exe`.
+    pub process: String,
+
+    /// The title of the foreground window.
+    pub window_title: String,
+
+    /// The address of the web page in the foreground window, if it is a
+    /// browser.
+    #[serde(default)]
+    pub url: Option<String>,
+
+    /// The path of the document in the foreground window, if its title
+    /// contains one.
+    #[serde(default)]
+    pub document_path: Option<PathBuf>,
+}
+
+/// Takes a snapshot of the foreground window's context, if recording context
+/// is enabled.
+pub fn snapshot() -> Option<Context> {
+    let mut enabled = false;
+    let mut browsers = Vec::new();
+
+    Settings::read(|s| {
+        enabled = s.context.enabled;
+        browsers = s.context.browsers.clone();
+    });
+
+    if !enabled {
+        return None;
+    }
+
+    let window = get_foreground_window();
+    let (process_id, _) = get_window_thread_and_process_id(window);
+
+    // Process names are NT paths, so we only keep the final path component
+    let process = match open_process(process_id)
+        .and_then(|process| get_process_image_file_name(process.value()))
+    {
+        Ok(process_name) => match process_name.rsplit('\\').next() {
+            Some(name) => name.to_owned(),
+            None => process_name,
+        },
+        Err(e) => {
+            debug!("Unable to find the foreground process: {:#?}", e);
+            return None;
+        }
+    };
+
+    let window_title = get_window_text(window);
+
+    let url = if browsers
+        .iter()
+        .any(|browser| browser.eq_ignore_ascii_case(&process))
+    {
+        read_address_bar(window)
+    } else {
+        None
+    };
+
+    Some(Context {
+        document_path: find_document_path(&window_title),
+        process,
+        window_title,
+        url,
+    })
+}
+
+/// Reads the address of the active tab from the address bar of the browser
+/// `window`.
+fn read_address_bar(window: HWND) -> Option<String> {
+    match get_first_edit_value(window) {
+        Ok(Some(url)) if !url.is_empty() => {
+            debug!("Source URL: {}", url);
+            Some(url)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            warn!("Unable to read the browser's address bar: {:#?}", e);
+            None
+        }
+    }
+}
+
+/// Returns the first part of `window_title` that is the full path of an
+/// existing file, if any. Unsaved change markers (e.g. `*`) are ignored.
+fn find_document_path(window_title: &str) -> Option<PathBuf> {
+    let mut parts = vec![window_title];
+
+    for separator in TITLE_SEPARATORS.iter() {
+        parts = parts
+            .into_iter()
+            .flat_map(|part| part.split(separator))
+            .collect();
+    }
+
+    parts
+        .into_iter()
+        .map(|part| {
+            Path::new(
+                part.trim()
+                    .trim_start_matches(&['*', '\u{25cf}'][..])
+                    .trim(),
+            )
+        })
+        .find(|path| path.is_absolute() && path.is_file())
+        .map(Path::to_path_buf)
+}
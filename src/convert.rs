@@ -1,8 +1,9 @@
 //! Data format conversion routines.
 
-use crate::windows::Clipboard;
-use bindings::Windows::Win32::Graphics::Gdi::{BITMAPINFO, BI_BITFIELDS};
+use crate::windows::{get_clipboard_data_size, Clipboard};
+use bindings::Windows::Win32::Graphics::Gdi::{BITMAPINFO, BITMAPINFOHEADER, BI_BITFIELDS, BI_RGB};
 use image::{Pixel, Rgb, RgbImage};
+use std::mem;
 use thiserror::Error;
 
 /// Errors that can occur whilst converting an image.
@@ -14,6 +15,8 @@ pub enum ConversionError {
     UnsupportedCompressionFormat(u32),
     #[error("Image has an unsupported bit depth of {0}-bits")]
     UnsupportedBitDepth(u16),
+    #[error("Image data is truncated")]
+    Truncated,
 }
 
 /// Reads the subpixel byte order of a device-independent bitmap.
@@ -36,16 +39,22 @@ unsafe fn subpixel_ordering(color_masks: *const u32) -> (u32, u32, u32) {
 /// Copies the image data from a device-independent bitmap into an [`RgbImage`].
 ///
 /// This function can currently only handle [`BI_BITFIELDS`] formatted DIB
-/// images, with a bit depth of 32-bpp.
+/// images, with a bit depth of 32-bpp, and [`BI_RGB`] formatted DIB images,
+/// with a bit depth of 24 or 32-bpp.
 ///
 /// This function can handle various subpixel orders, as well as both bottom and
 /// top-left corner origins.
 ///
+/// As any program can put a bitmap on the clipboard, the sizes in its header
+/// are checked against the size of the clipboard data before any pixels are
+/// read.
+///
 /// [`RgbImage`]: RgbImage
 /// [`BI_BITFIELDS`]: BI_BITFIELDS
+/// [`BI_RGB`]: BI_RGB
 pub fn dib_to_image(
     dib_image: *const BITMAPINFO,
-    _clipboard: &Clipboard,
+    clipboard: &Clipboard,
 ) -> Result<RgbImage, ConversionError> {
     unsafe {
         // Pre-flight sanity checks
@@ -53,60 +62,127 @@ pub fn dib_to_image(
             return Err(ConversionError::NullPointer);
         }
 
+        let available = get_clipboard_data_size(clipboard, dib_image) as u64;
+
+        if available < mem::size_of::<BITMAPINFOHEADER>() as u64 {
+            return Err(ConversionError::Truncated);
+        }
+
         let compression_format = (*dib_image).bmiHeader.biCompression;
         let bit_depth = (*dib_image).bmiHeader.biBitCount;
 
-        if compression_format != BI_BITFIELDS as u32 {
+        let supported_bit_depths: &[u16] = if compression_format == BI_BITFIELDS as u32 {
+            &[32]
+        } else if compression_format == BI_RGB as u32 {
+            &[24, 32]
+        } else {
             return Err(ConversionError::UnsupportedCompressionFormat(
                 compression_format,
             ));
-        }
+        };
 
-        if bit_depth != 32 {
+        if !supported_bit_depths.contains(&bit_depth) {
             return Err(ConversionError::UnsupportedBitDepth(bit_depth));
         }
 
         // Read DIB header
-        let width = (*dib_image).bmiHeader.biWidth.abs() as u32;
+        let width = (*dib_image).bmiHeader.biWidth.unsigned_abs();
         let height = (*dib_image).bmiHeader.biHeight;
 
         // Detect bottom-left corner origin
         let flip = height > 0;
-        let height = height.abs() as u32;
+        let height = height.unsigned_abs();
 
-        let bytes = (*dib_image).bmiHeader.biSizeImage;
         let data_offset = (*dib_image).bmiHeader.biSize;
-
         let dib_image_bytes = dib_image as *const u8;
-        let color_masks = dib_image_bytes.offset(data_offset as isize) as *const u32;
-        let image_data = color_masks.offset(3) as *const u8;
 
-        let (r, g, b) = subpixel_ordering(color_masks);
+        // Rows are padded to a multiple of 4 bytes
+        let bytes_per_pixel = bit_depth as u32 / 8;
+        let stride = (width as u64 * bytes_per_pixel as u64 + 3) & !3;
 
-        // Copy pixel data
-        let mut image = RgbImage::new(width as u32, height as u32);
-
-        for i in (0..bytes).step_by(4) {
-            let px = i / 4;
-            let x = px % width;
-            let y = if flip {
-                height - (px / width) - 1
+        let header_size = data_offset as u64
+            + if compression_format == BI_BITFIELDS as u32 {
+                3 * 4
             } else {
-                px / width
+                (*dib_image).bmiHeader.biClrUsed as u64 * 4
             };
 
-            image.put_pixel(
-                x,
-                y,
-                Rgb::from_channels(
-                    *image_data.offset((i + r) as isize),
-                    *image_data.offset((i + g) as isize),
-                    *image_data.offset((i + b) as isize),
-                    0,
-                ),
-            );
+        let image_size = stride * height as u64;
+        let declared_size = (*dib_image).bmiHeader.biSizeImage as u64;
+
+        // `biSizeImage` may be zero for uncompressed bitmaps
+        if header_size + image_size > available
+            || (declared_size != 0 && image_size > declared_size)
+        {
+            return Err(ConversionError::Truncated);
+        }
+
+        let stride = stride as u32;
+
+        let (image_data, (r, g, b)) = if compression_format == BI_BITFIELDS as u32 {
+            let color_masks = dib_image_bytes.offset(data_offset as isize) as *const u32;
+
+            (
+                color_masks.offset(3) as *const u8,
+                subpixel_ordering(color_masks),
+            )
+        } else {
+            // BI_RGB pixels are always stored in BGR order, after the (usually empty) color table
+            let color_table_size = (*dib_image).bmiHeader.biClrUsed * 4;
+
+            (
+                dib_image_bytes.offset((data_offset + color_table_size) as isize),
+                (2, 1, 0),
+            )
+        };
+
+        // Copy pixel data
+        let mut image = RgbImage::new(width as u32, height as u32);
+
+        for row in 0..height {
+            let y = if flip { height - row - 1 } else { row };
+
+            for x in 0..width {
+                let i = row * stride + x * bytes_per_pixel;
+
+                image.put_pixel(
+                    x,
+                    y,
+                    Rgb::from_channels(
+                        *image_data.offset((i + r) as isize),
+                        *image_data.offset((i + g) as isize),
+                        *image_data.offset((i + b) as isize),
+                        0,
+                    ),
+                );
+            }
         }
 
         Ok(image)
     }
 }
+
+/// Copies top-down, 32-bpp BGRX pixel data (as returned by [`capture_screen`])
+/// into an [`RgbImage`].
+///
+/// [`capture_screen`]: crate::windows::capture_screen
+/// [`RgbImage`]: RgbImage
+pub fn bgrx_to_image(width: u32, height: u32, pixels: &[u8]) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        let i = ((y * width + x) * 4) as usize;
+
+        Rgb([pixels[i + 2], pixels[i + 1], pixels[i]])
+    })
+}
+
+/// Copies the pixels of `image` into top-down, 32-bpp BGRA pixel data, with
+/// every pixel fully opaque.
+pub fn image_to_bgra(image: &RgbImage) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(image.width() as usize * image.height() as usize * 4);
+
+    for pixel in image.pixels() {
+        pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 0xFF]);
+    }
+
+    pixels
+}
@@ -0,0 +1,694 @@
+//! The quick crop window, which, if enabled, shows each screenshot before it is
+//! saved, so that it can be trimmed by dragging a rectangle over it, and
+//! marked up with arrows, boxes and text.
+//!
+//! Enter saves the selected part of the screenshot (or all of it, if nothing is
+//! selected), Escape or closing the window saves all of it, and Delete discards
+//! it. C, A, B and T switch between the crop, arrow, box and text tools, and
+//! Backspace removes the last annotation. Large screenshots are shown scaled
+//! down to fit on the screen, but are always annotated and cropped at full
+//! resolution.
+
+use crate::convert;
+use crate::settings::Settings;
+use crate::windows::{
+    create_visible_window, create_visible_window_class, destroy_window, draw_offscreen,
+    get_instance, message_loop, post_quit_message, show_window, to_wide_string,
+};
+use bindings::Windows::Win32::{
+    Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM},
+    Graphics::Gdi::{
+        BeginPaint, CreateFontW, CreatePen, DeleteObject, DrawFocusRect, EndPaint, InvalidateRect,
+        LineTo, MoveToEx, SelectObject, SetBkMode, SetStretchBltMode, SetTextColor, StretchDIBits,
+        TextOutW, ANTIALIASED_QUALITY, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, CLIP_DEFAULT_PRECIS,
+        DIB_RGB_COLORS, FF_SWISS, HALFTONE, HDC, OUT_DEFAULT_PRECIS, PAINTSTRUCT, PS_SOLID,
+        SRCCOPY, TRANSPARENT,
+    },
+    UI::{
+        KeyboardAndMouseInput::{ReleaseCapture, SetCapture},
+        WindowsAndMessaging::{
+            AdjustWindowRectEx, DefWindowProcW, GetSystemMetrics, SetWindowTextW, SM_CXSCREEN,
+            SM_CYSCREEN, VK_BACK, VK_DELETE, VK_ESCAPE, VK_RETURN, WM_CHAR, WM_DESTROY,
+            WM_ERASEBKGND, WM_KEYDOWN, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MOUSEMOVE, WM_PAINT,
+            WS_CAPTION, WS_EX_TOPMOST, WS_SYSMENU,
+        },
+    },
+};
+use image::{imageops, RgbImage};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// The class name of the quick crop window.
+const CLASS_NAME: &str = "SnASQuickCrop";
+
+/// The largest fraction of the screen that the screenshot is shown at.
+const MAX_SCREEN_FRACTION: f32 = 0.8;
+
+/// The colour of annotations, as a `COLORREF` (i.e. red).
+const ANNOTATION_COLOUR: u32 = 0x0000_00FF;
+
+/// The width of arrows and boxes, in on-screen pixels. Annotations are scaled
+/// up along with the screenshot when it is saved, so that they look the same
+/// in the saved file as they did in the window.
+const LINE_WIDTH: f32 = 4.0;
+
+/// The length of each side of an arrowhead, in on-screen pixels.
+const ARROWHEAD_LENGTH: f32 = 18.0;
+
+/// The angle between an arrow's shaft and each side of its head, in radians.
+const ARROWHEAD_ANGLE: f32 = 0.5;
+
+/// The height of text, in on-screen pixels.
+const FONT_HEIGHT: f32 = 28.0;
+
+/// The typeface and weight of text.
+const FONT_NAME: &str = "Segoe UI";
+const FONT_WEIGHT_BOLD: i32 = 700;
+
+/// `DEFAULT_CHARSET`, for `CreateFontW`.
+const DEFAULT_CHARSET: u32 = 1;
+
+/// What happens to the screenshot when the window closes.
+#[derive(Clone, Copy, PartialEq)]
+enum Outcome {
+    /// The selected part is saved.
+    Crop,
+
+    /// All of the screenshot is saved.
+    Keep,
+
+    /// Nothing is saved.
+    Discard,
+}
+
+/// What dragging (or clicking) on the screenshot does.
+#[derive(Clone, Copy, PartialEq)]
+enum Tool {
+    /// Selects the part of the screenshot to save.
+    Crop,
+
+    /// Draws an arrow, pointing to where the mouse button is released.
+    Arrow,
+
+    /// Draws a box.
+    Box,
+
+    /// Types text where the screenshot is clicked.
+    Text,
+}
+
+impl Tool {
+    /// Returns the tool that the key with virtual-key code `key` switches to.
+    fn from_key(key: u32) -> Option<Self> {
+        match char::from_u32(key)? {
+            'C' => Some(Tool::Crop),
+            'A' => Some(Tool::Arrow),
+            'B' => Some(Tool::Box),
+            'T' => Some(Tool::Text),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the tool, as shown in the window's title.
+    fn name(self) -> &'static str {
+        match self {
+            Tool::Crop => "Crop",
+            Tool::Arrow => "Arrow",
+            Tool::Box => "Box",
+            Tool::Text => "Text",
+        }
+    }
+}
+
+/// An annotation drawn on the screenshot, in screenshot coordinates.
+enum Annotation {
+    Arrow { from: (i32, i32), to: (i32, i32) },
+    Box { from: (i32, i32), to: (i32, i32) },
+    Text { at: (i32, i32), text: String },
+}
+
+/// The screenshot being cropped.
+struct Crop {
+    /// The screenshot's pixels, as top-down BGRA rows, for painting.
+    pixels: Vec<u8>,
+
+    /// The screenshot's dimensions, in pixels.
+    width: i32,
+    height: i32,
+
+    /// How much the screenshot is scaled by, to fit in the window.
+    scale: f32,
+
+    tool: Tool,
+
+    /// Where the mouse button was pressed, in client coordinates, whilst it is
+    /// held down to select a region or draw an annotation.
+    anchor: Option<(i32, i32)>,
+
+    /// The selection, in client coordinates, if there is one.
+    selection: Option<RECT>,
+
+    /// The annotations, in the order that they were drawn.
+    annotations: Vec<Annotation>,
+
+    /// Whether or not the last annotation is text that is still being typed.
+    editing_text: bool,
+
+    outcome: Outcome,
+}
+
+impl Crop {
+    /// Returns the size of the window's client area.
+    fn client_size(&self) -> (i32, i32) {
+        (
+            ((self.width as f32 * self.scale) as i32).max(1),
+            ((self.height as f32 * self.scale) as i32).max(1),
+        )
+    }
+
+    /// Returns the point in client coordinates encoded in `l_param`, clamped
+    /// to the screenshot.
+    fn point(&self, l_param: LPARAM) -> (i32, i32) {
+        let (client_width, client_height) = self.client_size();
+
+        (
+            ((l_param.0 & 0xFFFF) as i16 as i32).clamp(0, client_width),
+            ((l_param.0 >> 16 & 0xFFFF) as i16 as i32).clamp(0, client_height),
+        )
+    }
+
+    /// Converts `(x, y)` from client coordinates to screenshot coordinates.
+    fn to_image(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (
+            (x as f32 / self.scale) as i32,
+            (y as f32 / self.scale) as i32,
+        )
+    }
+
+    /// Stops typing text, removing the text annotation if nothing was typed.
+    fn finish_text(&mut self) {
+        if !self.editing_text {
+            return;
+        }
+
+        self.editing_text = false;
+
+        if let Some(Annotation::Text { text, .. }) = self.annotations.last() {
+            if text.is_empty() {
+                self.annotations.pop();
+            }
+        }
+    }
+}
+
+lazy_static! {
+    /// The screenshot being cropped, whilst the window is open.
+    static ref CROP: Mutex<Option<Crop>> = Mutex::new(None);
+}
+
+/// Returns whether or not each screenshot is shown for cropping before it is
+/// saved.
+pub fn is_enabled() -> bool {
+    let mut enabled = false;
+    Settings::read(|s| enabled = s.program.quick_crop);
+
+    enabled
+}
+
+/// Shows `image` in the quick crop window, until it is closed, returning the
+/// annotated part of it to save, or `None` if it was discarded. If the window
+/// can't be opened, all of `image` is returned.
+pub fn crop(image: RgbImage) -> Option<RgbImage> {
+    let (screen_width, screen_height) =
+        unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+
+    let scale = (screen_width as f32 * MAX_SCREEN_FRACTION / image.width() as f32)
+        .min(screen_height as f32 * MAX_SCREEN_FRACTION / image.height() as f32)
+        .min(1.0);
+
+    let crop = Crop {
+        pixels: convert::image_to_bgra(&image),
+        width: image.width() as i32,
+        height: image.height() as i32,
+        scale,
+        tool: Tool::Crop,
+        anchor: None,
+        selection: None,
+        annotations: Vec::new(),
+        editing_text: false,
+        outcome: Outcome::Keep,
+    };
+    let client_size = crop.client_size();
+
+    *CROP.lock().unwrap() = Some(crop);
+
+    if let Err(e) = open(client_size) {
+        warn!("Failed to open the quick crop window: {:#?}", e);
+        CROP.lock().unwrap().take();
+
+        return Some(image);
+    }
+
+    message_loop(HWND(0));
+
+    let mut crop = CROP.lock().unwrap().take()?;
+
+    if crop.outcome == Outcome::Discard {
+        info!("Screenshot discarded from the quick crop window");
+        return None;
+    }
+
+    crop.finish_text();
+
+    let image = if crop.annotations.is_empty() {
+        image
+    } else {
+        info!(
+            "Drawing {} annotations on screenshot",
+            crop.annotations.len()
+        );
+
+        match render(&crop) {
+            Ok(annotated) => annotated,
+            Err(e) => {
+                warn!("Failed to draw annotations on screenshot: {:#?}", e);
+                image
+            }
+        }
+    };
+
+    match (crop.outcome, crop.selection) {
+        (Outcome::Crop, Some(selection)) => {
+            let x = ((selection.left as f32 / scale) as u32).min(image.width() - 1);
+            let y = ((selection.top as f32 / scale) as u32).min(image.height() - 1);
+            let width = ((selection.right as f32 / scale) as u32).min(image.width()) - x;
+            let height = ((selection.bottom as f32 / scale) as u32).min(image.height()) - y;
+
+            info!(
+                "Cropping screenshot to {}x{} at ({}, {})",
+                width, height, x, y
+            );
+
+            Some(imageops::crop_imm(&image, x, y, width.max(1), height.max(1)).to_image())
+        }
+        _ => Some(image),
+    }
+}
+
+/// Opens the quick crop window, with a client area of `(width, height)`.
+fn open((width, height): (i32, i32)) -> windows::Result<()> {
+    static CLASS_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+    let instance = get_instance()?;
+
+    // Window classes can only be registered once per process
+    let class = if CLASS_REGISTERED.swap(true, Ordering::SeqCst) {
+        to_wide_string(CLASS_NAME)
+    } else {
+        create_visible_window_class(instance, CLASS_NAME, Some(window_proc))?
+    };
+
+    let styles = (WS_EX_TOPMOST, WS_CAPTION | WS_SYSMENU);
+
+    let mut window_rect = RECT {
+        left: 0,
+        top: 0,
+        right: width,
+        bottom: height,
+    };
+    unsafe { AdjustWindowRectEx(&mut window_rect, styles.1, false, styles.0) };
+
+    let window = create_visible_window(
+        instance,
+        &class,
+        &title(Tool::Crop),
+        styles,
+        (
+            window_rect.right - window_rect.left,
+            window_rect.bottom - window_rect.top,
+        ),
+    )?;
+
+    show_window(window);
+
+    Ok(())
+}
+
+/// Returns the title of the window, whilst `tool` is selected.
+fn title(tool: Tool) -> String {
+    format!(
+        "Crop Screenshot ({} tool) - Enter to save, Esc to save all, Delete to discard",
+        tool.name()
+    )
+}
+
+/// Draws the annotated screenshot at full resolution.
+fn render(crop: &Crop) -> windows::Result<RgbImage> {
+    let pixels = draw_offscreen(crop.width, crop.height, |dc| unsafe {
+        draw_screenshot(dc, crop, (crop.width, crop.height));
+        draw_annotations(dc, crop, 1.0);
+    })?;
+
+    Ok(convert::bgrx_to_image(
+        crop.width as u32,
+        crop.height as u32,
+        &pixels,
+    ))
+}
+
+/// Draws the screenshot on `dc`, stretched to `(width, height)`.
+unsafe fn draw_screenshot(dc: HDC, crop: &Crop, (width, height): (i32, i32)) {
+    let info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: crop.width,
+            // A negative height means top-down rows
+            biHeight: -crop.height,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB as u32,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    SetStretchBltMode(dc, HALFTONE);
+    StretchDIBits(
+        dc,
+        0,
+        0,
+        width,
+        height,
+        0,
+        0,
+        crop.width,
+        crop.height,
+        crop.pixels.as_ptr() as *const _,
+        &info,
+        DIB_RGB_COLORS,
+        SRCCOPY,
+    );
+}
+
+/// Draws the annotations on `dc`, in which the screenshot is drawn `scale`
+/// times its actual size.
+unsafe fn draw_annotations(dc: HDC, crop: &Crop, scale: f32) {
+    // Sizes are given in on-screen pixels, so are scaled relative to the window
+    let size = |length: f32| length / crop.scale * scale;
+    let point = |(x, y): (i32, i32)| ((x as f32 * scale) as i32, (y as f32 * scale) as i32);
+
+    let pen = CreatePen(
+        PS_SOLID,
+        size(LINE_WIDTH).max(1.0) as i32,
+        ANNOTATION_COLOUR,
+    );
+    let font = CreateFontW(
+        -(size(FONT_HEIGHT).max(1.0) as i32),
+        0,
+        0,
+        0,
+        FONT_WEIGHT_BOLD,
+        0,
+        0,
+        0,
+        DEFAULT_CHARSET,
+        OUT_DEFAULT_PRECIS,
+        CLIP_DEFAULT_PRECIS,
+        ANTIALIASED_QUALITY,
+        FF_SWISS,
+        FONT_NAME,
+    );
+
+    let previous_pen = SelectObject(dc, pen);
+    let previous_font = SelectObject(dc, font);
+    SetBkMode(dc, TRANSPARENT);
+    SetTextColor(dc, ANNOTATION_COLOUR);
+
+    for (i, annotation) in crop.annotations.iter().enumerate() {
+        match annotation {
+            Annotation::Arrow { from, to } => {
+                let (from_x, from_y) = point(*from);
+                let (to_x, to_y) = point(*to);
+
+                MoveToEx(dc, from_x, from_y, ptr::null_mut());
+                LineTo(dc, to_x, to_y);
+
+                let angle = ((to_y - from_y) as f32).atan2((to_x - from_x) as f32);
+
+                for side in &[-ARROWHEAD_ANGLE, ARROWHEAD_ANGLE] {
+                    MoveToEx(dc, to_x, to_y, ptr::null_mut());
+                    LineTo(
+                        dc,
+                        to_x - (size(ARROWHEAD_LENGTH) * (angle + side).cos()) as i32,
+                        to_y - (size(ARROWHEAD_LENGTH) * (angle + side).sin()) as i32,
+                    );
+                }
+            }
+            Annotation::Box { from, to } => {
+                let (left, top) = point(*from);
+                let (right, bottom) = point(*to);
+
+                MoveToEx(dc, left, top, ptr::null_mut());
+                LineTo(dc, right, top);
+                LineTo(dc, right, bottom);
+                LineTo(dc, left, bottom);
+                LineTo(dc, left, top);
+            }
+            Annotation::Text { at, text } => {
+                let (x, y) = point(*at);
+
+                // Text is always finished before the screenshot is saved, so this is only ever
+                // shown in the window
+                let caret = crop.editing_text && i == crop.annotations.len() - 1;
+
+                let text = if caret {
+                    format!("{}|", text)
+                } else {
+                    text.clone()
+                };
+
+                TextOutW(dc, x, y, text.as_str(), text.encode_utf16().count() as i32);
+            }
+        }
+    }
+
+    SelectObject(dc, previous_font);
+    SelectObject(dc, previous_pen);
+    DeleteObject(font);
+    DeleteObject(pen);
+}
+
+/// `WM_PAINT` message processor.
+fn on_paint(window: HWND) -> LRESULT {
+    let crop = CROP.lock().unwrap();
+
+    unsafe {
+        let mut paint = PAINTSTRUCT::default();
+        let dc = BeginPaint(window, &mut paint);
+
+        if let Some(crop) = crop.as_ref() {
+            draw_screenshot(dc, crop, crop.client_size());
+            draw_annotations(dc, crop, crop.scale);
+
+            if let Some(selection) = &crop.selection {
+                DrawFocusRect(dc, selection);
+            }
+        }
+
+        EndPaint(window, &paint);
+    }
+
+    LRESULT(0)
+}
+
+/// `WM_LBUTTONDOWN` message processor, which starts a new selection, or a new
+/// annotation.
+fn on_left_button_down(window: HWND, l_param: LPARAM) -> LRESULT {
+    if let Some(crop) = CROP.lock().unwrap().as_mut() {
+        crop.finish_text();
+
+        let point = crop.point(l_param);
+        let at = crop.to_image(point);
+
+        match crop.tool {
+            Tool::Crop => crop.selection = None,
+            Tool::Arrow => crop
+                .annotations
+                .push(Annotation::Arrow { from: at, to: at }),
+            Tool::Box => crop.annotations.push(Annotation::Box { from: at, to: at }),
+            Tool::Text => {
+                crop.annotations.push(Annotation::Text {
+                    at,
+                    text: String::new(),
+                });
+                crop.editing_text = true;
+            }
+        }
+
+        if crop.tool != Tool::Text {
+            crop.anchor = Some(point);
+            unsafe { SetCapture(window) };
+        }
+    }
+
+    unsafe { InvalidateRect(window, ptr::null(), false) };
+
+    LRESULT(0)
+}
+
+/// `WM_MOUSEMOVE` message processor, which resizes the selection, or the
+/// annotation being drawn, whilst the mouse button is held down.
+fn on_mouse_move(window: HWND, l_param: LPARAM) -> LRESULT {
+    if let Some(crop) = CROP.lock().unwrap().as_mut() {
+        let (anchor_x, anchor_y) = match crop.anchor {
+            Some(anchor) => anchor,
+            None => return LRESULT(0),
+        };
+        let (x, y) = crop.point(l_param);
+        let at = crop.to_image((x, y));
+
+        match (crop.tool, crop.annotations.last_mut()) {
+            (Tool::Crop, _) => {
+                crop.selection = Some(RECT {
+                    left: anchor_x.min(x),
+                    top: anchor_y.min(y),
+                    right: anchor_x.max(x),
+                    bottom: anchor_y.max(y),
+                })
+                .filter(|r| r.right > r.left && r.bottom > r.top);
+            }
+            (_, Some(Annotation::Arrow { to, .. })) | (_, Some(Annotation::Box { to, .. })) => {
+                *to = at;
+            }
+            _ => {}
+        }
+    }
+
+    unsafe { InvalidateRect(window, ptr::null(), false) };
+
+    LRESULT(0)
+}
+
+/// `WM_LBUTTONUP` message processor, which finishes the selection, or the
+/// annotation being drawn. Annotations with no length are removed.
+fn on_left_button_up() -> LRESULT {
+    if let Some(crop) = CROP.lock().unwrap().as_mut() {
+        if crop.anchor.take().is_some() {
+            match crop.annotations.last() {
+                Some(Annotation::Arrow { from, to }) | Some(Annotation::Box { from, to })
+                    if crop.tool != Tool::Crop && from == to =>
+                {
+                    crop.annotations.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    unsafe { ReleaseCapture() };
+
+    LRESULT(0)
+}
+
+/// `WM_KEYDOWN` message processor.
+fn on_key_down(window: HWND, w_param: WPARAM) -> LRESULT {
+    let key = w_param.0 as u32;
+
+    {
+        let mut crop = CROP.lock().unwrap();
+
+        let crop = match crop.as_mut() {
+            Some(crop) => crop,
+            None => return LRESULT(0),
+        };
+
+        // Whilst typing, keys are handled as characters, except those that finish the text
+        if crop.editing_text {
+            if key == VK_RETURN || key == VK_ESCAPE {
+                crop.finish_text();
+                unsafe { InvalidateRect(window, ptr::null(), false) };
+            }
+
+            return LRESULT(0);
+        }
+
+        if let Some(tool) = Tool::from_key(key) {
+            crop.tool = tool;
+            unsafe { SetWindowTextW(window, title(tool).as_str()) };
+
+            return LRESULT(0);
+        }
+
+        crop.outcome = match key {
+            VK_BACK => {
+                crop.annotations.pop();
+                unsafe { InvalidateRect(window, ptr::null(), false) };
+
+                return LRESULT(0);
+            }
+            VK_RETURN => Outcome::Crop,
+            VK_ESCAPE => Outcome::Keep,
+            VK_DELETE => Outcome::Discard,
+            _ => return LRESULT(0),
+        };
+    }
+
+    destroy_window(window);
+
+    LRESULT(0)
+}
+
+/// `WM_CHAR` message processor, which types into the text being edited.
+fn on_char(window: HWND, w_param: WPARAM) -> LRESULT {
+    /// The character sent for the Backspace key.
+    const BACKSPACE: char = '\u{8}';
+
+    if let Some(crop) = CROP.lock().unwrap().as_mut() {
+        if !crop.editing_text {
+            return LRESULT(0);
+        }
+
+        if let Some(Annotation::Text { text, .. }) = crop.annotations.last_mut() {
+            match char::from_u32(w_param.0 as u32) {
+                Some(BACKSPACE) => {
+                    text.pop();
+                }
+                Some(c) if !c.is_control() => text.push(c),
+                _ => {}
+            }
+        }
+    }
+
+    unsafe { InvalidateRect(window, ptr::null(), false) };
+
+    LRESULT(0)
+}
+
+/// `wndProc` for the quick crop window.
+// noinspection RsUnreachablePatterns
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_PAINT => on_paint(window),
+        // The screenshot covers the whole client area, so erasing it first only causes flicker
+        WM_ERASEBKGND => LRESULT(1),
+        WM_LBUTTONDOWN => on_left_button_down(window, l_param),
+        WM_MOUSEMOVE => on_mouse_move(window, l_param),
+        WM_LBUTTONUP => on_left_button_up(),
+        WM_KEYDOWN => on_key_down(window, w_param),
+        WM_CHAR => on_char(window, w_param),
+        WM_DESTROY => {
+            post_quit_message(0);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(window, message, w_param, l_param),
+    }
+}
@@ -0,0 +1,223 @@
+//! The guided detection test, which checks why screenshots are, or aren't,
+//! being detected, e.g. after a Windows update changes how Snip & Sketch
+//! writes to the clipboard.
+//!
+//! The user is asked to take a snip, then the next image put on the clipboard
+//! is checked against each heuristic in turn, and the results are reported,
+//! along with a suggested change to `settings.toml`, if it wasn't detected.
+
+use crate::heuristics::{self, Diagnosis};
+use crate::modal::{self, Dialog};
+use crate::settings::{ClipboardOwner, Settings};
+use crate::windows::{message_box, task_dialog, Clipboard, TaskDialogIcon};
+use bindings::Windows::Win32::UI::WindowsAndMessaging::{
+    IDOK, MB_ICONINFORMATION, MB_OKCANCEL, MB_SETFOREGROUND,
+};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the user to take a snip, once they start the test.
+const TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+/// The title of the test's dialogs.
+const DIALOG_TITLE: &str = "Test Detection";
+
+lazy_static! {
+    /// When the running test stops waiting for a snip, if one is running.
+    static ref DEADLINE: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+/// A change to `settings.toml` that would make the tested snip be detected.
+enum Suggestion {
+    /// Add the clipboard owner to the allowlist.
+    AddOwner(String),
+
+    /// Stop requiring the Snip & Sketch clipboard formats from the clipboard
+    /// owner.
+    DontRequireFormats(String),
+}
+
+impl Suggestion {
+    /// Returns the suggestion, as an instruction to the user.
+    fn describe(&self) -> String {
+        match self {
+            Self::AddOwner(process) => format!(
+                "Add {} to the clipboard owner allowlist, by adding this to settings.toml:\n\n\
+                 [[heuristics.clipboard_owners]]\nprocess = \"{}\"",
+                process, process
+            ),
+            Self::DontRequireFormats(process) => format!(
+                "Stop requiring the Snip & Sketch clipboard formats from {}, by setting \
+                 require_snip_formats = false in its [[heuristics.clipboard_owners]] entry in \
+                 settings.toml.",
+                process
+            ),
+        }
+    }
+
+    /// Makes the suggested change to the settings.
+    fn apply(&self) {
+        Settings::write(|s| match self {
+            Self::AddOwner(process) => s.heuristics.clipboard_owners.push(ClipboardOwner {
+                process: process.clone(),
+                require_snip_formats: false,
+            }),
+            Self::DontRequireFormats(process) => {
+                for owner in s.heuristics.clipboard_owners.iter_mut() {
+                    if owner.process.eq_ignore_ascii_case(process) {
+                        owner.require_snip_formats = false;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Starts the detection test, after explaining it to the user.
+pub fn start() {
+    modal::open(Dialog::DetectionTest, || {
+        let response = message_box(
+            DIALOG_TITLE,
+            "Take a snip with Snip & Sketch (or the screenshot tool that you use) after \
+             clicking OK.\n\nThe snip will be checked against each of the heuristics that \
+             screenshots are detected with, and the results will be shown.",
+            MB_OKCANCEL | MB_ICONINFORMATION | MB_SETFOREGROUND,
+        );
+
+        if response == IDOK {
+            info!("Detection test started - waiting for a snip");
+            *DEADLINE.lock().unwrap() = Some(Instant::now() + TIMEOUT);
+        }
+    });
+}
+
+/// Checks the clipboard data against each heuristic, if the detection test is
+/// waiting for a snip, then reports the results to the user. This ends the
+/// test.
+pub fn on_clipboard_update(clipboard: &Clipboard) {
+    let waiting = match DEADLINE.lock().unwrap().take() {
+        Some(deadline) => Instant::now() < deadline,
+        None => false,
+    };
+
+    if !waiting || !heuristics::clipboard_has_image(clipboard) {
+        return;
+    }
+
+    let diagnosis = heuristics::diagnose(clipboard);
+
+    modal::open(Dialog::DetectionTest, move || {
+        if let Err(e) = report(&diagnosis) {
+            warn!("Failed to show detection test results: {:#?}", e);
+        }
+    });
+}
+
+/// Shows the results of the detection test, and offers to apply the suggested
+/// settings change, if there is one.
+fn report(diagnosis: &Diagnosis) -> windows::Result<()> {
+    let check = |passed: bool| if passed { "Passed" } else { "Failed" };
+
+    let mut content = String::new();
+
+    match &diagnosis.owner_process {
+        Ok(process) => content += &format!("Clipboard owner: {}\n", process),
+        Err(e) => content += &format!("Clipboard owner: unknown ({})\n", e),
+    }
+
+    if let Some(package) = &diagnosis.package {
+        content += &format!("App package: {}\n", package);
+    }
+
+    content += &format!(
+        "\nOwner is in the allowlist: {}\n",
+        check(diagnosis.matched_owner.is_some())
+    );
+    content += &format!(
+        "Clipboard contains a bitmap: {}\n",
+        check(diagnosis.has_image)
+    );
+
+    let require_snip_formats =
+        !matches!(&diagnosis.matched_owner, Some(owner) if !owner.require_snip_formats);
+
+    content += &format!(
+        "Snip & Sketch clipboard formats: {}{}",
+        check(diagnosis.missing_snip_formats.is_empty()),
+        if require_snip_formats {
+            ""
+        } else {
+            " (not required)"
+        }
+    );
+
+    if !diagnosis.missing_snip_formats.is_empty() {
+        content += &format!(
+            "\nMissing formats: {}",
+            diagnosis.missing_snip_formats.join(", ")
+        );
+    }
+
+    let suggestion = suggest(diagnosis);
+    let details = suggestion.as_ref().map(Suggestion::describe);
+
+    info!(
+        "Detection test finished - snip {}",
+        if diagnosis.is_detected() {
+            "detected"
+        } else {
+            "not detected"
+        }
+    );
+
+    let action = task_dialog(
+        DIALOG_TITLE,
+        if diagnosis.is_detected() {
+            TaskDialogIcon::Information
+        } else {
+            TaskDialogIcon::Error
+        },
+        if diagnosis.is_detected() {
+            "The snip was detected as a screenshot"
+        } else {
+            "The snip wasn't detected as a screenshot"
+        },
+        &content,
+        details.as_deref(),
+        if suggestion.is_some() {
+            &["Apply Suggested Change"]
+        } else {
+            &[]
+        },
+    )?;
+
+    if let (Some(0), Some(suggestion)) = (action, suggestion) {
+        info!(
+            "Applying detection test suggestion: {}",
+            suggestion.describe()
+        );
+        suggestion.apply();
+    }
+
+    Ok(())
+}
+
+/// Returns the settings change that would make the tested snip be detected, if
+/// there is one.
+fn suggest(diagnosis: &Diagnosis) -> Option<Suggestion> {
+    if diagnosis.is_detected() || !diagnosis.has_image {
+        return None;
+    }
+
+    let process = diagnosis.owner_image_name()?.to_owned();
+
+    match &diagnosis.matched_owner {
+        None => Some(Suggestion::AddOwner(process)),
+        Some(owner) if owner.require_snip_formats => {
+            Some(Suggestion::DontRequireFormats(owner.process.clone()))
+        }
+        Some(_) => None,
+    }
+}
@@ -0,0 +1,157 @@
+//! A small window, which archives image files that are dragged onto it,
+//! through the normal screenshot save pipeline.
+
+use crate::animation::Animation;
+use crate::heuristics::CaptureSource;
+use crate::orientation;
+use crate::save::{self, Snapshot};
+use crate::windows::{
+    create_visible_window, create_visible_window_class, get_instance, show_window,
+    take_dropped_files, to_wide_string,
+};
+use bindings::Windows::Win32::{
+    Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM},
+    Graphics::Gdi::{
+        BeginPaint, DrawTextW, EndPaint, DT_CENTER, DT_SINGLELINE, DT_VCENTER, PAINTSTRUCT,
+    },
+    UI::{
+        Shell::HDROP,
+        WindowsAndMessaging::{
+            DefWindowProcW, GetClientRect, WM_DESTROY, WM_DROPFILES, WM_PAINT, WS_CAPTION,
+            WS_EX_ACCEPTFILES, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_SYSMENU,
+        },
+    },
+};
+use log::{info, warn};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::thread;
+
+/// The class name of the drop target window.
+const CLASS_NAME: &str = "SnASDropTarget";
+
+/// The title of the drop target window.
+const WINDOW_NAME: &str = "Archive Images";
+
+/// The text displayed inside the drop target window.
+static DROP_HINT: &str = "Drop images here to archive them";
+
+/// The handle of the drop target window, or `0` if it isn't open.
+static WINDOW: AtomicIsize = AtomicIsize::new(0);
+
+/// Opens the drop target window, or brings it to the foreground, if it is
+/// already open.
+pub fn show() -> windows::Result<()> {
+    static CLASS_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+    let existing_window = HWND(WINDOW.load(Ordering::SeqCst));
+
+    if !existing_window.is_null() {
+        show_window(existing_window);
+        return Ok(());
+    }
+
+    let instance = get_instance()?;
+
+    // Window classes can only be registered once per process
+    let class = if CLASS_REGISTERED.swap(true, Ordering::SeqCst) {
+        to_wide_string(CLASS_NAME)
+    } else {
+        create_visible_window_class(instance, CLASS_NAME, Some(window_proc))?
+    };
+
+    let window = create_visible_window(
+        instance,
+        &class,
+        WINDOW_NAME,
+        (
+            WS_EX_ACCEPTFILES | WS_EX_TOOLWINDOW | WS_EX_TOPMOST,
+            WS_CAPTION | WS_SYSMENU,
+        ),
+        (280, 140),
+    )?;
+
+    WINDOW.store(window.0, Ordering::SeqCst);
+    show_window(window);
+
+    Ok(())
+}
+
+/// Decodes an image file, then archives it, as if it were a new screenshot.
+/// Animated images are archived as they are.
+fn archive_file(path: &Path) {
+    info!("Archiving dropped file {}", path.display());
+
+    let animation = fs::read(path).ok().and_then(Animation::detect);
+    let snapshot = Snapshot::take();
+
+    match orientation::open_upright(path) {
+        Ok(image) => match animation {
+            Some(animation) => save::archive_animation(
+                image.to_rgb8(),
+                None,
+                CaptureSource::Local,
+                animation,
+                snapshot,
+            ),
+            None => save::archive(image.to_rgb8(), None, CaptureSource::Local, snapshot),
+        },
+        Err(e) => warn!("Unable to decode {}: {}", path.display(), e),
+    }
+}
+
+/// `WM_PAINT` message processor.
+fn on_paint(window: HWND) -> LRESULT {
+    unsafe {
+        let mut paint = PAINTSTRUCT::default();
+        let mut client_rect = RECT::default();
+
+        let dc = BeginPaint(window, &mut paint);
+        GetClientRect(window, &mut client_rect);
+
+        DrawTextW(
+            dc,
+            DROP_HINT,
+            -1,
+            &mut client_rect,
+            DT_CENTER | DT_VCENTER | DT_SINGLELINE,
+        );
+
+        EndPaint(window, &paint);
+    }
+
+    LRESULT(0)
+}
+
+/// `WM_DROPFILES` message processor.
+fn on_drop_files(w_param: WPARAM) -> LRESULT {
+    let paths = take_dropped_files(HDROP(w_param.0 as isize));
+
+    thread::spawn(move || {
+        for path in paths {
+            archive_file(&path);
+        }
+    });
+
+    LRESULT(0)
+}
+
+/// `wndProc` for the drop target window.
+// noinspection RsUnreachablePatterns
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_PAINT => on_paint(window),
+        WM_DROPFILES => on_drop_files(w_param),
+        WM_DESTROY => {
+            WINDOW.store(0, Ordering::SeqCst);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(window, message, w_param, l_param),
+    }
+}
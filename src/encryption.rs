@@ -0,0 +1,341 @@
+//! Encrypted screenshot archive support.
+//!
+//! When encryption is enabled, screenshots are appended to a single archive
+//! file in the screenshot directory, rather than being written out as plaintext
+//! PNG files. Each entry is encrypted with AES-256-GCM, using a randomly
+//! generated key, which is itself stored on disk wrapped with DPAPI, so that it
+//! can only be unwrapped by the Windows user that created it.
+//!
+//! The archive is a sequence of entries, each laid out as:
+//!
+//! | Field       | Size           |
+//! |-------------|----------------|
+//! | Name length | 2 bytes (LE)   |
+//! | Name        | UTF-8 string   |
+//! | Nonce       | 12 bytes       |
+//! | Data length | 4 bytes (LE)   |
+//! | Data        | AES-GCM output |
+//!
+//! Entry names are unique within an archive, and are authenticated as the
+//! associated data of their entry, so that they can't be swapped around.
+
+use crate::settings;
+use crate::windows::{dpapi_protect, dpapi_unprotect};
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// The name of the archive file, within the screenshot directory.
+pub const ARCHIVE_FILE: &str = "Screenshots.snas";
+
+/// The name of the file within the config directory that the DPAPI-wrapped
+/// archive key is stored in.
+const KEY_FILE: &str = "archive.key";
+
+/// Errors that can occur whilst reading or writing an encrypted archive.
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Unable to wrap or unwrap the archive key: {0}")]
+    KeyProtection(#[from] windows::Error),
+    #[error("Archive key is corrupt")]
+    InvalidKey,
+    #[error("There is no archive key on this machine, so the archive can't be decrypted")]
+    MissingKey,
+    #[error("Archive entry {0} could not be encrypted or decrypted")]
+    Cipher(String),
+    #[error("Archive is truncated or corrupt")]
+    InvalidArchive,
+    #[error("Archive entry {0} is too large to store")]
+    EntryTooLarge(String),
+    #[error("Every name for archive entry {0} is taken")]
+    NamesExhausted(String),
+}
+
+/// The most names that are tried for an entry, before giving up.
+const MAX_NAME_ATTEMPTS: u32 = 1000;
+
+lazy_static! {
+    /// Serialises appends to archives, so that concurrent saves don't
+    /// interleave their entries.
+    static ref ARCHIVE_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Serialises creation of the archive key, so that concurrent first saves
+    /// don't each generate (and encrypt entries with) a different key.
+    static ref KEY_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Returns the path to the DPAPI-wrapped archive key.
+fn key_file_path() -> PathBuf {
+    settings::config_dir().join(KEY_FILE)
+}
+
+/// Loads the archive key from disk, failing if it doesn't exist, as a new key
+/// couldn't decrypt anything that is already archived.
+fn load_key() -> Result<Aes256Gcm, EncryptionError> {
+    let key_path = key_file_path();
+
+    if !key_path.exists() {
+        return Err(EncryptionError::MissingKey);
+    }
+
+    let key: [u8; 32] = dpapi_unprotect(&fs::read(&key_path)?)?
+        .as_slice()
+        .try_into()
+        .map_err(|_| EncryptionError::InvalidKey)?;
+
+    Ok(Aes256Gcm::new(&Key::from(key)))
+}
+
+/// Loads the archive key from disk, generating (and storing) a new key if one
+/// does not exist yet.
+fn load_or_create_key() -> Result<Aes256Gcm, EncryptionError> {
+    let _lock = KEY_LOCK.lock().unwrap();
+    let key_path = key_file_path();
+
+    if key_path.exists() {
+        return load_key();
+    }
+
+    let key = rand::random::<[u8; 32]>();
+    let protected = dpapi_protect(&key)?;
+
+    fs::create_dir_all(key_path.parent().unwrap())?;
+
+    // Another copy of the program may have created the key in the meantime, in
+    // which case its key must be used, rather than replaced
+    let mut key_file = match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&key_path)
+    {
+        Ok(key_file) => key_file,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return load_key(),
+        Err(e) => return Err(e.into()),
+    };
+
+    if let Err(e) = key_file
+        .write_all(&protected)
+        .and_then(|_| key_file.sync_all())
+    {
+        drop(key_file);
+        let _ = fs::remove_file(&key_path);
+
+        return Err(e.into());
+    }
+
+    Ok(Aes256Gcm::new(&Key::from(key)))
+}
+
+/// Returns the first variant of `name` (`name` itself, then `name` with a
+/// numeric suffix) for which `taken` returns `false`.
+fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> Result<String, EncryptionError> {
+    let path = Path::new(name);
+
+    for attempt in 1..=MAX_NAME_ATTEMPTS {
+        let candidate = if attempt == 1 {
+            name.to_owned()
+        } else {
+            let mut candidate = path.file_stem().unwrap_or_default().to_os_string();
+            candidate.push(format!("_{}", attempt));
+
+            Path::new(&candidate)
+                .with_extension(path.extension().unwrap_or_default())
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        if !taken(&candidate) {
+            return Ok(candidate);
+        }
+    }
+
+    Err(EncryptionError::NamesExhausted(name.into()))
+}
+
+/// Returns the names of the entries in the archive at `archive_path`, without
+/// decrypting them. A missing archive has no entries.
+fn entry_names(archive_path: &Path) -> Result<HashSet<String>, EncryptionError> {
+    let mut names = HashSet::new();
+
+    let file = match File::open(archive_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(names),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut reader = BufReader::new(file);
+
+    while let Some(name) = read_entry_name(&mut reader)? {
+        let mut data_length = [0; 4];
+
+        reader
+            .seek_relative(12)
+            .and_then(|_| reader.read_exact(&mut data_length))
+            .map_err(|_| EncryptionError::InvalidArchive)?;
+
+        reader.seek_relative(u32::from_le_bytes(data_length) as i64)?;
+        names.insert(name);
+    }
+
+    Ok(names)
+}
+
+/// Encrypts `data`, then appends it to the archive at `archive_path`, under the
+/// entry name `name` (or, if an entry already has that name, `name` with a
+/// numeric suffix). The archive is created if it doesn't already exist.
+///
+/// Returns the name that the entry was stored under.
+pub fn append_to_archive(
+    archive_path: &Path,
+    name: &str,
+    data: &[u8],
+) -> Result<String, EncryptionError> {
+    let cipher = load_or_create_key()?;
+
+    let _lock = ARCHIVE_LOCK.lock().unwrap();
+
+    let existing = entry_names(archive_path)?;
+    let name = unique_name(name, |candidate| existing.contains(candidate))?;
+
+    let name_length: u16 = name
+        .len()
+        .try_into()
+        .map_err(|_| EncryptionError::EntryTooLarge(name.clone()))?;
+
+    let nonce = rand::random::<[u8; 12]>();
+
+    let encrypted = cipher
+        .encrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: data,
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| EncryptionError::Cipher(name.clone()))?;
+
+    let data_length: u32 = encrypted
+        .len()
+        .try_into()
+        .map_err(|_| EncryptionError::EntryTooLarge(name.clone()))?;
+
+    let mut entry = Vec::with_capacity(2 + name.len() + nonce.len() + 4 + encrypted.len());
+    entry.extend_from_slice(&name_length.to_le_bytes());
+    entry.extend_from_slice(name.as_bytes());
+    entry.extend_from_slice(&nonce);
+    entry.extend_from_slice(&data_length.to_le_bytes());
+    entry.extend_from_slice(&encrypted);
+
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(archive_path)?
+        .write_all(&entry)?;
+
+    Ok(name)
+}
+
+/// Decrypts every entry in the archive at `archive_path`, writing each entry
+/// to a separate file in `output_dir`. Existing files are never replaced - an
+/// entry whose name is taken is written with a numeric suffix instead.
+///
+/// Returns the number of entries that were extracted.
+pub fn extract_archive(archive_path: &Path, output_dir: &Path) -> Result<usize, EncryptionError> {
+    let cipher = load_key()?;
+    let mut reader = BufReader::new(File::open(archive_path)?);
+    let mut count = 0;
+
+    fs::create_dir_all(output_dir)?;
+
+    while let Some(name) = read_entry_name(&mut reader)? {
+        let mut nonce = [0; 12];
+        let mut data_length = [0; 4];
+
+        reader
+            .read_exact(&mut nonce)
+            .and_then(|_| reader.read_exact(&mut data_length))
+            .map_err(|_| EncryptionError::InvalidArchive)?;
+
+        let encrypted = read_exact_vec(&mut reader, u32::from_le_bytes(data_length) as usize)?;
+
+        let data = cipher
+            .decrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: encrypted.as_slice(),
+                    aad: name.as_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::Cipher(name.clone()))?;
+
+        // Entry names are generated by us, but guard against path traversal in
+        // tampered archives anyway
+        let file_name = Path::new(&name)
+            .file_name()
+            .ok_or(EncryptionError::InvalidArchive)?
+            .to_string_lossy();
+
+        write_new_file(output_dir, &file_name, &data)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Writes `data` to a new file named `name` (or `name` with a numeric suffix,
+/// if that is taken) in `dir`, without replacing any existing file.
+fn write_new_file(dir: &Path, name: &str, data: &[u8]) -> Result<(), EncryptionError> {
+    loop {
+        let name = unique_name(name, |candidate| dir.join(candidate).exists())?;
+
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(&name))
+        {
+            Ok(mut file) => return Ok(file.write_all(data)?),
+            // Something else created the file since it was checked for
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Reads the name of the next entry in an archive from `reader`, returning
+/// `None` at the end of the archive.
+fn read_entry_name(reader: &mut impl Read) -> Result<Option<String>, EncryptionError> {
+    let mut name_length = [0; 2];
+
+    match reader.read_exact(&mut name_length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let name = read_exact_vec(reader, u16::from_le_bytes(name_length) as usize)?;
+
+    String::from_utf8(name)
+        .map(Some)
+        .map_err(|_| EncryptionError::InvalidArchive)
+}
+
+/// Reads exactly `length` bytes from `reader`, treating a premature EOF as a
+/// corrupt archive.
+fn read_exact_vec(reader: &mut impl Read, length: usize) -> Result<Vec<u8>, EncryptionError> {
+    let mut buffer = vec![0; length];
+
+    reader
+        .read_exact(&mut buffer)
+        .map_err(|_| EncryptionError::InvalidArchive)?;
+
+    Ok(buffer)
+}
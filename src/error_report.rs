@@ -0,0 +1,99 @@
+//! Reporting of unexpected errors to the user.
+//!
+//! Errors are shown in a dialog, summarising the error and its causes, which
+//! can copy full diagnostics (program version, Windows version and recent log
+//! lines) to the clipboard, for inclusion in bug reports.
+
+use crate::logging;
+use crate::modal::{self, Dialog};
+use crate::windows::{error_task_dialog, get_windows_version, open_clipboard, set_clipboard_text};
+use log::error;
+use std::error::Error;
+
+/// The title of the error dialog.
+const DIALOG_TITLE: &str = "Snip & AutoSave";
+
+/// Logs `error`, then shows it to the user in an error dialog, on a separate
+/// thread. `summary` briefly describes what was being attempted when the
+/// error occurred.
+///
+/// If an error dialog is already open, it is brought to the foreground
+/// instead. The new error is still included in the open dialog's diagnostics,
+/// as they include the most recent log lines.
+pub fn report(summary: &str, error: &(dyn Error + 'static)) {
+    let causes = error_chain(error);
+    error!("{}: {}", summary, causes.join(": "));
+
+    let summary = summary.to_owned();
+
+    modal::open(Dialog::ErrorReport, move || show(&summary, &causes));
+}
+
+/// Shows a panic message in an error dialog. Unlike [`report`], this blocks
+/// until the dialog is closed, as the process may be aborted as soon as this
+/// function returns.
+///
+/// [`report`]: report
+pub fn report_panic(message: &str) {
+    let causes = vec![message.to_owned()];
+
+    show("Snip & AutoSave has stopped working", &causes);
+}
+
+/// Returns the messages of `error`, followed by those of each of its sources.
+fn error_chain(error: &(dyn Error + 'static)) -> Vec<String> {
+    let mut causes = vec![error.to_string()];
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        causes.push(error.to_string());
+        source = error.source();
+    }
+
+    causes
+}
+
+/// Shows the error dialog, copying diagnostics to the clipboard, if the user
+/// requests them.
+fn show(summary: &str, causes: &[String]) {
+    let details = causes
+        .iter()
+        .enumerate()
+        .map(|(i, cause)| {
+            if i == 0 {
+                cause.clone()
+            } else {
+                format!("Caused by: {}", cause)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    match error_task_dialog(DIALOG_TITLE, summary, &causes[0], &details, "Copy Details") {
+        Ok(true) => {
+            if let Err(e) = open_clipboard(None).and_then(|clipboard| {
+                set_clipboard_text(&clipboard, &diagnostics(summary, &details))
+            }) {
+                error!("Failed to copy error details to the clipboard: {}", e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => error!("Failed to show error dialog: {}", e),
+    }
+}
+
+/// Generates the full diagnostics for an error, to be copied into a bug report.
+fn diagnostics(summary: &str, details: &str) -> String {
+    let (major, minor, build) = get_windows_version();
+
+    format!(
+        "Snip & AutoSave {}\nWindows {}.{} (build {})\n\n{}\n{}\n\nRecent log:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        major,
+        minor,
+        build,
+        summary,
+        details,
+        logging::recent_lines().join("\n")
+    )
+}
@@ -0,0 +1,163 @@
+//! Capture pipeline event bus.
+//!
+//! The capture pipeline publishes an [`Event`] at each stage of handling a
+//! capture, which is delivered to every subscriber. This keeps UI side effects
+//! (notifications, etc.) out of the pipeline itself.
+//!
+//! [`Event`]: Event
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use lazy_static::lazy_static;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Something that happened in the capture pipeline.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// A new screenshot was found on the clipboard.
+    CaptureDetected,
+
+    /// A screenshot was saved to `path`. `incognito` is whether it was captured
+    /// in incognito mode, and `generation` is the [`save_queue`] generation
+    /// that it was captured in.
+    ///
+    /// [`save_queue`]: crate::save_queue
+    Saved {
+        path: PathBuf,
+        incognito: bool,
+        generation: usize,
+    },
+
+    /// A screenshot was deliberately not saved.
+    Skipped { reason: SkipReason },
+
+    /// A screenshot couldn't be saved.
+    Failed { kind: FailureKind, error: String },
+}
+
+/// Why a screenshot wasn't saved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    /// The screenshot is identical to a recently saved screenshot.
+    Duplicate,
+
+    /// The screenshot is almost identical to a recently saved screenshot.
+    NearDuplicate,
+
+    /// The screenshot was put back onto the clipboard by clipboard history.
+    Reannouncement,
+
+    /// Saving is paused.
+    Paused,
+
+    /// One of the program's own windows was in the foreground.
+    OwnWindow,
+
+    /// Saving is paused during Remote Desktop sessions.
+    RemoteSession,
+}
+
+impl SkipReason {
+    /// Every skip reason.
+    pub const ALL: [SkipReason; 6] = [
+        SkipReason::Duplicate,
+        SkipReason::NearDuplicate,
+        SkipReason::Reannouncement,
+        SkipReason::Paused,
+        SkipReason::OwnWindow,
+        SkipReason::RemoteSession,
+    ];
+
+    /// A short, machine-readable name for the reason.
+    pub fn label(self) -> &'static str {
+        match self {
+            SkipReason::Duplicate => "duplicate",
+            SkipReason::NearDuplicate => "near_duplicate",
+            SkipReason::Reannouncement => "reannouncement",
+            SkipReason::Paused => "paused",
+            SkipReason::OwnWindow => "own_window",
+            SkipReason::RemoteSession => "remote_session",
+        }
+    }
+
+    /// A human-readable explanation of why the screenshot wasn't saved.
+    pub fn description(self) -> &'static str {
+        match self {
+            SkipReason::Duplicate => "It's identical to a recently saved screenshot",
+            SkipReason::NearDuplicate => "It's almost identical to a recently saved screenshot",
+            SkipReason::Reannouncement => "It was put back on the clipboard by clipboard history",
+            SkipReason::Paused => "Saving screenshots is paused",
+            SkipReason::OwnWindow => "It was taken whilst a Snip & AutoSave window was open",
+            SkipReason::RemoteSession => {
+                "Saving screenshots is paused during Remote Desktop sessions"
+            }
+        }
+    }
+}
+
+/// The stage of the capture pipeline that a screenshot couldn't get past.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    /// The clipboard couldn't be read.
+    Clipboard,
+
+    /// The clipboard's image data couldn't be converted.
+    Conversion,
+
+    /// The screenshot couldn't be encoded.
+    Encode,
+
+    /// The screenshot couldn't be added to the encrypted archive.
+    Encryption,
+
+    /// The screenshot couldn't be written to disk.
+    Filesystem,
+}
+
+impl FailureKind {
+    /// Every failure kind.
+    pub const ALL: [FailureKind; 5] = [
+        FailureKind::Clipboard,
+        FailureKind::Conversion,
+        FailureKind::Encode,
+        FailureKind::Encryption,
+        FailureKind::Filesystem,
+    ];
+
+    /// A short, machine-readable name for the kind.
+    pub fn label(self) -> &'static str {
+        match self {
+            FailureKind::Clipboard => "clipboard",
+            FailureKind::Conversion => "conversion",
+            FailureKind::Encode => "encode",
+            FailureKind::Encryption => "encryption",
+            FailureKind::Filesystem => "filesystem",
+        }
+    }
+}
+
+lazy_static! {
+    /// The sending half of each subscriber's channel.
+    static ref SUBSCRIBERS: Mutex<Vec<Sender<Event>>> = Mutex::new(Vec::new());
+}
+
+/// Subscribes to every event published after this function returns.
+///
+/// Dropping the returned [`Receiver`] unsubscribes.
+///
+/// [`Receiver`]: Receiver
+pub fn subscribe() -> Receiver<Event> {
+    let (sender, receiver) = unbounded();
+    SUBSCRIBERS.lock().unwrap().push(sender);
+
+    receiver
+}
+
+/// Delivers `event` to every subscriber.
+pub fn publish(event: Event) {
+    // Subscribers whose receivers have been dropped are removed
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+}
@@ -0,0 +1,36 @@
+//! Explorer shell integration.
+
+use crate::settings;
+use crate::windows::{delete_user_registry_tree, set_user_registry_string};
+use std::env;
+
+/// The registry key of the folder context menu verb.
+const FOLDER_VERB_KEY: &str = r"Software\Classes\Directory\shell\SnipAndAutoSave";
+
+/// Registers a "Set as Snip & AutoSave folder" entry in the context menu of
+/// folders in Explorer, which runs this program's `set-folder` command.
+pub fn register_folder_verb() -> windows::Result<()> {
+    let exe_path = dunce::simplified(&env::current_exe().unwrap())
+        .to_string_lossy()
+        .into_owned();
+
+    set_user_registry_string(FOLDER_VERB_KEY, None, "Set as Snip && AutoSave folder")?;
+    set_user_registry_string(FOLDER_VERB_KEY, Some("Icon"), &exe_path)?;
+    set_user_registry_string(
+        &format!(r"{}\command", FOLDER_VERB_KEY),
+        None,
+        &match settings::portable_flag() {
+            Some(flag) => format!("\"{}\" {} set-folder \"%1\"", exe_path, flag),
+            None => format!("\"{}\" set-folder \"%1\"", exe_path),
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Removes the folder context menu entry added by [`register_folder_verb`].
+///
+/// [`register_folder_verb`]: register_folder_verb
+pub fn unregister_folder_verb() -> windows::Result<()> {
+    delete_user_registry_tree(FOLDER_VERB_KEY)
+}
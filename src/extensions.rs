@@ -1,33 +1,38 @@
 //! Extension methods for various types.
 
+use crate::hash_cache;
+use crate::history;
+use crate::migration::is_screenshot;
+use crate::perceptual_hash::PerceptualHash;
 use crate::settings::Settings;
-use bindings::Windows::Win32::Foundation::PSTR;
+use bindings::Windows::Win32::Foundation::PWSTR;
 use image::codecs::png::PngDecoder;
 use image::{ColorType, DynamicImage, ImageDecoder, RgbImage};
+use log::{debug, warn};
 use rayon::prelude::*;
-use std::ffi::CString;
-use std::fs::File;
+use std::fs::{File, Metadata};
+use std::os::windows::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::SystemTime;
 use std::{fs, io};
+use widestring::U16CString;
 
-/// Extension methods for [`CString`] instances.
+/// Extension methods for [`U16CString`] instances.
 ///
-/// [`CString`]: CString
-pub trait CStringExtensions {
-    /// Gets a [`PSTR`], which points to the character data of this string.
+/// [`U16CString`]: U16CString
+pub trait U16CStringExtensions {
+    /// Gets a [`PWSTR`], which points to the character data of this string.
     ///
     /// # Safety
     ///
     /// The returned pointer is always mutable. Many Win32 functions do not
     /// actually mutate the data, but this cannot be guaranteed.
-    unsafe fn as_pstr(&self) -> PSTR;
+    unsafe fn as_pwstr(&self) -> PWSTR;
 }
 
-impl CStringExtensions for CString {
-    unsafe fn as_pstr(&self) -> PSTR {
-        PSTR(self.as_ptr() as *mut u8)
+impl U16CStringExtensions for U16CString {
+    unsafe fn as_pwstr(&self) -> PWSTR {
+        PWSTR(self.as_ptr() as *mut u16)
     }
 }
 
@@ -35,18 +40,47 @@ impl CStringExtensions for CString {
 ///
 /// [`ImageBuffer`]: image::ImageBuffer
 pub trait ImageExtensions {
-    /// Returns whether or not this image is the same as the last captured
-    /// screenshot (i.e. has equal dimensions and pixel content).
-    fn is_same_as_last_screenshot(&self) -> bool;
+    /// Returns whether or not this image is the same as one of the last
+    /// `count` captured screenshots (i.e. has equal dimensions and pixel
+    /// content).
+    fn is_same_as_recent_screenshot(&self, count: usize) -> bool;
+
+    /// Returns whether or not this image is almost the same as one of the last
+    /// `count` captured screenshots (i.e. has equal dimensions, and a
+    /// perceptual hash differing by at most `max_distance` bits).
+    fn is_similar_to_recent_screenshot(&self, count: usize, max_distance: u32) -> bool;
 }
 
 impl ImageExtensions for RgbImage {
-    fn is_same_as_last_screenshot(&self) -> bool {
+    fn is_same_as_recent_screenshot(&self, count: usize) -> bool {
         let mut screenshot_path = PathBuf::new();
         Settings::read(|s| screenshot_path = s.paths.screenshots.clone());
 
-        if let Ok(Some(newest_file)) = newest_file_in_dir(&screenshot_path) {
-            println!(
+        // Comparing against the cached hashes avoids decoding recent screenshots from disk
+        let recent = hash_cache::recent_in_dir(&screenshot_path, count);
+
+        if !recent.is_empty() {
+            debug!(
+                "Comparing against {} recently saved screenshots",
+                recent.len()
+            );
+
+            // The image is hashed once, and only if it could match at all
+            if !recent.iter().any(|cached| cached.has_dimensions_of(self)) {
+                return false;
+            }
+
+            return match history::hash(self) {
+                Ok(hash) => recent.iter().any(|cached| cached.matches(self, &hash)),
+                Err(e) => {
+                    warn!("Failed to hash screenshot: {:#?}", e);
+                    false
+                }
+            };
+        }
+
+        if let Some(newest_file) = newest_screenshot_in_dir(&screenshot_path) {
+            debug!(
                 "Newest file in screenshot dir: {}",
                 newest_file.to_string_lossy()
             );
@@ -76,38 +110,76 @@ impl ImageExtensions for RgbImage {
 
         false
     }
+
+    fn is_similar_to_recent_screenshot(&self, count: usize, max_distance: u32) -> bool {
+        let mut screenshot_path = PathBuf::new();
+        Settings::read(|s| screenshot_path = s.paths.screenshots.clone());
+
+        let recent = hash_cache::recent_in_dir(&screenshot_path, count);
+
+        // The image is hashed once, and only if it could be similar at all
+        if !recent.iter().any(|cached| cached.has_dimensions_of(self)) {
+            return false;
+        }
+
+        let perceptual_hash = PerceptualHash::of(self);
+
+        recent
+            .iter()
+            .any(|cached| cached.is_similar_to(self, &perceptual_hash, max_distance))
+    }
 }
 
-/// Calculates in parallel, row by row, whether or not two images, with equal
-/// dimensions, have the same pixel content.
+/// Calculates whether or not two images have the same pixel content.
+///
+/// The images' raw sample buffers are compared in parallel, a chunk at a time,
+/// stopping as soon as any chunk differs. If the images' sample layouts differ
+/// (i.e. the same pixel isn't at the same offset in both buffers), they are
+/// compared pixel by pixel instead.
 fn image_content_is_equal(image_a: &RgbImage, image_b: &RgbImage) -> bool {
+    /// The number of bytes compared by each parallel task.
+    const CHUNK_SIZE: usize = 64 * 1024;
+
     if image_a.dimensions() != image_b.dimensions() {
         return false;
     }
 
-    let result = AtomicBool::new(true);
+    let layout = image_a.sample_layout();
 
-    image_a
-        .rows()
-        .zip(image_b.rows())
-        .par_bridge()
-        .for_each(|(row_a, row_b)| {
-            if !result.load(Ordering::Relaxed) {
-                // Skip processing rows once we know that the images aren't equal
-                return;
-            }
+    if layout != image_b.sample_layout() {
+        return image_a
+            .pixels()
+            .zip(image_b.pixels())
+            .all(|(pixel_a, pixel_b)| pixel_a == pixel_b);
+    }
 
-            for (a, b) in row_a.zip(row_b) {
-                if a != b {
-                    result.store(false, Ordering::Relaxed);
-                }
-            }
-        });
+    // The raw buffers may be longer than the image itself, so we only compare the image's samples
+    let length = layout.min_length().unwrap_or(0);
+    let raw_a = &image_a.as_raw()[..length];
+    let raw_b = &image_b.as_raw()[..length];
+
+    raw_a
+        .par_chunks(CHUNK_SIZE)
+        .zip(raw_b.par_chunks(CHUNK_SIZE))
+        .all(|(chunk_a, chunk_b)| chunk_a == chunk_b)
+}
 
-    result.into_inner()
+/// Gets the path to the newest screenshot in a directory, preferring the most
+/// recent screenshot recorded in the history, if it still exists, over
+/// scanning the directory.
+pub fn newest_screenshot_in_dir(dir: &Path) -> Option<PathBuf> {
+    history::latest_in_dir(dir).or_else(|| newest_file_in_dir(dir).ok().flatten())
 }
 
-/// Gets the path to the last-created file in a directory.
+/// Gets the path to the last-created screenshot file in a directory.
+///
+/// Files that aren't screenshots are ignored, including hidden and temporary
+/// files, such as those written by cloud sync clients (e.g. OneDrive's `~tmp`
+/// files) whilst they are uploading or downloading.
+///
+/// Files may be renamed or deleted by other writers (e.g. another machine saving
+/// to the same shared folder) whilst the directory is scanned, so files that
+/// disappear part way through are skipped, rather than failing the scan.
 ///
 /// Note that this function uses files' created at time, not modified at.
 fn newest_file_in_dir(dir: &Path) -> io::Result<Option<PathBuf>> {
@@ -121,14 +193,20 @@ fn newest_file_in_dir(dir: &Path) -> io::Result<Option<PathBuf>> {
     let mut newest_time = SystemTime::UNIX_EPOCH;
 
     for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => continue,
+        };
 
-        if !path.is_file() {
+        if !is_screenshot(&path) || is_hidden_or_temporary(&path, &metadata) {
             continue;
         }
 
-        let metadata = fs::metadata(&path)?;
         let created_at = metadata.created()?;
 
         if created_at > newest_time {
@@ -139,3 +217,18 @@ fn newest_file_in_dir(dir: &Path) -> io::Result<Option<PathBuf>> {
 
     Ok(newest_path)
 }
+
+/// Returns whether or not a file is hidden, or a temporary file.
+fn is_hidden_or_temporary(path: &Path, metadata: &Metadata) -> bool {
+    // https://docs.microsoft.com/en-us/windows/win32/fileio/file-attribute-constants
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    const FILE_ATTRIBUTE_TEMPORARY: u32 = 0x100;
+
+    let is_temporary_name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.starts_with('~') || name.starts_with('.'),
+        None => true,
+    };
+
+    is_temporary_name
+        || metadata.file_attributes() & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_TEMPORARY) != 0
+}
@@ -0,0 +1,142 @@
+//! An in-memory cache of the hashes of recently saved screenshots, so that
+//! duplicate detection can compare captures against several recent saves,
+//! without decoding any of them from disk.
+//!
+//! The cache is seeded from the history when it is first used, so that it is
+//! warm from the first capture after the program starts.
+
+use crate::history;
+use crate::perceptual_hash::PerceptualHash;
+use image::RgbImage;
+use lazy_static::lazy_static;
+use log::warn;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// How many saved screenshots are remembered, which is also the most that
+/// captures can be compared against.
+pub const CAPACITY: usize = 100;
+
+/// The hash and dimensions of a saved screenshot.
+#[derive(Clone)]
+pub struct CachedHash {
+    /// Where the screenshot was saved to.
+    pub path: PathBuf,
+
+    /// The width of the screenshot, in pixels.
+    pub width: u32,
+
+    /// The height of the screenshot, in pixels.
+    pub height: u32,
+
+    /// The hex-encoded SHA-256 hash of the screenshot's pixel data, as recorded
+    /// in the history.
+    pub hash: String,
+
+    /// The perceptual hash of the screenshot, if it is known. Screenshots
+    /// recorded in the history by older versions don't have one.
+    pub perceptual_hash: Option<PerceptualHash>,
+}
+
+impl CachedHash {
+    /// Returns whether or not `image` has the same dimensions as the cached
+    /// screenshot.
+    pub fn has_dimensions_of(&self, image: &RgbImage) -> bool {
+        image.dimensions() == (self.width, self.height)
+    }
+
+    /// Returns whether or not `image`, whose pixel data has the hex-encoded
+    /// SHA-256 hash `hash` (see [`history::hash`]), has the same dimensions and
+    /// pixel data as the cached screenshot.
+    ///
+    /// [`history::hash`]: history::hash
+    pub fn matches(&self, image: &RgbImage, hash: &str) -> bool {
+        self.has_dimensions_of(image) && hash == self.hash
+    }
+
+    /// Returns whether or not `image`, whose perceptual hash is
+    /// `perceptual_hash`, has the same dimensions as the cached screenshot, and
+    /// a perceptual hash that differs from it by at most `max_distance` bits.
+    pub fn is_similar_to(
+        &self,
+        image: &RgbImage,
+        perceptual_hash: &PerceptualHash,
+        max_distance: u32,
+    ) -> bool {
+        if !self.has_dimensions_of(image) {
+            return false;
+        }
+
+        match &self.perceptual_hash {
+            Some(cached_hash) => cached_hash.distance(perceptual_hash) <= max_distance,
+            None => false,
+        }
+    }
+}
+
+lazy_static! {
+    /// The recently saved screenshots, newest first, once they have been
+    /// seeded from the history.
+    static ref CACHE: Mutex<Option<VecDeque<CachedHash>>> = Mutex::new(None);
+}
+
+/// Calls `f` with the cache, seeding it from the history first, if necessary.
+fn with_cache<T>(f: impl FnOnce(&mut VecDeque<CachedHash>) -> T) -> T {
+    let mut cache = CACHE.lock().unwrap();
+
+    f(cache.get_or_insert_with(|| {
+        history::latest(CAPACITY)
+            .into_iter()
+            .filter_map(|entry| {
+                Some(CachedHash {
+                    hash: entry.hash?,
+                    perceptual_hash: entry
+                        .perceptual_hash
+                        .as_deref()
+                        .and_then(PerceptualHash::parse),
+                    path: entry.path,
+                    width: entry.width,
+                    height: entry.height,
+                })
+            })
+            .collect()
+    }))
+}
+
+/// Remembers `image`, which was saved to `path`.
+pub fn record(path: &Path, image: &RgbImage) {
+    let hash = match history::hash(image) {
+        Ok(hash) => hash,
+        Err(e) => {
+            warn!("Failed to hash {}: {:#?}", path.display(), e);
+            return;
+        }
+    };
+
+    with_cache(|cache| {
+        cache.push_front(CachedHash {
+            path: path.to_owned(),
+            width: image.width(),
+            height: image.height(),
+            hash,
+            perceptual_hash: Some(PerceptualHash::of(image)),
+        });
+        cache.truncate(CAPACITY);
+    });
+}
+
+/// Returns up to `count` of the most recently saved screenshots within `dir`
+/// (or one of its subdirectories), newest first, that have been remembered,
+/// and still exist.
+pub fn recent_in_dir(dir: &Path, count: usize) -> Vec<CachedHash> {
+    with_cache(|cache| {
+        cache
+            .iter()
+            // A deleted screenshot shouldn't stop the same image being saved again
+            .filter(|cached| cached.path.starts_with(dir) && cached.path.is_file())
+            .take(count)
+            .cloned()
+            .collect()
+    })
+}
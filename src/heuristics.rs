@@ -1,51 +1,221 @@
 //! Heuristics used to calculate, with some degree of probability, whether or
 //! not the current clipboard data was generated by Snip & Sketch.
+//!
+//! Images copied on another machine, and redirected to the clipboard by Remote
+//! Desktop or a virtual machine's integration tools, are owned by the process
+//! that redirected them, so they are recognised as coming from a remote source,
+//! rather than being checked against the clipboard owner allowlist.
 
+use crate::settings::{ClipboardOwner, Settings};
 use crate::windows::{
-    get_priority_clipboard_format, get_process_image_file_name, get_window_thread_and_process_id,
-    open_process, Clipboard,
+    from_wide_buffer, get_clipboard_owner, get_priority_clipboard_format,
+    get_process_image_file_name, get_window_thread_and_process_id, open_process, Clipboard,
 };
 use bindings::Windows::Win32::{
-    Foundation::PSTR,
+    Foundation::PWSTR,
     System::{
-        DataExchange::{EnumClipboardFormats, GetClipboardFormatNameA, GetClipboardOwner},
+        DataExchange::{EnumClipboardFormats, GetClipboardFormatNameW},
         SystemServices::CF_DIB,
     },
 };
-use maplit::hashset;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
+/// The registered clipboard formats that Snip & Sketch adds to its screenshots.
+const SNIP_FORMATS: [&str; 3] = ["DataObject", "Preferred DropEffect", "PNG"];
+
+/// The image names of the processes that redirect clipboard data from other
+/// machines, i.e. Remote Desktop (including Hyper-V enhanced sessions), VMware
+/// Tools, and VirtualBox Guest Additions.
+const REMOTE_CLIPBOARD_OWNERS: [&str; 3] = ["rdpclip.exe", "vmtoolsd.exe", "vboxtray.exe"];
+
+/// Where a screenshot came from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureSource {
+    /// This machine.
+    Local,
+
+    /// Another machine, via Remote Desktop or virtual machine clipboard
+    /// redirection.
+    Remote,
+}
+
+/// The result of checking each heuristic against the current clipboard data,
+/// for diagnosing why a screenshot was, or wasn't, detected.
+pub struct Diagnosis {
+    /// The NT path to the process that owns the clipboard data, or why it
+    /// couldn't be found.
+    pub owner_process: Result<String, String>,
+
+    /// The Store app package that the owning process belongs to, if any.
+    pub package: Option<String>,
+
+    /// The entry of the clipboard owner allowlist that the owning process
+    /// matched, if any.
+    pub matched_owner: Option<ClipboardOwner>,
+
+    /// Whether or not the clipboard data can be retrieved as a bitmap.
+    pub has_image: bool,
+
+    /// The Snip & Sketch clipboard formats that the clipboard data is missing.
+    pub missing_snip_formats: Vec<&'static str>,
+}
+
+impl Diagnosis {
+    /// Returns the image name of the process that owns the clipboard data, e.g.
+    /// `svchost.exe`, if it could be found.
+    pub fn owner_image_name(&self) -> Option<&str> {
+        self.owner_process
+            .as_ref()
+            .ok()
+            .and_then(|path| path.rsplit('\\').next())
+    }
+
+    /// Returns whether or not the clipboard data would be saved as a
+    /// screenshot.
+    pub fn is_detected(&self) -> bool {
+        match &self.matched_owner {
+            Some(owner) => {
+                self.has_image
+                    && (!owner.require_snip_formats || self.missing_snip_formats.is_empty())
+            }
+            None => false,
+        }
+    }
+}
+
+/// Returns where the current clipboard data came from, judging by the process
+/// that owns it.
+pub fn clipboard_source() -> CaptureSource {
+    let process_name = match get_clipboard_owner_process_name() {
+        Ok(process_name) => process_name.to_lowercase(),
+        Err(e) => {
+            warn!("Unable to find the clipboard owner: {:#?}", e);
+            return CaptureSource::Local;
+        }
+    };
+
+    let is_remote = REMOTE_CLIPBOARD_OWNERS
+        .iter()
+        .any(|owner| process_name.ends_with(&format!("\\{}", owner)));
+
+    if is_remote {
+        CaptureSource::Remote
+    } else {
+        CaptureSource::Local
+    }
+}
+
+/// Returns whether or not redirected clipboard data is likely to be a Snip &
+/// Sketch screenshot taken on the other machine, i.e. whether it's an image
+/// with all of Snip & Sketch's clipboard formats, which are redirected along
+/// with it.
+pub fn clipboard_has_remote_snip(clipboard: &Clipboard) -> bool {
+    clipboard_has_image(clipboard) && missing_snip_formats(clipboard).is_empty()
+}
+
 /// Returns whether or not the current clipboard data is likely owned by Snip &
-/// Sketch.
+/// Sketch, or another screenshot tool listed in the clipboard owner allowlist.
 pub fn clipboard_owned_by_snip_and_sketch(clipboard: &Clipboard) -> windows::Result<bool> {
-    let process_name = get_clipboard_owner_process_name()?;
-    let process_name_heuristic = process_name.ends_with("\\svchost.exe");
+    let process_name = get_clipboard_owner_process_name()?.to_lowercase();
+
+    let mut clipboard_owners = Vec::new();
+    Settings::read(|s| clipboard_owners = s.heuristics.clipboard_owners.clone());
+
+    let owner = match find_owner(&process_name, clipboard_owners) {
+        Some(owner) => owner,
+        None => return Ok(false),
+    };
 
     let priority_format = get_priority_clipboard_format(&[CF_DIB]);
     let priority_format_heuristic = priority_format.is_some();
 
-    // This basically abuses shell clipboard formats etc. to determine whether
-    // the clipboard object is an OLE object, and uses UWP's PNG format. This
-    // helps filter other programs like Adobe XD, that make `svchost.exe` own
-    // its image clipboard objects.
-    // https://docs.microsoft.com/en-us/windows/win32/shell/clipboard
-    let (_standard_formats, registered_formats) = clipboard_format_names(&clipboard);
-    let format_heuristic = registered_formats.is_superset(&hashset! {
-        "DataObject".into(),
-        "Preferred DropEffect".into(),
-        "PNG".into()
-    });
-
-    Ok(process_name_heuristic && priority_format_heuristic && format_heuristic)
+    if !owner.require_snip_formats {
+        return Ok(priority_format_heuristic);
+    }
+
+    let format_heuristic = missing_snip_formats(clipboard).is_empty();
+
+    Ok(priority_format_heuristic && format_heuristic)
+}
+
+/// Checks each heuristic against the current clipboard data, without stopping
+/// at the first one that fails.
+pub fn diagnose(clipboard: &Clipboard) -> Diagnosis {
+    let mut clipboard_owners = Vec::new();
+    Settings::read(|s| clipboard_owners = s.heuristics.clipboard_owners.clone());
+
+    let owner_process = get_clipboard_owner_process_name().map_err(|e| e.message());
+
+    let (package, matched_owner) = match &owner_process {
+        Ok(process_name) => (
+            find_package(process_name),
+            find_owner(&process_name.to_lowercase(), clipboard_owners),
+        ),
+        Err(_) => (None, None),
+    };
+
+    Diagnosis {
+        owner_process,
+        package,
+        matched_owner,
+        has_image: clipboard_has_image(clipboard),
+        missing_snip_formats: missing_snip_formats(clipboard),
+    }
+}
+
+/// Returns the entry of `clipboard_owners` that `process_name` (a lowercase NT
+/// path) matches, if any.
+fn find_owner(process_name: &str, clipboard_owners: Vec<ClipboardOwner>) -> Option<ClipboardOwner> {
+    // Process names are NT paths, so we compare the final path component only
+    clipboard_owners
+        .into_iter()
+        .find(|owner| process_name.ends_with(&format!("\\{}", owner.process.to_lowercase())))
+}
+
+/// Returns the name of the Store app package that the process at the NT path
+/// `process_name` was installed by, if it was installed by one.
+fn find_package(process_name: &str) -> Option<String> {
+    let mut components = process_name.split('\\');
+
+    components
+        .find(|component| component.eq_ignore_ascii_case("WindowsApps"))
+        .and_then(|_| components.next())
+        .map(String::from)
+}
+
+/// Returns the Snip & Sketch clipboard formats that the current clipboard data
+/// is missing.
+///
+/// This basically abuses shell clipboard formats etc. to determine whether the
+/// clipboard object is an OLE object, and uses UWP's PNG format. This helps
+/// filter other programs like Adobe XD, that make `svchost.exe` own its image
+/// clipboard objects.
+/// https://docs.microsoft.com/en-us/windows/win32/shell/clipboard
+fn missing_snip_formats(clipboard: &Clipboard) -> Vec<&'static str> {
+    let (_standard_formats, registered_formats) = clipboard_format_names(clipboard);
+
+    SNIP_FORMATS
+        .iter()
+        .copied()
+        .filter(|format| !registered_formats.contains(*format))
+        .collect()
+}
+
+/// Returns whether or not the current clipboard data can be retrieved as a
+/// device-independent bitmap, whichever program it came from.
+pub fn clipboard_has_image(_clipboard: &Clipboard) -> bool {
+    get_priority_clipboard_format(&[CF_DIB]).is_some()
 }
 
 /// Gets the NT path to the process that owns the current clipboard data.
 fn get_clipboard_owner_process_name() -> windows::Result<String> {
-    // TODO maybe move this to `windows.rs`
-    let owner_window = unsafe { GetClipboardOwner() };
+    let owner_window = get_clipboard_owner();
     let (process, thread) = get_window_thread_and_process_id(owner_window);
 
-    println!(
+    debug!(
         "Clipboard contents owned by process {}, thread {}",
         process, thread
     );
@@ -53,7 +223,7 @@ fn get_clipboard_owner_process_name() -> windows::Result<String> {
     let process_handle = open_process(process)?;
     let process_name = get_process_image_file_name(process_handle.value())?;
 
-    println!("Process name: {}", process_name);
+    debug!("Process name: {}", process_name);
 
     Ok(process_name)
 }
@@ -65,7 +235,7 @@ fn get_clipboard_owner_process_name() -> windows::Result<String> {
 /// formats, and the second element is the set of registered clipboard formats.
 fn clipboard_format_names(_clipboard: &Clipboard) -> (HashSet<String>, HashSet<String>) {
     // Maximum byte length of registered format names
-    const FORMAT_NAME_MAX_BYTES: usize = 256;
+    const FORMAT_NAME_MAX_CHARS: usize = 256;
 
     let mut standard_formats = HashSet::new();
     let mut registered_formats = HashSet::new();
@@ -74,30 +244,27 @@ fn clipboard_format_names(_clipboard: &Clipboard) -> (HashSet<String>, HashSet<S
 
     while format != 0 {
         if let Some(format_name) = standard_clipboard_format_name(format) {
-            println!(
+            debug!(
                 "Format {:#06X} name: {} (standard format)",
                 format, format_name
             );
 
             standard_formats.insert(format_name.into());
         } else {
-            // Read the name of a registered format, as a C-string
-            let mut format_name_raw = vec![0; FORMAT_NAME_MAX_BYTES + 1];
+            // Read the name of a registered format, as a UTF-16 string
+            let mut format_name_raw = vec![0; FORMAT_NAME_MAX_CHARS + 1];
 
             let format_name_length = unsafe {
-                GetClipboardFormatNameA(
+                GetClipboardFormatNameW(
                     format,
-                    PSTR(format_name_raw.as_mut_ptr()),
-                    FORMAT_NAME_MAX_BYTES as i32,
+                    PWSTR(format_name_raw.as_mut_ptr()),
+                    FORMAT_NAME_MAX_CHARS as i32,
                 )
             };
 
-            format_name_raw.truncate(format_name_length as usize);
-
-            let format_name = String::from_utf8(format_name_raw)
-                .expect("Invalid UTF-8 returned by GetClipboardFormatNameA");
+            let format_name = from_wide_buffer(&format_name_raw[..format_name_length as usize]);
 
-            println!("Format {:#06X} name: {}", format, format_name);
+            debug!("Format {:#06X} name: {}", format, format_name);
 
             registered_formats.insert(format_name);
         }
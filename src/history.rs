@@ -0,0 +1,330 @@
+//! Persistent history of saved screenshots.
+//!
+//! Each saved screenshot is recorded as a single JSON object per line in the
+//! history file, so that new entries can be appended without rewriting the
+//! whole file.
+
+use crate::animation::AnimationFormat;
+use crate::classify::Category;
+use crate::context::Context;
+use crate::heuristics::CaptureSource;
+use crate::perceptual_hash::PerceptualHash;
+use crate::region::Region;
+use crate::settings;
+use crate::windows::sha256;
+use chrono::{DateTime, Utc};
+use image::{imageops, ImageFormat, ImageResult, RgbImage};
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The name of the file within the config directory to store history in.
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// The name of the directory within the config directory to store thumbnails
+/// in.
+pub const THUMBNAIL_DIR: &str = "thumbnails";
+
+/// The maximum width and height of thumbnails, in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// A single saved screenshot.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    /// Where the screenshot was saved to.
+    pub path: PathBuf,
+
+    /// When the screenshot was saved.
+    pub saved_at: DateTime<Utc>,
+
+    /// The width of the screenshot, in pixels.
+    pub width: u32,
+
+    /// The height of the screenshot, in pixels.
+    pub height: u32,
+
+    /// The hex-encoded SHA-256 hash of the screenshot's pixel data.
+    #[serde(default)]
+    pub hash: Option<String>,
+
+    /// The hex-encoded perceptual hash of the screenshot, for recognising
+    /// near-duplicates.
+    #[serde(default)]
+    pub perceptual_hash: Option<String>,
+
+    /// A downscaled copy of the screenshot.
+    #[serde(default)]
+    pub thumbnail: Option<PathBuf>,
+
+    /// The region of the screen that the screenshot was captured from, if
+    /// known.
+    #[serde(default)]
+    pub region: Option<Region>,
+
+    /// The kind of content that the screenshot shows, if it was classified.
+    #[serde(default)]
+    pub category: Option<Category>,
+
+    /// The format of the screenshot, if it is animated.
+    #[serde(default)]
+    pub animation: Option<AnimationFormat>,
+
+    /// What was in the foreground when the screenshot was taken, if recording
+    /// context is enabled.
+    #[serde(default)]
+    pub context: Option<Context>,
+
+    /// Where the screenshot came from, if known.
+    #[serde(default)]
+    pub source: Option<CaptureSource>,
+
+    /// The colour that was picked from the screenshot, as `#RRGGBB`, if it was
+    /// taken with the colour picker, in which case it wasn't saved, and `path`
+    /// is empty.
+    #[serde(default)]
+    pub colour: Option<String>,
+}
+
+impl Entry {
+    /// Creates an entry for `image`, which was saved to `path`, hashing it, and
+    /// saving a thumbnail of it.
+    pub fn describe(path: PathBuf, image: &RgbImage, saved_at: DateTime<Utc>) -> Self {
+        let hash = match hash(image) {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                warn!("Failed to hash {}: {:#?}", path.display(), e);
+                None
+            }
+        };
+
+        let thumbnail = hash.as_ref().and_then(|hash| {
+            save_thumbnail(image, hash)
+                .map_err(|e| warn!("Failed to save thumbnail of {}: {}", path.display(), e))
+                .ok()
+        });
+
+        Self {
+            path,
+            saved_at,
+            width: image.width(),
+            height: image.height(),
+            hash,
+            perceptual_hash: Some(PerceptualHash::of(image).to_string()),
+            thumbnail,
+            region: None,
+            category: None,
+            animation: None,
+            context: None,
+            source: None,
+            colour: None,
+        }
+    }
+
+    /// Creates an entry for `colour`, which was picked from `image` with the
+    /// colour picker, rather than saving it.
+    pub fn colour_pick(colour: String, image: &RgbImage, picked_at: DateTime<Utc>) -> Self {
+        Self {
+            path: PathBuf::new(),
+            saved_at: picked_at,
+            width: image.width(),
+            height: image.height(),
+            hash: None,
+            perceptual_hash: None,
+            thumbnail: None,
+            region: None,
+            category: None,
+            animation: None,
+            context: None,
+            source: None,
+            colour: Some(colour),
+        }
+    }
+}
+
+lazy_static! {
+    /// In-memory copy of the history file, loaded on first access.
+    static ref HISTORY: Mutex<Option<Vec<Entry>>> = Mutex::new(None);
+}
+
+/// Returns the fully qualified path to the history file.
+fn history_file_path() -> PathBuf {
+    settings::config_dir().join(HISTORY_FILE)
+}
+
+/// Returns the hex-encoded SHA-256 hash of the pixel data of `image`.
+pub fn hash(image: &RgbImage) -> windows::Result<String> {
+    Ok(sha256(image.as_raw())?
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Saves a downscaled copy of `image`, named after its `hash`, returning the
+/// thumbnail's path.
+fn save_thumbnail(image: &RgbImage, hash: &str) -> ImageResult<PathBuf> {
+    let thumbnail_dir = settings::config_dir().join(THUMBNAIL_DIR);
+    fs::create_dir_all(&thumbnail_dir)?;
+
+    let path = thumbnail_dir.join(hash).with_extension("png");
+
+    // Identical screenshots share a thumbnail
+    if !path.exists() {
+        let scale = (THUMBNAIL_SIZE as f32 / image.width().max(image.height()) as f32).min(1.0);
+
+        imageops::thumbnail(
+            image,
+            ((image.width() as f32 * scale) as u32).max(1),
+            ((image.height() as f32 * scale) as u32).max(1),
+        )
+        .save_with_format(&path, ImageFormat::Png)?;
+    }
+
+    Ok(path)
+}
+
+/// Loads every entry from the history file.
+fn load() -> Vec<Entry> {
+    match fs::read_to_string(history_file_path()) {
+        Ok(contents) => parse(&contents),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Parses the entries in the contents of a history file. Lines that can't be
+/// parsed are skipped, so that a single corrupt entry doesn't lose the whole
+/// history.
+pub fn parse(contents: &str) -> Vec<Entry> {
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Calls `f` with the history, loading it from disk first, if necessary.
+fn with_history<T>(f: impl FnOnce(&mut Vec<Entry>) -> T) -> T {
+    let mut history = HISTORY.lock().unwrap();
+
+    f(history.get_or_insert_with(load))
+}
+
+/// Appends a new entry to the history.
+pub fn record(entry: Entry) {
+    let line = serde_json::to_string(&entry).expect("Failed to serialise history entry");
+
+    with_history(|entries| {
+        entries.push(entry);
+
+        let file_path = history_file_path();
+
+        if let Err(e) = fs::create_dir_all(file_path.parent().unwrap()).and_then(|_| {
+            OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&file_path)?
+                .write_all(format!("{}\n", line).as_bytes())
+        }) {
+            warn!("Failed to record history entry: {:#?}", e);
+        }
+    });
+}
+
+/// Calls `f` with a mutable reference to the history, then rewrites the
+/// history file with the modified entries.
+pub fn update(f: impl FnOnce(&mut Vec<Entry>)) {
+    with_history(|entries| {
+        f(entries);
+
+        let mut contents = String::new();
+
+        for entry in entries.iter() {
+            contents += &serde_json::to_string(entry).expect("Failed to serialise history entry");
+            contents.push('\n');
+        }
+
+        if let Err(e) = fs::write(history_file_path(), contents) {
+            warn!("Failed to rewrite history: {:#?}", e);
+        }
+    });
+}
+
+/// Deletes every thumbnail, and removes them from the history.
+pub fn delete_thumbnails() {
+    update(|entries| {
+        for entry in entries.iter_mut() {
+            entry.thumbnail = None;
+        }
+    });
+
+    if let Err(e) = fs::remove_dir_all(settings::config_dir().join(THUMBNAIL_DIR)) {
+        warn!("Failed to delete thumbnails: {:#?}", e);
+    }
+}
+
+/// Points each entry's thumbnail at the thumbnail directory, e.g. after the
+/// history is restored from a machine with a different config directory.
+pub fn relocate_thumbnails() {
+    let thumbnail_dir = settings::config_dir().join(THUMBNAIL_DIR);
+
+    update(|entries| {
+        for entry in entries.iter_mut() {
+            if let Some(file_name) = entry.thumbnail.as_ref().and_then(|t| t.file_name()) {
+                entry.thumbnail = Some(thumbnail_dir.join(file_name));
+            }
+        }
+    });
+}
+
+/// Returns the recorded hash of the screenshot saved at `path`, if any.
+pub fn hash_of(path: &Path) -> Option<String> {
+    with_history(|entries| {
+        entries
+            .iter()
+            .rev()
+            .find(|entry| entry.path == path)
+            .and_then(|entry| entry.hash.clone())
+    })
+}
+
+/// Returns whether or not a screenshot saved at `path` has been recorded.
+pub fn contains(path: &Path) -> bool {
+    with_history(|entries| entries.iter().any(|entry| entry.path == path))
+}
+
+/// Returns the entries of the screenshots saved from `from` (inclusive) until
+/// `to` (exclusive), oldest first.
+pub fn saved_between(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Entry> {
+    with_history(|entries| {
+        let mut saved: Vec<Entry> = entries
+            .iter()
+            .filter(|entry| entry.saved_at >= from && entry.saved_at < to)
+            // Picked colours weren't saved
+            .filter(|entry| entry.colour.is_none())
+            .cloned()
+            .collect();
+
+        saved.sort_by_key(|entry| entry.saved_at);
+        saved
+    })
+}
+
+/// Returns the `count` most recently recorded entries, newest first.
+pub fn latest(count: usize) -> Vec<Entry> {
+    with_history(|entries| entries.iter().rev().take(count).cloned().collect())
+}
+
+/// Returns the path of the most recently recorded screenshot that was saved
+/// within `dir` (or one of its subdirectories), if it still exists.
+pub fn latest_in_dir(dir: &Path) -> Option<PathBuf> {
+    with_history(|entries| {
+        entries
+            .iter()
+            .rev()
+            .map(|entry| &entry.path)
+            .find(|path| path.starts_with(dir) && path.is_file())
+            .cloned()
+    })
+}
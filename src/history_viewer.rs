@@ -0,0 +1,352 @@
+//! The history viewer, which lists saved screenshots (and picked colours),
+//! newest first, and finds them by their filename, category, or capture
+//! context.
+//!
+//! The viewer can also be started from the command line against a history
+//! copied from another machine (e.g. a backup), without starting the clipboard
+//! listener or notification area icon. Screenshots whose recorded paths don't
+//! exist on this machine are then found by name in a given directory.
+//!
+//! The viewer never modifies the history it shows.
+
+use crate::heuristics::CaptureSource;
+use crate::history::{self, Entry, HISTORY_FILE};
+use crate::migration::find_screenshots_recursive;
+use crate::modal::{self, Dialog};
+use crate::notification_area::open_screenshot;
+use crate::windows::{
+    dialog_box, end_dialog, get_dialog_item_text, get_list_box_selection, message_box,
+    open_clipboard, open_folder_and_select, set_clipboard_text, set_dialog_item_text,
+    set_list_box_items,
+};
+use crate::zip::{self, ZipError};
+use bindings::Windows::Win32::{
+    Foundation::{HWND, LPARAM, WPARAM},
+    UI::WindowsAndMessaging::{
+        EN_CHANGE, IDCANCEL, LBN_DBLCLK, MB_ICONWARNING, MB_OK, WM_COMMAND, WM_INITDIALOG,
+    },
+};
+use chrono::Local;
+use lazy_static::lazy_static;
+use log::{error, warn};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+
+// Resource IDs, which must match those in `resources.rc`
+const IDD_HISTORY: u16 = 400;
+const IDC_HISTORY_SEARCH: i32 = 401;
+const IDC_HISTORY_LIST: i32 = 402;
+const IDC_HISTORY_OPEN: i32 = 403;
+const IDC_HISTORY_SHOW: i32 = 404;
+const IDC_HISTORY_SUMMARY: i32 = 405;
+
+/// Errors that can occur whilst loading a history to view.
+#[derive(Error, Debug)]
+pub enum ViewerError {
+    #[error("Unable to read the history: {0}")]
+    Io(#[from] io::Error),
+    #[error("Unable to read the backup: {0}")]
+    Zip(#[from] ZipError),
+    #[error("The backup doesn't contain a history")]
+    NoHistory,
+    #[error("Unable to show the history: {0}")]
+    Dialog(#[from] windows::Error),
+}
+
+/// The history being viewed.
+struct Viewer {
+    /// Every entry in the history, newest first.
+    entries: Vec<Entry>,
+
+    /// The indices within `entries` of the entries that match the search, in
+    /// the order that they are listed.
+    shown: Vec<usize>,
+
+    /// The screenshots in the directory given on the command line, by file
+    /// name, for finding screenshots that were saved on another machine.
+    files_by_name: HashMap<OsString, PathBuf>,
+}
+
+lazy_static! {
+    /// The history being viewed, whilst the viewer is open.
+    static ref VIEWER: Mutex<Option<Viewer>> = Mutex::new(None);
+}
+
+/// Shows this machine's history in the viewer, on a separate thread.
+pub fn show() {
+    modal::open(Dialog::History, || {
+        if let Err(e) = run(history::latest(usize::MAX), HashMap::new()) {
+            error!("Failed to show the history: {:#?}", e);
+        }
+    });
+}
+
+/// Shows the history in `archive` (a backup, a history file, or a directory
+/// containing one), or this machine's history, if no archive is given, until
+/// the viewer is closed. Screenshots that don't exist at their recorded paths
+/// are looked for in `screenshot_dir`, if given.
+pub fn view_archive(
+    archive: Option<&Path>,
+    screenshot_dir: Option<&Path>,
+) -> Result<(), ViewerError> {
+    let entries = match archive {
+        Some(archive) => {
+            let mut entries = history::parse(&read_history(archive)?);
+            entries.reverse();
+            entries
+        }
+        None => history::latest(usize::MAX),
+    };
+
+    let mut files_by_name = HashMap::new();
+
+    if let Some(screenshot_dir) = screenshot_dir {
+        for screenshot in find_screenshots_recursive(screenshot_dir)? {
+            if let Some(name) = screenshot.file_name() {
+                files_by_name.insert(name.to_owned(), screenshot.clone());
+            }
+        }
+    }
+
+    Ok(run(entries, files_by_name)?)
+}
+
+/// Reads the contents of the history file in `archive`.
+fn read_history(archive: &Path) -> Result<String, ViewerError> {
+    if archive.is_dir() {
+        return Ok(fs::read_to_string(archive.join(HISTORY_FILE))?);
+    }
+
+    let is_zip = match archive.extension().and_then(|e| e.to_str()) {
+        Some(extension) => extension.eq_ignore_ascii_case("zip"),
+        None => false,
+    };
+
+    if !is_zip {
+        return Ok(fs::read_to_string(archive)?);
+    }
+
+    zip::read(archive)?
+        .into_iter()
+        .find(|(name, _)| name == HISTORY_FILE)
+        .map(|(_, data)| String::from_utf8_lossy(&data).into_owned())
+        .ok_or(ViewerError::NoHistory)
+}
+
+/// Shows `entries` (newest first) in the viewer, until it is closed.
+fn run(entries: Vec<Entry>, files_by_name: HashMap<OsString, PathBuf>) -> windows::Result<()> {
+    *VIEWER.lock().unwrap() = Some(Viewer {
+        entries,
+        shown: Vec::new(),
+        files_by_name,
+    });
+
+    let result = dialog_box(IDD_HISTORY, dialog_proc);
+
+    VIEWER.lock().unwrap().take();
+
+    result.map(|_| ())
+}
+
+/// The history viewer dialog box procedure.
+unsafe extern "system" fn dialog_proc(
+    dialog: HWND,
+    message: u32,
+    w_param: WPARAM,
+    _l_param: LPARAM,
+) -> isize {
+    match message {
+        WM_INITDIALOG => {
+            search(dialog);
+            1
+        }
+        WM_COMMAND => match ((w_param.0 & 0xFFFF) as i32, (w_param.0 >> 16) as u32) {
+            (IDC_HISTORY_SEARCH, EN_CHANGE) => {
+                search(dialog);
+                1
+            }
+            (IDC_HISTORY_OPEN, _) | (IDC_HISTORY_LIST, LBN_DBLCLK) => {
+                open_selected(dialog, false);
+                1
+            }
+            (IDC_HISTORY_SHOW, _) => {
+                open_selected(dialog, true);
+                1
+            }
+            (id, _) if id == IDCANCEL.0 => {
+                end_dialog(dialog, IDCANCEL.0 as isize);
+                1
+            }
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Lists the entries that match every word in the search box.
+fn search(dialog: HWND) {
+    let query = get_dialog_item_text(dialog, IDC_HISTORY_SEARCH).to_lowercase();
+    let words: Vec<&str> = query.split_whitespace().collect();
+
+    let mut viewer = VIEWER.lock().unwrap();
+    let viewer = match viewer.as_mut() {
+        Some(viewer) => viewer,
+        None => return,
+    };
+
+    let mut shown = Vec::new();
+    let mut items = Vec::new();
+
+    for (i, entry) in viewer.entries.iter().enumerate() {
+        let text = searchable_text(entry);
+
+        if words.iter().all(|word| text.contains(word)) {
+            shown.push(i);
+            items.push(describe(entry));
+        }
+    }
+
+    set_list_box_items(dialog, IDC_HISTORY_LIST, &items);
+    set_dialog_item_text(
+        dialog,
+        IDC_HISTORY_SUMMARY,
+        &format!("{} of {} screenshots", shown.len(), viewer.entries.len()),
+    );
+
+    viewer.shown = shown;
+}
+
+/// Returns the lowercase text that `entry` can be found by.
+fn searchable_text(entry: &Entry) -> String {
+    let mut text = entry.path.to_string_lossy().into_owned();
+
+    if let Some(colour) = &entry.colour {
+        text += &format!(" {} colour", colour);
+    }
+
+    if let Some(category) = entry.category {
+        text += " ";
+        text += category.label();
+    }
+
+    if let Some(context) = &entry.context {
+        text += &format!(" {} {}", context.process, context.window_title);
+
+        if let Some(url) = &context.url {
+            text += " ";
+            text += url;
+        }
+
+        if let Some(document_path) = &context.document_path {
+            text += " ";
+            text += &document_path.to_string_lossy();
+        }
+    }
+
+    if entry.source == Some(CaptureSource::Remote) {
+        text += " remote";
+    }
+
+    text.to_lowercase()
+}
+
+/// Returns the line that `entry` is listed as.
+fn describe(entry: &Entry) -> String {
+    let name = match &entry.colour {
+        Some(colour) => format!("{} (colour picked)", colour),
+        None => entry
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+    };
+
+    let mut line = format!(
+        "{}    {}    {}\u{d7}{}",
+        entry
+            .saved_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M"),
+        name,
+        entry.width,
+        entry.height
+    );
+
+    if let Some(context) = &entry.context {
+        line += "    ";
+        line += context.url.as_deref().unwrap_or(&context.window_title);
+    }
+
+    line
+}
+
+/// Opens the selected screenshot, or, if `in_folder` is `true`, selects it in
+/// Explorer. A picked colour is copied to the clipboard again instead.
+fn open_selected(dialog: HWND, in_folder: bool) {
+    let path = {
+        let viewer = VIEWER.lock().unwrap();
+
+        let viewer = match viewer.as_ref() {
+            Some(viewer) => viewer,
+            None => return,
+        };
+
+        let entry = match get_list_box_selection(dialog, IDC_HISTORY_LIST)
+            .and_then(|selected| viewer.shown.get(selected))
+        {
+            Some(&i) => &viewer.entries[i],
+            None => return,
+        };
+
+        if let Some(colour) = &entry.colour {
+            if let Err(e) =
+                open_clipboard(None).and_then(|clipboard| set_clipboard_text(&clipboard, colour))
+            {
+                warn!("Failed to copy colour to clipboard: {:#?}", e);
+            }
+
+            return;
+        }
+
+        locate(viewer, entry)
+    };
+
+    let path = match path {
+        Some(path) => path,
+        None => {
+            message_box(
+                "Snip & AutoSave",
+                "The screenshot couldn't be found. It may have been deleted, or saved on another \
+                 machine.",
+                MB_OK | MB_ICONWARNING,
+            );
+            return;
+        }
+    };
+
+    let result = if in_folder {
+        open_folder_and_select(&path)
+    } else {
+        open_screenshot(dialog, &path)
+    };
+
+    if let Err(e) = result {
+        warn!("Failed to open {}: {:#?}", path.display(), e);
+    }
+}
+
+/// Returns where the screenshot recorded by `entry` is on this machine, if it
+/// can be found.
+fn locate(viewer: &Viewer, entry: &Entry) -> Option<PathBuf> {
+    if entry.path.is_file() {
+        return Some(entry.path.clone());
+    }
+
+    viewer.files_by_name.get(entry.path.file_name()?).cloned()
+}
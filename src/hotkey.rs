@@ -0,0 +1,242 @@
+//! Global hotkeys, which launch a new Snip & Sketch capture, toggle
+//! before/after pairing, perform a panic wipe, open the notification area
+//! icon's menu, and pick a colour from the screen.
+//!
+//! The capture itself is saved by the usual clipboard listener, so all this
+//! module needs to do is open the screen snipping overlay.
+
+use crate::colour_picker;
+use crate::error_report;
+use crate::notification_area;
+use crate::pairing;
+use crate::panic_wipe;
+use crate::settings::Settings;
+use crate::windows::{register_hot_key, unregister_hot_key};
+use bindings::Windows::Win32::{
+    Foundation::{HWND, LRESULT, PWSTR, WPARAM},
+    UI::{
+        KeyboardAndMouseInput::{
+            HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN,
+        },
+        Shell::ShellExecuteW,
+        WindowsAndMessaging::{SW_SHOWNORMAL, VK_F1, VK_SNAPSHOT, WM_APP},
+    },
+};
+use log::{info, warn};
+use std::ptr;
+use thiserror::Error;
+
+/// The ID that the capture hotkey is registered with.
+const HOTKEY_ID: i32 = 1;
+
+/// The ID that the before/after pairing hotkey is registered with.
+const PAIRING_HOTKEY_ID: i32 = 2;
+
+/// The ID that the panic wipe hotkey is registered with.
+const PANIC_HOTKEY_ID: i32 = 3;
+
+/// The ID that the menu hotkey is registered with.
+const MENU_HOTKEY_ID: i32 = 4;
+
+/// The ID that the colour picker hotkey is registered with.
+const COLOUR_PICKER_HOTKEY_ID: i32 = 5;
+
+/// Sent to the main window when the hotkey settings change, so that the hotkeys
+/// are re-registered on the window's own thread.
+pub const WMAPP_HOTKEYS_CHANGED: u32 = WM_APP + 2;
+
+/// The URI that opens the Snip & Sketch screen snipping overlay.
+const SCREENCLIP_URI: &str = "ms-screenclip:";
+
+/// Errors that can occur whilst registering the hotkey.
+#[derive(Error, Debug)]
+pub enum HotkeyError {
+    #[error("\"{0}\" is not a valid key combination")]
+    InvalidCombination(String),
+    #[error("Unable to register \"{0}\": {1}")]
+    Register(String, windows::Error),
+}
+
+/// Parses a key combination, such as `Ctrl+Shift+S`, into the modifiers and
+/// virtual-key code expected by `RegisterHotKey`.
+///
+/// Supported keys are letters, digits, `F1` to `F24` and `PrintScreen`, which
+/// must be preceded by at least one of `Ctrl`, `Shift`, `Alt` or `Win`.
+fn parse_combination(combination: &str) -> Option<(HOT_KEY_MODIFIERS, u32)> {
+    let mut modifiers = 0;
+    let mut parts = combination.split('+').map(str::trim).peekable();
+
+    while let Some(part) = parts.next() {
+        if parts.peek().is_some() {
+            modifiers |= match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => MOD_CONTROL.0,
+                "shift" => MOD_SHIFT.0,
+                "alt" => MOD_ALT.0,
+                "win" | "windows" => MOD_WIN.0,
+                _ => return None,
+            };
+
+            continue;
+        }
+
+        let key = match part.to_ascii_uppercase().as_str() {
+            "PRINTSCREEN" | "PRTSC" => VK_SNAPSHOT,
+            key if key.len() == 1 && key.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                // Virtual-key codes for letters and digits are their ASCII values
+                key.as_bytes()[0] as u32
+            }
+            key => match key.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+                Some(n @ 1..=24) => VK_F1 + n - 1,
+                _ => return None,
+            },
+        };
+
+        // A hotkey without modifiers would swallow the key everywhere
+        return if modifiers == 0 {
+            None
+        } else {
+            Some((HOT_KEY_MODIFIERS(modifiers), key))
+        };
+    }
+
+    None
+}
+
+/// Returns whether or not `combination` is a key combination that can be
+/// registered as a hotkey.
+pub fn is_valid_combination(combination: &str) -> bool {
+    parse_combination(combination).is_some()
+}
+
+/// Registers the configured hotkeys with `window`. The Snip & Sketch hotkey is
+/// only registered if it is enabled, whereas each of the other hotkeys is
+/// registered if it has a combination. A hotkey that can't be registered
+/// doesn't stop the others from being registered, and the first failure is
+/// returned.
+pub fn register(window: HWND) -> Result<(), HotkeyError> {
+    let mut enabled = false;
+    let mut combination = String::new();
+    let mut pairing_combination = String::new();
+    let mut panic_combination = String::new();
+    let mut menu_combination = String::new();
+    let mut colour_picker_combination = String::new();
+
+    Settings::read(|s| {
+        enabled = s.hotkey.enabled;
+        combination = s.hotkey.combination.clone();
+        pairing_combination = s.hotkey.pairing_combination.clone();
+        panic_combination = s.hotkey.panic_combination.clone();
+        menu_combination = s.hotkey.menu_combination.clone();
+        colour_picker_combination = s.hotkey.colour_picker_combination.clone();
+    });
+
+    let hotkeys = [
+        (HOTKEY_ID, if enabled { combination } else { String::new() }),
+        (PAIRING_HOTKEY_ID, pairing_combination),
+        (PANIC_HOTKEY_ID, panic_combination),
+        (MENU_HOTKEY_ID, menu_combination),
+        (COLOUR_PICKER_HOTKEY_ID, colour_picker_combination),
+    ];
+
+    let mut result = Ok(());
+
+    for (id, combination) in hotkeys.iter() {
+        if combination.is_empty() {
+            continue;
+        }
+
+        if let Err(e) = register_combination(window, *id, combination) {
+            warn!("{}", e);
+
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+
+    result
+}
+
+/// Registers a single key combination with `window`, under `id`.
+fn register_combination(window: HWND, id: i32, combination: &str) -> Result<(), HotkeyError> {
+    let (modifiers, key) = parse_combination(combination)
+        .ok_or_else(|| HotkeyError::InvalidCombination(combination.into()))?;
+
+    register_hot_key(window, id, modifiers | MOD_NOREPEAT, key)
+        .map_err(|e| HotkeyError::Register(combination.into(), e))?;
+
+    info!("Registered hotkey {}", combination);
+
+    Ok(())
+}
+
+/// Unregisters the hotkeys from `window`, if they were registered.
+pub fn unregister(window: HWND) {
+    // Fails harmlessly if a hotkey was never registered
+    let _ = unregister_hot_key(window, HOTKEY_ID);
+    let _ = unregister_hot_key(window, PAIRING_HOTKEY_ID);
+    let _ = unregister_hot_key(window, PANIC_HOTKEY_ID);
+    let _ = unregister_hot_key(window, MENU_HOTKEY_ID);
+    let _ = unregister_hot_key(window, COLOUR_PICKER_HOTKEY_ID);
+}
+
+/// [`WMAPP_HOTKEYS_CHANGED`] message processor, which replaces the registered
+/// hotkeys with the configured ones.
+///
+/// [`WMAPP_HOTKEYS_CHANGED`]: WMAPP_HOTKEYS_CHANGED
+pub fn on_hotkeys_changed(window: HWND) -> LRESULT {
+    unregister(window);
+
+    if let Err(e) = register(window) {
+        error_report::report("Unable to register the hotkey", &e);
+    }
+
+    LRESULT(0)
+}
+
+/// `WM_HOTKEY` message processor.
+pub fn on_hotkey(window: HWND, w_param: WPARAM) -> LRESULT {
+    match w_param.0 as i32 {
+        HOTKEY_ID => {
+            info!("Hotkey pressed - opening Snip & Sketch");
+
+            if let Err(e) = open_screen_clip(window) {
+                warn!("Failed to open Snip & Sketch: {:#?}", e);
+            }
+        }
+        PAIRING_HOTKEY_ID => pairing::toggle(),
+        PANIC_HOTKEY_ID => panic_wipe::wipe(),
+        MENU_HOTKEY_ID => notification_area::show_menu_at_cursor(window),
+        COLOUR_PICKER_HOTKEY_ID => {
+            info!("Colour picker hotkey pressed - opening Snip & Sketch");
+            colour_picker::arm();
+
+            if let Err(e) = open_screen_clip(window) {
+                colour_picker::disarm();
+                warn!("Failed to open Snip & Sketch: {:#?}", e);
+            }
+        }
+        _ => {}
+    }
+
+    LRESULT(0)
+}
+
+/// Opens the Snip & Sketch screen snipping overlay.
+pub fn open_screen_clip(window: HWND) -> windows::Result<()> {
+    if unsafe {
+        ShellExecuteW(
+            window,
+            "open",
+            SCREENCLIP_URI,
+            PWSTR(ptr::null_mut()),
+            PWSTR(ptr::null_mut()),
+            SW_SHOWNORMAL.0 as i32,
+        )
+        .0 <= 32
+    } {
+        Err(windows::HRESULT::from_thread().into())
+    } else {
+        Ok(())
+    }
+}
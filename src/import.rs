@@ -0,0 +1,116 @@
+//! One-off import of screenshots that were taken before the program was
+//! installed.
+//!
+//! Snip & Sketch users will usually have already saved some screenshots to
+//! the default screenshot folder by hand. On first run, the user is offered the
+//! chance to add these to the screenshot history, so that they are covered by
+//! duplicate detection and search, just like screenshots saved by the program.
+
+use crate::animation::Animation;
+use crate::history::{self, Entry};
+use crate::migration::find_screenshots;
+use crate::modal::{self, Dialog};
+use crate::notification_area::{self, BalloonLevel};
+use crate::orientation;
+use crate::settings::{Paths, Settings};
+use crate::windows::message_box;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::{
+    IDYES, MB_ICONQUESTION, MB_SETFOREGROUND, MB_YESNO,
+};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Offers to import the screenshots in the default screenshot folder, if the
+/// offer hasn't been made before. The offer is only ever made once, whatever
+/// the user's answer.
+pub fn offer_on_first_run() {
+    let mut import_offered = true;
+
+    Settings::write(|s| {
+        import_offered = s.program.import_offered;
+        s.program.import_offered = true;
+    });
+
+    if import_offered {
+        return;
+    }
+
+    modal::open(Dialog::Import, || {
+        offer_import(&Paths::default().screenshots)
+    });
+}
+
+/// Asks the user whether they want to import the unrecorded screenshots in
+/// `dir`, then does so.
+fn offer_import(dir: &Path) {
+    let screenshots: Vec<PathBuf> = match find_screenshots(dir) {
+        Ok(screenshots) => screenshots
+            .into_iter()
+            .filter(|screenshot| !history::contains(screenshot))
+            .collect(),
+        Err(_) => return,
+    };
+
+    if screenshots.is_empty() {
+        return;
+    }
+
+    if message_box(
+        "Snip & AutoSave",
+        &format!(
+            "{} existing screenshots were found in:\n\n{}\n\n\
+             Do you want to add them to your screenshot history, so that duplicates of them \
+             aren't saved again?",
+            screenshots.len(),
+            dir.display()
+        ),
+        MB_YESNO | MB_ICONQUESTION | MB_SETFOREGROUND,
+    ) != IDYES
+    {
+        return;
+    }
+
+    let imported_count = screenshots
+        .into_iter()
+        .filter(|screenshot| match import(screenshot) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Failed to import {}: {}", screenshot.display(), e);
+                false
+            }
+        })
+        .count();
+
+    info!("Imported {} existing screenshots", imported_count);
+
+    let _ = notification_area::show_balloon(
+        "Screenshots imported",
+        &format!("Added {} screenshots to your history", imported_count),
+        BalloonLevel::Info,
+    );
+}
+
+/// Records a single existing screenshot in the history, dated by when the file
+/// was last modified.
+fn import(screenshot: &Path) -> image::ImageResult<()> {
+    let image = orientation::open_upright(screenshot)?.to_rgb8();
+
+    let saved_at = fs::metadata(screenshot)
+        .and_then(|metadata| metadata.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now());
+
+    let animation = fs::read(screenshot)
+        .ok()
+        .and_then(Animation::detect)
+        .map(|animation| animation.format);
+
+    history::record(Entry {
+        animation,
+        ..Entry::describe(screenshot.to_owned(), &image, saved_at)
+    });
+
+    Ok(())
+}
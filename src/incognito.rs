@@ -0,0 +1,103 @@
+//! Temporary "incognito" capture mode.
+//!
+//! Whilst incognito mode is enabled, screenshots are saved to a dedicated
+//! subdirectory of a temporary directory (see [`Incognito::directory`])
+//! instead of the configured screenshot directory. The subdirectory is wiped
+//! when incognito mode is toggled off, and when the program exits, if
+//! incognito mode was used. Nothing else in the temporary directory is ever
+//! touched.
+//!
+//! Windows has no RAM-backed temporary directory, so by default incognito
+//! captures are written to the (on-disk) user temporary directory. Users who
+//! need captures to never touch a physical disk must point
+//! [`Incognito::directory`] at a RAM disk.
+//!
+//! [`Incognito::directory`]: crate::settings::Incognito::directory
+
+use crate::settings::Settings;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The name of the subdirectory of the configured directory that incognito
+/// captures are saved to, which is deleted when they are wiped.
+const CAPTURE_DIR: &str = "snip-and-autosave-incognito";
+
+/// Whether incognito mode has been enabled since the program started, and so
+/// whether there may be captures to wipe.
+static USED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// The time at which incognito mode expires, if it is currently enabled.
+    static ref EXPIRES_AT: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+/// Returns whether or not incognito mode is currently enabled.
+///
+/// Incognito mode is automatically disabled once its configured duration
+/// elapses. Screenshots captured during the session are kept until the
+/// program exits, or incognito mode is explicitly toggled off.
+pub fn is_active() -> bool {
+    let mut expires_at = EXPIRES_AT.lock().unwrap();
+
+    match *expires_at {
+        Some(time) if Instant::now() < time => true,
+        Some(_) => {
+            info!("Incognito mode expired");
+            *expires_at = None;
+            false
+        }
+        None => false,
+    }
+}
+
+/// Enables incognito mode for the configured duration if it is currently
+/// disabled, otherwise disables it, and wipes any captures taken whilst it was
+/// enabled.
+pub fn toggle() {
+    let mut expires_at = EXPIRES_AT.lock().unwrap();
+
+    if matches!(*expires_at, Some(time) if Instant::now() < time) {
+        *expires_at = None;
+        drop(expires_at);
+
+        info!("Incognito mode disabled");
+        wipe();
+    } else {
+        let mut duration_minutes = 0;
+        Settings::read(|s| duration_minutes = s.incognito.duration_minutes);
+
+        *expires_at = Some(Instant::now() + Duration::from_secs(duration_minutes * 60));
+        USED.store(true, Ordering::SeqCst);
+
+        info!("Incognito mode enabled for {} minutes", duration_minutes);
+    }
+}
+
+/// Returns the directory that incognito captures are saved to.
+pub fn capture_dir() -> PathBuf {
+    let mut directory = PathBuf::new();
+    Settings::read(|s| directory = s.incognito.directory.clone());
+
+    directory.join(CAPTURE_DIR)
+}
+
+/// Deletes the incognito capture directory, along with everything in it, if
+/// incognito mode has been used since the program started.
+pub fn wipe() {
+    if !USED.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let directory = capture_dir();
+
+    if directory.exists() {
+        if let Err(e) = fs::remove_dir_all(&directory) {
+            warn!("Failed to wipe incognito directory: {:#?}", e);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! Handling of a second copy of the program starting in the same session.
+//!
+//! Only one instance of the program normally runs per session. However, when
+//! a portable copy (e.g. a new version being tested) is started whilst an
+//! installed copy is running, or vice versa, what happens is configurable
+//! (see [`InstanceConflict`]), rather than the new copy just exiting.
+//!
+//! A copy is considered to be installed if it is run from `Program Files`, or
+//! from the per-user `%LOCALAPPDATA%\Programs` directory.
+//!
+//! When a newer version of the program is started whilst an older version of
+//! the same installation kind is running (e.g. during an upgrade), the older
+//! version is asked to hand over:
+//! it finishes saving any screenshots it is working on, then exits, and the
+//! newer version takes its place, without the user being asked. A conflict
+//! between a portable and an installed copy is always resolved as configured,
+//! whatever their versions.
+//!
+//! Starting the same copy again just opens the running instance's settings
+//! window, so that there is some sign that the program is already running.
+//!
+//! [`InstanceConflict`]: InstanceConflict
+
+use crate::capture;
+use crate::ipc::{self, IpcCommand};
+use crate::notification_area;
+use crate::save_queue;
+use crate::session;
+use crate::settings::{InstanceConflict, Settings};
+use crate::settings_window;
+use crate::windows::{
+    allow_any_set_foreground_window, find_window, message_box, send_notify_message, AutoClose,
+    CLASS_NAME,
+};
+use crate::SHUTDOWN_TIMEOUT;
+use bindings::Windows::Win32::{
+    Foundation::{HANDLE, LPARAM, WPARAM},
+    UI::WindowsAndMessaging::{
+        IDNO, IDYES, MB_ICONINFORMATION, MB_ICONQUESTION, MB_ICONWARNING, MB_OK, MB_SETFOREGROUND,
+        MB_YESNOCANCEL, WM_CLOSE,
+    },
+};
+use log::{info, warn};
+use std::env;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the running instance to exit, when replacing it, once
+/// its window has closed (or if it was never found).
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The longest to wait for the running instance to exit, when replacing it,
+/// however long it is still saving screenshots for. An exiting instance gives
+/// up saving after [`SHUTDOWN_TIMEOUT`], so one that is still running well
+/// after that is assumed to be stuck (e.g. saving to an unresponsive share).
+///
+/// [`SHUTDOWN_TIMEOUT`]: SHUTDOWN_TIMEOUT
+const MAX_REPLACE_WAIT: Duration =
+    Duration::from_secs(SHUTDOWN_TIMEOUT.as_secs() + REPLACE_TIMEOUT.as_secs());
+
+/// How a copy of the program was deployed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Installation {
+    Installed,
+    Portable,
+}
+
+impl Installation {
+    /// Determines how the running executable was deployed, from its location.
+    pub fn current() -> Self {
+        let exe = match env::current_exe() {
+            Ok(exe) => exe,
+            Err(_) => return Installation::Portable,
+        };
+
+        let install_dirs = ["ProgramFiles", "ProgramFiles(x86)", "ProgramW6432"]
+            .iter()
+            .filter_map(|variable| env::var_os(variable).map(PathBuf::from))
+            .chain(env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join("Programs")));
+
+        for install_dir in install_dirs {
+            if exe.starts_with(install_dir) {
+                return Installation::Installed;
+            }
+        }
+
+        Installation::Portable
+    }
+
+    /// A short name for the installation kind.
+    fn name(self) -> &'static str {
+        match self {
+            Installation::Installed => "installed",
+            Installation::Portable => "portable",
+        }
+    }
+
+    /// The other installation kind.
+    fn other(self) -> Self {
+        match self {
+            Installation::Installed => Installation::Portable,
+            Installation::Portable => Installation::Installed,
+        }
+    }
+}
+
+/// What to do about a running instance.
+enum Resolution {
+    /// Exit, leaving the running instance alone.
+    Exit,
+
+    /// Close the running instance, and take its place.
+    Replace,
+
+    /// Run alongside the running instance, in a separate namespace.
+    RunSeparately,
+}
+
+/// Held for as long as this instance runs.
+pub struct InstanceLock {
+    _lock: AutoClose<HANDLE>,
+    _kind_marker: AutoClose<HANDLE>,
+}
+
+/// Attempts to become the running instance in the current session, resolving
+/// a conflict between portable and installed copies as configured.
+///
+/// Returns `None` if this instance should exit.
+pub fn acquire() -> windows::Result<Option<InstanceLock>> {
+    let current = Installation::current();
+
+    // Only a copy of the same installation kind is asked to hand over, so that
+    // a conflict between portable and installed copies is always resolved as
+    // configured
+    let lock = match session::acquire_instance_lock()? {
+        Some(lock) => lock,
+        None if !session::is_kind_running(current.other().name())? && request_hand_over() => {
+            match wait_for_lock()? {
+                Some(lock) => lock,
+                None => return Ok(None),
+            }
+        }
+        None => match resolve_conflict(current)? {
+            Some(lock) => lock,
+            None => return Ok(None),
+        },
+    };
+
+    Ok(Some(InstanceLock {
+        _lock: lock,
+        _kind_marker: session::acquire_kind_marker(current.name())?,
+    }))
+}
+
+/// Decides what to do about the instance that is already running, then does
+/// it, returning the instance lock, unless this instance should exit.
+fn resolve_conflict(current: Installation) -> windows::Result<Option<AutoClose<HANDLE>>> {
+    let running = current.other();
+
+    if !session::is_kind_running(running.name())? {
+        info!("Only one instance of this program can run at a time");
+
+        // Let the running instance's settings window take the foreground from
+        // this one
+        allow_any_set_foreground_window();
+
+        if !ipc::send(&IpcCommand::Activate) {
+            warn!("Running instance didn't respond to being activated");
+        }
+
+        return Ok(None);
+    }
+
+    info!(
+        "A {} copy is already running, whilst starting a {} copy",
+        running.name(),
+        current.name()
+    );
+
+    let mut conflict = InstanceConflict::Prompt;
+    Settings::read(|s| conflict = s.program.instance_conflict);
+
+    let resolution = match conflict {
+        InstanceConflict::DeferToInstalled if current == Installation::Installed => {
+            Resolution::Replace
+        }
+        InstanceConflict::DeferToInstalled => {
+            message_box(
+                "Snip & AutoSave",
+                "Snip & AutoSave is already installed and running, so this portable copy will \
+                 exit.",
+                MB_OK | MB_ICONINFORMATION | MB_SETFOREGROUND,
+            );
+
+            Resolution::Exit
+        }
+        InstanceConflict::Prompt => prompt(current, running),
+        InstanceConflict::Separate => Resolution::RunSeparately,
+    };
+
+    match resolution {
+        Resolution::Exit => Ok(None),
+        Resolution::Replace => replace_running_instance(),
+        Resolution::RunSeparately => {
+            info!("Running separately from the {} copy", running.name());
+            session::set_namespace(current.name());
+
+            session::acquire_instance_lock()
+        }
+    }
+}
+
+/// Asks the user what to do about the running instance.
+fn prompt(current: Installation, running: Installation) -> Resolution {
+    match message_box(
+        "Snip & AutoSave",
+        &format!(
+            "A {running} copy of Snip & AutoSave is already running.\n\n\
+             Do you want to replace it with this {current} copy?\n\n\
+             Yes: close the {running} copy, and run this copy instead\n\
+             No: run both copies (both will save screenshots)\n\
+             Cancel: exit this copy",
+            running = running.name(),
+            current = current.name()
+        ),
+        MB_YESNOCANCEL | MB_ICONQUESTION | MB_SETFOREGROUND,
+    ) {
+        IDYES => Resolution::Replace,
+        IDNO => Resolution::RunSeparately,
+        _ => Resolution::Exit,
+    }
+}
+
+/// Asks the running instance to exit, then waits for it to release the
+/// instance lock.
+fn replace_running_instance() -> windows::Result<Option<AutoClose<HANDLE>>> {
+    info!("Closing the running instance");
+
+    match find_window(CLASS_NAME, &session::window_name()) {
+        Some(window) => send_notify_message(window, WM_CLOSE, WPARAM(0), LPARAM(0))?,
+        None => warn!("Running instance's window not found"),
+    }
+
+    wait_for_lock()
+}
+
+/// Waits for the running instance to exit, and release the instance lock.
+///
+/// The running instance keeps its window until it has finished saving any
+/// queued screenshots, which can take longer than [`REPLACE_TIMEOUT`], so the
+/// timeout only starts once its window has closed. Otherwise, both instances
+/// could exit, leaving nothing running. A running instance that is still
+/// saving after [`MAX_REPLACE_WAIT`] is assumed to be stuck, and the user is
+/// told, rather than this instance waiting forever.
+///
+/// [`REPLACE_TIMEOUT`]: REPLACE_TIMEOUT
+/// [`MAX_REPLACE_WAIT`]: MAX_REPLACE_WAIT
+fn wait_for_lock() -> windows::Result<Option<AutoClose<HANDLE>>> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    let started = Instant::now();
+    let mut deadline = started + REPLACE_TIMEOUT;
+    let mut still_saving = false;
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        if let Some(lock) = session::acquire_instance_lock()? {
+            return Ok(Some(lock));
+        }
+
+        if started.elapsed() >= MAX_REPLACE_WAIT {
+            warn!("Running instance is stuck - giving up waiting for it to exit");
+            report_stuck_instance();
+
+            return Ok(None);
+        }
+
+        if find_window(CLASS_NAME, &session::window_name()).is_some() {
+            if started.elapsed() >= REPLACE_TIMEOUT && !still_saving {
+                info!("Running instance is still finishing up - waiting for it to exit");
+                still_saving = true;
+            }
+
+            deadline = Instant::now() + REPLACE_TIMEOUT;
+        } else if Instant::now() >= deadline {
+            warn!("Running instance didn't exit in time");
+            report_stuck_instance();
+
+            return Ok(None);
+        }
+    }
+}
+
+/// Tells the user that the running instance didn't exit when asked to, so
+/// this instance can't start.
+fn report_stuck_instance() {
+    message_box(
+        "Snip & AutoSave",
+        "The copy of Snip & AutoSave that is already running didn't exit when asked to, \
+         so this copy will exit instead.\n\n\
+         It may still be saving a screenshot, e.g. to a network share that isn't \
+         responding. Try again once it has exited, or end it from Task Manager.",
+        MB_OK | MB_ICONWARNING | MB_SETFOREGROUND,
+    );
+}
+
+/// Asks the running instance to hand over to this instance, returning whether
+/// or not it agreed to, i.e. whether it is an older version of the program.
+fn request_hand_over() -> bool {
+    let accepted = ipc::send(&IpcCommand::HandOver(env!("CARGO_PKG_VERSION").into()));
+
+    if accepted {
+        info!("Running instance is an older version - taking over from it");
+    }
+
+    accepted
+}
+
+/// Handles a request from another instance, running `version` of the program,
+/// to take over from this instance.
+///
+/// If `version` is newer than this instance's version, this instance stops
+/// saving screenshots, finishes saving any that are queued, then exits, and
+/// `true` is returned. Otherwise, the request is refused.
+pub fn hand_over(version: &str) -> bool {
+    let current = env!("CARGO_PKG_VERSION");
+
+    if parse_version(version) <= parse_version(current) {
+        info!(
+            "Refusing to hand over to version {} (running version {})",
+            version, current
+        );
+
+        return false;
+    }
+
+    info!("Handing over to version {}", version);
+
+    // The capture worker can take a while to drain, and the requesting
+    // instance is waiting for this message to be processed, so the rest
+    // happens off the window thread
+    thread::spawn(|| {
+        capture::stop();
+        save_queue::stop();
+
+        match find_window(CLASS_NAME, &session::window_name()) {
+            Some(window) => {
+                if let Err(e) = send_notify_message(window, WM_CLOSE, WPARAM(0), LPARAM(0)) {
+                    warn!("Failed to close window after handing over: {:#?}", e);
+                }
+            }
+            None => warn!("Own window not found whilst handing over"),
+        }
+    });
+
+    true
+}
+
+/// Handles the program being started again whilst this instance is running, by
+/// revealing the notification area icon, if it is hidden, and opening the
+/// settings window, so that starting the program (e.g. from the Start menu)
+/// always shows something. Always returns `true`.
+pub fn activate() -> bool {
+    info!("Program started again - opening the settings window");
+
+    match find_window(CLASS_NAME, &session::window_name()) {
+        Some(window) => {
+            notification_area::reveal_icon(window);
+            settings_window::show(window);
+        }
+        None => warn!("Own window not found whilst activating"),
+    }
+
+    true
+}
+
+/// Splits a dotted version number into its numeric components, so that
+/// versions can be compared. Anything after the first non-numeric component
+/// (e.g. a pre-release suffix) is ignored.
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split('.')
+        .map(str::parse)
+        .take_while(Result::is_ok)
+        .flatten()
+        .collect()
+}
@@ -0,0 +1,158 @@
+//! Inter-process communication with an already running instance of the
+//! program.
+//!
+//! Commands are sent to the running instance's hidden window as
+//! [`WM_COPYDATA`] messages, where the message's `dwData` field identifies the
+//! command, and its data buffer holds the command's UTF-8 encoded argument.
+//!
+//! [`WM_COPYDATA`]: bindings::Windows::Win32::UI::WindowsAndMessaging::WM_COPYDATA
+
+use crate::capture;
+use crate::instance;
+use crate::migration;
+use crate::session;
+use crate::windows::{find_window, send_copy_data, CLASS_NAME};
+use bindings::Windows::Win32::{
+    Foundation::{LPARAM, LRESULT},
+    System::DataExchange::COPYDATASTRUCT,
+};
+use log::info;
+use std::path::PathBuf;
+use std::slice;
+use std::time::Duration;
+use thiserror::Error;
+
+const IPC_SET_SCREENSHOT_DIR: usize = 1;
+const IPC_HAND_OVER: usize = 2;
+const IPC_ACTIVATE: usize = 3;
+const IPC_SAVE_NOW: usize = 4;
+
+/// How long to wait for the running instance to process a command.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reasons that a command couldn't be sent to the running instance.
+#[derive(Error, Debug)]
+pub enum IpcError {
+    #[error("Snip & AutoSave isn't running")]
+    NotRunning,
+    #[error("Snip & AutoSave didn't respond in time")]
+    Timeout,
+    #[error("Snip & AutoSave rejected the command")]
+    Rejected,
+}
+
+/// A command that can be sent to the running instance of the program.
+pub enum IpcCommand {
+    /// Changes the directory that screenshots are saved to.
+    SetScreenshotDir(PathBuf),
+
+    /// Asks the running instance to exit, once it has finished saving
+    /// screenshots, so that a newer version of the program (whose version is
+    /// the argument) can take over. Rejected if the version isn't newer.
+    HandOver(String),
+
+    /// Tells the running instance that the program was started again, whilst
+    /// it was already running.
+    Activate,
+
+    /// Saves the image on the clipboard, whatever it is.
+    SaveNow,
+}
+
+impl IpcCommand {
+    /// Converts this command into its message ID and data buffer.
+    fn encode(&self) -> (usize, Vec<u8>) {
+        match self {
+            Self::SetScreenshotDir(path) => (
+                IPC_SET_SCREENSHOT_DIR,
+                path.to_string_lossy().into_owned().into_bytes(),
+            ),
+            Self::HandOver(version) => (IPC_HAND_OVER, version.clone().into_bytes()),
+            Self::Activate => (IPC_ACTIVATE, Vec::new()),
+            Self::SaveNow => (IPC_SAVE_NOW, Vec::new()),
+        }
+    }
+
+    /// Converts a message ID and data buffer back into a command, returning
+    /// `None` if the message is not a valid command.
+    fn decode(id: usize, data: &[u8]) -> Option<Self> {
+        let argument = String::from_utf8(data.to_vec()).ok()?;
+
+        match id {
+            IPC_SET_SCREENSHOT_DIR => Some(Self::SetScreenshotDir(argument.into())),
+            IPC_HAND_OVER => Some(Self::HandOver(argument)),
+            IPC_ACTIVATE => Some(Self::Activate),
+            IPC_SAVE_NOW => Some(Self::SaveNow),
+            _ => None,
+        }
+    }
+}
+
+/// Sends `command` to the running instance of the program.
+///
+/// Returns `false` if no instance is running, or the running instance did not
+/// accept the command.
+pub fn send(command: &IpcCommand) -> bool {
+    try_send(command).is_ok()
+}
+
+/// Sends `command` to the running instance of the program, returning why it
+/// wasn't accepted, if it wasn't.
+pub fn try_send(command: &IpcCommand) -> Result<(), IpcError> {
+    let window = find_window(CLASS_NAME, &session::window_name()).ok_or(IpcError::NotRunning)?;
+    let (id, data) = command.encode();
+
+    match send_copy_data(window, id, &data, TIMEOUT) {
+        Some(true) => Ok(()),
+        Some(false) => Err(IpcError::Rejected),
+        None => Err(IpcError::Timeout),
+    }
+}
+
+/// Returns whether or not an instance of the program is running in this
+/// session.
+pub fn is_running() -> bool {
+    find_window(CLASS_NAME, &session::window_name()).is_some()
+}
+
+/// Runs `command` within the current process, returning whether or not the
+/// command was accepted.
+pub fn execute(command: IpcCommand) -> bool {
+    match command {
+        IpcCommand::SetScreenshotDir(path) => {
+            info!("Setting screenshot directory to {}", path.display());
+
+            migration::change_screenshot_dir(path)
+        }
+        IpcCommand::HandOver(version) => instance::hand_over(&version),
+        IpcCommand::Activate => instance::activate(),
+        IpcCommand::SaveNow => {
+            capture::save_now();
+            true
+        }
+    }
+}
+
+/// `WM_COPYDATA` message processor.
+///
+/// Returns `LRESULT(1)` if the message contained a valid command, which was
+/// accepted, otherwise `LRESULT(0)`.
+pub fn on_copy_data(l_param: LPARAM) -> LRESULT {
+    let command = unsafe {
+        let copy_data = &*(l_param.0 as *const COPYDATASTRUCT);
+        let data = if copy_data.lpData.is_null() {
+            &[]
+        } else {
+            slice::from_raw_parts(copy_data.lpData as *const u8, copy_data.cbData as usize)
+        };
+
+        IpcCommand::decode(copy_data.dwData, data)
+    };
+
+    let accepted = match command {
+        Some(command) => execute(command),
+        None => false,
+    };
+
+    LRESULT(accepted as i32)
+}
@@ -0,0 +1,190 @@
+//! Program logging.
+//!
+//! Log records are written to stdout (which is visible when the program is
+//! launched from a terminal, or when the debug console is shown), and to a log
+//! file in the config directory, once [`open_log_file`] has been called. The
+//! log file is rotated once it reaches a configurable size, keeping a few old
+//! log files around. The most recent records are also kept in memory, so that
+//! they can be included in error reports.
+//!
+//! [`open_log_file`]: open_log_file
+
+use crate::settings::{self, LogLevel, Settings};
+use chrono::Local;
+use lazy_static::lazy_static;
+use log::{warn, LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// The number of log lines kept in memory.
+const RECENT_LINE_COUNT: usize = 100;
+
+/// The directory within the config directory that log files are written to.
+const LOG_DIR: &str = "logs";
+
+/// The name of the current log file. Rotated log files are numbered, e.g.
+/// `snip-and-autosave.1.log` is the newest rotated log file.
+const LOG_FILE_STEM: &str = "snip-and-autosave";
+
+lazy_static! {
+    /// The most recently logged lines, oldest first.
+    static ref RECENT_LINES: Mutex<VecDeque<String>> =
+        Mutex::new(VecDeque::with_capacity(RECENT_LINE_COUNT));
+
+    /// The open log file, if there is one.
+    static ref LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+}
+
+/// The open log file, and how it is rotated.
+struct LogFile {
+    file: File,
+    size: u64,
+    max_size: u64,
+    rotated_files: u32,
+}
+
+impl LogFile {
+    /// Appends `line` to the log file, rotating it first, if it is full.
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+
+        Ok(())
+    }
+
+    /// Renames the log file (and every older log file) to the next number up,
+    /// deleting the oldest, then starts a new log file.
+    fn rotate(&mut self) -> io::Result<()> {
+        // Missing log files (e.g. before the first few rotations) aren't an error
+        let _ = fs::remove_file(log_file_path(self.rotated_files));
+
+        for number in (0..self.rotated_files).rev() {
+            let _ = fs::rename(log_file_path(number), log_file_path(number + 1));
+        }
+
+        self.file = File::create(log_file_path(0))?;
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+/// Logger that writes to stdout and the log file, and remembers the most
+/// recent log lines.
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let line = format!(
+            "{} [{}] {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.args()
+        );
+
+        println!("{}", line);
+
+        if let Some(log_file) = &mut *LOG_FILE.lock().unwrap() {
+            // Logging the failure would recurse, so it can only be printed
+            if let Err(e) = log_file.write_line(&line) {
+                println!("Failed to write to log file: {}", e);
+            }
+        }
+
+        let mut recent_lines = RECENT_LINES.lock().unwrap();
+
+        if recent_lines.len() == RECENT_LINE_COUNT {
+            recent_lines.pop_front();
+        }
+
+        recent_lines.push_back(line);
+    }
+
+    fn flush(&self) {
+        if let Some(log_file) = &mut *LOG_FILE.lock().unwrap() {
+            let _ = log_file.file.flush();
+        }
+    }
+}
+
+static LOGGER: Logger = Logger;
+
+/// Installs the program's logger. This must only be called once.
+pub fn init() {
+    log::set_logger(&LOGGER).expect("Logger already initialised");
+    log::set_max_level(LevelFilter::Debug);
+}
+
+/// Starts writing log records to the log file, at the log level configured in
+/// the settings. This should be called once the settings are known to be
+/// readable.
+pub fn open_log_file() {
+    let mut level = LogLevel::Debug;
+    let mut max_size = 0;
+    let mut rotated_files = 0;
+
+    Settings::read(|s| {
+        level = s.logging.level;
+        max_size = s.logging.max_file_size_kb * 1024;
+        rotated_files = s.logging.rotated_files;
+    });
+
+    log::set_max_level(match level {
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    });
+
+    let path = log_file_path(0);
+
+    let file = fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&path));
+
+    match file {
+        Ok(file) => {
+            let size = file.metadata().map_or(0, |metadata| metadata.len());
+
+            *LOG_FILE.lock().unwrap() = Some(LogFile {
+                file,
+                size,
+                max_size,
+                rotated_files,
+            });
+        }
+        Err(e) => warn!("Failed to open log file {}: {}", path.display(), e),
+    }
+}
+
+/// Returns the path of the current log file.
+pub fn current_log_file() -> PathBuf {
+    log_file_path(0)
+}
+
+/// Returns the path of log file `number`, where 0 is the current log file, 1
+/// is the newest rotated log file, and so on.
+fn log_file_path(number: u32) -> PathBuf {
+    let file_name = match number {
+        0 => format!("{}.log", LOG_FILE_STEM),
+        _ => format!("{}.{}.log", LOG_FILE_STEM, number),
+    };
+
+    settings::config_dir().join(LOG_DIR).join(file_name)
+}
+
+/// Returns the most recently logged lines, oldest first.
+pub fn recent_lines() -> Vec<String> {
+    RECENT_LINES.lock().unwrap().iter().cloned().collect()
+}
@@ -1,91 +1,190 @@
 #![windows_subsystem = "windows"]
 
-use crate::convert::dib_to_image;
-use crate::extensions::ImageExtensions;
-use crate::heuristics::clipboard_owned_by_snip_and_sketch;
-use crate::notification_area::WMAPP_NOTIFYCALLBACK;
+use crate::notification_area::{BalloonLevel, WMAPP_NOTIFYCALLBACK};
+use crate::self_check::{Check, SelfCheck};
 use crate::settings::Settings;
 use crate::windows::{
-    add_clipboard_listener, attach_console, com_initialize, create_window, create_window_class,
-    destroy_window, find_window, get_clipboard_dib, get_instance, message_loop, open_clipboard,
-    post_quit_message, CLASS_NAME, WINDOW_NAME,
+    add_clipboard_listener, attach_console, block_shutdown, com_initialize, create_window,
+    create_window_class, destroy_window, get_instance, message_loop, post_quit_message,
+    unblock_shutdown, CLASS_NAME,
 };
 use bindings::Windows::Win32::{
     Foundation::{HWND, LPARAM, LRESULT, WPARAM},
     System::Com::COINIT_APARTMENTTHREADED,
     UI::WindowsAndMessaging::{
-        DefWindowProcA, WM_CLIPBOARDUPDATE, WM_CLOSE, WM_COMMAND, WM_CREATE, WM_DESTROY,
+        DefWindowProcW, WM_CLIPBOARDUPDATE, WM_CLOSE, WM_COMMAND, WM_COPYDATA, WM_DESTROY,
+        WM_DISPLAYCHANGE, WM_ENDSESSION, WM_HOTKEY, WM_POWERBROADCAST, WM_QUERYENDSESSION,
     },
 };
-use chrono::Local;
-use image::ImageFormat;
-use lazy_static::lazy_static;
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::Mutex;
-use std::time::{Duration, Instant};
-use std::{fs, thread};
-
+use crossbeam_channel::bounded;
+use log::{debug, error, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use std::{env, panic, process, thread};
+
+mod animation;
+mod auto_start;
+mod backup;
+mod burst;
+mod capture;
+mod classify;
+mod cli;
+mod clipboard_history;
+mod clipboard_watchdog;
+mod colour_picker;
+mod console;
+mod contact_sheet;
+mod context;
 mod convert;
+mod crop;
+mod detection_test;
+mod drop_target;
+mod encryption;
+mod error_report;
+mod events;
+mod explorer;
 mod extensions;
+mod hash_cache;
 mod heuristics;
+mod history;
+mod history_viewer;
+mod hotkey;
+mod import;
+mod incognito;
+mod instance;
+mod ipc;
+mod logging;
+mod metrics;
+mod migration;
+mod modal;
+mod monitors;
+mod naming;
 mod notification_area;
+mod ocr;
+mod orientation;
+mod output;
+mod pairing;
+mod panic_wipe;
+mod perceptual_hash;
+mod placement;
+mod policy;
+mod post_save;
+mod reencode;
+mod region;
+mod remote_config;
+mod retention;
+mod save;
+mod save_queue;
+mod scan;
+mod self_check;
+mod session;
 mod settings;
+mod settings_watcher;
+mod settings_window;
+mod share;
+mod stats;
+mod status_server;
+mod template;
+mod trash;
+mod upload;
+mod webhook;
+mod whats_new;
 mod windows;
+mod zip;
+mod zone;
 
-/// Debounces incoming window messages, returning `true` if the debounce period
-/// for a specific `message` has been exceeded.
-fn debounce_message(message: u32) -> bool {
-    const DEBOUNCE_TIME: Duration = Duration::from_millis(1000);
+/// How long to wait for pending screenshots to be saved, when exiting.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
-    lazy_static! {
-        static ref MESSAGE_TIMES: Mutex<HashMap<u32, Instant>> = Mutex::new(HashMap::new());
-    }
+/// Whether or not the program has been shut down, by closing the window, or by
+/// the session ending.
+static SHUT_DOWN: AtomicBool = AtomicBool::new(false);
 
-    let mut message_times = (*MESSAGE_TIMES).lock().unwrap();
+/// `WM_CLOSE` message processor.
+fn on_close(window: HWND) -> LRESULT {
+    shut_down(window);
+    destroy_window(window);
 
-    let result = if let Some(message_time) = message_times.get(&message) {
-        Instant::now().duration_since(*message_time) <= DEBOUNCE_TIME
-    } else {
-        false
-    };
+    LRESULT(0)
+}
 
-    message_times.insert(message, Instant::now());
+/// `WM_QUERYENDSESSION` message processor.
+///
+/// The session is always allowed to end, but if screenshots are still being
+/// saved, Windows is told why we might hold it up.
+fn on_query_end_session(window: HWND) -> LRESULT {
+    let pending = capture::queue_depth() + save_queue::depth();
+
+    if pending > 0 {
+        if let Err(e) = block_shutdown(window, "Saving screenshots...") {
+            warn!("Failed to set shutdown block reason: {:#?}", e);
+        }
+    }
 
-    result
+    LRESULT(1)
 }
 
-/// Generates the fully qualified path for a new screenshot.
-fn generate_output_path() -> PathBuf {
-    let mut screenshot_path = PathBuf::new();
-    Settings::read(|s| screenshot_path = s.paths.screenshots.clone());
-
-    // Make sure that the screenshot path exists, if we are running for the first time
-    fs::create_dir_all(&screenshot_path).unwrap();
+/// `WM_ENDSESSION` message processor.
+///
+/// The process is terminated as soon as this returns, if the session is ending,
+/// so we clean up here, as `WM_CLOSE` is never received.
+fn on_end_session(window: HWND, w_param: WPARAM) -> LRESULT {
+    if w_param.0 != 0 {
+        info!("Session is ending - shutting down");
+        shut_down(window);
+    }
 
-    let now = Local::now();
+    unblock_shutdown(window);
 
-    screenshot_path
-        .join(format!(
-            "Screenshot_{}",
-            now.format("%Y%m%d_%H%M%S").to_string()
-        ))
-        .with_extension("png")
+    LRESULT(0)
 }
 
-/// `WM_CREATE` message processor.
-fn on_create(window: HWND) -> LRESULT {
-    notification_area::create_icon(window).unwrap();
+/// Saves pending screenshots, then undoes everything that must not outlive the
+/// program. Only the first call does anything.
+fn shut_down(window: HWND) {
+    if SHUT_DOWN.swap(true, Ordering::SeqCst) {
+        return;
+    }
 
-    LRESULT(0)
+    flush_pending_saves();
+
+    if notification_area::has_icon() {
+        if let Err(e) = notification_area::remove_icon(window) {
+            warn!("Failed to remove the notification area icon: {:#?}", e);
+        }
+    }
+    hotkey::unregister(window);
+    incognito::wipe();
 }
 
-/// `WM_CLOSE` message processor.
-fn on_close(window: HWND) -> LRESULT {
-    notification_area::remove_icon(window).unwrap();
-    destroy_window(window);
+/// Waits for the clipboard updates and screenshots that have already been
+/// queued to be saved, so that they aren't lost (or left half-written) when
+/// the program exits, giving up after [`SHUTDOWN_TIMEOUT`].
+///
+/// [`SHUTDOWN_TIMEOUT`]: SHUTDOWN_TIMEOUT
+fn flush_pending_saves() {
+    let pending = capture::queue_depth() + save_queue::depth();
 
-    LRESULT(0)
+    if pending > 0 {
+        info!("Waiting for {} pending screenshots to be saved", pending);
+    }
+
+    // The workers are stopped on another thread, so that we can stop waiting for them
+    let (done_sender, done) = bounded(1);
+
+    thread::spawn(move || {
+        capture::stop();
+        save_queue::stop();
+
+        let _ = done_sender.send(());
+    });
+
+    if done.recv_timeout(SHUTDOWN_TIMEOUT).is_err() {
+        warn!(
+            "Gave up waiting for {} pending screenshots to be saved",
+            save_queue::depth()
+        );
+    }
 }
 
 /// `WM_DESTROY` message processor.
@@ -107,48 +206,20 @@ fn on_command(window: HWND, message: u32, w_param: WPARAM, l_param: LPARAM) -> L
         }
     }
 
-    unsafe { DefWindowProcA(window, message, w_param, l_param) }
+    unsafe { DefWindowProcW(window, message, w_param, l_param) }
 }
 
 /// `WM_CLIPBOARDUPDATE` message processor.
-fn on_clipboard_update(window: HWND) -> LRESULT {
-    println!("\nWM_CLIPBOARDUPDATE message received");
-
-    // Give the Snip & Sketch screenshot overlay a chance to
-    // disappear before we block the clipboard to copy image data
-    thread::sleep(Duration::from_millis(100));
-
-    let clipboard = open_clipboard(Some(window)).unwrap();
-
-    if debounce_message(WM_CLIPBOARDUPDATE) {
-        println!("WM_CLIPBOARDUPDATE debounced - message ignored");
-        return LRESULT(0);
-    } else if clipboard_owned_by_snip_and_sketch(&clipboard).unwrap_or_else(|e| {
-        println!("Heuristics failed: {:#?}", e);
-        false
-    }) {
-        println!("Clipboard is owned by Snip & Sketch - saving screenshot to disk");
-
-        // TODO: don't unwrap here
-        let image = {
-            let bitmap = get_clipboard_dib(&clipboard).unwrap();
-
-            dib_to_image(bitmap, &clipboard).unwrap()
-        };
-
-        thread::spawn(move || {
-            if image.is_same_as_last_screenshot() {
-                println!("Screenshot is the same as the last saved image - ignoring");
-                return;
-            }
-
-            image
-                .save_with_format(generate_output_path(), ImageFormat::Png)
-                .unwrap();
-        });
-    } else {
-        println!("Clipboard not owned by Snip & Sketch");
-    }
+///
+/// The clipboard is handled on the capture worker thread, so that the message
+/// loop isn't blocked whilst the screenshot is read and saved.
+fn on_clipboard_update() -> LRESULT {
+    debug!("WM_CLIPBOARDUPDATE message received");
+    clipboard_watchdog::on_clipboard_update();
+
+    // Repeated updates are skipped by the worker thread, if the clipboard sequence number hasn't
+    // changed since the last update that it handled
+    capture::on_clipboard_update();
 
     LRESULT(0)
 }
@@ -163,13 +234,25 @@ unsafe extern "system" fn window_proc(
     l_param: LPARAM,
 ) -> LRESULT {
     match message {
-        WM_CREATE => on_create(window),
         WM_COMMAND => on_command(window, message, w_param, l_param),
-        WM_CLIPBOARDUPDATE => on_clipboard_update(window),
+        WM_CLIPBOARDUPDATE => on_clipboard_update(),
+        WM_COPYDATA => ipc::on_copy_data(l_param),
+        WM_HOTKEY => hotkey::on_hotkey(window, w_param),
+        hotkey::WMAPP_HOTKEYS_CHANGED => hotkey::on_hotkeys_changed(window),
+        WM_DISPLAYCHANGE => monitors::on_display_change(),
+        WM_POWERBROADCAST => clipboard_watchdog::on_power_broadcast(window, w_param),
+        clipboard_watchdog::WMAPP_CLIPBOARD_LISTENER_STALLED => {
+            clipboard_watchdog::on_listener_stalled(window)
+        }
         WMAPP_NOTIFYCALLBACK => notification_area::notify_callback(window, w_param, l_param),
+        message if notification_area::is_taskbar_created(message) => {
+            notification_area::on_taskbar_created(window)
+        }
+        WM_QUERYENDSESSION => on_query_end_session(window),
+        WM_ENDSESSION => on_end_session(window, w_param),
         WM_CLOSE => on_close(window),
         WM_DESTROY => on_destroy(),
-        _ => DefWindowProcA(window, message, w_param, l_param),
+        _ => DefWindowProcW(window, message, w_param, l_param),
     }
 }
 
@@ -177,19 +260,108 @@ fn main() -> ::windows::Result<()> {
     attach_console();
     com_initialize(COINIT_APARTMENTTHREADED)?;
 
-    // Only allow one instance of the program to run at a time
-    if find_window(CLASS_NAME, WINDOW_NAME).is_some() {
-        println!("Only one instance of this program can run at a time");
+    match cli::parse_args(env::args().skip(1)) {
+        Ok(Some(command)) => process::exit(cli::run(command) as i32),
+        Ok(None) => {}
+        Err(e) => {
+            println!("{}\n\n{}", e, cli::USAGE);
+            process::exit(cli::ExitCode::Usage as i32);
+        }
+    }
+
+    logging::init();
+
+    // The default hook still prints the panic to stderr (and the debug console)
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        error!("Panic: {}", info);
+        error_report::report_panic(&info.to_string());
+    }));
+
+    // Settings are needed by almost everything else, so we can't continue without them. A file
+    // that can't be loaded is replaced with the defaults first, rather than stopping us
+    Settings::read(|_| {});
+    let mut self_check = SelfCheck::default();
+
+    if !self_check.check(Check::Settings, settings::check_settings_file()) {
+        self_check.report();
         return Ok(());
     }
 
+    // Only allow one instance of the program to run at a time, per session (unless the user
+    // chooses to run a portable copy alongside an installed copy)
+    let _instance_lock = match instance::acquire()? {
+        Some(lock) => lock,
+        None => return Ok(()),
+    };
+
+    logging::open_log_file();
+
+    if let Err(e) = console::restore() {
+        warn!("Failed to show debug console: {:#?}", e);
+    }
+
+    auto_start::refresh();
+    remote_config::start();
+
     // Create a hidden window, so we can receive clipboard messages
     let instance = get_instance()?;
     let class = create_window_class(instance, CLASS_NAME, Some(window_proc))?;
-    let window = create_window(instance, &class, WINDOW_NAME)?;
+    let window = create_window(instance, &class, &session::window_name())?;
+
+    if let Err(e) = notification_area::register_taskbar_created() {
+        warn!("Failed to register for taskbar creation: {:#?}", e);
+    }
+
+    let mut show_tray_icon = true;
+    Settings::read(|s| show_tray_icon = s.program.show_tray_icon);
+
+    if show_tray_icon {
+        self_check.check(
+            Check::NotificationIcon,
+            notification_area::create_icon(window),
+        );
+    } else {
+        info!("Notification area icon is hidden");
+    }
+
+    if let Some(notice) = settings::take_recovery_notice() {
+        let _ =
+            notification_area::show_balloon("Settings were reset", &notice, BalloonLevel::Warning);
+    }
+
+    notification_area::subscribe_to_events();
+    metrics::subscribe_to_events();
+    upload::subscribe_to_events();
+    webhook::subscribe_to_events();
+    post_save::subscribe_to_events();
+    save_queue::start();
+    capture::start();
 
     // Register our hidden window as a clipboard listener
-    add_clipboard_listener(window)?;
+    self_check.check(Check::ClipboardListener, add_clipboard_listener(window));
+    clipboard_watchdog::start(window);
+    self_check.check(Check::Hotkey, hotkey::register(window));
+    settings_watcher::start(window);
+    self_check.check(Check::StatusServer, status_server::start());
+
+    // The screenshot directory may be on a slow network share, so we check it in the background
+    thread::spawn(move || {
+        self_check.check(Check::ScreenshotDir, save::check_screenshot_dir());
+        save::remove_stale_temp_files();
+        self_check.report();
+
+        if let Err(e) = trash::purge_expired() {
+            warn!("Failed to purge the trash: {:#?}", e);
+        }
+
+        retention::start();
+    });
+
+    // Must come before the import offer, which it uses to detect the first run
+    whats_new::show_after_update();
+    import::offer_on_first_run();
 
     // Await clipboard messages indefinitely
     message_loop(HWND(0));
@@ -0,0 +1,247 @@
+//! Capture pipeline metrics.
+//!
+//! Counts of what the capture pipeline has done since the program started are
+//! collected from pipeline [`Event`]s (and, for clipboard updates that never
+//! become captures, recorded directly), so that they can be served by the
+//! [`status_server`], and regressions in detection can be spotted over time.
+//!
+//! [`Event`]: Event
+//! [`status_server`]: crate::status_server
+
+use crate::capture;
+use crate::events::{self, Event, FailureKind, SkipReason};
+use crate::save_queue;
+use crate::settings::Settings;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// The number of clipboard updates ignored, as the clipboard sequence number
+/// hadn't changed.
+static CLIPBOARD_UPDATES_UNCHANGED: AtomicU64 = AtomicU64::new(0);
+
+/// The number of clipboard updates that the heuristics decided weren't
+/// captures.
+static HEURISTIC_REJECTIONS: AtomicU64 = AtomicU64::new(0);
+
+/// The number of captures found on the clipboard.
+static CAPTURES_DETECTED: AtomicU64 = AtomicU64::new(0);
+
+/// The number of screenshots saved.
+static SCREENSHOTS_SAVED: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    /// The number of screenshots deliberately not saved, by reason.
+    static ref SCREENSHOTS_SKIPPED: Mutex<HashMap<SkipReason, u64>> = Mutex::new(HashMap::new());
+
+    /// The number of screenshots that couldn't be saved, by failure kind.
+    static ref SAVES_FAILED: Mutex<HashMap<FailureKind, u64>> = Mutex::new(HashMap::new());
+
+    /// The most recent failure, if there has been one.
+    static ref LAST_ERROR: Mutex<Option<LastError>> = Mutex::new(None);
+}
+
+/// A snapshot of the program's state.
+#[derive(Serialize)]
+pub struct Status {
+    pub clipboard_updates_unchanged: u64,
+    pub heuristic_rejections: u64,
+    pub captures_detected: u64,
+    pub screenshots_saved: u64,
+
+    /// Skipped screenshots, keyed by [`SkipReason::label`].
+    ///
+    /// [`SkipReason::label`]: SkipReason::label
+    pub screenshots_skipped: BTreeMap<&'static str, u64>,
+
+    /// Failed saves, keyed by [`FailureKind::label`].
+    ///
+    /// [`FailureKind::label`]: FailureKind::label
+    pub saves_failed: BTreeMap<&'static str, u64>,
+
+    /// The number of clipboard updates waiting to be processed.
+    pub queue_depth: usize,
+
+    /// The number of screenshots waiting to be saved, or being saved.
+    pub save_queue_depth: usize,
+
+    /// Whether or not saving is paused.
+    pub paused: bool,
+
+    pub last_error: Option<LastError>,
+}
+
+/// The most recent failure.
+#[derive(Serialize, Clone)]
+pub struct LastError {
+    pub at: DateTime<Utc>,
+    pub kind: &'static str,
+    pub message: String,
+}
+
+/// Starts counting capture pipeline events, on a separate thread.
+pub fn subscribe_to_events() {
+    let events = events::subscribe();
+
+    thread::spawn(move || {
+        for event in events {
+            match event {
+                Event::CaptureDetected => {
+                    CAPTURES_DETECTED.fetch_add(1, Ordering::Relaxed);
+                }
+                Event::Saved { .. } => {
+                    SCREENSHOTS_SAVED.fetch_add(1, Ordering::Relaxed);
+                }
+                Event::Skipped { reason } => {
+                    *SCREENSHOTS_SKIPPED
+                        .lock()
+                        .unwrap()
+                        .entry(reason)
+                        .or_default() += 1;
+                }
+                Event::Failed { kind, error } => {
+                    *SAVES_FAILED.lock().unwrap().entry(kind).or_default() += 1;
+                    *LAST_ERROR.lock().unwrap() = Some(LastError {
+                        at: Utc::now(),
+                        kind: kind.label(),
+                        message: error,
+                    });
+                }
+            }
+        }
+    });
+}
+
+/// Counts a clipboard update ignored because the clipboard was unchanged.
+pub fn record_unchanged_clipboard() {
+    CLIPBOARD_UPDATES_UNCHANGED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts a clipboard update that the heuristics rejected.
+pub fn record_heuristic_rejection() {
+    HEURISTIC_REJECTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns a snapshot of the program's state.
+pub fn status() -> Status {
+    let mut paused = false;
+    Settings::read(|s| paused = s.program.paused);
+
+    let skipped = SCREENSHOTS_SKIPPED.lock().unwrap();
+    let failed = SAVES_FAILED.lock().unwrap();
+
+    Status {
+        clipboard_updates_unchanged: CLIPBOARD_UPDATES_UNCHANGED.load(Ordering::Relaxed),
+        heuristic_rejections: HEURISTIC_REJECTIONS.load(Ordering::Relaxed),
+        captures_detected: CAPTURES_DETECTED.load(Ordering::Relaxed),
+        screenshots_saved: SCREENSHOTS_SAVED.load(Ordering::Relaxed),
+        // Every label is included, so that series don't appear out of nowhere
+        screenshots_skipped: SkipReason::ALL
+            .iter()
+            .map(|reason| (reason.label(), skipped.get(reason).copied().unwrap_or(0)))
+            .collect(),
+        saves_failed: FailureKind::ALL
+            .iter()
+            .map(|kind| (kind.label(), failed.get(kind).copied().unwrap_or(0)))
+            .collect(),
+        queue_depth: capture::queue_depth(),
+        save_queue_depth: save_queue::depth(),
+        paused,
+        last_error: LAST_ERROR.lock().unwrap().clone(),
+    }
+}
+
+/// Renders the program's state in the Prometheus text exposition format.
+pub fn prometheus() -> String {
+    let status = status();
+    let mut metrics = String::new();
+
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, u64)]| {
+        let _ = writeln!(metrics, "# HELP snip_and_autosave_{} {}", name, help);
+        let _ = writeln!(metrics, "# TYPE snip_and_autosave_{} {}", name, kind);
+
+        for (labels, value) in samples {
+            let _ = writeln!(metrics, "snip_and_autosave_{}{} {}", name, labels, value);
+        }
+    };
+
+    let labelled = |label: &str, values: &BTreeMap<&'static str, u64>| -> Vec<(String, u64)> {
+        values
+            .iter()
+            .map(|(value, count)| (format!("{{{}=\"{}\"}}", label, value), *count))
+            .collect()
+    };
+
+    metric(
+        "clipboard_updates_unchanged_total",
+        "counter",
+        "Clipboard updates ignored as the clipboard sequence number was unchanged.",
+        &[(String::new(), status.clipboard_updates_unchanged)],
+    );
+    metric(
+        "heuristic_rejections_total",
+        "counter",
+        "Clipboard updates that the heuristics decided weren't captures.",
+        &[(String::new(), status.heuristic_rejections)],
+    );
+    metric(
+        "captures_detected_total",
+        "counter",
+        "Captures found on the clipboard.",
+        &[(String::new(), status.captures_detected)],
+    );
+    metric(
+        "screenshots_saved_total",
+        "counter",
+        "Screenshots saved.",
+        &[(String::new(), status.screenshots_saved)],
+    );
+    metric(
+        "screenshots_skipped_total",
+        "counter",
+        "Screenshots deliberately not saved, by reason.",
+        &labelled("reason", &status.screenshots_skipped),
+    );
+    metric(
+        "saves_failed_total",
+        "counter",
+        "Screenshots that couldn't be saved, by failure kind.",
+        &labelled("kind", &status.saves_failed),
+    );
+    metric(
+        "queue_depth",
+        "gauge",
+        "Clipboard updates waiting to be processed.",
+        &[(String::new(), status.queue_depth as u64)],
+    );
+    metric(
+        "save_queue_depth",
+        "gauge",
+        "Screenshots waiting to be saved, or being saved.",
+        &[(String::new(), status.save_queue_depth as u64)],
+    );
+    metric(
+        "paused",
+        "gauge",
+        "Whether or not saving is paused.",
+        &[(String::new(), status.paused as u64)],
+    );
+    metric(
+        "last_error_timestamp_seconds",
+        "gauge",
+        "When the most recent failure happened, or 0 if there hasn't been one.",
+        &[(
+            String::new(),
+            status
+                .last_error
+                .map_or(0, |last_error| last_error.at.timestamp() as u64),
+        )],
+    );
+
+    metrics
+}
@@ -0,0 +1,272 @@
+//! Migration of existing screenshots, when the screenshot directory changes.
+
+use crate::encryption;
+use crate::history;
+use crate::modal::{self, Dialog};
+use crate::notification_area::{self, BalloonLevel};
+use crate::policy;
+use crate::retention;
+use crate::save;
+use crate::scan;
+use crate::settings::Settings;
+use crate::trash;
+use crate::windows::message_box;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::{
+    IDNO, IDYES, MB_ICONQUESTION, MB_SETFOREGROUND, MB_YESNOCANCEL,
+};
+use log::{error, info, warn};
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// File extensions of images that are considered to be screenshots.
+pub const SCREENSHOT_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "webp"];
+
+/// How existing screenshots should be migrated to a new directory.
+#[derive(Clone, Copy, PartialEq)]
+enum MigrationMode {
+    Move,
+    Copy,
+}
+
+/// Changes the screenshot directory to `new_dir`, then offers to migrate the
+/// screenshots in the previous directory over to it. Returns `false` if the
+/// screenshot directory is enforced by policy, so can't be changed.
+///
+/// The offer is made on a separate thread, so this function can safely be
+/// called from a window procedure. If an earlier offer is still open, it is
+/// brought to the foreground, and no new offer is made.
+pub fn change_screenshot_dir(new_dir: PathBuf) -> bool {
+    let old_dir = match set_screenshot_dir(&new_dir) {
+        Some(old_dir) => old_dir,
+        None => {
+            let _ = notification_area::show_balloon(
+                "Screenshot folder not changed",
+                "The screenshot folder is set by your administrator.",
+                BalloonLevel::Warning,
+            );
+
+            return false;
+        }
+    };
+
+    thread::spawn(save::verify_screenshot_dir);
+
+    if old_dir != new_dir {
+        modal::open(Dialog::Migration, move || {
+            offer_migration(&old_dir, &new_dir)
+        });
+    }
+
+    true
+}
+
+/// Changes the screenshot directory setting to `new_dir`, returning the
+/// previous screenshot directory, or `None` if the screenshot directory is
+/// enforced by policy, so can't be changed.
+pub fn set_screenshot_dir(new_dir: &Path) -> Option<PathBuf> {
+    if policy::get().screenshot_dir.is_some() {
+        warn!("Not changing the screenshot directory - it is enforced by policy");
+        return None;
+    }
+
+    let mut old_dir = PathBuf::new();
+    Settings::read(|s| old_dir = s.paths.screenshots.clone());
+
+    Settings::write(|s| s.paths.screenshots = new_dir.to_owned());
+
+    Some(old_dir)
+}
+
+/// Returns whether or not `path` has the file extension of a screenshot.
+pub fn is_screenshot(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => SCREENSHOT_EXTENSIONS.contains(&extension.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+/// Lists the screenshots directly within `dir`.
+pub fn find_screenshots(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut screenshots = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() && is_screenshot(&path) {
+            screenshots.push(path);
+        }
+    }
+
+    Ok(screenshots)
+}
+
+/// Lists the screenshots within `dir`, and within its subdirectories (e.g. the
+/// dated subdirectories that [`Paths::subdirectory_format`] creates), skipping
+/// quarantined, trashed and archived screenshots.
+///
+/// [`Paths::subdirectory_format`]: crate::settings::Paths::subdirectory_format
+pub fn find_screenshots_recursive(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    find_files_recursive(dir, &is_screenshot)
+}
+
+/// Lists the encrypted screenshot archives within `dir`, and within its
+/// subdirectories, in the same way as [`find_screenshots_recursive`].
+///
+/// [`find_screenshots_recursive`]: find_screenshots_recursive
+fn find_archives_recursive(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    find_files_recursive(dir, &|path| {
+        path.file_name() == Some(OsStr::new(encryption::ARCHIVE_FILE))
+    })
+}
+
+/// Lists the files within `dir`, and within its subdirectories, for which
+/// `include` returns `true`, skipping quarantined, trashed and archived
+/// screenshots.
+fn find_files_recursive(dir: &Path, include: &dyn Fn(&Path) -> bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() && include(&path) {
+            files.push(path);
+        } else if path.is_dir()
+            && !path.ends_with(scan::QUARANTINE_DIR)
+            && !path.ends_with(trash::TRASH_DIR)
+            && !path.ends_with(retention::ARCHIVE_DIR)
+        {
+            files.extend(find_files_recursive(&path, include)?);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Asks the user whether they want to move or copy the screenshots in
+/// `old_dir` (including those in its subdirectories, and any encrypted
+/// archives) to `new_dir`, then does so, keeping their paths relative to
+/// `old_dir`. This blocks until the user has answered, and the screenshots
+/// have been migrated.
+pub fn offer_migration(old_dir: &Path, new_dir: &Path) {
+    // If the new directory is within the old one, its contents are already
+    // where they need to be
+    let not_in_new_dir = |path: &PathBuf| !path.starts_with(new_dir);
+
+    let screenshots: Vec<PathBuf> = match find_screenshots_recursive(old_dir) {
+        Ok(screenshots) => screenshots.into_iter().filter(not_in_new_dir).collect(),
+        Err(_) => return,
+    };
+
+    let archives: Vec<PathBuf> = match find_archives_recursive(old_dir) {
+        Ok(archives) => archives.into_iter().filter(not_in_new_dir).collect(),
+        Err(_) => return,
+    };
+
+    if screenshots.is_empty() && archives.is_empty() {
+        return;
+    }
+
+    let archive_note = if archives.is_empty() {
+        ""
+    } else {
+        ", along with the encrypted screenshot archive,"
+    };
+
+    let mode = match message_box(
+        "Snip & AutoSave",
+        &format!(
+            "{} screenshots{} were saved in the previous screenshot folder:\n\n{}\n\n\
+             Do you want to move them to the new folder?\n\n\
+             Yes: move them\nNo: copy them\nCancel: leave them where they are",
+            screenshots.len(),
+            archive_note,
+            old_dir.display()
+        ),
+        MB_YESNOCANCEL | MB_ICONQUESTION | MB_SETFOREGROUND,
+    ) {
+        IDYES => MigrationMode::Move,
+        IDNO => MigrationMode::Copy,
+        _ => return,
+    };
+
+    if let Err(e) = fs::create_dir_all(new_dir) {
+        error!("Unable to create {}: {:#?}", new_dir.display(), e);
+        return;
+    }
+
+    let mut moved = Vec::new();
+    let mut migrated_count = 0;
+
+    for screenshot in screenshots.into_iter().chain(archives) {
+        let relative_path = match screenshot.strip_prefix(old_dir) {
+            Ok(relative_path) => relative_path,
+            Err(_) => continue,
+        };
+        let destination = new_dir.join(relative_path);
+
+        if destination.exists() {
+            info!(
+                "Not migrating {} - destination exists",
+                screenshot.display()
+            );
+            continue;
+        }
+
+        let result = fs::create_dir_all(destination.parent().unwrap()).and_then(|_| match mode {
+            MigrationMode::Move => move_file(&screenshot, &destination),
+            MigrationMode::Copy => fs::copy(&screenshot, &destination).map(|_| ()),
+        });
+
+        match result {
+            Ok(()) => {
+                migrated_count += 1;
+
+                if mode == MigrationMode::Move {
+                    moved.push((screenshot, destination));
+                }
+            }
+            Err(e) => warn!("Failed to migrate {}: {:#?}", screenshot.display(), e),
+        }
+    }
+
+    if !moved.is_empty() {
+        history::update(|entries| {
+            for entry in entries.iter_mut() {
+                if let Some((_, destination)) =
+                    moved.iter().find(|(source, _)| *source == entry.path)
+                {
+                    entry.path = destination.clone();
+                }
+            }
+        });
+    }
+
+    let _ = notification_area::show_balloon(
+        "Screenshots migrated",
+        &format!(
+            "{} {} screenshots to {}",
+            if mode == MigrationMode::Move {
+                "Moved"
+            } else {
+                "Copied"
+            },
+            migrated_count,
+            new_dir.display()
+        ),
+        BalloonLevel::Info,
+    );
+}
+
+/// Moves a file, falling back to a copy and delete, if the destination is on a
+/// different volume.
+fn move_file(source: &Path, destination: &Path) -> io::Result<()> {
+    if fs::rename(source, destination).is_err() {
+        fs::copy(source, destination)?;
+        fs::remove_file(source)?;
+    }
+
+    Ok(())
+}
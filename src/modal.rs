@@ -0,0 +1,98 @@
+//! Single-instance management of the program's dialogs.
+//!
+//! Every dialog runs on its own thread, so that it never blocks the main
+//! window's message loop. Only one instance of each dialog may be open at a
+//! time - re-invoking a dialog that is already open brings the existing one to
+//! the foreground instead.
+
+use crate::windows::{find_thread_window, foreground_window, get_current_thread_id};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+
+/// The dialogs that the program can show.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Dialog {
+    /// The detection test's instructions and results.
+    DetectionTest,
+
+    /// The screenshot directory picker.
+    DirectoryPicker,
+
+    /// The offer to migrate screenshots after the screenshot directory changes.
+    Migration,
+
+    /// The unexpected error report.
+    ErrorReport,
+
+    /// The history viewer.
+    History,
+
+    /// The offer to import existing screenshots on first run.
+    Import,
+
+    /// The settings window.
+    Settings,
+
+    /// The picker for screenshots to zip and share.
+    Share,
+
+    /// The screenshot directory statistics.
+    Statistics,
+
+    /// The deleted screenshots in the trash.
+    Trash,
+
+    /// The changes made in recent versions.
+    WhatsNew,
+}
+
+lazy_static! {
+    /// The thread ID of each open dialog. An ID of `0` means that the dialog's
+    /// thread has been spawned, but hasn't started running yet.
+    static ref OPEN_DIALOGS: Mutex<HashMap<Dialog, u32>> = Mutex::new(HashMap::new());
+}
+
+/// Removes a dialog from [`OPEN_DIALOGS`] when dropped, so that the dialog can
+/// be reopened, even if its thread panics.
+///
+/// [`OPEN_DIALOGS`]: OPEN_DIALOGS
+struct OpenDialog(Dialog);
+
+impl Drop for OpenDialog {
+    fn drop(&mut self) {
+        OPEN_DIALOGS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Runs `show` on a new thread, which should show `dialog` and block until it
+/// is closed.
+///
+/// If `dialog` is already open, `show` is not called, and the open dialog is
+/// brought to the foreground instead.
+pub fn open(dialog: Dialog, show: impl FnOnce() + Send + 'static) {
+    let mut open_dialogs = OPEN_DIALOGS.lock().unwrap();
+
+    if let Some(&thread_id) = open_dialogs.get(&dialog) {
+        if let Some(window) = find_thread_window(thread_id) {
+            foreground_window(window);
+        }
+
+        return;
+    }
+
+    open_dialogs.insert(dialog, 0);
+    drop(open_dialogs);
+
+    thread::spawn(move || {
+        let _open_dialog = OpenDialog(dialog);
+
+        OPEN_DIALOGS
+            .lock()
+            .unwrap()
+            .insert(dialog, get_current_thread_id());
+
+        show();
+    });
+}
@@ -0,0 +1,111 @@
+//! Cache of the monitors that make up the desktop.
+//!
+//! Monitor details are looked up for every capture, so they are cached, and
+//! only refreshed when the display configuration changes (e.g. when a laptop
+//! is docked or undocked), so that captures are never tagged with the details
+//! of a monitor that no longer exists.
+
+use crate::windows::{
+    enum_display_monitors, get_dpi_for_monitor, get_monitor_info, monitor_from_window,
+};
+use bindings::Windows::Win32::{
+    Foundation::{HWND, LRESULT, RECT},
+    Graphics::Gdi::HMONITOR,
+};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::sync::RwLock;
+
+/// The details of a single monitor.
+#[derive(Clone, Copy)]
+pub struct Monitor {
+    /// The monitor's handle, which is only valid until the display
+    /// configuration changes.
+    handle: isize,
+
+    /// The bounds of the monitor, in virtual screen coordinates.
+    pub bounds: RECT,
+
+    /// The monitor's effective DPI (96 being 100% scaling).
+    pub dpi: u32,
+}
+
+impl Monitor {
+    /// Returns the resolution (width, height) of the monitor.
+    pub fn resolution(&self) -> (i32, i32) {
+        (
+            self.bounds.right - self.bounds.left,
+            self.bounds.bottom - self.bounds.top,
+        )
+    }
+}
+
+lazy_static! {
+    /// Every monitor, as of the last refresh, or `None` before the first.
+    static ref MONITORS: RwLock<Option<Vec<Monitor>>> = RwLock::new(None);
+}
+
+/// Re-enumerates every monitor.
+pub fn refresh() {
+    let monitors: Vec<Monitor> = enum_display_monitors()
+        .into_iter()
+        .filter_map(|handle| {
+            let info = get_monitor_info(handle)
+                .map_err(|e| warn!("Failed to get monitor info: {:#?}", e))
+                .ok()?;
+
+            Some(Monitor {
+                handle: handle.0,
+                bounds: info.rcMonitor,
+                dpi: get_dpi_for_monitor(handle).unwrap_or(96),
+            })
+        })
+        .collect();
+
+    info!("Found {} monitor(s)", monitors.len());
+
+    *MONITORS.write().unwrap() = Some(monitors);
+}
+
+/// Returns every monitor.
+pub fn all() -> Vec<Monitor> {
+    if MONITORS.read().unwrap().is_none() {
+        refresh();
+    }
+
+    MONITORS.read().unwrap().clone().unwrap_or_default()
+}
+
+/// Returns the monitor that `window` is on, or that is nearest to it.
+pub fn for_window(window: HWND) -> Option<Monitor> {
+    let handle = monitor_from_window(window);
+
+    if MONITORS.read().unwrap().is_none() {
+        refresh();
+    }
+
+    find(handle).or_else(|| {
+        // The display configuration may have changed before we were notified
+        refresh();
+        find(handle)
+    })
+}
+
+/// Returns the cached details of the monitor with handle `handle`.
+fn find(handle: HMONITOR) -> Option<Monitor> {
+    MONITORS
+        .read()
+        .unwrap()
+        .as_ref()?
+        .iter()
+        .find(|monitor| monitor.handle == handle.0)
+        .copied()
+}
+
+/// `WM_DISPLAYCHANGE` message processor.
+pub fn on_display_change() -> LRESULT {
+    info!("Display configuration changed - refreshing monitors");
+    refresh();
+
+    LRESULT(0)
+}
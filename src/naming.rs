@@ -0,0 +1,153 @@
+//! Naming strategies, which choose the filenames of new screenshots.
+//!
+//! Besides expanding the user's filename template, there are built-in
+//! strategies for the most common needs, selected by [`Paths::naming`]. The
+//! content hash strategy always gives the same screenshot the same name, so
+//! archiving the same screenshots again (e.g. in a pipeline that may be re-run)
+//! is recognised as a duplicate, rather than saving another copy. These
+//! screenshots are sharded into subdirectories named after the first two
+//! digits of their hash, so that no directory grows too large.
+//!
+//! [`Paths::naming`]: crate::settings::Paths::naming
+
+use crate::history;
+use crate::ocr;
+use crate::settings::Naming;
+use crate::template::{self, Context};
+use image::RgbImage;
+use log::warn;
+use std::path::Path;
+
+/// The template that [`Naming::Timestamp`] names screenshots with, which is
+/// also used when another strategy can't name a screenshot.
+///
+/// [`Naming::Timestamp`]: Naming::Timestamp
+const TIMESTAMP_TEMPLATE: &str = "{date:%Y%m%d_%H%M%S}";
+
+/// The template that [`Naming::Counter`] names screenshots with.
+///
+/// [`Naming::Counter`]: Naming::Counter
+const COUNTER_TEMPLATE: &str = "{counter:6}";
+
+/// The number of digits of a content hash that its shard directory is named
+/// after.
+const SHARD_LENGTH: usize = 2;
+
+/// A way of choosing the filename of a new screenshot.
+pub trait NamingStrategy {
+    /// Does any slow work that naming `image` needs (e.g. recognising its
+    /// text), before [`filename`] is called. Other saves may be waiting to
+    /// reserve a filename whilst [`filename`] runs, so it mustn't be slow.
+    ///
+    /// [`filename`]: NamingStrategy::filename
+    fn prepare(&mut self, _image: &RgbImage, _context: &mut Context) {}
+
+    /// Returns the filename (excluding its extension, but possibly including
+    /// subdirectories) for the screenshot that was prepared, which was
+    /// captured in `context`. `is_used` returns whether or not a candidate
+    /// filename is already used, as for [`template::expand`].
+    ///
+    /// [`template::expand`]: template::expand
+    fn filename(&self, context: &mut Context, is_used: &dyn Fn(&str) -> bool) -> String;
+}
+
+/// Expands a filename template. See [`template`].
+///
+/// [`template`]: crate::template
+pub struct TemplateNaming(pub String);
+
+impl NamingStrategy for TemplateNaming {
+    fn prepare(&mut self, image: &RgbImage, context: &mut Context) {
+        // Text is only recognised when it's needed, as it's slow
+        if self.0.contains("{title}") {
+            context.title = ocr::title(image);
+        }
+    }
+
+    fn filename(&self, context: &mut Context, is_used: &dyn Fn(&str) -> bool) -> String {
+        template::expand(&self.0, context, is_used)
+    }
+}
+
+/// Names screenshots after their capture time.
+pub struct TimestampNaming;
+
+impl NamingStrategy for TimestampNaming {
+    fn filename(&self, context: &mut Context, is_used: &dyn Fn(&str) -> bool) -> String {
+        template::expand(TIMESTAMP_TEMPLATE, context, is_used)
+    }
+}
+
+/// Numbers screenshots, from 1 upwards.
+pub struct CounterNaming;
+
+impl NamingStrategy for CounterNaming {
+    fn filename(&self, context: &mut Context, is_used: &dyn Fn(&str) -> bool) -> String {
+        template::expand(COUNTER_TEMPLATE, context, is_used)
+    }
+}
+
+/// Names screenshots after the SHA-256 hash of their pixels, within a shard
+/// directory. A screenshot is given the same name even if it is already used,
+/// as it can only be used by the same screenshot.
+#[derive(Default)]
+pub struct ContentHashNaming {
+    /// The hash of the prepared screenshot, if it could be hashed.
+    hash: Option<String>,
+}
+
+impl NamingStrategy for ContentHashNaming {
+    fn prepare(&mut self, image: &RgbImage, _context: &mut Context) {
+        self.hash = history::hash(image)
+            .map_err(|e| warn!("Failed to hash screenshot for its filename: {:#?}", e))
+            .ok();
+    }
+
+    fn filename(&self, context: &mut Context, is_used: &dyn Fn(&str) -> bool) -> String {
+        match &self.hash {
+            Some(hash) => Path::new(&hash[..SHARD_LENGTH])
+                .join(hash)
+                .to_string_lossy()
+                .into_owned(),
+            None => TimestampNaming.filename(context, is_used),
+        }
+    }
+}
+
+/// Names screenshots after the first line of text in them, or their capture
+/// time, if they don't contain any.
+pub struct OcrTitleNaming;
+
+impl NamingStrategy for OcrTitleNaming {
+    fn prepare(&mut self, image: &RgbImage, context: &mut Context) {
+        context.title = ocr::recognise_title(image);
+    }
+
+    fn filename(&self, context: &mut Context, is_used: &dyn Fn(&str) -> bool) -> String {
+        if context.title.is_empty() {
+            return TimestampNaming.filename(context, is_used);
+        }
+
+        template::expand("{title}", context, is_used)
+    }
+}
+
+/// Returns whether or not screenshots named by `naming` that are already saved
+/// are duplicates, rather than other screenshots with the same name.
+pub fn names_by_content(naming: Naming) -> bool {
+    naming == Naming::ContentHash
+}
+
+/// Returns the strategy for `naming`, which expands `filename_template` if it
+/// is [`Naming::Template`].
+///
+/// [`Naming::Template`]: Naming::Template
+pub fn strategy(naming: Naming, filename_template: &str) -> Box<dyn NamingStrategy> {
+    match naming {
+        Naming::Template => Box::new(TemplateNaming(filename_template.to_owned())),
+        Naming::Timestamp => Box::new(TimestampNaming),
+        Naming::Counter => Box::new(CounterNaming),
+        Naming::ContentHash => Box::new(ContentHashNaming::default()),
+        Naming::OcrTitle => Box::new(OcrTitleNaming),
+    }
+}
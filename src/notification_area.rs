@@ -5,33 +5,65 @@
 //!
 //! [`windows`]: crate::windows
 
-use crate::extensions::CStringExtensions;
-use crate::settings::Settings;
+use crate::auto_start;
+use crate::capture;
+use crate::console;
+use crate::detection_test;
+use crate::drop_target;
+use crate::encryption;
+use crate::error_report;
+use crate::events::{self, Event};
+use crate::explorer;
+use crate::extensions::{newest_screenshot_in_dir, U16CStringExtensions};
+use crate::history_viewer;
+use crate::incognito;
+use crate::logging;
+use crate::migration;
+use crate::modal::{self, Dialog};
+use crate::policy;
+use crate::region;
+use crate::save_queue;
+use crate::settings::{AutoStartMethod, NotificationVerbosity, Settings};
+use crate::settings_window;
+use crate::share;
+use crate::stats;
+use crate::trash;
+use crate::whats_new;
 use crate::windows::{
-    create_link, get_instance, get_known_folder_path, load_menu, send_notify_message,
+    get_instance, get_registry_dword, load_menu, open_folder_and_select, register_window_message,
+    send_notify_message,
 };
 use bindings::Windows::Win32::{
-    Foundation::{HWND, LPARAM, LRESULT, PSTR, WPARAM},
-    System::SystemServices::CHAR,
+    Foundation::{HWND, LPARAM, LRESULT, POINT, PWSTR, WPARAM},
+    System::Registry::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE},
     UI::{
         Controls::{LoadIconMetric, LIM_SMALL, WM_CONTEXTMENU},
         Shell::{
-            FOLDERID_Startup, ShellExecuteA, Shell_NotifyIconA, NIF_ICON, NIF_MESSAGE, NIF_SHOWTIP,
-            NIF_TIP, NIM_ADD, NIM_DELETE, NIM_SETVERSION, NOTIFYICONDATAA, NOTIFYICONDATAA_0,
-            NOTIFYICON_VERSION_4, NOTIFY_ICON_DATA_FLAGS, NOTIFY_ICON_MESSAGE,
+            SHQueryUserNotificationState, ShellExecuteW, Shell_NotifyIconW, NIF_ICON, NIF_INFO,
+            NIF_MESSAGE, NIF_SHOWTIP, NIF_TIP, NIIF_ERROR, NIIF_INFO, NIIF_WARNING, NIM_ADD,
+            NIM_DELETE, NIM_MODIFY, NIM_SETVERSION, NINF_KEY, NIN_BALLOONUSERCLICK, NIN_SELECT,
+            NOTIFYICONDATAW, NOTIFYICONDATAW_0, NOTIFYICON_VERSION_4, NOTIFY_ICON_DATA_FLAGS,
+            NOTIFY_ICON_MESSAGE, QUNS_ACCEPTS_NOTIFICATIONS,
         },
         WindowsAndMessaging::{
-            CheckMenuItem, GetSubMenu, GetSystemMetrics, SetForegroundWindow, SetMenuDefaultItem,
-            TrackPopupMenuEx, HICON, MF_CHECKED, MF_UNCHECKED, SM_MENUDROPALIGNMENT, SW_SHOWNORMAL,
-            TPM_LEFTALIGN, TPM_RIGHTALIGN, TPM_RIGHTBUTTON, WM_APP, WM_CLOSE, WM_LBUTTONDBLCLK,
+            CheckMenuItem, EnableMenuItem, GetCursorPos, GetSubMenu, GetSystemMetrics,
+            PostMessageW, SetForegroundWindow, SetMenuDefaultItem, SetMenuItemInfoW,
+            TrackPopupMenuEx, HICON, MENUITEMINFOW, MFS_DEFAULT, MFS_HILITE, MF_CHECKED, MF_GRAYED,
+            MF_UNCHECKED, MIIM_STATE, SM_MENUDROPALIGNMENT, SW_SHOWNORMAL, TPM_LEFTALIGN,
+            TPM_RIGHTALIGN, TPM_RIGHTBUTTON, WM_APP, WM_CLOSE, WM_LBUTTONDBLCLK, WM_NULL,
+            WM_RBUTTONUP,
         },
     },
 };
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
 use rfd::FileDialog;
-use std::ffi::CString;
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::{env, mem, ptr, thread};
+use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::{mem, ptr, thread};
+use widestring::U16CString;
 use windows::{Guid, HRESULT};
 
 // Specified in `build.rs:compile_windows_resources`
@@ -45,10 +77,62 @@ const IDM_EXIT: usize = 121;
 const IDM_SET_LOCATION: usize = 122;
 const IDM_OPEN_LOCATION: usize = 123;
 const IDM_START_AUTOMATICALLY: usize = 124;
+const IDM_INCOGNITO: usize = 125;
+const IDM_FOLDER_CONTEXT_MENU: usize = 126;
+const IDM_ARCHIVE_IMAGES: usize = 127;
+const IDM_OPEN_LAST_LOCATION: usize = 128;
+const IDM_DEBUG_CONSOLE: usize = 129;
+const IDM_PAUSE: usize = 130;
+const IDM_SAVE_ALL_IMAGES: usize = 131;
+const IDM_STATISTICS: usize = 132;
+const IDM_SNIP_AGAIN: usize = 133;
+const IDM_OPEN_LOG: usize = 134;
+const IDM_SAVE_NOW: usize = 135;
+const IDM_TRASH: usize = 136;
+const IDM_OPEN_LAST_SCREENSHOT: usize = 137;
+const IDM_SETTINGS: usize = 138;
+const IDM_SHARE: usize = 139;
+const IDM_TEST_DETECTION: usize = 140;
+const IDM_WHATS_NEW: usize = 141;
+const IDM_HISTORY: usize = 142;
+const IDM_SKIP_DUPLICATES: usize = 143;
+
+/// Sent when the user activates the icon with the keyboard (i.e. by pressing
+/// Enter whilst it has focus).
+const NIN_KEYSELECT: u32 = NIN_SELECT | NINF_KEY;
 
 /// The message ID of notification area icon messages.
 pub const WMAPP_NOTIFYCALLBACK: u32 = WM_APP + 1;
 
+/// The window that owns the notification area icon, or `0` if the icon hasn't
+/// been created.
+static ICON_WINDOW: AtomicIsize = AtomicIsize::new(0);
+
+/// The ID of the `TaskbarCreated` message, or `0` if it hasn't been registered.
+static TASKBAR_CREATED: AtomicU32 = AtomicU32::new(0);
+
+/// Whether the most recent context menu request was preceded by a right click.
+/// If it wasn't, the menu was opened with the keyboard (Shift+F10 or the menu
+/// key).
+static RIGHT_CLICKED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// The file that clicking the current balloon notification opens, if any.
+    static ref BALLOON_TARGET: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+    /// The most recently saved screenshot, since the program started.
+    static ref LAST_SAVED: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+/// The severity of a balloon notification, which determines the icon shown
+/// alongside it.
+#[derive(Clone, Copy)]
+pub enum BalloonLevel {
+    Info,
+    Warning,
+    Error,
+}
+
 /// Creates a notification area icon for this application.
 ///
 /// `window` specifies the window that owns the icon. Notification area icon
@@ -66,13 +150,10 @@ pub fn create_icon(window: HWND) -> windows::Result<()> {
     // a new icon. Therefore, we remove it, if it exists.
     let _ = remove_icon(window);
 
-    let mut tooltip = [CHAR(0); 128];
+    let mut tooltip = [0; 128];
+    copy_to_wide_buffer(&mut tooltip, ICON_TOOLTIP);
 
-    tooltip[..ICON_TOOLTIP.len()]
-        .copy_from_slice(unsafe { mem::transmute::<_, &[CHAR]>(ICON_TOOLTIP.as_bytes()) });
-    tooltip[127] = CHAR(0);
-
-    let mut icon_data = NOTIFYICONDATAA {
+    let mut icon_data = NOTIFYICONDATAW {
         hWnd: window,
         uID: 0,
         uFlags: NIF_ICON | NIF_TIP | NIF_MESSAGE | NIF_SHOWTIP,
@@ -81,7 +162,7 @@ pub fn create_icon(window: HWND) -> windows::Result<()> {
             LoadIconMetric(get_instance().unwrap(), ICON_IDENTIFIER, LIM_SMALL).unwrap()
         },
         szTip: tooltip,
-        Anonymous: NOTIFYICONDATAA_0 {
+        Anonymous: NOTIFYICONDATAW_0 {
             uVersion: NOTIFYICON_VERSION_4,
         },
         ..default_notify_icon_data()
@@ -90,12 +171,14 @@ pub fn create_icon(window: HWND) -> windows::Result<()> {
     shell_notify_icon(NIM_ADD, &mut icon_data)?;
     shell_notify_icon(NIM_SETVERSION, &mut icon_data)?;
 
+    ICON_WINDOW.store(window.0, Ordering::SeqCst);
+
     Ok(())
 }
 
 /// Removes the notification area icon for this application.
 pub fn remove_icon(window: HWND) -> windows::Result<()> {
-    let mut icon_data = NOTIFYICONDATAA {
+    let mut icon_data = NOTIFYICONDATAW {
         hWnd: window,
         uID: 0,
         ..default_notify_icon_data()
@@ -106,6 +189,320 @@ pub fn remove_icon(window: HWND) -> windows::Result<()> {
     Ok(())
 }
 
+/// Registers the `TaskbarCreated` message, which Explorer broadcasts to every
+/// top-level window when it (re)starts, so that the notification area icon can
+/// be re-created, if Explorer crashes or is restarted.
+pub fn register_taskbar_created() -> windows::Result<()> {
+    TASKBAR_CREATED.store(register_window_message("TaskbarCreated")?, Ordering::SeqCst);
+
+    Ok(())
+}
+
+/// Returns whether or not `message` is the `TaskbarCreated` message.
+pub fn is_taskbar_created(message: u32) -> bool {
+    message != 0 && message == TASKBAR_CREATED.load(Ordering::SeqCst)
+}
+
+/// `TaskbarCreated` message processor, which re-creates the notification area
+/// icon, as Explorer forgets every icon when it restarts.
+pub fn on_taskbar_created(window: HWND) -> LRESULT {
+    if !has_icon() {
+        return LRESULT(0);
+    }
+
+    info!("Taskbar created - re-creating notification area icon");
+
+    if let Err(e) = create_icon(window) {
+        warn!("Failed to re-create notification area icon: {:#?}", e);
+    }
+
+    LRESULT(0)
+}
+
+/// Creates the notification area icon, if it is hidden, until the program
+/// exits.
+pub fn reveal_icon(window: HWND) {
+    if has_icon() {
+        return;
+    }
+
+    info!("Revealing the hidden notification area icon");
+
+    if let Err(e) = create_icon(window) {
+        warn!("Failed to create notification area icon: {:#?}", e);
+    }
+}
+
+/// Returns whether or not the notification area icon has been created.
+pub fn has_icon() -> bool {
+    ICON_WINDOW.load(Ordering::SeqCst) != 0
+}
+
+/// Shows a balloon notification (displayed as a toast on Windows 10 and later),
+/// originating from the notification area icon.
+///
+/// If notifications are disabled (e.g. by group policy, or whilst Focus Assist
+/// is on), the notification is shown in the icon's tooltip instead, so that it
+/// isn't silently lost.
+///
+/// This can be called from any thread, but does nothing if the notification
+/// area icon hasn't been created yet.
+pub fn show_balloon(title: &str, text: &str, level: BalloonLevel) -> windows::Result<()> {
+    let window = HWND(ICON_WINDOW.load(Ordering::SeqCst));
+
+    if window.is_null() {
+        return Ok(());
+    }
+
+    // Replacing a balloon also replaces its click action
+    *BALLOON_TARGET.lock().unwrap() = None;
+
+    if !notifications_available() {
+        info!(
+            "Notifications are unavailable - showing in tooltip instead: {}: {}",
+            title, text
+        );
+
+        return show_in_tooltip(window, title, text);
+    }
+
+    let mut icon_data = NOTIFYICONDATAW {
+        hWnd: window,
+        uID: 0,
+        uFlags: NIF_INFO,
+        dwInfoFlags: match level {
+            BalloonLevel::Info => NIIF_INFO,
+            BalloonLevel::Warning => NIIF_WARNING,
+            BalloonLevel::Error => NIIF_ERROR,
+        },
+        ..default_notify_icon_data()
+    };
+
+    copy_to_wide_buffer(&mut icon_data.szInfoTitle, title);
+    copy_to_wide_buffer(&mut icon_data.szInfo, text);
+
+    shell_notify_icon(NIM_MODIFY, &mut icon_data)
+}
+
+/// Shows notifications for capture pipeline events, on a background thread, as
+/// chattily as the configured notification verbosity allows.
+pub fn subscribe_to_events() {
+    let receiver = events::subscribe();
+
+    thread::spawn(move || {
+        for event in receiver {
+            let mut verbosity = NotificationVerbosity::EverySave;
+            Settings::read(|s| verbosity = s.notifications.verbosity);
+
+            match event {
+                Event::CaptureDetected => {}
+                // Screenshots that finished saving after a panic wipe must not
+                // be brought back to anyone's attention
+                Event::Saved { generation, .. } if generation != save_queue::generation() => {
+                    debug!("Screenshot captured before a panic wipe saved - not shown");
+                }
+                Event::Saved { path, .. } => {
+                    *LAST_SAVED.lock().unwrap() = Some(path.clone());
+
+                    if verbosity >= NotificationVerbosity::EverySave {
+                        show_saved_balloon(path);
+                    }
+                }
+                Event::Skipped { reason } => {
+                    debug!("Screenshot skipped: {:?}", reason);
+
+                    if verbosity >= NotificationVerbosity::Verbose {
+                        let _ = show_balloon(
+                            "Screenshot not saved",
+                            reason.description(),
+                            BalloonLevel::Info,
+                        );
+                    }
+                }
+                Event::Failed { error, .. } => {
+                    if verbosity >= NotificationVerbosity::ErrorsOnly {
+                        let _ = show_balloon("Screenshot not saved", &error, BalloonLevel::Error);
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Shows a balloon notification with the filename of a newly saved screenshot,
+/// which opens the screenshot when clicked.
+fn show_saved_balloon(path: PathBuf) {
+    debug!("Screenshot saved to {}", path.display());
+
+    let filename = path.file_name().unwrap_or_default().to_string_lossy();
+
+    if show_balloon("Screenshot saved", &filename, BalloonLevel::Info).is_ok() {
+        *BALLOON_TARGET.lock().unwrap() = Some(path);
+    }
+}
+
+/// Forgets the most recently saved screenshot, and the screenshot that the
+/// current balloon notification refers to.
+pub fn forget_recent_screenshots() {
+    *LAST_SAVED.lock().unwrap() = None;
+    *BALLOON_TARGET.lock().unwrap() = None;
+}
+
+/// Opens the file that the current balloon notification refers to, if any.
+fn open_balloon_target(window: HWND) -> windows::Result<()> {
+    match BALLOON_TARGET.lock().unwrap().clone() {
+        Some(path) => open_screenshot(window, &path),
+        None => Ok(()),
+    }
+}
+
+/// Opens the most recently saved screenshot in its default viewer, or the
+/// screenshot directory, if no screenshots have been saved yet.
+fn open_last_screenshot(window: HWND) -> windows::Result<()> {
+    let last_saved = LAST_SAVED.lock().unwrap().clone().or_else(|| {
+        // Nothing has been saved since the program started
+        let mut screenshot_path = PathBuf::new();
+        Settings::read(|s| screenshot_path = s.paths.screenshots.clone());
+
+        newest_screenshot_in_dir(&screenshot_path)
+    });
+
+    match last_saved {
+        Some(path) if path.exists() => open_screenshot(window, &path),
+        _ => explore_screenshot_dir(window),
+    }
+}
+
+/// Opens the screenshot saved at `path` in its default viewer.
+///
+/// Encrypted archives can't be opened directly, so they are selected in
+/// Explorer instead.
+pub fn open_screenshot(window: HWND, path: &Path) -> windows::Result<()> {
+    if path.file_name() == Some(OsStr::new(encryption::ARCHIVE_FILE)) {
+        return open_folder_and_select(path);
+    }
+
+    shell_open(window, path)
+}
+
+/// Opens `path` with its default program.
+fn shell_open(window: HWND, path: &Path) -> windows::Result<()> {
+    if unsafe {
+        ShellExecuteW(
+            window,
+            "open",
+            &*path.to_string_lossy(),
+            PWSTR(ptr::null_mut()),
+            PWSTR(ptr::null_mut()),
+            SW_SHOWNORMAL.0 as i32,
+        )
+        .0 <= 32
+    } {
+        Err(HRESULT::from_thread().into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Shows `status` (e.g. progress) beneath the program's name in the tooltip of
+/// the notification area icon, or just the program's name, if `status` is
+/// `None`.
+///
+/// This can be called from any thread, but does nothing if the notification
+/// area icon hasn't been created yet.
+pub fn set_status(status: Option<&str>) -> windows::Result<()> {
+    let window = HWND(ICON_WINDOW.load(Ordering::SeqCst));
+
+    if window.is_null() {
+        return Ok(());
+    }
+
+    let tooltip = match status {
+        Some(status) => format!("{}\n{}", ICON_TOOLTIP, status.replace('&', "&&&")),
+        None => ICON_TOOLTIP.to_owned(),
+    };
+
+    let mut icon_data = NOTIFYICONDATAW {
+        hWnd: window,
+        uID: 0,
+        uFlags: NIF_TIP | NIF_SHOWTIP,
+        ..default_notify_icon_data()
+    };
+
+    copy_to_wide_buffer(&mut icon_data.szTip, &tooltip);
+
+    shell_notify_icon(NIM_MODIFY, &mut icon_data)
+}
+
+/// Replaces the tooltip of the notification area icon with a notification.
+fn show_in_tooltip(window: HWND, title: &str, text: &str) -> windows::Result<()> {
+    let tooltip = format!("{}\n{}: {}", ICON_TOOLTIP, title, text.replace('&', "&&&"));
+
+    let mut icon_data = NOTIFYICONDATAW {
+        hWnd: window,
+        uID: 0,
+        uFlags: NIF_TIP | NIF_SHOWTIP,
+        ..default_notify_icon_data()
+    };
+
+    copy_to_wide_buffer(&mut icon_data.szTip, &tooltip);
+
+    shell_notify_icon(NIM_MODIFY, &mut icon_data)
+}
+
+/// Returns whether or not balloon notifications will currently be shown to the
+/// user, taking notification policies, user preferences, and Focus Assist into
+/// account.
+fn notifications_available() -> bool {
+    const EXPLORER_POLICY_KEY: &str = r"Software\Policies\Microsoft\Windows\Explorer";
+    const PUSH_NOTIFICATIONS_POLICY_KEY: &str =
+        r"Software\Policies\Microsoft\Windows\CurrentVersion\PushNotifications";
+    const EXPLORER_ADVANCED_KEY: &str =
+        r"Software\Microsoft\Windows\CurrentVersion\Explorer\Advanced";
+
+    for &key in &[HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE] {
+        if get_registry_dword(key, EXPLORER_POLICY_KEY, "TaskbarNoNotification") == Some(1)
+            || get_registry_dword(
+                key,
+                PUSH_NOTIFICATIONS_POLICY_KEY,
+                "NoToastApplicationNotification",
+            ) == Some(1)
+        {
+            return false;
+        }
+    }
+
+    if get_registry_dword(
+        HKEY_CURRENT_USER,
+        EXPLORER_ADVANCED_KEY,
+        "EnableBalloonTips",
+    ) == Some(0)
+    {
+        return false;
+    }
+
+    // If the state can't be queried, we optimistically assume that notifications are shown
+    match unsafe { SHQueryUserNotificationState() } {
+        Ok(state) => state == QUNS_ACCEPTS_NOTIFICATIONS,
+        Err(_) => true,
+    }
+}
+
+/// Copies `text` into the fixed length UTF-16 `buffer`, truncating it if it
+/// doesn't fit, and null-terminating it.
+fn copy_to_wide_buffer(buffer: &mut [u16], text: &str) {
+    let text: Vec<u16> = text.encode_utf16().collect();
+    let mut length = text.len().min(buffer.len() - 1);
+
+    // Don't split a surrogate pair when truncating
+    if length < text.len() && (0xD800..0xDC00).contains(&text[length - 1]) {
+        length -= 1;
+    }
+
+    buffer[..length].copy_from_slice(&text[..length]);
+    buffer[length] = 0;
+}
+
 /// Message handler for notification area icon messages.
 ///
 /// This should be called from the `wndProc` function for the [`HWND`] that the
@@ -115,14 +512,28 @@ pub fn remove_icon(window: HWND) -> windows::Result<()> {
 //noinspection RsUnreachablePatterns
 pub fn notify_callback(window: HWND, w_param: WPARAM, l_param: LPARAM) -> LRESULT {
     match (l_param.0 & 0xFFFF) as u32 {
+        WM_RBUTTONUP => {
+            RIGHT_CLICKED.store(true, Ordering::SeqCst);
+            LRESULT(0)
+        }
         WM_CONTEXTMENU => {
             let click_location = (w_param.0 & 0xFFFF, (w_param.0 >> 16) & 0xFFFF);
-            show_context_menu(window, click_location);
+            let from_keyboard = !RIGHT_CLICKED.swap(false, Ordering::SeqCst);
 
+            show_context_menu(window, click_location, from_keyboard);
+
+            LRESULT(0)
+        }
+        NIN_BALLOONUSERCLICK => {
+            if let Err(e) = open_balloon_target(window) {
+                error_report::report("Unable to open the screenshot", &e);
+            }
             LRESULT(0)
         }
-        WM_LBUTTONDBLCLK => {
-            explore_screenshot_dir(window).unwrap();
+        WM_LBUTTONDBLCLK | NIN_KEYSELECT => {
+            if let Err(e) = open_last_screenshot(window) {
+                error_report::report("Unable to open the last screenshot", &e);
+            }
             LRESULT(0)
         }
         _ => LRESULT(0),
@@ -144,52 +555,149 @@ pub fn on_command(window: HWND, command: usize) -> Option<LRESULT> {
             Some(LRESULT(0))
         }
         IDM_OPEN_LOCATION => {
-            explore_screenshot_dir(window).unwrap();
+            if let Err(e) = explore_screenshot_dir(window) {
+                error_report::report("Unable to open the screenshot folder", &e);
+            }
+            Some(LRESULT(0))
+        }
+        IDM_OPEN_LAST_SCREENSHOT => {
+            if let Err(e) = open_last_screenshot(window) {
+                error_report::report("Unable to open the last screenshot", &e);
+            }
+            Some(LRESULT(0))
+        }
+        IDM_OPEN_LAST_LOCATION => {
+            if let Err(e) = select_newest_screenshot(window) {
+                error_report::report("Unable to open the last screenshot location", &e);
+            }
             Some(LRESULT(0))
         }
         IDM_START_AUTOMATICALLY => {
-            toggle_auto_start().unwrap();
+            if let Err(e) = toggle_auto_start() {
+                error_report::report(
+                    "Unable to change whether the program starts automatically",
+                    &e,
+                );
+            }
+            Some(LRESULT(0))
+        }
+        IDM_PAUSE => {
+            toggle_paused();
+            Some(LRESULT(0))
+        }
+        IDM_SAVE_ALL_IMAGES => {
+            Settings::write(|s| {
+                s.program.save_all_clipboard_images = !s.program.save_all_clipboard_images
+            });
+            Some(LRESULT(0))
+        }
+        IDM_SKIP_DUPLICATES => {
+            Settings::write(|s| s.dedup.enabled = !s.dedup.enabled);
+            Some(LRESULT(0))
+        }
+        IDM_INCOGNITO => {
+            incognito::toggle();
+            Some(LRESULT(0))
+        }
+        IDM_ARCHIVE_IMAGES => {
+            if let Err(e) = drop_target::show() {
+                error_report::report("Unable to open the archive images window", &e);
+            }
+            Some(LRESULT(0))
+        }
+        IDM_STATISTICS => {
+            stats::show();
+            Some(LRESULT(0))
+        }
+        IDM_TRASH => {
+            trash::show();
+            Some(LRESULT(0))
+        }
+        IDM_SETTINGS => {
+            settings_window::show(window);
+            Some(LRESULT(0))
+        }
+        IDM_SHARE => {
+            share::show();
+            Some(LRESULT(0))
+        }
+        IDM_TEST_DETECTION => {
+            detection_test::start();
+            Some(LRESULT(0))
+        }
+        IDM_WHATS_NEW => {
+            whats_new::show();
+            Some(LRESULT(0))
+        }
+        IDM_HISTORY => {
+            history_viewer::show();
+            Some(LRESULT(0))
+        }
+        IDM_SAVE_NOW => {
+            capture::save_now();
+            Some(LRESULT(0))
+        }
+        IDM_SNIP_AGAIN => {
+            region::snip_again(window);
+            Some(LRESULT(0))
+        }
+        IDM_FOLDER_CONTEXT_MENU => {
+            if let Err(e) = toggle_folder_context_menu() {
+                error_report::report("Unable to change the folder context menu entry", &e);
+            }
+            Some(LRESULT(0))
+        }
+        IDM_OPEN_LOG => {
+            if let Err(e) = shell_open(window, &logging::current_log_file()) {
+                error_report::report("Unable to open the log file", &e);
+            }
+            Some(LRESULT(0))
+        }
+        IDM_DEBUG_CONSOLE => {
+            if let Err(e) = console::toggle() {
+                error_report::report("Unable to show or hide the debug console", &e);
+            }
             Some(LRESULT(0))
         }
         _ => None,
     }
 }
 
-/// Safe wrapper around [`Shell_NotifyIconA`].
+/// Safe wrapper around [`Shell_NotifyIconW`].
 ///
-/// [`Shell_NotifyIconA`]: Shell_NotifyIconA
+/// [`Shell_NotifyIconW`]: Shell_NotifyIconW
 fn shell_notify_icon(
     message: NOTIFY_ICON_MESSAGE,
-    data: &mut NOTIFYICONDATAA,
+    data: &mut NOTIFYICONDATAW,
 ) -> windows::Result<()> {
-    if unsafe { Shell_NotifyIconA(message, data).0 != 0 } {
+    if unsafe { Shell_NotifyIconW(message, data).0 != 0 } {
         Ok(())
     } else {
         Err(HRESULT::from_thread().into())
     }
 }
 
-/// Returns a default (zeroed) [`NOTIFYICONDATAA`] instance. the `cbSize` field
-/// is initialised properly, to the size of the [`NOTIFYICONDATAA`] struct.
+/// Returns a default (zeroed) [`NOTIFYICONDATAW`] instance. the `cbSize` field
+/// is initialised properly, to the size of the [`NOTIFYICONDATAW`] struct.
 ///
 /// This essentially functions like a [`Default`] implementation.
 ///
-/// [`NOTIFYICONDATAA`]: NOTIFYICONDATAA
+/// [`NOTIFYICONDATAW`]: NOTIFYICONDATAW
 /// [`Default`]: Default
-fn default_notify_icon_data() -> NOTIFYICONDATAA {
-    NOTIFYICONDATAA {
-        cbSize: mem::size_of::<NOTIFYICONDATAA>() as u32,
+fn default_notify_icon_data() -> NOTIFYICONDATAW {
+    NOTIFYICONDATAW {
+        cbSize: mem::size_of::<NOTIFYICONDATAW>() as u32,
         hWnd: HWND(0),
         uID: 0,
         uFlags: NOTIFY_ICON_DATA_FLAGS(0),
         uCallbackMessage: 0,
         hIcon: HICON(0),
-        szTip: [CHAR(0); 128],
+        szTip: [0; 128],
         dwState: 0,
         dwStateMask: 0,
-        szInfo: [CHAR(0); 256],
-        Anonymous: NOTIFYICONDATAA_0 { uVersion: 0 },
-        szInfoTitle: [CHAR(0); 64],
+        szInfo: [0; 256],
+        Anonymous: NOTIFYICONDATAW_0 { uVersion: 0 },
+        szInfoTitle: [0; 64],
         dwInfoFlags: 0,
         guidItem: Guid::zeroed(),
         hBalloonIcon: HICON(0),
@@ -200,18 +708,52 @@ fn default_notify_icon_data() -> NOTIFYICONDATAA {
 ///
 /// # Arguments
 ///
-/// * `window`  - The window that owns the notification area icon.
-/// * `click_x` - The mouse X position of the right click.
-/// * `click_y` - The mouse Y position of the right click.
-fn show_context_menu(window: HWND, (click_x, click_y): (usize, usize)) {
+/// * `window`        - The window that owns the notification area icon.
+/// * `click_x`       - The mouse X position of the right click.
+/// * `click_y`       - The mouse Y position of the right click.
+/// * `from_keyboard` - Whether the menu was opened with the keyboard, in which
+///   case the default item is highlighted, so that it can be activated by
+///   pressing Enter.
+fn show_context_menu(window: HWND, (click_x, click_y): (usize, usize), from_keyboard: bool) {
     let mut auto_start = false;
-    Settings::read(|s| auto_start = s.program.auto_start);
+    let mut folder_context_menu = false;
+    let mut paused = false;
+    let mut save_all_clipboard_images = false;
+    let mut skip_duplicates = false;
+
+    Settings::read(|s| {
+        auto_start = s.program.auto_start;
+        folder_context_menu = s.program.folder_context_menu;
+        paused = s.program.paused;
+        save_all_clipboard_images = s.program.save_all_clipboard_images;
+        skip_duplicates = s.dedup.enabled;
+    });
 
     unsafe {
-        let menu = load_menu(get_instance().unwrap(), PSTR(200 as *mut u8));
+        let menu = load_menu(get_instance().unwrap(), PWSTR(200 as *mut u16));
         let submenu = GetSubMenu(menu.value(), 0);
 
-        SetMenuDefaultItem(submenu, IDM_OPEN_LOCATION as u32, 0);
+        SetMenuDefaultItem(submenu, IDM_OPEN_LAST_SCREENSHOT as u32, 0);
+
+        if policy::get().screenshot_dir.is_some() {
+            EnableMenuItem(submenu, IDM_SET_LOCATION as u32, MF_GRAYED);
+        }
+
+        if from_keyboard {
+            let mut item_info = MENUITEMINFOW {
+                cbSize: mem::size_of::<MENUITEMINFOW>() as u32,
+                fMask: MIIM_STATE,
+                fState: MFS_DEFAULT | MFS_HILITE,
+                ..Default::default()
+            };
+
+            SetMenuItemInfoW(
+                submenu,
+                IDM_OPEN_LAST_SCREENSHOT as u32,
+                false,
+                &mut item_info,
+            );
+        }
 
         CheckMenuItem(
             submenu,
@@ -223,6 +765,62 @@ fn show_context_menu(window: HWND, (click_x, click_y): (usize, usize)) {
             },
         );
 
+        CheckMenuItem(
+            submenu,
+            IDM_FOLDER_CONTEXT_MENU as u32,
+            if folder_context_menu {
+                MF_CHECKED.0
+            } else {
+                MF_UNCHECKED.0
+            },
+        );
+
+        CheckMenuItem(
+            submenu,
+            IDM_DEBUG_CONSOLE as u32,
+            if console::is_shown() {
+                MF_CHECKED.0
+            } else {
+                MF_UNCHECKED.0
+            },
+        );
+
+        CheckMenuItem(
+            submenu,
+            IDM_PAUSE as u32,
+            if paused { MF_CHECKED.0 } else { MF_UNCHECKED.0 },
+        );
+
+        CheckMenuItem(
+            submenu,
+            IDM_SAVE_ALL_IMAGES as u32,
+            if save_all_clipboard_images {
+                MF_CHECKED.0
+            } else {
+                MF_UNCHECKED.0
+            },
+        );
+
+        CheckMenuItem(
+            submenu,
+            IDM_SKIP_DUPLICATES as u32,
+            if skip_duplicates {
+                MF_CHECKED.0
+            } else {
+                MF_UNCHECKED.0
+            },
+        );
+
+        CheckMenuItem(
+            submenu,
+            IDM_INCOGNITO as u32,
+            if incognito::is_active() {
+                MF_CHECKED.0
+            } else {
+                MF_UNCHECKED.0
+            },
+        );
+
         SetForegroundWindow(window);
 
         let mut popup_flags = TPM_RIGHTBUTTON;
@@ -241,7 +839,25 @@ fn show_context_menu(window: HWND, (click_x, click_y): (usize, usize)) {
             window,
             ptr::null_mut(),
         );
+
+        // Without this, the menu may not close when it loses focus, and keyboard
+        // navigation can stop working after it is dismissed:
+        // https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-trackpopupmenu#remarks
+        PostMessageW(window, WM_NULL, WPARAM(0), LPARAM(0));
+    }
+}
+
+/// Shows the notification area icon's context menu at the mouse cursor, so that
+/// the menu can be used even if the icon is hidden.
+pub fn show_menu_at_cursor(window: HWND) {
+    let mut cursor = POINT::default();
+
+    if unsafe { GetCursorPos(&mut cursor).0 == 0 } {
+        warn!("Failed to get the cursor position");
+        return;
     }
+
+    show_context_menu(window, (cursor.x as usize, cursor.y as usize), true);
 }
 
 /// Opens a folder select dialog, to select the directory to save captured
@@ -250,21 +866,12 @@ fn show_context_menu(window: HWND, (click_x, click_y): (usize, usize)) {
 /// If the user accepts a directory in the dialog, it is written to the global
 /// application [`Settings`].
 ///
-/// This function is a no-op if a folder select dialog is already open.
+/// If a folder select dialog is already open, it is brought to the foreground
+/// instead.
 ///
 /// [`Settings`]: Settings
 fn set_screenshot_dir() {
-    static IS_BROWSING: AtomicBool = AtomicBool::new(false);
-
-    thread::spawn(|| {
-        if IS_BROWSING
-            .compare_exchange(false, true, Ordering::SeqCst, Ordering::Acquire)
-            .is_err()
-        {
-            // We already have a file dialog open
-            return;
-        }
-
+    modal::open(Dialog::DirectoryPicker, || {
         let mut screenshot_path = PathBuf::new();
 
         Settings::read(|s| {
@@ -275,33 +882,30 @@ fn set_screenshot_dir() {
             .set_directory(screenshot_path)
             .pick_folder()
         {
-            Settings::write(|s| s.paths.screenshots = new_path);
+            migration::change_screenshot_dir(new_path);
         }
-
-        IS_BROWSING.store(false, Ordering::SeqCst);
     });
 }
 
 /// Opens an explorer window to the current screenshot output directory.
 fn explore_screenshot_dir(window: HWND) -> windows::Result<()> {
-    let operation = CString::new("explore").unwrap();
-    let mut folder: CString = CString::new("").unwrap();
+    let mut folder = U16CString::default();
 
     Settings::read(|s| {
         // This isn't optimal, but, rather than failing if the path doesn't exist, we just open an
         // explorer window to a parent folder in the path that does exist
         let parent = find_existing_parent(&s.paths.screenshots);
 
-        folder = CString::new(parent.to_str().unwrap()).unwrap();
+        folder = U16CString::from_os_str(parent.as_os_str()).unwrap();
     });
 
     if unsafe {
-        ShellExecuteA(
+        ShellExecuteW(
             window,
-            operation.as_pstr(),
-            folder.as_pstr(),
-            PSTR(ptr::null_mut()),
-            PSTR(ptr::null_mut()),
+            "explore",
+            folder.as_pwstr(),
+            PWSTR(ptr::null_mut()),
+            PWSTR(ptr::null_mut()),
             SW_SHOWNORMAL.0 as i32,
         )
         .0 <= 32
@@ -312,6 +916,21 @@ fn explore_screenshot_dir(window: HWND) -> windows::Result<()> {
     }
 }
 
+/// Opens an explorer window to the current screenshot output directory, with
+/// the newest screenshot selected. If the directory is empty, this falls back
+/// to [`explore_screenshot_dir`].
+///
+/// [`explore_screenshot_dir`]: explore_screenshot_dir
+fn select_newest_screenshot(window: HWND) -> windows::Result<()> {
+    let mut screenshot_path = PathBuf::new();
+    Settings::read(|s| screenshot_path = s.paths.screenshots.clone());
+
+    match newest_screenshot_in_dir(&screenshot_path) {
+        Some(newest_file) => open_folder_and_select(&newest_file),
+        None => explore_screenshot_dir(window),
+    }
+}
+
 /// Finds the first existing path, starting from a child path. This allows
 /// callers to handle navigation to directories that have since been deleted by
 /// the user.
@@ -332,29 +951,57 @@ fn find_existing_parent(path: &Path) -> PathBuf {
     current_path.into()
 }
 
-/// Adds / removes a shortcut to this program from the user's start-up folder,
-/// depending on their current auto-start setting.
+/// Pauses saving screenshots if it is resumed, otherwise resumes it.
+fn toggle_paused() {
+    let mut paused = false;
+
+    Settings::write(|s| {
+        s.program.paused = !s.program.paused;
+        paused = s.program.paused;
+    });
+
+    info!("Saving {}", if paused { "paused" } else { "resumed" });
+}
+
+/// Registers / unregisters this program to start automatically when the user
+/// logs in, depending on their current auto-start setting.
 fn toggle_auto_start() -> windows::Result<()> {
     let mut auto_start = false;
-    Settings::read(|s| auto_start = s.program.auto_start);
+    let mut method = AutoStartMethod::Registry;
 
-    let mut startup_path = get_known_folder_path(FOLDERID_Startup)?;
-    startup_path.push("Snip & AutoSave.lnk");
+    Settings::read(|s| {
+        auto_start = s.program.auto_start;
+        method = s.program.auto_start_method;
+    });
 
     if auto_start {
-        // Remove shortcut from start-up folder
-        let _ = std::fs::remove_file(startup_path);
+        auto_start::disable();
 
         Settings::write(|s| s.program.auto_start = false);
     } else {
-        // Add shortcut to start-up folder
-        create_link(
-            &startup_path,
-            dunce::simplified(&env::current_exe().unwrap()),
-        )?;
+        auto_start::enable(method)?;
 
         Settings::write(|s| s.program.auto_start = true);
     }
 
     Ok(())
 }
+
+/// Adds / removes the "Set as Snip & AutoSave folder" entry from the context
+/// menu of folders in Explorer, depending on its current setting.
+fn toggle_folder_context_menu() -> windows::Result<()> {
+    let mut folder_context_menu = false;
+    Settings::read(|s| folder_context_menu = s.program.folder_context_menu);
+
+    if folder_context_menu {
+        let _ = explorer::unregister_folder_verb();
+
+        Settings::write(|s| s.program.folder_context_menu = false);
+    } else {
+        explorer::register_folder_verb()?;
+
+        Settings::write(|s| s.program.folder_context_menu = true);
+    }
+
+    Ok(())
+}
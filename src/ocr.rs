@@ -0,0 +1,122 @@
+//! Text recognition in screenshots, using the OCR engine built into Windows,
+//! in the languages of the user's profile.
+//!
+//! Recognition is opt-in, as it adds a noticeable delay to each save, and is
+//! never done when prohibited by Group Policy.
+
+use crate::convert;
+use crate::policy;
+use crate::settings::Settings;
+use bindings::Windows::{
+    Graphics::Imaging::{BitmapPixelFormat, SoftwareBitmap},
+    Media::Ocr::OcrEngine,
+    Security::Cryptography::CryptographicBuffer,
+};
+use image::{imageops, RgbImage};
+use log::{debug, warn};
+use std::borrow::Cow;
+
+/// Returns whether or not text in screenshots should be recognised.
+pub fn is_enabled() -> bool {
+    let mut enabled = false;
+    Settings::read(|s| enabled = s.ocr.enabled);
+
+    enabled && !policy::get().disable_ocr
+}
+
+/// Recognises the text in `image`, returning each line, from top to bottom.
+pub fn recognise_lines(image: &RgbImage) -> windows::Result<Vec<String>> {
+    let engine = OcrEngine::TryCreateFromUserProfileLanguages()?;
+
+    // The engine rejects images larger than its maximum size
+    let max_dimension = OcrEngine::MaxImageDimension()?;
+    let image = if image.width().max(image.height()) > max_dimension {
+        let scale = max_dimension as f32 / image.width().max(image.height()) as f32;
+
+        Cow::Owned(imageops::thumbnail(
+            image,
+            ((image.width() as f32 * scale) as u32).max(1),
+            ((image.height() as f32 * scale) as u32).max(1),
+        ))
+    } else {
+        Cow::Borrowed(image)
+    };
+
+    let bitmap = SoftwareBitmap::CreateCopyFromBuffer(
+        CryptographicBuffer::CreateFromByteArray(&convert::image_to_bgra(&image))?,
+        BitmapPixelFormat::Bgra8,
+        image.width() as i32,
+        image.height() as i32,
+    )?;
+
+    let result = engine.RecognizeAsync(&bitmap)?.get()?;
+
+    result
+        .Lines()?
+        .into_iter()
+        .map(|line| Ok(line.Text()?.to_string()))
+        .collect()
+}
+
+/// Returns the first line of text in `image` that contains a letter or digit,
+/// with runs of whitespace collapsed, truncated to the configured maximum
+/// title length. Returns an empty string if recognition is disabled, or no
+/// text was recognised.
+pub fn title(image: &RgbImage) -> String {
+    if !is_enabled() {
+        return String::new();
+    }
+
+    recognise_title(image)
+}
+
+/// Returns the title of `image`, as [`title`] does, even if recognition is
+/// turned off in the settings (but not if it is prohibited by Group Policy).
+///
+/// [`title`]: title
+pub fn recognise_title(image: &RgbImage) -> String {
+    if policy::get().disable_ocr {
+        return String::new();
+    }
+
+    let mut max_length = 0;
+    Settings::read(|s| max_length = s.ocr.max_title_length);
+
+    let lines = match recognise_lines(image) {
+        Ok(lines) => lines,
+        Err(e) => {
+            warn!("Failed to recognise text in screenshot: {:#?}", e);
+            return String::new();
+        }
+    };
+
+    let line = match lines
+        .iter()
+        .find(|line| line.chars().any(char::is_alphanumeric))
+    {
+        Some(line) => line.split_whitespace().collect::<Vec<_>>().join(" "),
+        None => {
+            debug!("No text recognised in screenshot");
+            return String::new();
+        }
+    };
+
+    truncate(&line, max_length)
+}
+
+/// Truncates `text` to at most `max_length` characters, at the end of a word
+/// if there is one in the second half of the allowed length.
+fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_owned();
+    }
+
+    let truncated: String = text.chars().take(max_length).collect();
+
+    match truncated.rfind(' ') {
+        Some(space) if truncated[..space].chars().count() >= max_length / 2 => {
+            truncated[..space].to_owned()
+        }
+        _ => truncated,
+    }
+}
@@ -0,0 +1,132 @@
+//! EXIF orientation handling for imported images.
+//!
+//! Cameras (and phones) often save images in the sensor's orientation, with an
+//! EXIF tag saying how they should be rotated for display. The `image` crate
+//! ignores this tag, so images that are imported or archived are rotated
+//! upright here, before they are hashed, thumbnailed, or re-encoded (which
+//! would otherwise lose the tag).
+//!
+//! Only the orientation tag is read, from JPEG `APP1` segments and PNG `eXIf`
+//! chunks.
+
+use crate::output;
+use image::{DynamicImage, ImageResult};
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+/// The EXIF tag that holds the orientation.
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// Decodes the image file at `path`, rotating and flipping it upright, as its
+/// EXIF orientation (if any) says.
+pub fn open_upright(path: &Path) -> ImageResult<DynamicImage> {
+    let data = fs::read(path)?;
+    let image = output::decode(&data)?;
+
+    Ok(match read_orientation(&data) {
+        Some(2) => image.fliph(),
+        Some(3) => image.rotate180(),
+        Some(4) => image.flipv(),
+        Some(5) => image.rotate90().fliph(),
+        Some(6) => image.rotate90(),
+        Some(7) => image.rotate270().fliph(),
+        Some(8) => image.rotate270(),
+        _ => image,
+    })
+}
+
+/// Returns the EXIF orientation (from 1 to 8) of an encoded image, if it has
+/// one.
+fn read_orientation(data: &[u8]) -> Option<u16> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        jpeg_exif(data).and_then(tiff_orientation)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_exif(data).and_then(tiff_orientation)
+    } else {
+        None
+    }
+}
+
+/// Finds the EXIF data (a TIFF structure) within a JPEG file.
+fn jpeg_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut position = 2;
+
+    while position + 4 <= data.len() && data[position] == 0xFF {
+        let marker = data[position + 1];
+        let length = u16::from_be_bytes([data[position + 2], data[position + 3]]) as usize;
+        let segment = data.get(position + 4..position + 2 + length)?;
+
+        match marker {
+            // APP1
+            0xE1 if segment.starts_with(b"Exif\0\0") => return Some(&segment[6..]),
+            // Start of scan, after which there is only image data
+            0xDA => return None,
+            _ => position += 2 + length,
+        }
+    }
+
+    None
+}
+
+/// Finds the EXIF data (a TIFF structure) within a PNG file.
+fn png_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut position = 8;
+
+    while position + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[position..position + 4].try_into().ok()?) as usize;
+        let chunk_type = &data[position + 4..position + 8];
+        let chunk = data.get(position + 8..position + 8 + length)?;
+
+        match chunk_type {
+            b"eXIf" => return Some(chunk),
+            b"IDAT" | b"IEND" => return None,
+            // Data, then CRC
+            _ => position += 8 + length + 4,
+        }
+    }
+
+    None
+}
+
+/// Reads the orientation tag from the first IFD of a TIFF structure.
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(0..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let bytes = tiff.get(offset..offset + 2)?.try_into().ok()?;
+
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = tiff.get(offset..offset + 4)?.try_into().ok()?;
+
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    if read_u16(2)? != 42 {
+        return None;
+    }
+
+    let ifd = read_u32(4)? as usize;
+    let entry_count = read_u16(ifd)? as usize;
+
+    (0..entry_count)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|&entry| read_u16(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| read_u16(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
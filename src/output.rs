@@ -0,0 +1,154 @@
+//! Encoding of screenshots into the user's configured image format.
+
+use crate::settings::OutputFormat;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::error::{DecodingError, EncodingError, ImageFormatHint, LimitError, LimitErrorKind};
+use image::{ColorType, DynamicImage, ImageError, ImageFormat, ImageResult, RgbImage, RgbaImage};
+use image_webp::{WebPDecoder, WebPEncoder};
+use std::fs::{self, File};
+use std::io::{BufWriter, Cursor, Write};
+use std::path::Path;
+
+/// Returns the file extension of images saved in `format`.
+pub fn extension(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Png => "png",
+        OutputFormat::Jpeg => "jpg",
+        OutputFormat::Bmp => "bmp",
+        OutputFormat::Webp => "webp",
+    }
+}
+
+/// Saves `image` to `path`, in `format`, with `jpeg_quality` (from 1 to 100),
+/// if it is JPEG.
+pub fn save(
+    image: &RgbImage,
+    path: &Path,
+    format: OutputFormat,
+    jpeg_quality: u8,
+) -> ImageResult<()> {
+    encode(image, path, format, jpeg_quality)?;
+    check_not_empty(path)
+}
+
+/// Saves `image` to `path` as a PNG, with a simpler encoder configuration
+/// than [`save`], for when encoding with the configured format fails.
+///
+/// [`save`]: save
+pub fn save_fallback(image: &RgbImage, path: &Path) -> ImageResult<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    PngEncoder::new_with_quality(&mut file, CompressionType::Default, FilterType::NoFilter)
+        .encode(image, image.width(), image.height(), ColorType::Rgb8)?;
+    file.flush()?;
+
+    check_not_empty(path)
+}
+
+/// Encodes `image` into `path`, in `format`.
+fn encode(
+    image: &RgbImage,
+    path: &Path,
+    format: OutputFormat,
+    jpeg_quality: u8,
+) -> ImageResult<()> {
+    match format {
+        OutputFormat::Png => image.save_with_format(path, ImageFormat::Png),
+        OutputFormat::Bmp => image.save_with_format(path, ImageFormat::Bmp),
+        OutputFormat::Jpeg => {
+            let mut file = BufWriter::new(File::create(path)?);
+
+            JpegEncoder::new_with_quality(&mut file, jpeg_quality.clamp(1, 100)).encode(
+                image,
+                image.width(),
+                image.height(),
+                ColorType::Rgb8,
+            )?;
+            file.flush()?;
+
+            Ok(())
+        }
+        OutputFormat::Webp => {
+            let mut file = BufWriter::new(File::create(path)?);
+
+            // The `image` crate can only decode WebP
+            WebPEncoder::new(&mut file)
+                .encode(
+                    image,
+                    image.width(),
+                    image.height(),
+                    image_webp::ColorType::Rgb8,
+                )
+                .map_err(|e| {
+                    ImageError::Encoding(EncodingError::new(
+                        ImageFormatHint::Exact(ImageFormat::WebP),
+                        e,
+                    ))
+                })?;
+            file.flush()?;
+
+            Ok(())
+        }
+    }
+}
+
+/// Decodes the image file at `path`, as [`decode`] does.
+///
+/// [`decode`]: decode
+pub fn open(path: &Path) -> ImageResult<DynamicImage> {
+    decode(&fs::read(path)?)
+}
+
+/// Decodes the contents of an image file. WebP images are decoded separately,
+/// as the `image` crate can't decode lossless WebP, which screenshots are saved
+/// as.
+pub fn decode(data: &[u8]) -> ImageResult<DynamicImage> {
+    if !(data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP")) {
+        return image::load_from_memory(data);
+    }
+
+    let to_error = |e: image_webp::DecodingError| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Exact(ImageFormat::WebP),
+            e,
+        ))
+    };
+
+    let mut decoder = WebPDecoder::new(Cursor::new(data)).map_err(to_error)?;
+    let (width, height) = decoder.dimensions();
+
+    let mut buffer = vec![
+        0;
+        decoder.output_buffer_size().ok_or_else(|| {
+            ImageError::Limits(LimitError::from_kind(LimitErrorKind::InsufficientMemory))
+        })?
+    ];
+    decoder.read_image(&mut buffer).map_err(to_error)?;
+
+    let image = if decoder.has_alpha() {
+        RgbaImage::from_raw(width, height, buffer).map(DynamicImage::ImageRgba8)
+    } else {
+        RgbImage::from_raw(width, height, buffer).map(DynamicImage::ImageRgb8)
+    };
+
+    image.ok_or_else(|| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Exact(ImageFormat::WebP),
+            "The decoded image is the wrong size",
+        ))
+    })
+}
+
+/// Returns an error if the encoder wrote nothing to `path`, which would
+/// otherwise be left behind as an unreadable screenshot.
+fn check_not_empty(path: &Path) -> ImageResult<()> {
+    if fs::metadata(path)?.len() == 0 {
+        return Err(ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Unknown,
+            "The encoder wrote an empty file",
+        )));
+    }
+
+    Ok(())
+}
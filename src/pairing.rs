@@ -0,0 +1,78 @@
+//! Before/after capture pairing.
+//!
+//! Whilst pairing is active, captures are saved in pairs, sharing a base
+//! filename, with `_before` and `_after` suffixes, which is handy for
+//! documenting visual changes. If no "after" capture is taken soon enough
+//! after a "before" capture, the next capture starts a new pair instead.
+
+use crate::notification_area::{self, BalloonLevel};
+use crate::settings::Settings;
+use lazy_static::lazy_static;
+use log::info;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The suffix of the first capture of a pair.
+pub const BEFORE_SUFFIX: &str = "_before";
+
+/// The suffix of the second capture of a pair.
+pub const AFTER_SUFFIX: &str = "_after";
+
+/// Whether or not pairing is active.
+static IS_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// The base filename and capture time of the "before" capture of the
+    /// current pair, if the pair is awaiting its "after" capture.
+    static ref PENDING_PAIR: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+}
+
+/// Returns whether or not pairing is active.
+pub fn is_active() -> bool {
+    IS_ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Activates pairing if it is inactive, otherwise deactivates it, abandoning
+/// any incomplete pair.
+pub fn toggle() {
+    let active = !IS_ACTIVE.fetch_xor(true, Ordering::SeqCst);
+    *PENDING_PAIR.lock().unwrap() = None;
+
+    info!(
+        "Before/after pairing {}",
+        if active { "activated" } else { "deactivated" }
+    );
+
+    let _ = notification_area::show_balloon(
+        "Before/after pairing",
+        if active {
+            "The next two screenshots will be saved as a before/after pair"
+        } else {
+            "Screenshots will be saved individually"
+        },
+        BalloonLevel::Info,
+    );
+}
+
+/// Returns the base filename of the pair awaiting its "after" capture, if
+/// there is one, and it was captured recently enough. The pair is completed by
+/// calling this function.
+pub fn take_pending() -> Option<String> {
+    let mut window_seconds = 0;
+    Settings::read(|s| window_seconds = s.hotkey.pairing_window_seconds);
+
+    match PENDING_PAIR.lock().unwrap().take() {
+        Some((base, captured_at))
+            if is_active() && captured_at.elapsed() <= Duration::from_secs(window_seconds) =>
+        {
+            Some(base)
+        }
+        _ => None,
+    }
+}
+
+/// Starts a new pair, whose "before" capture has base filename `base`.
+pub fn start(base: &str) {
+    *PENDING_PAIR.lock().unwrap() = Some((base.into(), Instant::now()));
+}
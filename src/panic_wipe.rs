@@ -0,0 +1,121 @@
+//! The emergency "panic wipe", for urgent privacy situations, such as a
+//! sensitive screenshot being taken whilst screen sharing.
+//!
+//! Wiping pauses saving, cancels the screenshots waiting to be saved, forgets
+//! the program's in-memory references to recent screenshots, deletes every
+//! thumbnail, and, if configured, empties the clipboard and permanently
+//! deletes the most recently saved screenshots (along with their copies in
+//! other formats). Deleted screenshots bypass the trash, so that no copy is
+//! left behind.
+
+use crate::history;
+use crate::notification_area;
+use crate::region;
+use crate::save;
+use crate::save_queue;
+use crate::settings::Settings;
+use crate::windows::{empty_clipboard, open_clipboard};
+use log::{info, warn};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The longest to wait for screenshots that are already being saved to
+/// finish, so that they can be deleted too.
+const SAVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Performs a panic wipe. This is deliberately silent, as any notification
+/// could be seen by whoever the screen is being shared with.
+pub fn wipe() {
+    let mut delete_count = 0;
+
+    Settings::write(|s| {
+        s.program.paused = true;
+        delete_count = s.hotkey.panic_delete_count;
+    });
+
+    info!("Panic wipe - saving paused");
+
+    save_queue::cancel();
+    notification_area::forget_recent_screenshots();
+    region::forget();
+
+    if delete_count > 0 {
+        if let Err(e) = open_clipboard(None).and_then(|clipboard| empty_clipboard(&clipboard)) {
+            warn!("Failed to empty the clipboard: {:#?}", e);
+        }
+    }
+
+    // Screenshots that were already being saved can't be cancelled, so are
+    // waited for (off the window thread), then deleted along with the rest
+    thread::spawn(move || {
+        let started = Instant::now();
+
+        while save_queue::depth() > 0 && started.elapsed() < SAVE_TIMEOUT {
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        if delete_count > 0 {
+            delete_latest(delete_count);
+        }
+
+        history::delete_thumbnails();
+
+        info!("Panic wipe complete");
+    });
+}
+
+/// Permanently deletes the `count` most recently saved screenshots, and their
+/// copies in other formats, and removes them (and any colours picked since)
+/// from the history.
+fn delete_latest(count: usize) {
+    history::update(|entries| {
+        // Entries aren't necessarily recorded in the order that they were saved
+        // (e.g. imported screenshots), and picked colours don't count towards
+        // `count`, but are removed along with them
+        let mut saved_at: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.colour.is_none())
+            .map(|entry| entry.saved_at)
+            .collect();
+
+        saved_at.sort_unstable_by(|a, b| b.cmp(a));
+
+        let cutoff = match saved_at.get(count - 1).or_else(|| saved_at.last()) {
+            Some(&cutoff) => cutoff,
+            None => return,
+        };
+
+        let (latest, kept): (Vec<_>, Vec<_>) = entries
+            .drain(..)
+            .partition(|entry| entry.saved_at >= cutoff);
+
+        *entries = kept;
+
+        for entry in latest.into_iter().filter(|entry| entry.colour.is_none()) {
+            for copy in save::existing_copies(&entry.path) {
+                delete(&copy);
+            }
+
+            if !delete(&entry.path) {
+                entries.push(entry);
+            }
+        }
+    });
+}
+
+/// Permanently deletes the file at `path`, returning whether or not it is gone.
+fn delete(path: &Path) -> bool {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            warn!("Failed to delete {}: {}", path.display(), e);
+            false
+        }
+        _ => {
+            info!("Deleted {}", path.display());
+            true
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Perceptual hashing of screenshots, for detecting near-duplicates.
+//!
+//! Unlike the SHA-256 hash recorded in the history, similar images have similar
+//! perceptual hashes, so a screenshot that differs from another by a blinking
+//! cursor, or by being shifted a pixel, can still be recognised.
+//!
+//! The hash is a 256-bit difference hash ("dHash"): the image is shrunk to
+//! 17×16 greyscale pixels, and each bit records whether a pixel is brighter
+//! than its right-hand neighbour.
+
+use image::{imageops, RgbImage};
+use std::fmt::{self, Display, Formatter};
+
+/// The width of the shrunken image that the hash is calculated from.
+const WIDTH: u32 = 17;
+
+/// The height of the shrunken image that the hash is calculated from.
+const HEIGHT: u32 = 16;
+
+/// The perceptual hash of an image.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PerceptualHash([u64; 4]);
+
+impl PerceptualHash {
+    /// Calculates the perceptual hash of `image`.
+    pub fn of(image: &RgbImage) -> Self {
+        let small = imageops::thumbnail(image, WIDTH, HEIGHT);
+
+        let brightness = |x, y| {
+            let pixel = small.get_pixel(x, y);
+            299 * pixel[0] as u32 + 587 * pixel[1] as u32 + 114 * pixel[2] as u32
+        };
+
+        let mut hash = [0; 4];
+        let mut bit = 0;
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH - 1 {
+                if brightness(x, y) > brightness(x + 1, y) {
+                    hash[bit / 64] |= 1 << (bit % 64);
+                }
+
+                bit += 1;
+            }
+        }
+
+        Self(hash)
+    }
+
+    /// Parses a hash written by [`PerceptualHash::to_string`], returning
+    /// `None` if it isn't valid.
+    ///
+    /// [`PerceptualHash::to_string`]: ToString::to_string
+    pub fn parse(hex: &str) -> Option<Self> {
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+
+        let mut hash = [0; 4];
+
+        for (i, part) in hash.iter_mut().enumerate() {
+            *part = u64::from_str_radix(&hex[i * 16..(i + 1) * 16], 16).ok()?;
+        }
+
+        Some(Self(hash))
+    }
+
+    /// Returns how many bits differ between this hash and `other`. The lower
+    /// the distance, the more similar the images are.
+    pub fn distance(&self, other: &Self) -> u32 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+}
+
+impl Display for PerceptualHash {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for part in &self.0 {
+            write!(f, "{:016x}", part)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! Persistence of the position and size of the program's windows.
+//!
+//! Placements are stored in the settings file when a window closes, and
+//! restored when it next opens. As monitors may have been disconnected or
+//! rearranged in the meantime, restored windows are clamped to the work area of
+//! the monitor that they overlap the most.
+
+use crate::settings::{Settings, WindowPlacement};
+use crate::windows::{get_window_rect, get_work_area, set_window_rect};
+use bindings::Windows::Win32::Foundation::{HWND, RECT};
+
+/// Remembers the current position and size of `window`, under `name`.
+pub fn save(window: HWND, name: &str) -> windows::Result<()> {
+    let rect = get_window_rect(window)?;
+
+    Settings::write(|s| {
+        s.windows.insert(
+            name.into(),
+            WindowPlacement {
+                x: rect.left,
+                y: rect.top,
+                width: rect.right - rect.left,
+                height: rect.bottom - rect.top,
+            },
+        );
+    });
+
+    Ok(())
+}
+
+/// Moves `window` to the position and size last saved under `name`, if any.
+pub fn restore(window: HWND, name: &str) -> windows::Result<()> {
+    let mut placement = None;
+    Settings::read(|s| placement = s.windows.get(name).copied());
+
+    let placement = match placement {
+        Some(placement) => placement,
+        None => return Ok(()),
+    };
+
+    let rect = RECT {
+        left: placement.x,
+        top: placement.y,
+        right: placement.x + placement.width,
+        bottom: placement.y + placement.height,
+    };
+
+    set_window_rect(window, &clamp_to(&rect, &get_work_area(&rect)?))
+}
+
+/// Shrinks and moves `rect` as little as possible, so that it is entirely
+/// within `bounds`.
+fn clamp_to(rect: &RECT, bounds: &RECT) -> RECT {
+    let width = (rect.right - rect.left).min(bounds.right - bounds.left);
+    let height = (rect.bottom - rect.top).min(bounds.bottom - bounds.top);
+
+    let left = rect.left.max(bounds.left).min(bounds.right - width);
+    let top = rect.top.max(bounds.top).min(bounds.bottom - height);
+
+    RECT {
+        left,
+        top,
+        right: left + width,
+        bottom: top + height,
+    }
+}
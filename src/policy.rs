@@ -0,0 +1,96 @@
+//! Group Policy support.
+//!
+//! Administrators can enforce parts of the program's configuration by setting
+//! values under `Software\Policies\snip-and-autosave`, in either
+//! `HKEY_LOCAL_MACHINE` or `HKEY_CURRENT_USER` (the former takes precedence).
+//! Enforced values override `settings.toml`, and can't be changed by the user.
+//!
+//! | Value                 | Type                       | Effect                                  |
+//! |-----------------------|----------------------------|-----------------------------------------|
+//! | `ScreenshotDirectory` | `REG_SZ` / `REG_EXPAND_SZ` | Forces the screenshot directory         |
+//! | `DisableUploads`      | `REG_DWORD`                | `1` prevents uploading screenshots      |
+//! | `DisableOcr`          | `REG_DWORD`                | `1` prevents text recognition           |
+//!
+//! An administrative template for these values can be found in the `policies`
+//! directory of the repository.
+
+use crate::settings::Settings;
+use crate::windows::{get_registry_dword, get_registry_string};
+use bindings::Windows::Win32::System::Registry::{HKEY, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+use lazy_static::lazy_static;
+use log::info;
+use std::path::PathBuf;
+
+/// The registry key (under both `HKEY_LOCAL_MACHINE` and `HKEY_CURRENT_USER`)
+/// that policies are read from.
+const POLICY_KEY: &str = r"Software\Policies\snip-and-autosave";
+
+/// Policy roots, in order of precedence.
+const POLICY_ROOTS: [HKEY; 2] = [HKEY_LOCAL_MACHINE, HKEY_CURRENT_USER];
+
+/// Configuration enforced by Group Policy.
+pub struct Policy {
+    /// The directory that screenshots must be saved to, if it is enforced.
+    pub screenshot_dir: Option<PathBuf>,
+
+    /// Whether or not uploading screenshots is prohibited.
+    pub disable_uploads: bool,
+
+    /// Whether or not recognising text in screenshots is prohibited.
+    pub disable_ocr: bool,
+}
+
+lazy_static! {
+    /// Policies are only read once, as Group Policy changes normally only
+    /// apply to newly started programs anyway.
+    static ref POLICY: Policy = load();
+}
+
+/// Returns the configuration enforced by Group Policy.
+pub fn get() -> &'static Policy {
+    &POLICY
+}
+
+/// Overrides the values of `settings` that are enforced by Group Policy.
+pub fn apply(settings: &mut Settings) {
+    if let Some(screenshot_dir) = &get().screenshot_dir {
+        settings.paths.screenshots = screenshot_dir.clone();
+    }
+}
+
+/// Reads every policy value from the registry.
+fn load() -> Policy {
+    let policy = Policy {
+        screenshot_dir: POLICY_ROOTS
+            .iter()
+            .find_map(|&root| get_registry_string(root, POLICY_KEY, "ScreenshotDirectory"))
+            .map(PathBuf::from),
+        disable_uploads: read_flag("DisableUploads"),
+        disable_ocr: read_flag("DisableOcr"),
+    };
+
+    if let Some(screenshot_dir) = &policy.screenshot_dir {
+        info!(
+            "Screenshot directory enforced by policy: {}",
+            screenshot_dir.display()
+        );
+    }
+
+    if policy.disable_uploads {
+        info!("Uploads disabled by policy");
+    }
+
+    if policy.disable_ocr {
+        info!("Text recognition disabled by policy");
+    }
+
+    policy
+}
+
+/// Reads a `REG_DWORD` policy flag, returning whether or not it is set to `1`.
+fn read_flag(value_name: &str) -> bool {
+    POLICY_ROOTS
+        .iter()
+        .find_map(|&root| get_registry_dword(root, POLICY_KEY, value_name))
+        == Some(1)
+}
@@ -0,0 +1,118 @@
+//! Running a user-configured command after each screenshot is saved.
+//!
+//! The command (see [`Program::post_save_command`]) is split into arguments
+//! like a Windows command line, with double quotes grouping arguments that
+//! contain spaces, then `{path}` is replaced in each argument with the path of
+//! the saved screenshot. The command is run directly, rather than through the
+//! shell, without a console window.
+//!
+//! [`Program::post_save_command`]: crate::settings::Program::post_save_command
+
+use crate::encryption;
+use crate::events::{self, Event};
+use crate::settings::Settings;
+use log::{info, warn};
+use std::ffi::OsStr;
+use std::mem;
+use std::os::windows::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Prevents a console window from being created for console programs.
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// Runs the post-save command for each saved screenshot, on a background
+/// thread, if one is configured.
+pub fn subscribe_to_events() {
+    let events = events::subscribe();
+
+    thread::spawn(move || {
+        for event in events {
+            // Incognito captures must never leave a trace, even if incognito
+            // mode has expired by the time they're saved
+            if let Event::Saved {
+                path,
+                incognito: false,
+                ..
+            } = event
+            {
+                on_saved(&path);
+            }
+        }
+    });
+}
+
+/// Runs the post-save command for the screenshot saved at `path`, and waits for
+/// it to exit.
+fn on_saved(path: &Path) {
+    let mut command = String::new();
+    Settings::read(|s| command = s.program.post_save_command.clone());
+
+    if command.trim().is_empty() {
+        return;
+    }
+
+    // Encrypted screenshots don't have a file of their own
+    if path.file_name() == Some(OsStr::new(encryption::ARCHIVE_FILE)) {
+        return;
+    }
+
+    let path = path.to_string_lossy();
+    let mut arguments = split_arguments(&command)
+        .into_iter()
+        .map(|argument| argument.replace("{path}", &path));
+
+    let program = match arguments.next() {
+        Some(program) => program,
+        None => return,
+    };
+
+    info!("Running post-save command {}", program);
+
+    let status = Command::new(&program)
+        .args(arguments)
+        .stdin(Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("Post-save command {} exited with {}", program, status),
+        Err(e) => warn!("Failed to run post-save command {}: {}", program, e),
+    }
+}
+
+/// Splits `command` into arguments at spaces, except for spaces within double
+/// quotes. The quotes themselves are removed.
+fn split_arguments(command: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut argument = String::new();
+    let mut in_argument = false;
+    let mut quoted = false;
+
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_argument = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_argument {
+                    arguments.push(mem::take(&mut argument));
+                    in_argument = false;
+                }
+            }
+            c => {
+                argument.push(c);
+                in_argument = true;
+            }
+        }
+    }
+
+    if in_argument {
+        arguments.push(argument);
+    }
+
+    arguments
+}
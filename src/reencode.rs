@@ -0,0 +1,134 @@
+//! Re-encoding of existing screenshots into the configured image format.
+//!
+//! When the configured format changes, only new screenshots are saved in the
+//! new format. This module converts the screenshots that were already saved,
+//! keeping their timestamps, and updating their history entries.
+
+use crate::animation::Animation;
+use crate::history;
+use crate::migration::find_screenshots_recursive;
+use crate::orientation;
+use crate::output;
+use crate::settings::Output;
+use crate::trash;
+use crate::windows::copy_file_times;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Errors that can occur whilst re-encoding a single screenshot.
+#[derive(Error, Debug)]
+pub enum ReencodeError {
+    #[error("{0} already exists")]
+    DestinationExists(PathBuf),
+    #[error("Unable to convert image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Unable to preserve timestamps: {0}")]
+    Timestamps(#[from] windows::Error),
+    #[error("Unable to remove original: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The outcome of re-encoding a directory of screenshots.
+#[derive(Default)]
+pub struct Report {
+    /// How many screenshots were re-encoded.
+    pub converted: usize,
+
+    /// How many screenshots were already in the configured format.
+    pub skipped: usize,
+
+    /// How many screenshots were left alone because they are animated, and
+    /// re-encoding them would keep only their first frame.
+    pub animated: usize,
+
+    /// The screenshots that couldn't be re-encoded, and why.
+    pub failures: Vec<(PathBuf, ReencodeError)>,
+}
+
+/// Re-encodes every screenshot within `dir` (or its subdirectories) that isn't
+/// already in the format configured by `output`, except for animations. Each
+/// original is moved to the trash once it has been converted.
+pub fn reencode_dir(dir: &Path, output: &Output) -> io::Result<Report> {
+    let extension = output::extension(output.format);
+    let mut report = Report::default();
+    let mut moved = Vec::new();
+
+    for screenshot in find_screenshots_recursive(dir)? {
+        let is_converted = match screenshot.extension().and_then(|e| e.to_str()) {
+            Some(existing) => existing.eq_ignore_ascii_case(extension),
+            None => false,
+        };
+
+        if is_converted {
+            report.skipped += 1;
+            continue;
+        }
+
+        if fs::read(&screenshot)
+            .ok()
+            .and_then(Animation::detect)
+            .is_some()
+        {
+            report.animated += 1;
+            continue;
+        }
+
+        let destination = screenshot.with_extension(extension);
+
+        match reencode(&screenshot, &destination, output) {
+            Ok(()) => {
+                report.converted += 1;
+                moved.push((screenshot, destination));
+            }
+            Err(e) => report.failures.push((screenshot, e)),
+        }
+    }
+
+    if !moved.is_empty() {
+        history::update(|entries| {
+            for entry in entries.iter_mut() {
+                if let Some((_, destination)) =
+                    moved.iter().find(|(source, _)| *source == entry.path)
+                {
+                    entry.path = destination.clone();
+                }
+            }
+        });
+    }
+
+    Ok(report)
+}
+
+/// Re-encodes a single screenshot from `source` to `destination`, then moves
+/// `source` to the trash.
+fn reencode(source: &Path, destination: &Path, output: &Output) -> Result<(), ReencodeError> {
+    if destination.exists() {
+        return Err(ReencodeError::DestinationExists(destination.to_owned()));
+    }
+
+    let image = orientation::open_upright(source)?.to_rgb8();
+
+    if let Err(e) = output::save(&image, destination, output.format, output.jpeg_quality) {
+        let _ = fs::remove_file(destination);
+        return Err(e.into());
+    }
+
+    let times_copied = File::open(source)
+        .map_err(ReencodeError::from)
+        .and_then(|source| {
+            let destination = fs::OpenOptions::new().write(true).open(destination)?;
+
+            Ok(copy_file_times(&source, &destination)?)
+        });
+
+    if let Err(e) = times_copied {
+        let _ = fs::remove_file(destination);
+        return Err(e);
+    }
+
+    trash::discard(source)?;
+
+    Ok(())
+}
@@ -0,0 +1,167 @@
+//! Screen region memory, for repeated snips.
+//!
+//! Snip & Sketch doesn't report which region of the screen a snip was taken
+//! from, and its `ms-screenclip:` URI can't be told which region to snip. So,
+//! the region is found by searching the screen for each snip as soon as it is
+//! captured, and "Snip Same Region" then captures that region directly, rather
+//! than through Snip & Sketch. If no region is known (e.g. because the screen
+//! changed before the snip was found), Snip & Sketch is opened as usual.
+
+use crate::convert::bgrx_to_image;
+use crate::error_report;
+use crate::events::{self, Event};
+use crate::heuristics::CaptureSource;
+use crate::hotkey;
+use crate::monitors;
+use crate::save::{self, Snapshot};
+use crate::settings::Settings;
+use crate::windows::capture_screen;
+use bindings::Windows::Win32::Foundation::{HWND, RECT};
+use image::RgbImage;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::thread;
+
+/// A region of the screen, in virtual screen coordinates.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+lazy_static! {
+    /// The region of the most recent snip that was found on the screen.
+    static ref LAST_REGION: Mutex<Option<Region>> = Mutex::new(None);
+}
+
+/// Forgets the region of the most recent snip.
+pub fn forget() {
+    *LAST_REGION.lock().unwrap() = None;
+}
+
+/// Searches each monitor for `image`, returning the region of the screen that
+/// it was snipped from, if it is still on the screen, and region memory is
+/// enabled. The region is remembered for [`snip_again`].
+///
+/// [`snip_again`]: snip_again
+pub fn locate(image: &RgbImage) -> Option<Region> {
+    let mut remember_snip_region = false;
+    Settings::read(|s| remember_snip_region = s.program.remember_snip_region);
+
+    if !remember_snip_region {
+        return None;
+    }
+
+    let region = find_on_screen(image);
+
+    match region {
+        Some(region) => {
+            debug!("Snip was taken from {:?}", region);
+            *LAST_REGION.lock().unwrap() = Some(region);
+        }
+        None => debug!("Snip not found on screen - region unknown"),
+    }
+
+    region
+}
+
+/// Searches each monitor for `image`, returning the region of the screen that
+/// it was snipped from, if it is still on the screen, whether or not region
+/// memory is enabled. The region isn't remembered.
+pub fn find_on_screen(image: &RgbImage) -> Option<Region> {
+    monitors::all().into_iter().find_map(|monitor| {
+        let screen = capture(&monitor.bounds)
+            .map_err(|e| warn!("Failed to capture monitor: {:#?}", e))
+            .ok()?;
+
+        let (x, y) = find(&screen, image)?;
+
+        Some(Region {
+            x: monitor.bounds.left + x as i32,
+            y: monitor.bounds.top + y as i32,
+            width: image.width(),
+            height: image.height(),
+        })
+    })
+}
+
+/// Captures the same region of the screen as the last snip, on a separate
+/// thread, or opens Snip & Sketch, if the last snip's region is unknown.
+pub fn snip_again(window: HWND) {
+    let region = match *LAST_REGION.lock().unwrap() {
+        Some(region) => region,
+        None => {
+            info!("Last snip region unknown - opening Snip & Sketch");
+
+            if let Err(e) = hotkey::open_screen_clip(window) {
+                error_report::report("Unable to open Snip & Sketch", &e);
+            }
+
+            return;
+        }
+    };
+
+    // Recorded before the capture, which may take a while
+    let snapshot = Snapshot::take();
+
+    thread::spawn(move || {
+        let bounds = RECT {
+            left: region.x,
+            top: region.y,
+            right: region.x + region.width as i32,
+            bottom: region.y + region.height as i32,
+        };
+
+        match capture(&bounds) {
+            Ok(image) => {
+                info!("Captured {:?}", region);
+                events::publish(Event::CaptureDetected);
+                save::archive(image, Some(region), CaptureSource::Local, snapshot);
+            }
+            Err(e) => error_report::report("Unable to capture the last snip region", &e),
+        }
+    });
+}
+
+/// Captures the pixels within `bounds` from the screen.
+fn capture(bounds: &RECT) -> windows::Result<RgbImage> {
+    let pixels = capture_screen(bounds)?;
+
+    Ok(bgrx_to_image(
+        (bounds.right - bounds.left) as u32,
+        (bounds.bottom - bounds.top) as u32,
+        &pixels,
+    ))
+}
+
+/// Returns the position of the top-left corner of the first occurrence of
+/// `needle` within `haystack`.
+fn find(haystack: &RgbImage, needle: &RgbImage) -> Option<(u32, u32)> {
+    if needle.width() == 0
+        || needle.height() == 0
+        || needle.width() > haystack.width()
+        || needle.height() > haystack.height()
+    {
+        return None;
+    }
+
+    for y in 0..=haystack.height() - needle.height() {
+        for x in 0..=haystack.width() - needle.width() {
+            // Mismatches are almost always found in the first few pixels
+            let matches = (0..needle.height()).all(|dy| {
+                (0..needle.width())
+                    .all(|dx| haystack.get_pixel(x + dx, y + dy) == needle.get_pixel(dx, dy))
+            });
+
+            if matches {
+                return Some((x, y));
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,239 @@
+//! Remote base configuration, so that several machines can share capture
+//! policies.
+//!
+//! `settings.toml` can point to a base settings file on a web server, which is
+//! fetched over HTTPS, along with a detached ECDSA P-256 signature (at the same
+//! URL, with `.sig` appended). Only base files whose signature matches the
+//! configured public key are used. The last verified base file is cached in
+//! the config directory, so that it still applies whilst the server can't be
+//! reached.
+//!
+//! Values in `settings.toml` override those in the base file, table by table,
+//! and values enforced by Group Policy override both.
+
+use crate::settings::{self, RemoteConfig, Settings};
+use crate::windows::{base64_decode, http_request, verify_ecdsa_p256};
+use log::{info, warn};
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use thiserror::Error;
+
+/// The name of the file within the config directory that the last verified
+/// base settings file is cached in.
+const CACHE_FILE: &str = "remote-settings.toml";
+
+/// The extension appended to the base settings file's URL to fetch its
+/// signature.
+const SIGNATURE_EXTENSION: &str = ".sig";
+
+/// The shortest allowed time between checks for changes.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Errors that can occur whilst fetching the base settings file.
+#[derive(Error, Debug)]
+pub enum RemoteConfigError {
+    #[error("The remote configuration URL must use HTTPS")]
+    NotHttps,
+    #[error("The remote configuration public key is invalid")]
+    InvalidPublicKey,
+    #[error("Unable to fetch the remote configuration: {0}")]
+    Request(#[from] windows::Error),
+    #[error("The remote configuration server responded with status {0}")]
+    Status(u32),
+    #[error("The remote configuration signature is invalid")]
+    InvalidSignature,
+    #[error("The remote configuration is invalid: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Unable to cache the remote configuration: {0}")]
+    Cache(#[from] io::Error),
+}
+
+/// Returns the fully qualified path to the cached base settings file.
+fn cache_file_path() -> PathBuf {
+    settings::config_dir().join(CACHE_FILE)
+}
+
+/// Returns the cached base settings file, if remote configuration is enabled,
+/// i.e. `url` isn't empty, and one has been fetched. The base file can't
+/// configure remote configuration itself.
+pub fn cached(url: &str) -> Option<toml::Value> {
+    if url.is_empty() {
+        return None;
+    }
+
+    let contents = fs::read_to_string(cache_file_path()).ok()?;
+
+    let mut base: toml::Value = match toml::from_str(&contents) {
+        Ok(base) => base,
+        Err(e) => {
+            warn!("Ignoring invalid cached remote configuration: {}", e);
+            return None;
+        }
+    };
+
+    if let toml::Value::Table(table) = &mut base {
+        table.remove("remote_config");
+    }
+
+    Some(base)
+}
+
+/// Starts checking for changes to the base settings file periodically, on a
+/// background thread, if remote configuration is enabled.
+pub fn start() {
+    let mut remote_config = RemoteConfig::default();
+    Settings::read(|s| remote_config = s.remote_config.clone());
+
+    if remote_config.url.is_empty() {
+        return;
+    }
+
+    thread::Builder::new()
+        .name("remote-config".into())
+        .spawn(|| loop {
+            let mut remote_config = RemoteConfig::default();
+            Settings::read(|s| remote_config = s.remote_config.clone());
+
+            match refresh(&remote_config) {
+                Ok(true) => {
+                    info!("Remote configuration changed - reloading settings");
+                    settings::reload();
+                }
+                Ok(false) => {}
+                Err(e) => warn!("Failed to refresh the remote configuration: {}", e),
+            }
+
+            thread::sleep(
+                Duration::from_secs(remote_config.refresh_interval_hours as u64 * 60 * 60)
+                    .max(MIN_REFRESH_INTERVAL),
+            );
+        })
+        .expect("Failed to start remote configuration thread");
+}
+
+/// Fetches the base settings file, verifies its signature, and caches it,
+/// returning whether or not it has changed since it was last cached.
+fn refresh(remote_config: &RemoteConfig) -> Result<bool, RemoteConfigError> {
+    if remote_config.url.is_empty() {
+        return Ok(false);
+    }
+
+    let scheme = remote_config.url.get(..8).unwrap_or_default();
+
+    if !scheme.eq_ignore_ascii_case("https://") {
+        return Err(RemoteConfigError::NotHttps);
+    }
+
+    let public_key = base64_decode(&remote_config.public_key)
+        .ok()
+        .and_then(|key| raw_public_key(&key))
+        .ok_or(RemoteConfigError::InvalidPublicKey)?;
+
+    let body = fetch(&remote_config.url)?;
+    let signature = fetch(&(remote_config.url.clone() + SIGNATURE_EXTENSION))?;
+
+    let signature = raw_signature(&signature).ok_or(RemoteConfigError::InvalidSignature)?;
+
+    verify_ecdsa_p256(&public_key, &body, &signature)
+        .map_err(|_| RemoteConfigError::InvalidSignature)?;
+
+    let contents = String::from_utf8_lossy(&body);
+
+    // The base file must be usable as settings by itself
+    toml::from_str::<Settings>(&contents)?;
+
+    let cache_file = cache_file_path();
+
+    if fs::read_to_string(&cache_file).ok().as_deref() == Some(&*contents) {
+        return Ok(false);
+    }
+
+    fs::create_dir_all(cache_file.parent().unwrap())?;
+    fs::write(cache_file, contents.as_bytes())?;
+
+    Ok(true)
+}
+
+/// Sends a GET request to `url`, returning the response body.
+fn fetch(url: &str) -> Result<Vec<u8>, RemoteConfigError> {
+    let response = http_request("GET", url, "", &[])?;
+
+    if response.status != 200 {
+        return Err(RemoteConfigError::Status(response.status));
+    }
+
+    Ok(response.body)
+}
+
+/// Returns the concatenated `x` and `y` coordinates of the P-256 public `key`,
+/// which is either a DER-encoded `SubjectPublicKeyInfo` (as written by
+/// `openssl ec -pubout`), an uncompressed point, or the bare coordinates.
+fn raw_public_key(key: &[u8]) -> Option<[u8; 64]> {
+    /// The length of a DER-encoded P-256 `SubjectPublicKeyInfo`, which ends
+    /// with the uncompressed point.
+    const SUBJECT_PUBLIC_KEY_INFO_LENGTH: usize = 91;
+
+    let point = match key.len() {
+        SUBJECT_PUBLIC_KEY_INFO_LENGTH => &key[SUBJECT_PUBLIC_KEY_INFO_LENGTH - 65..],
+        _ => key,
+    };
+
+    match point.len() {
+        65 if point[0] == 0x04 => point[1..].try_into().ok(),
+        64 => point.try_into().ok(),
+        _ => None,
+    }
+}
+
+/// Returns the concatenated `r` and `s` values of `signature`, which is either
+/// DER-encoded (as written by `openssl dgst -sign`), or already concatenated.
+fn raw_signature(signature: &[u8]) -> Option<[u8; 64]> {
+    if signature.len() == 64 {
+        return signature.try_into().ok();
+    }
+
+    // SEQUENCE { INTEGER r, INTEGER s }, which is always short enough for
+    // single byte lengths
+    let (&tag, rest) = signature.split_first()?;
+    let (&length, mut rest) = rest.split_first()?;
+
+    if tag != 0x30 || length as usize != rest.len() {
+        return None;
+    }
+
+    let mut raw = [0u8; 64];
+
+    for half in raw.chunks_mut(32) {
+        let (&tag, after_tag) = rest.split_first()?;
+        let (&length, after_length) = after_tag.split_first()?;
+
+        if tag != 0x02 || length as usize > after_length.len() {
+            return None;
+        }
+
+        let (integer, after_integer) = after_length.split_at(length as usize);
+
+        // Integers are signed, so may have a leading zero byte
+        let integer = match integer.iter().position(|&byte| byte != 0) {
+            Some(start) => &integer[start..],
+            None => &[],
+        };
+
+        if integer.len() > half.len() {
+            return None;
+        }
+
+        half[32 - integer.len()..].copy_from_slice(integer);
+        rest = after_integer;
+    }
+
+    if rest.is_empty() {
+        Some(raw)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,141 @@
+//! Automatic clean-up of old screenshots.
+//!
+//! Left alone, the screenshot directory grows without bound. When a maximum
+//! age, or a maximum total size, is configured (see [`Retention`]), the oldest
+//! screenshots are either moved to the trash, or into an `Archive`
+//! subdirectory of the screenshot directory, at startup, and periodically
+//! whilst the program runs.
+//!
+//! [`Retention`]: crate::settings::Retention
+
+use crate::history;
+use crate::migration::find_screenshots_recursive;
+use crate::settings::{Retention, RetentionAction, Settings};
+use crate::trash;
+use log::{info, warn};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// The subdirectory of the screenshot directory that old screenshots are moved
+/// into, when they are archived.
+pub const ARCHIVE_DIR: &str = "Archive";
+
+/// How often the retention policy is applied whilst the program runs.
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Applies the retention policy now, then periodically, on a separate thread.
+pub fn start() {
+    thread::Builder::new()
+        .name("retention".into())
+        .spawn(|| loop {
+            if let Err(e) = apply() {
+                warn!("Failed to apply the retention policy: {:#?}", e);
+            }
+
+            thread::sleep(INTERVAL);
+        })
+        .expect("Failed to start retention thread");
+}
+
+/// Removes (or archives) the oldest screenshots in the screenshot directory,
+/// until none are older than the configured maximum age, and their total size
+/// is within the configured maximum size.
+pub fn apply() -> io::Result<()> {
+    let mut screenshot_dir = PathBuf::new();
+    let mut retention = Retention::default();
+    Settings::read(|s| {
+        screenshot_dir = s.paths.screenshots.clone();
+        retention = s.retention;
+    });
+
+    if retention.max_age_days == 0 && retention.max_total_size_mb == 0 {
+        return Ok(());
+    }
+
+    let mut screenshots = Vec::new();
+
+    // Archived screenshots are skipped
+    for path in find_screenshots_recursive(&screenshot_dir)? {
+        // Screenshots can be deleted or moved by other programs meanwhile
+        match fs::metadata(&path).and_then(|metadata| Ok((metadata.modified()?, metadata.len()))) {
+            Ok((modified, size)) => screenshots.push((path, modified, size)),
+            Err(e) => warn!("Failed to read {} - skipping it: {}", path.display(), e),
+        }
+    }
+
+    // Oldest first
+    screenshots.sort_by_key(|(_, modified, _)| *modified);
+
+    let max_age = Duration::from_secs(retention.max_age_days as u64 * 24 * 60 * 60);
+    let max_size = retention.max_total_size_mb * 1024 * 1024;
+    let now = SystemTime::now();
+
+    let mut total_size: u64 = screenshots.iter().map(|(_, _, size)| size).sum();
+    let mut removed = Vec::new();
+
+    for (path, modified, size) in screenshots {
+        let is_expired = retention.max_age_days != 0
+            && now.duration_since(modified).unwrap_or_default() > max_age;
+        let is_over_size = retention.max_total_size_mb != 0 && total_size > max_size;
+
+        if !is_expired && !is_over_size {
+            break;
+        }
+
+        let result = match retention.action {
+            RetentionAction::Delete => trash::discard(&path).map(|_| None),
+            RetentionAction::Archive => archive(&path, &screenshot_dir).map(Some),
+        };
+
+        match result {
+            Ok(archived) => {
+                total_size -= size;
+                removed.push((path, archived));
+            }
+            Err(e) => warn!("Failed to remove old screenshot {}: {}", path.display(), e),
+        }
+    }
+
+    if removed.is_empty() {
+        return Ok(());
+    }
+
+    info!("Retention policy removed {} old screenshots", removed.len());
+
+    // Trashed screenshots stay in the history until they are purged
+    history::update(|entries| {
+        for entry in entries.iter_mut() {
+            if let Some((_, Some(archived))) = removed.iter().find(|(path, _)| *path == entry.path)
+            {
+                entry.path = archived.clone();
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Moves `path` into the archive directory, keeping its location relative to
+/// `screenshot_dir`, and returns its new path.
+fn archive(path: &Path, screenshot_dir: &Path) -> io::Result<PathBuf> {
+    let relative = path.strip_prefix(screenshot_dir).unwrap_or(path);
+    let archived = screenshot_dir.join(ARCHIVE_DIR).join(relative);
+
+    if let Some(parent) = archived.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if archived.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", archived.display()),
+        ));
+    }
+
+    fs::rename(path, &archived)?;
+
+    Ok(archived)
+}
@@ -0,0 +1,816 @@
+//! The screenshot save pipeline, shared by every source of images (clipboard
+//! captures, manually archived files, etc).
+
+use crate::animation::Animation;
+use crate::burst;
+use crate::classify::{self, Category};
+use crate::context;
+use crate::convert;
+use crate::encryption::{self, EncryptionError};
+use crate::events::{self, Event, FailureKind, SkipReason};
+use crate::extensions::ImageExtensions;
+use crate::hash_cache;
+use crate::heuristics::CaptureSource;
+use crate::history;
+use crate::incognito;
+use crate::migration::is_screenshot;
+use crate::naming;
+use crate::notification_area::{self, BalloonLevel};
+use crate::output;
+use crate::pairing;
+use crate::region::Region;
+use crate::save_queue;
+use crate::session;
+use crate::settings::{self, Naming, Output, OutputTarget, RemoteSessionAction, Settings};
+use crate::template::{self, Context, Moment};
+use crate::windows::{open_clipboard, rename_without_replacing, set_clipboard_files};
+use crate::zone;
+use chrono::{Local, Utc};
+use image::codecs::png::PngEncoder;
+use image::{imageops, ColorType, ImageError, RgbImage};
+use lazy_static::lazy_static;
+use log::{debug, error, info, warn};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+/// The extension appended to the names of screenshots whilst they are being
+/// written.
+const TEMP_EXTENSION: &str = "tmp";
+
+/// How long ago a temporary screenshot file must have last been written to,
+/// before it is considered to have been abandoned.
+const STALE_TEMP_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// The name of the directory within the config directory that the raw pixel
+/// data of screenshots that couldn't be encoded is kept in.
+const FAILED_ENCODE_DIR: &str = "failed-encodes";
+
+/// The most names that are tried for a screenshot, if other writers keep
+/// saving files with the same name first.
+const MAX_NAME_ATTEMPTS: u32 = 100;
+
+lazy_static! {
+    /// The paths that screenshots are currently being saved to, which mustn't
+    /// be used by other save workers, even though they don't exist yet.
+    static ref RESERVED_PATHS: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+
+    /// Identifies this copy of the program in the names of its temporary files,
+    /// so that copies on other machines saving to the same shared folder never
+    /// write to the same temporary file.
+    static ref WRITER_TOKEN: String = format!(
+        "{}-{}",
+        env::var("COMPUTERNAME").unwrap_or_else(|_| "unknown".into()),
+        process::id()
+    );
+}
+
+/// A path reserved for a screenshot being saved, which is released when this
+/// is dropped.
+struct Reservation(PathBuf);
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        RESERVED_PATHS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// The outcome of successfully saving a screenshot.
+enum Saved {
+    /// The screenshot was saved to this path.
+    New(PathBuf),
+
+    /// The screenshot, named by its content, had already been saved to this
+    /// path (possibly by another machine sharing the directory), so nothing
+    /// was written.
+    Existing(PathBuf),
+}
+
+/// What was going on when a screenshot was captured, which is recorded as it is
+/// captured, rather than when it is saved, which may be much later (e.g. after
+/// waiting for the quick crop window, or in the [`save_queue`]).
+///
+/// [`save_queue`]: save_queue
+#[derive(Clone)]
+pub struct Snapshot {
+    /// The details of the moment, for the screenshot's filename.
+    pub moment: Moment,
+
+    /// What was in the foreground, if recording context is enabled, and the
+    /// screenshot isn't an incognito capture.
+    pub context: Option<context::Context>,
+
+    /// Whether incognito mode was enabled.
+    pub incognito: bool,
+
+    /// The [`save_queue`] generation, so that screenshots captured before the
+    /// queue was cancelled (e.g. by a panic wipe) are discarded.
+    ///
+    /// [`save_queue`]: save_queue
+    pub generation: usize,
+}
+
+impl Snapshot {
+    /// Records what is going on now, for a screenshot that was just captured.
+    pub fn take() -> Self {
+        let incognito = incognito::is_active();
+
+        Self {
+            moment: Moment::now(),
+            // Incognito captures must never leave a trace outside of the
+            // incognito directory
+            context: if incognito { None } else { context::snapshot() },
+            incognito,
+            generation: save_queue::generation(),
+        }
+    }
+}
+
+/// Errors that can occur whilst saving a screenshot.
+#[derive(Error, Debug)]
+pub enum SaveError {
+    #[error("Unable to encode screenshot: {0}")]
+    Encode(#[from] ImageError),
+    #[error("Unable to add screenshot to the encrypted archive: {0}")]
+    Encryption(#[from] EncryptionError),
+    #[error("Unable to create the screenshot directory: {0}")]
+    CreateDir(#[source] io::Error),
+}
+
+impl SaveError {
+    /// Returns the stage of the capture pipeline that failed.
+    fn kind(&self) -> FailureKind {
+        match self {
+            SaveError::Encode(ImageError::IoError(_)) => FailureKind::Filesystem,
+            SaveError::Encode(_) => FailureKind::Encode,
+            SaveError::Encryption(_) => FailureKind::Encryption,
+            SaveError::CreateDir(_) => FailureKind::Filesystem,
+        }
+    }
+}
+
+/// Queues `image` to be saved, unless it is a duplicate of a recently saved
+/// screenshot, publishing the outcome as an [`Event`]. `region` is the region
+/// of the screen that it was captured from, if known, `source` is where it
+/// came from, and `snapshot` is what was going on when it was captured. This
+/// waits whilst the [`save_queue`] is full.
+///
+/// [`Event`]: Event
+/// [`save_queue`]: save_queue
+pub fn archive(image: RgbImage, region: Option<Region>, source: CaptureSource, snapshot: Snapshot) {
+    save_queue::push(image, region, None, source, snapshot);
+}
+
+/// Saves the original bytes of `animation`, rather than flattening it, in the
+/// same way as [`archive`]. `first_frame` is used for its history entry, etc.
+///
+/// [`archive`]: archive
+pub fn archive_animation(
+    first_frame: RgbImage,
+    region: Option<Region>,
+    source: CaptureSource,
+    animation: Animation,
+    snapshot: Snapshot,
+) {
+    save_queue::push(first_frame, region, Some(animation), source, snapshot);
+}
+
+/// Saves `image` (or `animation`, if there is one) immediately, as described
+/// by [`archive`].
+///
+/// [`archive`]: archive
+pub fn archive_capture(
+    image: &RgbImage,
+    region: Option<Region>,
+    animation: Option<&Animation>,
+    source: CaptureSource,
+    snapshot: &Snapshot,
+) {
+    let incognito = snapshot.incognito;
+    let mut dedup = true;
+    let mut compare_last = 1;
+    let mut perceptual = false;
+    let mut max_distance = 0;
+
+    Settings::read(|s| {
+        dedup = s.dedup.enabled;
+        compare_last = s.dedup.compare_last.clamp(1, hash_cache::CAPACITY);
+        perceptual = s.dedup.perceptual;
+        max_distance = s.dedup.max_distance;
+    });
+
+    if dedup && image.is_same_as_recent_screenshot(compare_last) {
+        info!("Screenshot is the same as a recently saved image - ignoring");
+        events::publish(Event::Skipped {
+            reason: SkipReason::Duplicate,
+        });
+
+        return;
+    }
+
+    if dedup && perceptual && image.is_similar_to_recent_screenshot(compare_last, max_distance) {
+        info!("Screenshot is almost the same as a recently saved image - ignoring");
+        events::publish(Event::Skipped {
+            reason: SkipReason::NearDuplicate,
+        });
+
+        return;
+    }
+
+    match save_screenshot(image, region, animation, source, snapshot) {
+        Ok(Saved::New(path)) => {
+            hash_cache::record(&path, image);
+
+            // Collapsing moves screenshots to the trash, outside of the
+            // incognito directory
+            if !incognito {
+                burst::collapse(&path, image);
+            }
+
+            events::publish(Event::Saved {
+                path,
+                incognito,
+                generation: snapshot.generation,
+            });
+        }
+        Ok(Saved::Existing(path)) => {
+            hash_cache::record(&path, image);
+            events::publish(Event::Skipped {
+                reason: SkipReason::Duplicate,
+            });
+        }
+        Err(e) => {
+            error!("Failed to save screenshot: {}", e);
+            events::publish(Event::Failed {
+                kind: e.kind(),
+                error: e.to_string(),
+            });
+        }
+    }
+}
+
+/// Writes a screenshot to `path` with `write`, which is given the path of a
+/// temporary file next to `path` to write to. Once it has been written, the
+/// temporary file is renamed to `path`, so that a truncated screenshot is never
+/// left behind if the program is killed, or the disk fills up, mid-write.
+///
+/// If another writer (e.g. another machine saving to the same shared folder)
+/// saves a file to `path` first, the screenshot is saved with a numeric suffix
+/// instead, unless `keep_existing` is `true`, in which case the existing file
+/// is assumed to be the same screenshot, and kept. Returns the path that the
+/// screenshot ends up at.
+fn write_atomically(
+    path: &Path,
+    keep_existing: bool,
+    write: impl FnOnce(&Path) -> Result<(), ImageError>,
+) -> Result<PathBuf, ImageError> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.{}", *WRITER_TOKEN, TEMP_EXTENSION));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = write(&temp_path).and_then(|_| publish(&temp_path, path, keep_existing));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// Renames the temporary file `temp_path` to `path`, or to the first name with
+/// a numeric suffix that another writer hasn't taken, without ever replacing an
+/// existing file. See [`write_atomically`].
+///
+/// [`write_atomically`]: write_atomically
+fn publish(temp_path: &Path, path: &Path, keep_existing: bool) -> Result<PathBuf, ImageError> {
+    for attempt in 1..=MAX_NAME_ATTEMPTS {
+        let candidate = if attempt == 1 {
+            path.to_owned()
+        } else {
+            let mut name = path.file_stem().unwrap_or_default().to_os_string();
+            name.push(format!("_{}", attempt));
+
+            path.with_file_name(name)
+                .with_extension(path.extension().unwrap_or_default())
+        };
+
+        let renamed = rename_without_replacing(temp_path, &candidate)
+            .map_err(|e| ImageError::IoError(io::Error::new(io::ErrorKind::Other, e)))?;
+
+        if renamed {
+            return Ok(candidate);
+        }
+
+        if keep_existing {
+            info!(
+                "{} was already saved by another writer",
+                candidate.display()
+            );
+            fs::remove_file(temp_path)?;
+
+            return Ok(candidate);
+        }
+
+        info!(
+            "{} was saved by another writer first - trying another name",
+            candidate.display()
+        );
+    }
+
+    Err(ImageError::IoError(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("Every name for {} is taken", path.display()),
+    )))
+}
+
+/// Removes the temporary files left behind in the screenshot directory (and its
+/// subdirectories) by screenshots that were never finished writing, e.g.
+/// because the program was killed.
+///
+/// Files written to recently are left alone, in case another copy of the
+/// program (e.g. in another session) is still writing them.
+pub fn remove_stale_temp_files() {
+    let mut screenshot_path = PathBuf::new();
+    Settings::read(|s| screenshot_path = s.paths.screenshots.clone());
+
+    if let Err(e) = remove_stale_temp_files_in(&screenshot_path) {
+        warn!("Failed to remove stale temporary files: {:#?}", e);
+    }
+}
+
+/// Removes the stale temporary screenshot files within `dir`, and within its
+/// subdirectories.
+fn remove_stale_temp_files_in(dir: &Path) -> io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    let now = SystemTime::now();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            remove_stale_temp_files_in(&path)?;
+            continue;
+        }
+
+        // Temporary files are named after the screenshot, followed by the writer's token
+        let is_temp_screenshot = path.extension() == Some(OsStr::new(TEMP_EXTENSION))
+            && (is_screenshot(&path.with_extension(""))
+                || is_screenshot(&path.with_extension("").with_extension("")));
+
+        if !is_temp_screenshot {
+            continue;
+        }
+
+        let modified = fs::metadata(&path)?.modified()?;
+
+        if now.duration_since(modified).unwrap_or_default() > STALE_TEMP_AGE {
+            info!("Removing stale temporary file {}", path.display());
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Verifies that screenshots can be written to the screenshot directory, by
+/// writing (then deleting) a probe file in it. This catches problems such as
+/// the directory being on a read-only share, or being locked by OneDrive's
+/// known folder redirection.
+pub fn check_screenshot_dir() -> io::Result<()> {
+    let mut screenshot_path = PathBuf::new();
+    Settings::read(|s| screenshot_path = s.paths.screenshots.clone());
+
+    let probe_path = screenshot_path.join(format!(".snip-and-autosave-{}.probe", process::id()));
+
+    fs::create_dir_all(&screenshot_path)
+        .and_then(|_| fs::write(&probe_path, b"probe"))
+        .and_then(|_| fs::remove_file(&probe_path))
+}
+
+/// Runs [`check_screenshot_dir`], and notifies the user immediately if it
+/// fails, rather than when their next screenshot fails to save.
+///
+/// [`check_screenshot_dir`]: check_screenshot_dir
+pub fn verify_screenshot_dir() {
+    if let Err(e) = check_screenshot_dir() {
+        let mut screenshot_path = PathBuf::new();
+        Settings::read(|s| screenshot_path = s.paths.screenshots.clone());
+
+        warn!(
+            "Screenshot directory {} is not writable: {:#?}",
+            screenshot_path.display(),
+            e
+        );
+
+        let _ = notification_area::show_balloon(
+            "Screenshots can't be saved",
+            &format!("Unable to write to {}: {}", screenshot_path.display(), e),
+            BalloonLevel::Warning,
+        );
+    }
+}
+
+/// Generates the fully qualified path for a new screenshot `image` of
+/// `category` content from `source`, captured as described by `snapshot`, with
+/// `extension`, using the user's naming strategy, creating the screenshot
+/// directory (including any category or dated subdirectory) if needed.
+/// Incognito captures are kept in the incognito directory. The path is
+/// reserved until the returned [`Reservation`] is dropped.
+///
+/// [`Reservation`]: Reservation
+fn generate_output_path(
+    image: &RgbImage,
+    category: Category,
+    source: CaptureSource,
+    extension: &str,
+    snapshot: &Snapshot,
+) -> io::Result<(PathBuf, Reservation)> {
+    let incognito = snapshot.incognito;
+    let mut screenshot_path = if incognito {
+        incognito::capture_dir()
+    } else {
+        let mut screenshot_path = PathBuf::new();
+        Settings::read(|s| screenshot_path = s.paths.screenshots.clone());
+
+        screenshot_path
+    };
+
+    let mut naming = Naming::Template;
+    let mut filename_template = String::new();
+    let mut subdirectory_format = String::new();
+    let mut category_dir = String::new();
+    let mut remote_dir = String::new();
+    let mut remote_source_dir = String::new();
+
+    Settings::read(|s| {
+        naming = s.paths.naming;
+        filename_template = s.paths.filename_template.clone();
+        subdirectory_format = s.paths.subdirectory_format.clone();
+        remote_dir = s.remote_session.subdirectory.clone();
+        remote_source_dir = s.routing.remote.clone();
+        category_dir = match category {
+            Category::Ui => s.routing.ui.clone(),
+            Category::Code => s.routing.code.clone(),
+            Category::Photo => s.routing.photo.clone(),
+            Category::Document => s.routing.document.clone(),
+        };
+    });
+
+    let mut context = Context::capture(&snapshot.moment, category);
+
+    // Incognito captures are kept together, whatever they show
+    if !incognito {
+        if session::remote_action() == RemoteSessionAction::Route {
+            screenshot_path.push(remote_dir);
+        } else if source == CaptureSource::Remote && !remote_source_dir.is_empty() {
+            screenshot_path.push(remote_source_dir);
+        } else if !category_dir.is_empty() {
+            screenshot_path.push(category_dir);
+        }
+    }
+
+    if !subdirectory_format.is_empty() {
+        if let Some(subdirectory) = template::expand_directory(&subdirectory_format, &context) {
+            screenshot_path.push(subdirectory);
+        }
+    }
+
+    // Make sure that the screenshot path exists, if we are running for the first time
+    fs::create_dir_all(&screenshot_path)?;
+
+    let pending = pairing::take_pending();
+    let mut strategy = naming::strategy(naming, &filename_template);
+
+    // Text recognition and hashing are slow, so are done before other saves are
+    // kept waiting to reserve their filenames
+    if pending.is_none() {
+        strategy.prepare(image, &mut context);
+    }
+
+    let mut reserved_paths = RESERVED_PATHS.lock().unwrap();
+
+    let filename = match pending {
+        Some(base) => format!("{}{}", base, pairing::AFTER_SUFFIX),
+        None => {
+            let suffix = if pairing::is_active() {
+                pairing::BEFORE_SUFFIX
+            } else {
+                ""
+            };
+
+            let base = strategy.filename(&mut context, &|filename| {
+                let path = screenshot_path
+                    .join(format!("{}{}", filename, suffix))
+                    .with_extension(extension);
+
+                path.exists() || reserved_paths.contains(&path)
+            });
+
+            if pairing::is_active() {
+                pairing::start(&base);
+            }
+
+            format!("{}{}", base, suffix)
+        }
+    };
+
+    let path = screenshot_path.join(filename).with_extension(extension);
+    reserved_paths.insert(path.clone());
+    drop(reserved_paths);
+
+    let reservation = Reservation(path.clone());
+
+    // The naming strategy may have put the screenshot in a subdirectory
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    Ok((path, reservation))
+}
+
+/// Saves a screenshot to disk, either as a plaintext PNG file, or as an entry in
+/// the encrypted screenshot archive, depending on the user's settings.
+///
+/// Animated screenshots are saved as their original bytes, in their original
+/// format.
+///
+/// Returns the path that the screenshot was saved to (which is the archive's
+/// path, for encrypted screenshots), and whether it had already been saved.
+fn save_screenshot(
+    image: &RgbImage,
+    region: Option<Region>,
+    animation: Option<&Animation>,
+    source: CaptureSource,
+    snapshot: &Snapshot,
+) -> Result<Saved, SaveError> {
+    // Incognito captures must never leave a trace outside of the incognito directory
+    let record_history = !snapshot.incognito;
+    let category = classify::classify(image);
+    debug!("Screenshot classified as {}", category.label());
+
+    let mut encrypt = false;
+    let mut naming = Naming::Template;
+    let mut output = Output::default();
+    let mut copy_file_to_clipboard = false;
+
+    Settings::read(|s| {
+        encrypt = s.encryption.enabled;
+        naming = s.paths.naming;
+        output = s.output.clone();
+        copy_file_to_clipboard = s.program.copy_file_to_clipboard;
+    });
+
+    let (output_path, _reservation) = generate_output_path(
+        image,
+        category,
+        source,
+        output::extension(output.format),
+        snapshot,
+    )
+    .map_err(SaveError::CreateDir)?;
+
+    let output_path = match animation {
+        Some(animation) => output_path.with_extension(animation.format.extension()),
+        None => output_path,
+    };
+
+    if encrypt {
+        let entry = match animation {
+            Some(animation) => animation.data.clone(),
+            None => {
+                let mut png = Vec::new();
+
+                PngEncoder::new(&mut png).encode(
+                    image,
+                    image.width(),
+                    image.height(),
+                    ColorType::Rgb8,
+                )?;
+
+                png
+            }
+        };
+
+        // Archive entries are always PNGs (unless animated), whatever the configured format
+        let output_path = if animation.is_some() {
+            output_path
+        } else {
+            output_path.with_extension("png")
+        };
+        let archive_path = output_path.with_file_name(encryption::ARCHIVE_FILE);
+        let entry_name = output_path.file_name().unwrap().to_string_lossy();
+
+        encryption::append_to_archive(&archive_path, &entry_name, &entry)?;
+
+        Ok(Saved::New(archive_path))
+    } else {
+        // A screenshot named by its content that's already saved (possibly by
+        // another machine sharing the directory) can't be anything else
+        if naming::names_by_content(naming) && output_path.exists() {
+            info!(
+                "{} has already been saved - not saving it again",
+                output_path.display()
+            );
+
+            return Ok(Saved::Existing(output_path));
+        }
+
+        let keep_existing = naming::names_by_content(naming);
+
+        let result = write_atomically(&output_path, keep_existing, |temp_path| match animation {
+            Some(animation) => OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(temp_path)
+                .and_then(|mut file| file.write_all(&animation.data))
+                .map_err(ImageError::IoError),
+            None => output::save(image, temp_path, output.format, output.jpeg_quality),
+        });
+
+        let output_path = match result {
+            Ok(output_path) => output_path,
+            Err(e) if animation.is_none() => recover_failed_encode(
+                image,
+                &output_path,
+                &output,
+                record_history,
+                keep_existing,
+                e,
+            )?,
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Err(e) = zone::apply(&output_path) {
+            warn!("Failed to update zone identifier: {:#?}", e);
+        }
+
+        for target in &output.additional {
+            if let Err(e) = save_copy(image, &output_path, target) {
+                warn!(
+                    "Failed to save {} copy of {}: {}",
+                    output::extension(target.format),
+                    output_path.display(),
+                    e
+                );
+            }
+        }
+
+        if copy_file_to_clipboard {
+            if let Err(e) = open_clipboard(None)
+                .and_then(|clipboard| set_clipboard_files(&clipboard, &[&output_path]))
+            {
+                warn!("Failed to copy screenshot file to clipboard: {:#?}", e);
+            }
+        }
+
+        if record_history {
+            history::record(history::Entry {
+                region,
+                category: Some(category),
+                source: Some(source),
+                animation: animation.map(|animation| animation.format),
+                context: snapshot.context.clone(),
+                ..history::Entry::describe(output_path.clone(), image, Utc::now())
+            });
+        }
+
+        Ok(Saved::New(output_path))
+    }
+}
+
+/// Handles `image` failing to be saved to `path` with `error`, by keeping its
+/// raw pixel data for diagnosis (unless `keep_trace` is `false`, or it's
+/// disabled), then saving it with the fallback encoder (unless disabled),
+/// returning the path that it was saved to. `keep_existing` is passed to
+/// [`write_atomically`].
+///
+/// [`write_atomically`]: write_atomically
+fn recover_failed_encode(
+    image: &RgbImage,
+    path: &Path,
+    output: &Output,
+    keep_trace: bool,
+    keep_existing: bool,
+    error: ImageError,
+) -> Result<PathBuf, ImageError> {
+    warn!("Failed to save {}: {}", path.display(), error);
+
+    if output.keep_failed_encodes && keep_trace {
+        match dump_raw(image) {
+            Ok(dump_path) => info!("Kept raw screenshot data in {}", dump_path.display()),
+            Err(e) => warn!("Failed to keep raw screenshot data: {:#?}", e),
+        }
+    }
+
+    if !output.encode_fallback {
+        return Err(error);
+    }
+
+    let fallback_path =
+        write_atomically(&path.with_extension("png"), keep_existing, |temp_path| {
+            output::save_fallback(image, temp_path)
+        })?;
+
+    info!(
+        "Saved {} with the fallback PNG encoder",
+        fallback_path.display()
+    );
+
+    Ok(fallback_path)
+}
+
+/// Writes the pixel data of `image`, as 32-bit BGRA, to a new file in
+/// [`FAILED_ENCODE_DIR`] within the config directory, named after the current
+/// time and the image's dimensions, returning its path.
+///
+/// [`FAILED_ENCODE_DIR`]: FAILED_ENCODE_DIR
+fn dump_raw(image: &RgbImage) -> io::Result<PathBuf> {
+    let dir = settings::config_dir().join(FAILED_ENCODE_DIR);
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join(format!(
+        "{}_{}x{}.bgra",
+        Local::now().format("%Y%m%d_%H%M%S%.3f"),
+        image.width(),
+        image.height()
+    ));
+
+    fs::write(&path, convert::image_to_bgra(image))?;
+
+    Ok(path)
+}
+
+/// Returns where the copy of the screenshot saved to `path` is saved, as
+/// configured by `target`.
+fn copy_path(path: &Path, target: &OutputTarget) -> PathBuf {
+    path.parent()
+        .unwrap()
+        .join(&target.subdirectory)
+        .join(path.file_name().unwrap())
+        .with_extension(output::extension(target.format))
+}
+
+/// Returns the paths of the additional copies of the screenshot saved to
+/// `path` that currently exist.
+pub fn existing_copies(path: &Path) -> Vec<PathBuf> {
+    let mut additional = Vec::new();
+    Settings::read(|s| additional = s.output.additional.clone());
+
+    additional
+        .iter()
+        .map(|target| copy_path(path, target))
+        .filter(|copy_path| copy_path != path && copy_path.is_file())
+        .collect()
+}
+
+/// Saves a copy of `image`, which was saved to `path`, as configured by
+/// `target`, named after the screenshot.
+fn save_copy(image: &RgbImage, path: &Path, target: &OutputTarget) -> Result<(), SaveError> {
+    let copy_path = copy_path(path, target);
+    fs::create_dir_all(copy_path.parent().unwrap()).map_err(SaveError::CreateDir)?;
+
+    // A copy in the same format and directory would replace the screenshot
+    if copy_path == path {
+        return Ok(());
+    }
+
+    let scale = if target.max_size == 0 {
+        1.0
+    } else {
+        (target.max_size as f32 / image.width().max(image.height()) as f32).min(1.0)
+    };
+
+    let copy = if scale < 1.0 {
+        Cow::Owned(imageops::thumbnail(
+            image,
+            ((image.width() as f32 * scale) as u32).max(1),
+            ((image.height() as f32 * scale) as u32).max(1),
+        ))
+    } else {
+        Cow::Borrowed(image)
+    };
+
+    let mut naming = Naming::Template;
+    Settings::read(|s| naming = s.paths.naming);
+
+    let copy_path = write_atomically(&copy_path, naming::names_by_content(naming), |temp_path| {
+        output::save(&copy, temp_path, target.format, target.jpeg_quality)
+    })?;
+
+    if let Err(e) = zone::apply(&copy_path) {
+        warn!("Failed to update zone identifier: {:#?}", e);
+    }
+
+    Ok(())
+}
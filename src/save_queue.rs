@@ -0,0 +1,249 @@
+//! The save queue.
+//!
+//! Captured screenshots are queued, then encoded and saved by a small pool of
+//! save worker threads, so that a burst of snips can't start an unbounded
+//! number of encodes at once. The queue is bounded: once it is full, whatever
+//! is queueing a screenshot waits for space, rather than using ever more
+//! memory.
+//!
+//! A screenshot that's identical to one that's already waiting to be saved (or
+//! being saved) is skipped as a duplicate straight away, as it can't have been
+//! recorded as a recent save yet.
+//!
+//! Whilst screenshots are waiting, the notification area icon's tooltip says
+//! how many.
+
+use crate::animation::Animation;
+use crate::events::{self, Event, SkipReason};
+use crate::heuristics::CaptureSource;
+use crate::notification_area;
+use crate::region::Region;
+use crate::save::{self, Snapshot};
+use crate::settings::Settings;
+use crate::windows::com_initialize;
+use bindings::Windows::Win32::System::Com::COINIT_MULTITHREADED;
+use crc32fast::Hasher;
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use image::RgbImage;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+
+/// How many screenshots can wait to be saved, before queueing another blocks.
+const CAPACITY: usize = 8;
+
+/// The most save worker threads that can be started.
+const MAX_WORKERS: usize = 4;
+
+/// The dimensions and CRC-32 checksum of a screenshot's pixel data, for
+/// recognising identical screenshots in the queue.
+type Fingerprint = (u32, u32, u32);
+
+/// A screenshot waiting to be saved.
+struct Job {
+    image: RgbImage,
+    region: Option<Region>,
+    animation: Option<Animation>,
+    source: CaptureSource,
+    snapshot: Snapshot,
+    fingerprint: Fingerprint,
+}
+
+/// Incremented whenever the queued screenshots are cancelled. Jobs captured in
+/// an earlier generation are discarded, rather than saved.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// The sending half of the save workers' job channel, once they have been
+    /// started.
+    static ref JOBS: Mutex<Option<Sender<Job>>> = Mutex::new(None);
+
+    /// The save worker threads, once they have been started.
+    static ref WORKERS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+    /// The fingerprints of the screenshots that are queued or being saved.
+    static ref PENDING: Mutex<Vec<Fingerprint>> = Mutex::new(Vec::new());
+}
+
+/// Starts the save worker threads. Screenshots are saved on the thread that
+/// queues them until this is called.
+pub fn start() {
+    let mut worker_count = 1;
+    Settings::read(|s| worker_count = s.program.save_workers.clamp(1, MAX_WORKERS));
+
+    let (sender, receiver) = bounded(CAPACITY);
+    *JOBS.lock().unwrap() = Some(sender);
+
+    let mut workers = WORKERS.lock().unwrap();
+
+    for i in 0..worker_count {
+        let receiver = receiver.clone();
+
+        workers.push(
+            thread::Builder::new()
+                .name(format!("save-{}", i))
+                .spawn(move || run(receiver))
+                .expect("Failed to start save worker thread"),
+        );
+    }
+}
+
+/// Stops the save worker threads, once they have saved every queued
+/// screenshot. Screenshots are saved on the thread that queues them after this
+/// is called.
+pub fn stop() {
+    JOBS.lock().unwrap().take();
+
+    for worker in WORKERS.lock().unwrap().drain(..) {
+        if worker.join().is_err() {
+            warn!("Save worker thread panicked");
+        }
+    }
+}
+
+/// Queues `image` (or `animation`, if there is one), from `source`, captured
+/// as described by `snapshot`, to be saved, as if by [`save::archive_capture`],
+/// unless an identical screenshot is already queued. This waits whilst the
+/// queue is full.
+///
+/// [`save::archive_capture`]: save::archive_capture
+pub fn push(
+    image: RgbImage,
+    region: Option<Region>,
+    animation: Option<Animation>,
+    source: CaptureSource,
+    snapshot: Snapshot,
+) {
+    let fingerprint = fingerprint(&image);
+
+    let mut dedup = true;
+    Settings::read(|s| dedup = s.dedup.enabled);
+
+    {
+        let mut pending = PENDING.lock().unwrap();
+
+        if dedup && pending.contains(&fingerprint) {
+            info!("Screenshot is the same as one waiting to be saved - ignoring");
+            events::publish(Event::Skipped {
+                reason: SkipReason::Duplicate,
+            });
+
+            return;
+        }
+
+        pending.push(fingerprint);
+    }
+
+    show_status();
+
+    let job = Job {
+        image,
+        region,
+        animation,
+        source,
+        snapshot,
+        fingerprint,
+    };
+
+    // The sender is cloned, so that the lock isn't held whilst waiting for space
+    let jobs = JOBS.lock().unwrap().clone();
+
+    let job = match jobs {
+        Some(jobs) => match jobs.try_send(job) {
+            Ok(()) => return,
+            Err(TrySendError::Full(job)) => {
+                info!("Save queue is full - waiting for space");
+
+                match jobs.send(job) {
+                    Ok(()) => return,
+                    Err(e) => e.into_inner(),
+                }
+            }
+            Err(TrySendError::Disconnected(job)) => job,
+        },
+        None => job,
+    };
+
+    // Without any workers, the screenshot is saved on this thread instead
+    save(job);
+}
+
+/// Discards every screenshot that is waiting to be saved. Screenshots that are
+/// already being saved are unaffected.
+pub fn cancel() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    info!("Cancelling queued screenshots");
+}
+
+/// Returns the current generation, which changes whenever the queued
+/// screenshots are cancelled.
+pub fn generation() -> usize {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Returns the number of screenshots that are queued or being saved.
+pub fn depth() -> usize {
+    PENDING.lock().unwrap().len()
+}
+
+/// A save worker thread's main loop, which exits when the job channel closes.
+fn run(jobs: Receiver<Job>) {
+    // Needed to read browser address bars with UI Automation, and recognise text
+    if let Err(e) = com_initialize(COINIT_MULTITHREADED) {
+        warn!("Failed to initialise COM on a save worker thread: {:#?}", e);
+    }
+
+    for job in jobs {
+        save(job);
+    }
+}
+
+/// Saves the screenshot in `job`, unless it has been cancelled, then removes
+/// it from the queue.
+fn save(job: Job) {
+    if job.snapshot.generation == generation() {
+        save::archive_capture(
+            &job.image,
+            job.region,
+            job.animation.as_ref(),
+            job.source,
+            &job.snapshot,
+        );
+    } else {
+        info!("Discarding cancelled screenshot");
+    }
+
+    {
+        let mut pending = PENDING.lock().unwrap();
+
+        if let Some(i) = pending.iter().position(|&f| f == job.fingerprint) {
+            pending.remove(i);
+        }
+    }
+
+    show_status();
+}
+
+/// Shows how many screenshots are waiting to be saved in the notification
+/// area icon's tooltip.
+fn show_status() {
+    let status = match depth() {
+        0 => None,
+        1 => Some("Saving a screenshot...".to_owned()),
+        depth => Some(format!("Saving {} screenshots...", depth)),
+    };
+
+    if let Err(e) = notification_area::set_status(status.as_deref()) {
+        warn!("Failed to show save queue status: {:#?}", e);
+    }
+}
+
+/// Returns the fingerprint of `image`.
+fn fingerprint(image: &RgbImage) -> Fingerprint {
+    let mut crc = Hasher::new();
+    crc.update(image.as_raw());
+
+    (image.width(), image.height(), crc.finalize())
+}
@@ -0,0 +1,88 @@
+//! Corruption scan of saved screenshots.
+//!
+//! Screenshots can silently rot on unreliable storage (e.g. a NAS without
+//! checksumming). The scan decodes every screenshot, and compares it against
+//! the hash recorded in the history when it was saved, so that damage is found
+//! while backups still exist.
+
+use crate::history;
+use crate::migration::find_screenshots_recursive;
+use crate::output;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The name of the directory within the scanned directory that corrupt
+/// screenshots are moved to.
+pub const QUARANTINE_DIR: &str = "Quarantine";
+
+/// File extensions of lossless formats, whose decoded pixels are expected to
+/// exactly match the hash recorded when they were saved.
+const LOSSLESS_EXTENSIONS: &[&str] = &["png", "bmp"];
+
+/// The outcome of scanning a directory of screenshots.
+#[derive(Default)]
+pub struct Report {
+    /// How many screenshots were checked.
+    pub checked: usize,
+
+    /// The screenshots that are corrupt, and why.
+    pub corrupt: Vec<(PathBuf, String)>,
+}
+
+/// Checks every screenshot directly within `dir`. If `quarantine` is `true`,
+/// corrupt screenshots are moved into [`QUARANTINE_DIR`].
+///
+/// [`QUARANTINE_DIR`]: QUARANTINE_DIR
+pub fn scan_dir(dir: &Path, quarantine: bool) -> io::Result<Report> {
+    let mut report = Report::default();
+
+    for screenshot in find_screenshots_recursive(dir)? {
+        report.checked += 1;
+
+        if let Err(reason) = check(&screenshot) {
+            report.corrupt.push((screenshot, reason));
+        }
+    }
+
+    if quarantine && !report.corrupt.is_empty() {
+        let quarantine_dir = dir.join(QUARANTINE_DIR);
+        fs::create_dir_all(&quarantine_dir)?;
+
+        for (screenshot, _) in &report.corrupt {
+            fs::rename(
+                screenshot,
+                quarantine_dir.join(screenshot.file_name().unwrap()),
+            )?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Checks that a single screenshot decodes, and that it matches its recorded
+/// hash, returning why it is corrupt, if it isn't.
+fn check(screenshot: &Path) -> Result<(), String> {
+    let image = output::open(screenshot)
+        .map_err(|e| format!("Unable to decode: {}", e))?
+        .to_rgb8();
+
+    let is_lossless = match screenshot.extension().and_then(|e| e.to_str()) {
+        Some(extension) => LOSSLESS_EXTENSIONS.contains(&extension.to_lowercase().as_str()),
+        None => false,
+    };
+
+    if !is_lossless {
+        return Ok(());
+    }
+
+    if let Some(recorded_hash) = history::hash_of(screenshot) {
+        let hash = history::hash(&image).map_err(|e| format!("Unable to hash: {}", e))?;
+
+        if hash != recorded_hash {
+            return Err("Pixel data doesn't match the hash recorded when it was saved".into());
+        }
+    }
+
+    Ok(())
+}
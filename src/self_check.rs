@@ -0,0 +1,111 @@
+//! Start-up self-check of the Windows features that the program relies on.
+//!
+//! Rather than failing one feature at a time (e.g. with a panic the first time
+//! a screenshot is saved), every check is run at start-up, and any failures are
+//! reported to the user together, alongside hints on how to fix them.
+
+use crate::notification_area::{self, BalloonLevel};
+use crate::windows::message_box;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::{MB_ICONWARNING, MB_OK, MB_SETFOREGROUND};
+use log::{info, warn};
+use std::fmt::Display;
+
+/// A feature that is verified by the self-check.
+#[derive(Clone, Copy)]
+pub enum Check {
+    /// Reading and writing `settings.toml`.
+    Settings,
+
+    /// Creating the notification area icon.
+    NotificationIcon,
+
+    /// Registering for clipboard update notifications.
+    ClipboardListener,
+
+    /// Writing files to the screenshot directory.
+    ScreenshotDir,
+
+    /// Registering the global hotkey.
+    Hotkey,
+
+    /// Listening for status endpoint requests.
+    StatusServer,
+}
+
+impl Check {
+    /// Describes what is broken when this check fails.
+    fn description(self) -> &'static str {
+        match self {
+            Check::Settings => "Settings can't be loaded or saved",
+            Check::NotificationIcon => "The tray icon couldn't be created",
+            Check::ClipboardListener => "Screenshots can't be detected",
+            Check::ScreenshotDir => "Screenshots can't be saved",
+            Check::Hotkey => "The hotkey couldn't be registered",
+            Check::StatusServer => "The status endpoint couldn't be started",
+        }
+    }
+
+    /// Suggests how the user can fix this check's failure.
+    fn hint(self) -> &'static str {
+        match self {
+            Check::Settings => "check that settings.toml is valid and not read-only",
+            Check::NotificationIcon => "try restarting Explorer",
+            Check::ClipboardListener => "try restarting the program",
+            Check::ScreenshotDir => "choose another screenshot folder",
+            Check::Hotkey => "check the key combination, or choose one that isn't already in use",
+            Check::StatusServer => "choose a port that isn't already in use",
+        }
+    }
+}
+
+/// Accumulates the results of each check, to be reported together.
+#[derive(Default)]
+pub struct SelfCheck {
+    failures: Vec<Check>,
+}
+
+impl SelfCheck {
+    /// Records the result of `check`, returning whether or not it passed.
+    pub fn check<E: Display>(&mut self, check: Check, result: Result<(), E>) -> bool {
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Self-check failed: {}: {}", check.description(), e);
+                self.failures.push(check);
+
+                false
+            }
+        }
+    }
+
+    /// Notifies the user of every failed check in a single notification. If
+    /// the notification area icon is unavailable, a message box is shown
+    /// instead.
+    pub fn report(self) {
+        if self.failures.is_empty() {
+            info!("Self-check passed");
+            return;
+        }
+
+        let text = self
+            .failures
+            .iter()
+            .map(|check| format!("{} - {}.", check.description(), check.hint()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if notification_area::has_icon() {
+            let _ = notification_area::show_balloon(
+                "Snip & AutoSave isn't working properly",
+                &text,
+                BalloonLevel::Warning,
+            );
+        } else {
+            message_box(
+                "Snip & AutoSave isn't working properly",
+                &text,
+                MB_OK | MB_ICONWARNING | MB_SETFOREGROUND,
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Per-session namespacing of the program's named objects.
+//!
+//! On a Remote Desktop Services host, several users may run the program at
+//! once, each in their own session. Every object used to find the running
+//! instance (i.e. the single-instance mutex, and the hidden window) is named
+//! after the current session, so that instances in different sessions never
+//! collide.
+//!
+//! Within a session, instances can also be given a separate namespace, so that
+//! e.g. a portable copy can run alongside an installed copy.
+//!
+//! Screenshots taken in a Remote Desktop session are often images copied on
+//! the remote host, so the user can choose to pause saving, or keep them apart,
+//! whilst the session is remote. A session can be reconnected from the console
+//! or remotely at any time, so this is checked for each screenshot.
+
+use crate::settings::{RemoteSessionAction, Settings};
+use crate::windows::{create_mutex, get_session_id, is_remote_session, AutoClose, WINDOW_NAME};
+use bindings::Windows::Win32::Foundation::HANDLE;
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+lazy_static! {
+    /// The ID of the session that the program is running in. If it can't be
+    /// determined, we fall back to session 0, which just means that instances
+    /// aren't namespaced.
+    static ref SESSION_ID: u32 = get_session_id().unwrap_or(0);
+
+    /// The namespace that this instance runs in within the session, or an
+    /// empty string for the default namespace.
+    static ref NAMESPACE: RwLock<String> = RwLock::new(String::new());
+}
+
+/// Moves this instance into a separate namespace within the session. This
+/// must be called before the instance lock is acquired, or the hidden window
+/// is created.
+pub fn set_namespace(namespace: &str) {
+    *NAMESPACE.write().unwrap() = namespace.to_owned();
+}
+
+/// Returns the name of the hidden window owned by the instance running in the
+/// current session.
+pub fn window_name() -> String {
+    match NAMESPACE.read().unwrap().as_str() {
+        "" => format!("{} (Session {})", WINDOW_NAME, *SESSION_ID),
+        namespace => format!("{} (Session {}, {})", WINDOW_NAME, *SESSION_ID, namespace),
+    }
+}
+
+/// Attempts to become the only instance running in the current session.
+///
+/// Returns `None` if another instance already holds the lock. Otherwise, the
+/// lock is held until the returned handle is dropped.
+pub fn acquire_instance_lock() -> windows::Result<Option<AutoClose<HANDLE>>> {
+    // `Local\` objects are already scoped to the current session, but we include the session ID
+    // anyway, so that the name is unambiguous
+    let name = match NAMESPACE.read().unwrap().as_str() {
+        "" => format!(r"Local\SnipAndAutoSave-Session{}", *SESSION_ID),
+        namespace => format!(
+            r"Local\SnipAndAutoSave-Session{}-{}",
+            *SESSION_ID, namespace
+        ),
+    };
+
+    let (mutex, already_exists) = create_mutex(&name)?;
+
+    if already_exists {
+        Ok(None)
+    } else {
+        Ok(Some(mutex))
+    }
+}
+
+/// Marks the current session as running an instance of kind `kind` (e.g.
+/// `installed`), until the returned handle is dropped.
+pub fn acquire_kind_marker(kind: &str) -> windows::Result<AutoClose<HANDLE>> {
+    let (mutex, _) = create_mutex(&kind_marker_name(kind))?;
+
+    Ok(mutex)
+}
+
+/// Returns whether or not an instance of kind `kind` is running in the current
+/// session.
+pub fn is_kind_running(kind: &str) -> windows::Result<bool> {
+    // The mutex is closed again straight away, so this doesn't mark anything
+    let (_mutex, already_exists) = create_mutex(&kind_marker_name(kind))?;
+
+    Ok(already_exists)
+}
+
+/// Returns the name of the mutex that marks an instance of kind `kind` as
+/// running.
+fn kind_marker_name(kind: &str) -> String {
+    format!(
+        r"Local\SnipAndAutoSave-Session{}-Kind-{}",
+        *SESSION_ID, kind
+    )
+}
+
+/// Returns how screenshots should be handled in the current session, which is
+/// always [`RemoteSessionAction::Save`] outside of Remote Desktop sessions.
+///
+/// [`RemoteSessionAction::Save`]: RemoteSessionAction::Save
+pub fn remote_action() -> RemoteSessionAction {
+    let mut action = RemoteSessionAction::Save;
+    Settings::read(|s| action = s.remote_session.action);
+
+    if action != RemoteSessionAction::Save && is_remote_session() {
+        action
+    } else {
+        RemoteSessionAction::Save
+    }
+}
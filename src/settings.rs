@@ -1,52 +1,262 @@
 //! Global application settings management.
 
+use crate::policy;
+use crate::remote_config;
+use chrono::Local;
 use lazy_static::lazy_static;
+use log::{error, info, warn};
 use platform_dirs::{AppDirs, UserDirs};
 use serde::{Deserialize, Serialize};
-use std::fs::{create_dir_all, File, OpenOptions};
-use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::sync::RwLock;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, create_dir_all, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+use thiserror::Error;
 
-/// The directory within `%APPDATA%` to store settings in.
+/// The directory within `%APPDATA%` (or beside the executable, in portable
+/// mode) to store settings in.
 const SETTINGS_DIR: &str = "snip-and-autosave";
 
+/// The name of the file which, if it exists beside the executable, turns on
+/// portable mode. Its contents are ignored.
+pub const PORTABLE_MARKER_FILE: &str = "portable.toml";
+
+/// The command line flag that turns on portable mode.
+pub const PORTABLE_FLAG: &str = "--portable";
+
 /// The name of the file within [`SETTINGS_DIR`] to store global settings in.
 ///
 /// [`SETTINGS_DIR`]: SETTINGS_DIR
-const SETTINGS_FILE: &str = "settings.toml";
+pub const SETTINGS_FILE: &str = "settings.toml";
 
 /// Top-level global settings object.
 ///
 /// Each object stored within this object is de/serialised from a separate TOML
 /// section in the settings file.
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct Settings {
     /// General program configuration.
     pub program: Program,
 
     /// Paths used by the application.
     pub paths: Paths,
+
+    /// Temporary "incognito" capture mode configuration.
+    pub incognito: Incognito,
+
+    /// Encrypted screenshot archive configuration.
+    pub encryption: Encryption,
+
+    /// Zone identifier ("Mark of the Web") handling for saved files.
+    pub zone: Zone,
+
+    /// Global hotkey configuration.
+    pub hotkey: Hotkey,
+
+    /// The image format that screenshots are saved in.
+    pub output: Output,
+
+    /// Configuration of how screenshots are recognised on the clipboard.
+    pub heuristics: Heuristics,
+
+    /// Configuration of which screenshots are skipped as duplicates.
+    pub dedup: Dedup,
+
+    /// Log file configuration.
+    pub logging: Logging,
+
+    /// Local HTTP status endpoint configuration.
+    pub status_server: StatusServer,
+
+    /// Configuration of how long deleted screenshots are kept.
+    pub trash: Trash,
+
+    /// Automatic clean-up of old screenshots.
+    pub retention: Retention,
+
+    /// Configuration of which capture events are shown as notifications.
+    pub notifications: Notifications,
+
+    /// Image host upload configuration.
+    pub upload: Upload,
+
+    /// Per-category screenshot subdirectories.
+    pub routing: Routing,
+
+    /// Webhook configuration.
+    pub webhook: Webhook,
+
+    /// Configuration of the context that is recorded with each screenshot.
+    pub context: CaptureContext,
+
+    /// Text recognition configuration.
+    pub ocr: Ocr,
+
+    /// What happens to screenshots taken in Remote Desktop sessions.
+    pub remote_session: RemoteSession,
+
+    /// Shared base configuration, which this file's settings are layered over.
+    pub remote_config: RemoteConfig,
+
+    /// The last position and size of each of the program's windows, keyed by
+    /// window name.
+    pub windows: HashMap<String, WindowPlacement>,
 }
 
 /// General program configuration.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Program {
     /// Whether or not to start the program automatically when the user logs in.
     pub auto_start: bool,
+
+    /// How the program is started automatically, if [`auto_start`] is enabled.
+    ///
+    /// [`auto_start`]: Program::auto_start
+    pub auto_start_method: AutoStartMethod,
+
+    /// Whether or not folders in Explorer have a context menu entry, which sets
+    /// them as the screenshot directory.
+    pub folder_context_menu: bool,
+
+    /// Whether or not the debug console is shown.
+    pub debug_console: bool,
+
+    /// Whether or not saving screenshots is paused.
+    pub paused: bool,
+
+    /// Whether or not every image copied to the clipboard is saved, rather than
+    /// only Snip & Sketch screenshots.
+    pub save_all_clipboard_images: bool,
+
+    /// Whether or not the user has been offered to import the screenshots in
+    /// the default screenshot folder.
+    pub import_offered: bool,
+
+    /// The version of the program that last ran, so that what's new can be
+    /// shown after it is updated.
+    pub last_run_version: String,
+
+    /// Whether or not reading a screenshot from the clipboard is retried once,
+    /// if another program is holding the clipboard open.
+    pub retry_failed_captures: bool,
+
+    /// Whether or not the screen region of each snip is found and remembered,
+    /// so that it can be snipped again.
+    pub remember_snip_region: bool,
+
+    /// What happens when a portable copy of the program is started whilst an
+    /// installed copy is running, or vice versa.
+    pub instance_conflict: InstanceConflict,
+
+    /// Whether or not each saved screenshot file is put on the clipboard, in
+    /// place of the captured image, so that it can be pasted as a file.
+    pub copy_file_to_clipboard: bool,
+
+    /// A command that is run after each screenshot is saved, in which `{path}`
+    /// is replaced with the screenshot's path, or an empty string for none.
+    pub post_save_command: String,
+
+    /// Whether or not the notification area icon is shown. Without it, the
+    /// menu can still be opened with [`menu_combination`], or by starting the
+    /// program again.
+    ///
+    /// [`menu_combination`]: Hotkey::menu_combination
+    pub show_tray_icon: bool,
+
+    /// How many screenshots (from 1 to 4) can be encoded and saved at once.
+    /// With more than one, screenshots taken in quick succession may finish
+    /// saving out of order.
+    pub save_workers: usize,
+
+    /// Whether or not each screenshot is shown in the quick crop window, so
+    /// that it can be trimmed, before it is saved.
+    pub quick_crop: bool,
 }
 
 impl Default for Program {
     fn default() -> Self {
-        Self { auto_start: false }
+        Self {
+            auto_start: false,
+            auto_start_method: AutoStartMethod::Registry,
+            folder_context_menu: false,
+            debug_console: false,
+            paused: false,
+            save_all_clipboard_images: false,
+            import_offered: false,
+            last_run_version: String::new(),
+            retry_failed_captures: true,
+            remember_snip_region: true,
+            instance_conflict: InstanceConflict::Prompt,
+            copy_file_to_clipboard: false,
+            post_save_command: String::new(),
+            show_tray_icon: true,
+            save_workers: 1,
+            quick_crop: false,
+        }
     }
 }
 
+/// Ways of starting the program automatically when the user logs in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoStartMethod {
+    /// A value in the user's `Run` registry key.
+    Registry,
+
+    /// A shortcut in the user's Startup folder.
+    Shortcut,
+}
+
+/// Ways of handling a portable and an installed copy of the program running at
+/// the same time.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InstanceConflict {
+    /// The installed copy keeps running (or replaces the portable copy), and
+    /// the portable copy exits.
+    DeferToInstalled,
+
+    /// The user is asked what to do.
+    Prompt,
+
+    /// Both copies run, independently of each other.
+    Separate,
+}
+
 /// Container for paths used by the application.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Paths {
     /// Where captured screenshots should be saved.
     pub screenshots: PathBuf,
+
+    /// The template that screenshot filenames (excluding their extension) are
+    /// generated from. See [`template`] for the supported placeholders.
+    ///
+    /// [`template`]: crate::template
+    pub filename_template: String,
+
+    /// How screenshot filenames are chosen. Only [`Naming::Template`] uses
+    /// [`filename_template`].
+    ///
+    /// [`Naming::Template`]: Naming::Template
+    /// [`filename_template`]: Paths::filename_template
+    pub naming: Naming,
+
+    /// Whether times in screenshot filenames are local times, or UTC.
+    pub filename_time_zone: FilenameTimeZone,
+
+    /// A `strftime` format string for the subdirectory of [`screenshots`] that
+    /// screenshots are saved in, e.g. `%Y/%m` for one directory per month, or
+    /// an empty string to save every screenshot directly in [`screenshots`].
+    ///
+    /// [`screenshots`]: Paths::screenshots
+    pub subdirectory_format: String,
 }
 
 impl Default for Paths {
@@ -55,13 +265,772 @@ impl Default for Paths {
 
         Self {
             screenshots: user_dirs.picture_dir.join("Screenshots"),
+            filename_template: "Screenshot_{date:%Y%m%d_%H%M%S}".into(),
+            naming: Naming::Template,
+            filename_time_zone: FilenameTimeZone::Local,
+            subdirectory_format: String::new(),
+        }
+    }
+}
+
+/// The ways that screenshot filenames can be chosen. See [`naming`].
+///
+/// [`naming`]: crate::naming
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Naming {
+    /// Expanded from [`Paths::filename_template`].
+    Template,
+
+    /// The capture time, e.g. `20240501_093000`.
+    Timestamp,
+
+    /// The lowest unused number, from `000001`.
+    Counter,
+
+    /// The SHA-256 hash of the screenshot's pixels, in a subdirectory named
+    /// after its first two digits, so that the same screenshot is always given
+    /// the same name, and is only saved once.
+    ContentHash,
+
+    /// The first line of text recognised in the screenshot.
+    OcrTitle,
+}
+
+/// The time zones that times in screenshot filenames can be in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilenameTimeZone {
+    /// The local time zone, including daylight saving time.
+    Local,
+
+    /// Coordinated Universal Time, which never changes for daylight saving.
+    Utc,
+}
+
+/// Configuration for incognito mode, during which screenshots are only saved to
+/// a temporary location, which is wiped when the program exits.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Incognito {
+    /// How long incognito mode stays enabled for, once toggled on.
+    pub duration_minutes: u64,
+
+    /// The directory that screenshots are saved within (in a dedicated
+    /// subdirectory) whilst incognito mode is enabled. The default, the user's
+    /// temporary directory, is on disk - point this at a RAM disk to avoid
+    /// sensitive captures ever touching a physical disk.
+    pub directory: PathBuf,
+}
+
+impl Default for Incognito {
+    fn default() -> Self {
+        Self {
+            duration_minutes: 15,
+            directory: env::temp_dir(),
+        }
+    }
+}
+
+/// Configuration for the encrypted screenshot archive.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Encryption {
+    /// Whether or not screenshots should be appended to an encrypted archive,
+    /// rather than being saved as plaintext PNG files.
+    pub enabled: bool,
+}
+
+/// Configuration for zone identifier handling of saved files.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Zone {
+    /// What to do with the `Zone.Identifier` stream of saved files.
+    pub mode: ZoneMode,
+
+    /// The zone ID written to saved files when [`mode`] is [`ZoneMode::Set`]
+    /// (e.g. `1` for the local intranet, or `3` for the internet).
+    ///
+    /// [`mode`]: Zone::mode
+    /// [`ZoneMode::Set`]: ZoneMode::Set
+    pub zone_id: u32,
+}
+
+impl Default for Zone {
+    fn default() -> Self {
+        Self {
+            mode: ZoneMode::Keep,
+            zone_id: 3,
+        }
+    }
+}
+
+/// Ways in which the zone identifier of saved files can be handled.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneMode {
+    /// Leave the zone identifier as Windows creates it.
+    Keep,
+
+    /// Mark saved files as originating from [`Zone::zone_id`].
+    ///
+    /// [`Zone::zone_id`]: Zone::zone_id
+    Set,
+
+    /// Remove any zone identifier from saved files.
+    Strip,
+}
+
+/// Configuration for the global hotkey, which opens Snip & Sketch.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Hotkey {
+    /// Whether or not the hotkey is registered.
+    pub enabled: bool,
+
+    /// The key combination, e.g. `Ctrl+Shift+S`. See [`hotkey`] for the
+    /// supported keys.
+    ///
+    /// [`hotkey`]: crate::hotkey
+    pub combination: String,
+
+    /// The key combination that toggles before/after pairing, or an empty
+    /// string for none.
+    pub pairing_combination: String,
+
+    /// How long after a "before" capture an "after" capture is paired with it.
+    pub pairing_window_seconds: u64,
+
+    /// The key combination that performs a panic wipe, or an empty string for
+    /// none. See [`panic_wipe`].
+    ///
+    /// [`panic_wipe`]: crate::panic_wipe
+    pub panic_combination: String,
+
+    /// How many of the most recently saved screenshots a panic wipe
+    /// permanently deletes.
+    pub panic_delete_count: usize,
+
+    /// The key combination that opens the notification area icon's menu at the
+    /// mouse cursor, or an empty string for none.
+    pub menu_combination: String,
+
+    /// The key combination that opens Snip & Sketch to pick a colour, rather
+    /// than save a screenshot, or an empty string for none. See
+    /// [`colour_picker`].
+    ///
+    /// [`colour_picker`]: crate::colour_picker
+    pub colour_picker_combination: String,
+}
+
+impl Default for Hotkey {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            combination: "Ctrl+Shift+S".into(),
+            pairing_combination: String::new(),
+            pairing_window_seconds: 300,
+            panic_combination: String::new(),
+            panic_delete_count: 0,
+            menu_combination: String::new(),
+            colour_picker_combination: String::new(),
+        }
+    }
+}
+
+/// Configuration of the image format that screenshots are saved in.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Output {
+    /// The image format that screenshots are saved in.
+    pub format: OutputFormat,
+
+    /// The quality (from 1 to 100) of JPEG screenshots.
+    pub jpeg_quality: u8,
+
+    /// The additional copies of each screenshot that are saved, e.g. a JPEG
+    /// preview alongside each PNG.
+    pub additional: Vec<OutputTarget>,
+
+    /// Whether or not a screenshot that can't be encoded in `format` is saved
+    /// as a PNG instead, with a simpler encoder.
+    pub encode_fallback: bool,
+
+    /// Whether or not the raw pixel data of a screenshot that can't be encoded
+    /// is kept in the config directory, for diagnosing the failure.
+    pub keep_failed_encodes: bool,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Png,
+            jpeg_quality: 90,
+            additional: Vec::new(),
+            encode_fallback: true,
+            keep_failed_encodes: true,
+        }
+    }
+}
+
+/// An additional copy of each screenshot, in another format.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct OutputTarget {
+    /// The image format of the copy.
+    pub format: OutputFormat,
+
+    /// The quality (from 1 to 100) of JPEG copies.
+    pub jpeg_quality: u8,
+
+    /// The subdirectory (of the directory that the screenshot is saved in) to
+    /// save the copy in. Empty to save it alongside the screenshot.
+    pub subdirectory: String,
+
+    /// The maximum width and height of the copy, in pixels. Larger screenshots
+    /// are scaled down to fit. `0` to keep the copy full size.
+    pub max_size: u32,
+}
+
+impl Default for OutputTarget {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Jpeg,
+            jpeg_quality: 90,
+            subdirectory: String::new(),
+            max_size: 0,
+        }
+    }
+}
+
+/// The image formats that screenshots can be saved in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Lossless PNG.
+    Png,
+
+    /// Lossy JPEG, which is much smaller than PNG for photographic content.
+    Jpeg,
+
+    /// Uncompressed BMP.
+    Bmp,
+
+    /// Lossless WebP.
+    Webp,
+}
+
+/// Configuration of how screenshots are recognised on the clipboard.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Heuristics {
+    /// The processes whose clipboard images are treated as screenshots.
+    pub clipboard_owners: Vec<ClipboardOwner>,
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Self {
+            // Snip & Sketch's clipboard writes are made by a generic service host process, so
+            // its clipboard formats have to be checked too
+            clipboard_owners: vec![ClipboardOwner {
+                process: "svchost.exe".into(),
+                require_snip_formats: true,
+            }],
+        }
+    }
+}
+
+/// A process whose clipboard images are treated as screenshots.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClipboardOwner {
+    /// The image name of the process, e.g. `ScreenClippingHost.exe`.
+    pub process: String,
+
+    /// Whether or not the clipboard must also contain the OLE formats that
+    /// Snip & Sketch adds to its screenshots, to filter out other programs
+    /// hosted by the same process.
+    #[serde(default)]
+    pub require_snip_formats: bool,
+}
+
+/// Configuration of which screenshots are skipped as duplicates.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Dedup {
+    /// Whether or not screenshots that are identical (or, if `perceptual` is
+    /// set, almost identical) to a recently saved screenshot are skipped.
+    pub enabled: bool,
+
+    /// How many of the most recently saved screenshots each capture is
+    /// compared against, from 1 to 100.
+    pub compare_last: usize,
+
+    /// Whether or not screenshots that are almost identical to a recently
+    /// saved screenshot (e.g. differing only by a blinking cursor) are
+    /// skipped, as well as identical ones.
+    pub perceptual: bool,
+
+    /// The number of bits (out of 256) that the perceptual hashes of two
+    /// screenshots may differ by, for them to be considered almost identical.
+    pub max_distance: u32,
+
+    /// Whether or not a screenshot is moved to the trash when a near-identical
+    /// screenshot of the same window is saved shortly after it, so that only
+    /// the last of a burst of retries is kept.
+    pub collapse_bursts: bool,
+
+    /// How soon after a screenshot the next one must be saved, in seconds, to
+    /// be part of the same burst.
+    pub burst_seconds: u64,
+}
+
+impl Default for Dedup {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            compare_last: 1,
+            perceptual: false,
+            max_distance: 4,
+            collapse_bursts: false,
+            burst_seconds: 5,
+        }
+    }
+}
+
+/// Log file configuration.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Logging {
+    /// The least severe level of log records that are written.
+    pub level: LogLevel,
+
+    /// The size that the log file is rotated at, in KiB.
+    pub max_file_size_kb: u64,
+
+    /// How many rotated log files are kept, in addition to the current one.
+    pub rotated_files: u32,
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::Debug,
+            max_file_size_kb: 1024,
+            rotated_files: 4,
+        }
+    }
+}
+
+/// Configuration for the local HTTP status endpoint.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct StatusServer {
+    /// Whether or not the status endpoint is served.
+    pub enabled: bool,
+
+    /// The port that the status endpoint listens on, on the loopback interface.
+    pub port: u16,
+}
+
+impl Default for StatusServer {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9788,
+        }
+    }
+}
+
+/// Configuration of how long deleted screenshots are kept, before they are
+/// permanently deleted.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Trash {
+    /// How many days deleted screenshots are kept in the trash.
+    pub retention_days: u32,
+}
+
+impl Default for Trash {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+/// Configuration of how old screenshots are cleaned up. Each limit is disabled
+/// when it is `0`.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct Retention {
+    /// The age, in days, after which screenshots are removed.
+    pub max_age_days: u32,
+
+    /// The maximum total size, in megabytes, of the screenshot directory. The
+    /// oldest screenshots are removed until it fits.
+    pub max_total_size_mb: u64,
+
+    /// What happens to removed screenshots.
+    pub action: RetentionAction,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_age_days: 0,
+            max_total_size_mb: 0,
+            action: RetentionAction::Delete,
+        }
+    }
+}
+
+/// What happens to screenshots that are removed by the retention policy.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionAction {
+    /// Screenshots are moved to the trash, and later permanently deleted.
+    Delete,
+
+    /// Screenshots are moved into the `Archive` subdirectory.
+    Archive,
+}
+
+/// Configuration of which capture events are shown as notifications.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Notifications {
+    /// How chatty notifications are.
+    pub verbosity: NotificationVerbosity,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            verbosity: NotificationVerbosity::EverySave,
+        }
+    }
+}
+
+/// The subdirectories of the screenshot directory that each category of
+/// screenshot (see [`classify`]) is saved in. An empty string saves screenshots
+/// of that category directly in the screenshot directory.
+///
+/// [`classify`]: crate::classify
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Routing {
+    /// The subdirectory for screenshots of application windows, dialogs, etc.
+    pub ui: String,
+
+    /// The subdirectory for screenshots of source code and terminals.
+    pub code: String,
+
+    /// The subdirectory for photos, and other natural images.
+    pub photo: String,
+
+    /// The subdirectory for screenshots of text documents.
+    pub document: String,
+
+    /// The subdirectory for screenshots redirected from another machine (see
+    /// [`CaptureSource::Remote`]), whatever their category.
+    ///
+    /// [`CaptureSource::Remote`]: crate::heuristics::CaptureSource::Remote
+    pub remote: String,
+}
+
+/// Configuration of uploading saved screenshots to an image host.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Upload {
+    /// Whether or not saved screenshots are uploaded.
+    pub enabled: bool,
+
+    /// The URL that screenshots are uploaded to.
+    pub endpoint: String,
+
+    /// The Imgur API client ID, sent as a `Client-ID` authorization header, or
+    /// an empty string for none.
+    pub client_id: String,
+
+    /// The name of the form field that the screenshot is uploaded as.
+    pub field_name: String,
+
+    /// A JSON pointer to the link to the uploaded image, within the endpoint's
+    /// response.
+    pub link_pointer: String,
+}
+
+impl Default for Upload {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "https://api.imgur.com/3/image".into(),
+            client_id: String::new(),
+            field_name: "image".into(),
+            link_pointer: "/data/link".into(),
+        }
+    }
+}
+
+/// Configuration of the webhook that saved screenshots are posted to.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Webhook {
+    /// Whether or not saved screenshots are posted to the webhook.
+    pub enabled: bool,
+
+    /// The URL that screenshots are posted to.
+    pub url: String,
+
+    /// How the screenshot and its metadata are encoded.
+    pub format: WebhookFormat,
+
+    /// Additional request headers, e.g. for authorization.
+    pub headers: HashMap<String, String>,
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            format: WebhookFormat::Multipart,
+            headers: HashMap::new(),
+        }
+    }
+}
+
+/// Configuration of the context that is recorded with each screenshot.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CaptureContext {
+    /// Whether or not the foreground process, window title, and web page or
+    /// document are recorded in the history of each screenshot.
+    pub enabled: bool,
+
+    /// The image names of the browsers whose address bars are read.
+    pub browsers: Vec<String>,
+}
+
+impl Default for CaptureContext {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            browsers: vec![
+                "chrome.exe".into(),
+                "msedge.exe".into(),
+                "firefox.exe".into(),
+                "brave.exe".into(),
+                "opera.exe".into(),
+                "vivaldi.exe".into(),
+            ],
+        }
+    }
+}
+
+/// Text recognition configuration.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Ocr {
+    /// Whether or not text in screenshots is recognised, e.g. for the `{title}`
+    /// filename template placeholder.
+    pub enabled: bool,
+
+    /// The maximum length of the `{title}` placeholder, in characters.
+    pub max_title_length: usize,
+}
+
+impl Default for Ocr {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_title_length: 60,
+        }
+    }
+}
+
+/// Configuration of screenshots taken whilst the program is running in a Remote
+/// Desktop session, where images copied on the remote host are redirected to
+/// the clipboard, and would otherwise be saved alongside local screenshots.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RemoteSession {
+    /// What happens to screenshots taken in a Remote Desktop session.
+    pub action: RemoteSessionAction,
+
+    /// The subdirectory of the screenshot directory that screenshots are saved
+    /// in, if [`action`] is [`RemoteSessionAction::Route`].
+    ///
+    /// [`action`]: RemoteSession::action
+    /// [`RemoteSessionAction::Route`]: RemoteSessionAction::Route
+    pub subdirectory: String,
+}
+
+impl Default for RemoteSession {
+    fn default() -> Self {
+        Self {
+            action: RemoteSessionAction::Save,
+            subdirectory: "Remote Desktop".into(),
+        }
+    }
+}
+
+/// The ways that screenshots taken in Remote Desktop sessions can be handled.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteSessionAction {
+    /// Screenshots are saved as usual.
+    Save,
+
+    /// Saving is paused whilst the session is a Remote Desktop session.
+    Pause,
+
+    /// Screenshots are saved in [`RemoteSession::subdirectory`].
+    ///
+    /// [`RemoteSession::subdirectory`]: RemoteSession::subdirectory
+    Route,
+}
+
+/// Shared base configuration, fetched from a web server, which the local
+/// settings file is layered over.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// The HTTPS URL of the base settings file. Empty to disable remote
+    /// configuration.
+    pub url: String,
+
+    /// The PEM or base64-encoded ECDSA P-256 public key that the base settings
+    /// file must be signed with.
+    pub public_key: String,
+
+    /// How often to check for changes to the base settings file, in hours.
+    pub refresh_interval_hours: u32,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            public_key: String::new(),
+            refresh_interval_hours: 24,
         }
     }
 }
 
+/// The ways that screenshots can be posted to the webhook.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// A `multipart/form-data` request, with `metadata` and `file` fields.
+    Multipart,
+
+    /// A JSON object, with the screenshot base64-encoded in its `image` field.
+    Json,
+}
+
+/// Notification verbosity levels, from least to most chatty. Each level also
+/// shows the notifications of the levels before it.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationVerbosity {
+    /// No notifications are shown. Errors are still logged.
+    Silent,
+
+    /// Notifications are only shown when a screenshot couldn't be saved.
+    ErrorsOnly,
+
+    /// A notification is shown for each saved screenshot.
+    EverySave,
+
+    /// Notifications are also shown when a screenshot is deliberately not
+    /// saved (e.g. because it's a duplicate).
+    Verbose,
+}
+
+/// Log record severity levels, from most to least severe.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// The position and size of a window, in virtual screen coordinates, so that
+/// the monitor the window was on is remembered too.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct WindowPlacement {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// The global settings, as the user configured them, and as they apply.
+struct LoadedSettings {
+    /// The user's own settings, which are what is written to the settings
+    /// file.
+    own: Settings,
+
+    /// The user's settings, with the values enforced by [`policy`] applied,
+    /// which are what the rest of the application reads.
+    ///
+    /// [`policy`]: crate::policy
+    effective: Settings,
+}
+
+impl LoadedSettings {
+    /// Wraps the user's own settings, applying [`policy`] to a copy of them.
+    ///
+    /// [`policy`]: crate::policy
+    fn new(own: Settings) -> Self {
+        let mut effective = own.clone();
+        policy::apply(&mut effective);
+
+        Self { own, effective }
+    }
+}
+
 lazy_static! {
     /// Global settings object.
-    static ref SETTINGS: RwLock<Option<Settings>> = RwLock::new(None);
+    static ref SETTINGS: RwLock<Option<LoadedSettings>> = RwLock::new(None);
+
+    /// The message to show the user, if the settings file couldn't be loaded.
+    static ref RECOVERY_NOTICE: Mutex<Option<String>> = Mutex::new(None);
+
+    /// The directory that the application's configuration and state files are
+    /// stored in, which is decided the first time that it is needed.
+    static ref CONFIG_DIR: PathBuf = find_config_dir();
+}
+
+/// Whether or not portable mode was turned on with [`PORTABLE_FLAG`].
+///
+/// [`PORTABLE_FLAG`]: PORTABLE_FLAG
+static PORTABLE_FLAG_GIVEN: AtomicBool = AtomicBool::new(false);
+
+/// Errors that can occur whilst loading the settings file.
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("Unable to read settings.toml: {0}")]
+    Read(#[from] io::Error),
+    #[error("settings.toml is invalid: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+impl SettingsError {
+    /// Returns a short explanation of the error, suitable for a notification.
+    fn summary(&self) -> &'static str {
+        match self {
+            SettingsError::Read(_) => {
+                "settings.toml couldn't be read, so the default settings are being used."
+            }
+            SettingsError::Parse(_) => {
+                "settings.toml is invalid, so the default settings are being used."
+            }
+        }
+    }
 }
 
 impl Settings {
@@ -75,7 +1044,7 @@ impl Settings {
             let reader = SETTINGS.read().unwrap();
 
             if let Some(ref settings) = *reader {
-                f(settings);
+                f(&settings.effective);
                 return;
             }
         }
@@ -84,13 +1053,17 @@ impl Settings {
         Self::read(f);
     }
 
-    /// Writes settings to disk, by calling `f` with a mutable [`Settings`]
-    /// reference, then serialising the instance to disk once `f` returns.
+    /// Writes settings to disk, by calling `f` with a mutable reference to the
+    /// user's own [`Settings`], then serialising them to disk once `f` returns.
+    /// Values enforced by [`policy`] aren't applied to the settings that `f`
+    /// sees, so they are never written to the settings file, and changes that
+    /// `f` makes to them have no effect whilst the policy is in force.
     ///
     /// Calls [`read`] before calling `f`, if the application has only just
     /// started, and the settings have not yet been read.
     ///
     /// [`Settings`]: Settings
+    /// [`policy`]: crate::policy
     /// [`read`]: read
     pub fn write(f: impl FnOnce(&mut Settings)) {
         // Force settings to be read, if this is the first time being called
@@ -100,76 +1073,304 @@ impl Settings {
             let mut writer = SETTINGS.write().unwrap();
 
             if let Some(ref mut settings) = *writer {
-                f(settings);
+                f(&mut settings.own);
+
+                *settings = LoadedSettings::new(settings.own.clone());
             }
         }
 
-        write_settings();
+        if let Err(e) = write_settings() {
+            error!("Failed to write settings: {:#?}", e);
+        }
+    }
+}
+
+/// Returns the fully qualified path to the directory that the application's
+/// configuration and state files are stored in.
+pub fn config_dir() -> PathBuf {
+    CONFIG_DIR.clone()
+}
+
+/// Turns on portable mode, as if [`PORTABLE_MARKER_FILE`] existed. This must
+/// be called before anything reads the settings, or writes a log file.
+///
+/// [`PORTABLE_MARKER_FILE`]: PORTABLE_MARKER_FILE
+pub fn enable_portable_mode() {
+    PORTABLE_FLAG_GIVEN.store(true, Ordering::SeqCst);
+}
+
+/// Returns [`PORTABLE_FLAG`], if it was given, as the executable must then be
+/// passed it whenever Windows starts it (e.g. automatically at log in), to use
+/// the same settings as this instance.
+///
+/// [`PORTABLE_FLAG`]: PORTABLE_FLAG
+pub fn portable_flag() -> Option<&'static str> {
+    if PORTABLE_FLAG_GIVEN.load(Ordering::SeqCst) {
+        Some(PORTABLE_FLAG)
+    } else {
+        None
+    }
+}
+
+/// Returns the directory to store configuration and state files in: beside
+/// the executable, in portable mode, otherwise within `%APPDATA%`.
+fn find_config_dir() -> PathBuf {
+    let exe_dir = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dunce::simplified(dir).to_path_buf()));
+
+    if let Some(exe_dir) = exe_dir {
+        if PORTABLE_FLAG_GIVEN.load(Ordering::SeqCst)
+            || exe_dir.join(PORTABLE_MARKER_FILE).is_file()
+        {
+            let config_dir = exe_dir.join(SETTINGS_DIR);
+            info!(
+                "Portable mode - storing settings in {}",
+                config_dir.display()
+            );
+
+            return config_dir;
+        }
     }
+
+    let app_dirs = AppDirs::new(Some(SETTINGS_DIR), false).expect("Could not generate AppDirs");
+
+    app_dirs.config_dir
 }
 
 /// Returns the fully qualified path to the TOML file that settings should
 /// loaded from / stored in.
-fn settings_file_path() -> PathBuf {
-    let app_dirs = AppDirs::new(Some(SETTINGS_DIR), false).expect("Could not generate AppDirs");
+pub fn settings_file_path() -> PathBuf {
+    config_dir().join(SETTINGS_FILE)
+}
 
-    app_dirs.config_dir.join(SETTINGS_FILE)
+/// Verifies that the settings file can be parsed, and that it (or the directory
+/// it will be created in) is writable, without modifying it.
+pub fn check_settings_file() -> io::Result<()> {
+    let file_path = settings_file_path();
+
+    if file_path.exists() {
+        let settings_str = fs::read_to_string(&file_path)?;
+
+        parse(&settings_str).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        OpenOptions::new().append(true).open(file_path)?;
+    } else {
+        create_dir_all(file_path.parent().unwrap())?;
+    }
+
+    Ok(())
 }
 
 /// Opens the settings file, then deserialises the TOML configuration within.
 ///
 /// If the settings file does not exist, a [`Default`] instance is created,
-/// then written to disk.
+/// then written to disk. If it can't be loaded, it is replaced in the same way,
+/// as described by [`recover`].
 ///
 /// [`Default`]: Default
+/// [`recover`]: recover
 fn read_settings() {
     let file_path = settings_file_path();
 
     if file_path.exists() {
-        let mut settings_str = String::new();
+        let settings = match load(&file_path) {
+            Ok(settings) => settings,
+            Err(e) => recover(&file_path, e),
+        };
 
-        File::open(file_path)
-            .expect("Unable to open settings.toml")
-            .read_to_string(&mut settings_str)
-            .expect("Unable to read from settings.toml");
+        {
+            let mut writer = SETTINGS.write().unwrap();
+            *writer = Some(LoadedSettings::new(settings));
+        }
 
-        let mut writer = SETTINGS.write().unwrap();
-        *writer = Some(toml::from_str(&settings_str).expect("Failed to parse settings.toml"));
+        // The defaults replace a file that couldn't be loaded, once it has been moved aside
+        if !file_path.exists() {
+            if let Err(e) = write_settings() {
+                error!("Failed to write default settings: {:#?}", e);
+            }
+        }
     } else {
         {
-            let settings = Settings::default();
             let mut writer = SETTINGS.write().unwrap();
+            *writer = Some(LoadedSettings::new(Settings::default()));
+        }
+
+        // The defaults are still used if they can't be written, so that
+        // `check_settings_file` can report the problem
+        if let Err(e) = write_settings() {
+            error!("Failed to write default settings: {:#?}", e);
+        }
+    }
+}
+
+/// Reads and parses the settings file at `file_path`.
+fn load(file_path: &Path) -> Result<Settings, SettingsError> {
+    Ok(parse(&fs::read_to_string(file_path)?)?)
+}
+
+/// Handles the settings file at `file_path` failing to load with `error`, by
+/// moving it aside (so that the user can fix it, and copy back what they need),
+/// then returning the default settings, to be written in its place. The user is
+/// told once the notification area icon exists, by [`take_recovery_notice`].
+///
+/// [`take_recovery_notice`]: take_recovery_notice
+fn recover(file_path: &Path, error: SettingsError) -> Settings {
+    error!("Failed to load settings - using defaults: {}", error);
+
+    let backup_path = file_path.with_file_name(format!(
+        "settings.broken-{}.toml",
+        Local::now().format("%Y%m%d_%H%M%S")
+    ));
+
+    let notice = match fs::rename(file_path, &backup_path) {
+        Ok(()) => {
+            info!("Moved invalid settings file to {}", backup_path.display());
+
+            format!(
+                "{} The old settings were saved as {}.",
+                error.summary(),
+                backup_path.file_name().unwrap().to_string_lossy()
+            )
+        }
+        Err(e) => {
+            warn!("Failed to move invalid settings file aside: {:#?}", e);
+            error.summary().to_owned()
+        }
+    };
+
+    *RECOVERY_NOTICE.lock().unwrap() = Some(notice);
+
+    Settings::default()
+}
+
+/// Returns the message to show the user if their settings file couldn't be
+/// loaded, and had to be replaced with the defaults, the first time that it's
+/// called after that happens.
+pub fn take_recovery_notice() -> Option<String> {
+    RECOVERY_NOTICE.lock().unwrap().take()
+}
+
+/// Re-reads the settings file, e.g. after it has been edited by hand, or the
+/// remote base configuration has changed. The current settings are kept if the
+/// file can't be loaded, as the user may be part way through editing it.
+pub fn reload() {
+    let file_path = settings_file_path();
+
+    if !file_path.exists() {
+        read_settings();
+        return;
+    }
+
+    match load(&file_path) {
+        Ok(settings) => *SETTINGS.write().unwrap() = Some(LoadedSettings::new(settings)),
+        Err(e) => warn!(
+            "Failed to reload settings - keeping current settings: {}",
+            e
+        ),
+    }
+}
 
-            *writer = Some(settings);
+/// Parses the contents of the settings file, layered over the cached remote
+/// base configuration, if there is one.
+fn parse(settings_str: &str) -> Result<Settings, toml::de::Error> {
+    let mut settings: toml::Value = toml::from_str(settings_str)?;
+
+    let url = settings
+        .get("remote_config")
+        .and_then(|remote_config| remote_config.get("url"))
+        .and_then(toml::Value::as_str)
+        .unwrap_or_default();
+
+    if let Some(mut base) = remote_config::cached(url) {
+        merge(&mut base, settings);
+        settings = base;
+    }
+
+    settings.try_into()
+}
+
+/// Merges `overrides` into `base`, recursively, so that tables present in both
+/// keep the keys that are only in `base`.
+fn merge(base: &mut toml::Value, overrides: toml::Value) {
+    match (base, overrides) {
+        (toml::Value::Table(base), toml::Value::Table(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
         }
+        (base, overrides) => *base = overrides,
+    }
+}
 
-        write_settings();
+/// Removes the values from `settings` that are the same in `base`, so that
+/// only local overrides are written to the settings file, and changes to the
+/// remote base configuration aren't masked by stale copies of its values.
+fn remove_inherited(settings: &mut toml::Value, base: &toml::Value) {
+    if let (toml::Value::Table(settings), toml::Value::Table(base)) = (settings, base) {
+        let inherited: Vec<String> = settings
+            .iter_mut()
+            .filter_map(|(key, value)| match base.get(key) {
+                Some(inherited @ toml::Value::Table(_)) => {
+                    remove_inherited(value, inherited);
+                    matches!(value, toml::Value::Table(table) if table.is_empty())
+                        .then(|| key.clone())
+                }
+                Some(inherited) if value == inherited => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+
+        for key in inherited {
+            settings.remove(&key);
+        }
     }
 }
 
 /// Opens the settings file, the serialises the global application settings into
 /// it.
-fn write_settings() {
+///
+/// The settings are written to a temporary file, which then replaces the
+/// settings file, so that the file is never left truncated if the program is
+/// terminated mid-write, e.g. when the user logs off.
+fn write_settings() -> io::Result<()> {
     let file_path = settings_file_path();
     let reader = SETTINGS.read().unwrap();
 
     if !file_path.parent().unwrap().exists() {
-        create_dir_all(file_path.parent().unwrap())
-            .expect("Unable to create parent directories for settings.toml");
+        create_dir_all(file_path.parent().unwrap())?;
     }
 
     if let Some(ref settings) = *reader {
+        let settings = &settings.own;
+        let temp_path = file_path.with_extension("toml.tmp");
+
         OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(file_path)
-            .expect("Unable to open settings.toml")
-            .write_all(
-                toml::to_string_pretty(&settings)
-                    .expect("Failed to serialise settings")
-                    .as_bytes(),
-            )
-            .expect("Unable to write to settings.toml");
+            .open(&temp_path)?
+            .write_all(serialise(settings).as_bytes())?;
+
+        fs::rename(temp_path, file_path)?;
     }
+
+    Ok(())
+}
+
+/// Serialises `settings` to TOML, leaving out the values inherited from the
+/// remote base configuration.
+fn serialise(settings: &Settings) -> String {
+    let mut value = toml::Value::try_from(settings).expect("Failed to serialise settings");
+
+    if let Some(base) = remote_config::cached(&settings.remote_config.url) {
+        remove_inherited(&mut value, &base);
+    }
+
+    toml::to_string_pretty(&value).expect("Failed to serialise settings")
 }
@@ -0,0 +1,134 @@
+//! Live reloading of `settings.toml`, so that changes made by hand take effect
+//! without restarting the program.
+//!
+//! The config directory is watched on a background thread. Whenever the
+//! settings file's contents change, the settings are reloaded (or kept, if the
+//! file can't be parsed, as it may be part way through being edited), then the
+//! things that are only set up at start-up, such as the hotkeys, are updated to
+//! match.
+
+use crate::auto_start;
+use crate::explorer;
+use crate::hotkey;
+use crate::save;
+use crate::settings::{self, Settings};
+use crate::windows::{send_notify_message, wait_for_directory_change, watch_directory};
+use bindings::Windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+use log::{error, info, warn};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait after the settings file changes before reloading it, as
+/// editors often save a file in several steps.
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// Starts watching the settings file for changes, on a background thread.
+/// `window` (the main window) is told to re-register its hotkeys when they
+/// change.
+pub fn start(window: HWND) {
+    thread::Builder::new()
+        .name("settings-watcher".into())
+        .spawn(move || {
+            if let Err(e) = run(window) {
+                error!("Stopped watching the settings file: {:#?}", e);
+            }
+        })
+        .expect("Failed to start settings watcher thread");
+}
+
+/// The watcher thread's main loop, which only returns if the config directory
+/// can't be watched.
+fn run(window: HWND) -> windows::Result<()> {
+    let file_path = settings::settings_file_path();
+    let watch = watch_directory(&settings::config_dir())?;
+
+    let mut contents = fs::read(&file_path).ok();
+
+    loop {
+        wait_for_directory_change(&watch)?;
+        thread::sleep(SETTLE_DELAY);
+
+        // Other files in the config directory, such as the history, change far more often
+        let new_contents = fs::read(&file_path).ok();
+
+        if new_contents == contents {
+            continue;
+        }
+
+        contents = new_contents;
+
+        let before = snapshot();
+        settings::reload();
+        let after = snapshot();
+
+        // Our own changes are written to the file too, but are already in effect
+        if before != after {
+            info!("Settings file changed - applying new settings");
+            apply(window, &before, &after);
+        }
+    }
+}
+
+/// Returns the current settings, as a TOML value, for comparison.
+fn snapshot() -> toml::Value {
+    let mut value = toml::Value::Boolean(false);
+    Settings::read(|s| {
+        if let Ok(settings) = toml::Value::try_from(s) {
+            value = settings;
+        }
+    });
+
+    value
+}
+
+/// Updates the things that are set up from the settings at start-up, for each
+/// setting that differs between `before` and `after`.
+fn apply(window: HWND, before: &toml::Value, after: &toml::Value) {
+    let changed = |path: &[&str]| {
+        let get = |value: &toml::Value| {
+            path.iter()
+                .try_fold(value.clone(), |value, key| value.get(key).cloned())
+        };
+
+        get(before) != get(after)
+    };
+
+    if changed(&["hotkey"]) {
+        if let Err(e) =
+            send_notify_message(window, hotkey::WMAPP_HOTKEYS_CHANGED, WPARAM(0), LPARAM(0))
+        {
+            error!("Failed to re-register hotkeys: {:#?}", e);
+        }
+    }
+
+    if changed(&["paths", "screenshots"]) {
+        save::verify_screenshot_dir();
+    }
+
+    if changed(&["program", "auto_start"]) || changed(&["program", "auto_start_method"]) {
+        let mut auto_start = false;
+        Settings::read(|s| auto_start = s.program.auto_start);
+
+        if auto_start {
+            auto_start::refresh();
+        } else {
+            auto_start::disable();
+        }
+    }
+
+    if changed(&["program", "folder_context_menu"]) {
+        let mut folder_context_menu = false;
+        Settings::read(|s| folder_context_menu = s.program.folder_context_menu);
+
+        let result = if folder_context_menu {
+            explorer::register_folder_verb()
+        } else {
+            explorer::unregister_folder_verb()
+        };
+
+        if let Err(e) = result {
+            warn!("Failed to update the folder context menu entry: {:#?}", e);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! The settings window, which edits the most commonly changed settings,
+//! without having to edit `settings.toml` by hand.
+//!
+//! The window is a native dialog box, whose layout is defined in
+//! `resources.rc`. Less common settings are still only available in
+//! `settings.toml`.
+
+use crate::hotkey;
+use crate::migration;
+use crate::modal::{self, Dialog};
+use crate::policy;
+use crate::settings::{OutputFormat, Settings};
+use crate::windows::{
+    check_dialog_button, dialog_box, enable_dialog_item, end_dialog, get_combo_box_selection,
+    get_dialog_item_text, is_dialog_button_checked, message_box, send_notify_message,
+    set_combo_box_items, set_dialog_item_text,
+};
+use bindings::Windows::Win32::{
+    Foundation::{HWND, LPARAM, WPARAM},
+    UI::WindowsAndMessaging::{IDCANCEL, IDOK, MB_ICONWARNING, MB_OK, WM_COMMAND, WM_INITDIALOG},
+};
+use log::{error, info};
+use rfd::FileDialog;
+use std::path::PathBuf;
+
+// Resource IDs, which must match those in `resources.rc`
+const IDD_SETTINGS: u16 = 300;
+const IDC_SETTINGS_SCREENSHOTS: i32 = 301;
+const IDC_SETTINGS_BROWSE: i32 = 302;
+const IDC_SETTINGS_FILENAME_TEMPLATE: i32 = 303;
+const IDC_SETTINGS_SUBDIRECTORY_FORMAT: i32 = 304;
+const IDC_SETTINGS_FORMAT: i32 = 305;
+const IDC_SETTINGS_JPEG_QUALITY: i32 = 306;
+const IDC_SETTINGS_CAPTURE_SOURCE: i32 = 307;
+const IDC_SETTINGS_HOTKEY_ENABLED: i32 = 308;
+const IDC_SETTINGS_HOTKEY: i32 = 309;
+const IDC_SETTINGS_PAIRING_HOTKEY: i32 = 310;
+const IDC_SETTINGS_PANIC_HOTKEY: i32 = 311;
+
+/// The image formats listed in the format combo box, in order.
+const FORMATS: [(OutputFormat, &str); 4] = [
+    (OutputFormat::Png, "PNG"),
+    (OutputFormat::Jpeg, "JPEG"),
+    (OutputFormat::Bmp, "BMP"),
+    (OutputFormat::Webp, "WebP"),
+];
+
+/// The entries of the capture source combo box, in order. The second entry
+/// corresponds to [`save_all_clipboard_images`].
+///
+/// [`save_all_clipboard_images`]: crate::settings::Program::save_all_clipboard_images
+const CAPTURE_SOURCES: [&str; 2] = ["Screenshot tools only", "Any app"];
+
+/// Shows the settings window, or brings it to the foreground, if it is already
+/// open. Once the changes are saved, `window` (the main window) is told to
+/// re-register its hotkeys.
+pub fn show(window: HWND) {
+    modal::open(Dialog::Settings, move || {
+        match dialog_box(IDD_SETTINGS, dialog_proc) {
+            Ok(result) if result == IDOK.0 as isize => {
+                if let Err(e) =
+                    send_notify_message(window, hotkey::WMAPP_HOTKEYS_CHANGED, WPARAM(0), LPARAM(0))
+                {
+                    error!("Failed to re-register hotkeys: {:#?}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => error!("Failed to show the settings window: {:#?}", e),
+        }
+    });
+}
+
+/// The settings dialog box procedure.
+unsafe extern "system" fn dialog_proc(
+    dialog: HWND,
+    message: u32,
+    w_param: WPARAM,
+    _l_param: LPARAM,
+) -> isize {
+    match message {
+        WM_INITDIALOG => {
+            load(dialog);
+            1
+        }
+        WM_COMMAND => match (w_param.0 & 0xFFFF) as i32 {
+            IDC_SETTINGS_BROWSE => {
+                browse(dialog);
+                1
+            }
+            id if id == IDOK.0 => {
+                if save(dialog) {
+                    end_dialog(dialog, IDOK.0 as isize);
+                }
+                1
+            }
+            id if id == IDCANCEL.0 => {
+                end_dialog(dialog, IDCANCEL.0 as isize);
+                1
+            }
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Fills in the dialog's controls from the current settings.
+fn load(dialog: HWND) {
+    Settings::read(|s| {
+        set_dialog_item_text(
+            dialog,
+            IDC_SETTINGS_SCREENSHOTS,
+            &s.paths.screenshots.to_string_lossy(),
+        );
+        set_dialog_item_text(
+            dialog,
+            IDC_SETTINGS_FILENAME_TEMPLATE,
+            &s.paths.filename_template,
+        );
+        set_dialog_item_text(
+            dialog,
+            IDC_SETTINGS_SUBDIRECTORY_FORMAT,
+            &s.paths.subdirectory_format,
+        );
+
+        let format = FORMATS
+            .iter()
+            .position(|(format, _)| *format == s.output.format)
+            .unwrap_or(0);
+        let formats: Vec<&str> = FORMATS.iter().map(|(_, name)| *name).collect();
+
+        set_combo_box_items(dialog, IDC_SETTINGS_FORMAT, &formats, format);
+        set_dialog_item_text(
+            dialog,
+            IDC_SETTINGS_JPEG_QUALITY,
+            &s.output.jpeg_quality.to_string(),
+        );
+
+        set_combo_box_items(
+            dialog,
+            IDC_SETTINGS_CAPTURE_SOURCE,
+            &CAPTURE_SOURCES,
+            s.program.save_all_clipboard_images as usize,
+        );
+
+        check_dialog_button(dialog, IDC_SETTINGS_HOTKEY_ENABLED, s.hotkey.enabled);
+        set_dialog_item_text(dialog, IDC_SETTINGS_HOTKEY, &s.hotkey.combination);
+        set_dialog_item_text(
+            dialog,
+            IDC_SETTINGS_PAIRING_HOTKEY,
+            &s.hotkey.pairing_combination,
+        );
+        set_dialog_item_text(
+            dialog,
+            IDC_SETTINGS_PANIC_HOTKEY,
+            &s.hotkey.panic_combination,
+        );
+    });
+
+    // The screenshot folder can't be changed if it is enforced
+    if policy::get().screenshot_dir.is_some() {
+        enable_dialog_item(dialog, IDC_SETTINGS_SCREENSHOTS, false);
+        enable_dialog_item(dialog, IDC_SETTINGS_BROWSE, false);
+    }
+}
+
+/// Asks the user to pick a new screenshot folder, and fills it in.
+fn browse(dialog: HWND) {
+    let current = PathBuf::from(get_dialog_item_text(dialog, IDC_SETTINGS_SCREENSHOTS));
+
+    if let Some(new_dir) = FileDialog::new().set_directory(current).pick_folder() {
+        set_dialog_item_text(dialog, IDC_SETTINGS_SCREENSHOTS, &new_dir.to_string_lossy());
+    }
+}
+
+/// Validates the dialog's controls, and saves them to the settings. Returns
+/// `false`, after telling the user what is wrong, if any of them are invalid.
+fn save(dialog: HWND) -> bool {
+    let screenshots = PathBuf::from(get_dialog_item_text(dialog, IDC_SETTINGS_SCREENSHOTS).trim());
+    let filename_template = get_dialog_item_text(dialog, IDC_SETTINGS_FILENAME_TEMPLATE);
+    let subdirectory_format = get_dialog_item_text(dialog, IDC_SETTINGS_SUBDIRECTORY_FORMAT);
+    let format = get_combo_box_selection(dialog, IDC_SETTINGS_FORMAT)
+        .and_then(|i| FORMATS.get(i))
+        .map(|(format, _)| *format);
+    let jpeg_quality = get_dialog_item_text(dialog, IDC_SETTINGS_JPEG_QUALITY);
+    let save_all_clipboard_images =
+        get_combo_box_selection(dialog, IDC_SETTINGS_CAPTURE_SOURCE) == Some(1);
+    let hotkey_enabled = is_dialog_button_checked(dialog, IDC_SETTINGS_HOTKEY_ENABLED);
+    let combination = get_dialog_item_text(dialog, IDC_SETTINGS_HOTKEY);
+    let pairing_combination = get_dialog_item_text(dialog, IDC_SETTINGS_PAIRING_HOTKEY);
+    let panic_combination = get_dialog_item_text(dialog, IDC_SETTINGS_PANIC_HOTKEY);
+
+    let jpeg_quality = match jpeg_quality.trim().parse::<u8>() {
+        Ok(quality @ 1..=100) => quality,
+        _ => return invalid("The JPEG quality must be a number from 1 to 100."),
+    };
+
+    if screenshots.as_os_str().is_empty() {
+        return invalid("A screenshot folder must be chosen.");
+    }
+
+    if filename_template.trim().is_empty() {
+        return invalid("The filename template can't be empty.");
+    }
+
+    if hotkey_enabled {
+        let combinations = [
+            (combination.trim(), true),
+            (pairing_combination.trim(), false),
+            (panic_combination.trim(), false),
+        ];
+
+        for (combination, required) in combinations.iter() {
+            if (*required || !combination.is_empty()) && !hotkey::is_valid_combination(combination)
+            {
+                return invalid(&format!(
+                    "\"{}\" is not a valid key combination. Combinations are written like \
+                     \"Ctrl+Shift+S\".",
+                    combination
+                ));
+            }
+        }
+    }
+
+    // Read as it applies, as that's what the dialog shows, if it's enforced by policy
+    let mut old_screenshots = PathBuf::new();
+    Settings::read(|s| old_screenshots = s.paths.screenshots.clone());
+
+    Settings::write(|s| {
+        s.paths.filename_template = filename_template;
+        s.paths.subdirectory_format = subdirectory_format.trim().into();
+
+        if let Some(format) = format {
+            s.output.format = format;
+        }
+
+        s.output.jpeg_quality = jpeg_quality;
+        s.program.save_all_clipboard_images = save_all_clipboard_images;
+
+        s.hotkey.enabled = hotkey_enabled;
+        s.hotkey.combination = combination.trim().into();
+        s.hotkey.pairing_combination = pairing_combination.trim().into();
+        s.hotkey.panic_combination = panic_combination.trim().into();
+    });
+
+    info!("Settings changed from the settings window");
+
+    if screenshots != old_screenshots {
+        migration::change_screenshot_dir(screenshots);
+    }
+
+    true
+}
+
+/// Tells the user that a setting is invalid. Always returns `false`.
+fn invalid(message: &str) -> bool {
+    message_box("Invalid setting", message, MB_OK | MB_ICONWARNING);
+
+    false
+}
@@ -0,0 +1,131 @@
+//! Bundling screenshots into a zip file for sharing, e.g. to attach a set of
+//! reproduction screenshots to a bug report.
+//!
+//! The screenshots are chosen in a file picker, which opens in the screenshot
+//! folder, then zipped into a temporary file named after the dates that they
+//! were taken on, which is put on the clipboard as a file, ready to be pasted
+//! into an email, chat, or issue tracker.
+
+use crate::migration::SCREENSHOT_EXTENSIONS;
+use crate::modal::{self, Dialog};
+use crate::notification_area::{self, BalloonLevel};
+use crate::settings::Settings;
+use crate::windows::{open_clipboard, set_clipboard_files};
+use crate::zip::{self, Entry, ZipError};
+use chrono::NaiveDate;
+use log::{info, warn};
+use rfd::FileDialog;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// The directory within the temporary directory that zip files are saved in.
+const SHARE_DIR: &str = "snip-and-autosave";
+
+/// Errors that can occur whilst zipping and sharing screenshots.
+#[derive(Error, Debug)]
+pub enum ShareError {
+    #[error("Unable to create the zip file: {0}")]
+    Io(#[from] io::Error),
+    #[error("Unable to create the zip file: {0}")]
+    Zip(#[from] ZipError),
+    #[error("Unable to copy the zip file to the clipboard: {0}")]
+    Clipboard(#[from] windows::Error),
+}
+
+/// Asks the user to choose some screenshots, then zips them, and copies the zip
+/// file to the clipboard, on a separate thread.
+pub fn show() {
+    modal::open(Dialog::Share, || {
+        let mut screenshot_dir = PathBuf::new();
+        Settings::read(|s| screenshot_dir = s.paths.screenshots.clone());
+
+        let screenshots = match FileDialog::new()
+            .set_directory(screenshot_dir)
+            .add_filter("Screenshots", SCREENSHOT_EXTENSIONS)
+            .pick_files()
+        {
+            Some(screenshots) if !screenshots.is_empty() => screenshots,
+            _ => return,
+        };
+
+        match share(&screenshots) {
+            Ok(zip) => {
+                let _ = notification_area::show_balloon(
+                    "Screenshots copied",
+                    &format!(
+                        "{} screenshots were zipped into {}, which is ready to paste",
+                        screenshots.len(),
+                        zip.file_name().unwrap_or_default().to_string_lossy()
+                    ),
+                    BalloonLevel::Info,
+                );
+            }
+            Err(e) => {
+                warn!("Failed to share screenshots: {}", e);
+
+                let _ = notification_area::show_balloon(
+                    "Screenshots not shared",
+                    &e.to_string(),
+                    BalloonLevel::Error,
+                );
+            }
+        }
+    });
+}
+
+/// Zips `screenshots` into a temporary file, and puts it on the clipboard,
+/// returning the zip file's path.
+pub fn share(screenshots: &[PathBuf]) -> Result<PathBuf, ShareError> {
+    let entries: Vec<Entry> = screenshots
+        .iter()
+        .map(|screenshot| {
+            Ok(Entry {
+                name: screenshot
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                data: fs::read(screenshot)?,
+                modified: fs::metadata(screenshot)?.modified()?.into(),
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    let dates = entries
+        .iter()
+        .map(|entry| entry.modified.date().naive_local());
+    let name = zip_name(dates.clone().min(), dates.max());
+
+    let share_dir = env::temp_dir().join(SHARE_DIR);
+    fs::create_dir_all(&share_dir)?;
+
+    let zip = share_dir.join(name);
+    zip::write(&zip, &entries)?;
+
+    info!(
+        "Zipped {} screenshots into {}",
+        screenshots.len(),
+        zip.display()
+    );
+
+    let clipboard = open_clipboard(None)?;
+    set_clipboard_files(&clipboard, &[&zip])?;
+
+    Ok(zip)
+}
+
+/// Returns the file name of a zip of screenshots taken from `first` to `last`.
+fn zip_name(first: Option<NaiveDate>, last: Option<NaiveDate>) -> String {
+    match (first, last) {
+        (Some(first), Some(last)) if first != last => format!(
+            "Screenshots_{}_to_{}.zip",
+            first.format("%Y-%m-%d"),
+            last.format("%Y-%m-%d")
+        ),
+        (Some(date), _) => format!("Screenshots_{}.zip", date.format("%Y-%m-%d")),
+        _ => "Screenshots.zip".into(),
+    }
+}
@@ -0,0 +1,266 @@
+//! Statistics about the screenshot directory.
+//!
+//! Shows how much space screenshots take up, both in total and per month, and
+//! lists the largest screenshots, which can then be recompressed or deleted.
+
+use crate::animation::Animation;
+use crate::history;
+use crate::migration::find_screenshots_recursive;
+use crate::modal::{self, Dialog};
+use crate::notification_area::{self, BalloonLevel};
+use crate::settings::Settings;
+use crate::windows::{copy_file_times, move_to_recycle_bin, task_dialog, TaskDialogIcon};
+use chrono::{DateTime, Local};
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ColorType, ImageError};
+use log::{info, warn};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// How many of the largest screenshots are listed.
+const LARGEST_COUNT: usize = 10;
+
+/// Errors that can occur whilst recompressing a screenshot.
+#[derive(Error, Debug)]
+pub enum RecompressError {
+    #[error("Unable to encode image: {0}")]
+    Image(#[from] ImageError),
+    #[error("Unable to preserve timestamps: {0}")]
+    Timestamps(#[from] windows::Error),
+    #[error("Unable to replace original: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Statistics about a directory of screenshots.
+pub struct Stats {
+    /// The directory that the statistics are about.
+    pub dir: PathBuf,
+
+    /// How many screenshots are in the directory.
+    pub count: usize,
+
+    /// The total size of the screenshots, in bytes.
+    pub total_size: u64,
+
+    /// The total size of the screenshots last modified in each month, keyed
+    /// by `YYYY-MM`.
+    pub per_month: BTreeMap<String, u64>,
+
+    /// The largest screenshots, and their sizes, largest first.
+    pub largest: Vec<(PathBuf, u64)>,
+}
+
+/// Collects statistics about the screenshots directly within `dir`.
+pub fn collect(dir: &Path) -> io::Result<Stats> {
+    let mut stats = Stats {
+        dir: dir.to_owned(),
+        count: 0,
+        total_size: 0,
+        per_month: BTreeMap::new(),
+        largest: Vec::new(),
+    };
+
+    for screenshot in find_screenshots_recursive(dir)? {
+        let metadata = fs::metadata(&screenshot)?;
+        let size = metadata.len();
+
+        let month = metadata
+            .modified()
+            .map(|modified| {
+                DateTime::<Local>::from(modified)
+                    .format("%Y-%m")
+                    .to_string()
+            })
+            .unwrap_or_else(|_| "Unknown".into());
+
+        stats.count += 1;
+        stats.total_size += size;
+        *stats.per_month.entry(month).or_default() += size;
+        stats.largest.push((screenshot, size));
+    }
+
+    stats.largest.sort_by_key(|&(_, size)| Reverse(size));
+    stats.largest.truncate(LARGEST_COUNT);
+
+    Ok(stats)
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} screenshots, {} in total",
+            self.count,
+            format_size(self.total_size)
+        )?;
+
+        writeln!(f, "\nBy month:")?;
+
+        for (month, size) in self.per_month.iter().rev() {
+            writeln!(f, "    {}  {}", month, format_size(*size))?;
+        }
+
+        writeln!(f, "\nLargest screenshots:")?;
+
+        for (path, size) in &self.largest {
+            writeln!(
+                f,
+                "    {}  {}",
+                format_size(*size),
+                path.file_name().unwrap_or_default().to_string_lossy()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a number of bytes for display, e.g. `1.5 MiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["bytes", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Shows the statistics of the screenshot directory in a dialog, on a separate
+/// thread, with actions to recompress or delete the largest screenshots.
+pub fn show() {
+    modal::open(Dialog::Statistics, || {
+        let mut screenshot_dir = PathBuf::new();
+        Settings::read(|s| screenshot_dir = s.paths.screenshots.clone());
+
+        let stats = match collect(&screenshot_dir) {
+            Ok(stats) => stats,
+            Err(e) => {
+                warn!("Failed to collect statistics: {:#?}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = show_dialog(&stats) {
+            warn!("Failed to show statistics: {:#?}", e);
+        }
+    });
+}
+
+/// Shows `stats` in a dialog, then performs the action that the user chose.
+fn show_dialog(stats: &Stats) -> windows::Result<()> {
+    let largest: Vec<PathBuf> = stats.largest.iter().map(|(path, _)| path.clone()).collect();
+
+    let recompress_text = format!("Recompress the {} largest PNGs", largest.len());
+    let delete_text = format!("Move the {} largest to the Recycle Bin", largest.len());
+    let actions = [recompress_text.as_str(), delete_text.as_str()];
+
+    let action = task_dialog(
+        "Snip & AutoSave",
+        TaskDialogIcon::Information,
+        "Screenshot statistics",
+        &format!("{}\n\n{}", stats.dir.display(), stats),
+        None,
+        if largest.is_empty() { &[] } else { &actions },
+    )?;
+
+    match action {
+        Some(0) => {
+            let saved = recompress(&largest);
+
+            let _ = notification_area::show_balloon(
+                "Screenshots recompressed",
+                &format!("Saved {}", format_size(saved)),
+                BalloonLevel::Info,
+            );
+        }
+        Some(1) => {
+            move_to_recycle_bin(&largest)?;
+
+            history::update(|entries| entries.retain(|entry| !largest.contains(&entry.path)));
+            info!("Moved {} screenshots to the Recycle Bin", largest.len());
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Losslessly recompresses each PNG in `paths` with maximum compression,
+/// keeping their timestamps. Animated PNGs are left alone, as only their first
+/// frame would be kept. Returns the number of bytes saved.
+pub fn recompress(paths: &[PathBuf]) -> u64 {
+    paths
+        .iter()
+        .filter(|path| match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) => extension.eq_ignore_ascii_case("png"),
+            None => false,
+        })
+        .map(|path| {
+            recompress_png(path).unwrap_or_else(|e| {
+                warn!("Failed to recompress {}: {}", path.display(), e);
+                0
+            })
+        })
+        .sum()
+}
+
+/// Recompresses a single PNG, replacing it only if the result is smaller.
+/// Returns the number of bytes saved.
+fn recompress_png(path: &Path) -> Result<u64, RecompressError> {
+    let original = fs::read(path)?;
+    let original_size = original.len() as u64;
+
+    if Animation::detect(original).is_some() {
+        info!("Not recompressing animated {}", path.display());
+        return Ok(0);
+    }
+    let image = image::open(path)?.to_rgba8();
+
+    let mut png = Vec::new();
+
+    PngEncoder::new_with_quality(&mut png, CompressionType::Best, FilterType::Paeth).encode(
+        &image,
+        image.width(),
+        image.height(),
+        ColorType::Rgba8,
+    )?;
+
+    if png.len() as u64 >= original_size {
+        return Ok(0);
+    }
+
+    // Write alongside the original first, so that it is never left half-written
+    let temp_path = path.with_extension("png.tmp");
+    fs::write(&temp_path, &png)?;
+
+    let replaced = (|| -> Result<(), RecompressError> {
+        let original = File::open(path)?;
+        let temp = fs::OpenOptions::new().write(true).open(&temp_path)?;
+
+        copy_file_times(&original, &temp)?;
+        drop((original, temp));
+
+        Ok(fs::rename(&temp_path, path)?)
+    })();
+
+    if let Err(e) = replaced {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    Ok(original_size - png.len() as u64)
+}
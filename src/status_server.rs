@@ -0,0 +1,92 @@
+//! Optional local HTTP status endpoint.
+//!
+//! When enabled, a minimal HTTP server listens on the loopback interface, and
+//! serves the program's state as JSON at `/status`, and in the Prometheus
+//! text exposition format at `/metrics`, so that it can be monitored with
+//! existing dashboards. Only `GET` requests are supported, and connections are
+//! handled one at a time, which is plenty for a scraper.
+
+use crate::metrics;
+use crate::settings::Settings;
+use log::{debug, info, warn};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/// How long a client has to send its request, before it is disconnected.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Starts the status server on a separate thread, if it is enabled.
+pub fn start() -> io::Result<()> {
+    let mut enabled = false;
+    let mut port = 0;
+
+    Settings::read(|s| {
+        enabled = s.status_server.enabled;
+        port = s.status_server.port;
+    });
+
+    if !enabled {
+        return Ok(());
+    }
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    info!("Status server listening on http://127.0.0.1:{}/", port);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Err(e) = stream.and_then(handle) {
+                debug!("Status server request failed: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Reads a single request from `stream`, and writes the response.
+fn handle(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(&stream);
+    reader.read_line(&mut request_line)?;
+
+    // The request headers aren't needed, but are read so that the client doesn't see a reset
+    let mut header = String::new();
+
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/status") => match serde_json::to_string_pretty(&metrics::status()) {
+            Ok(json) => ("200 OK", "application/json", json),
+            Err(e) => {
+                warn!("Failed to serialise status: {}", e);
+                ("500 Internal Server Error", "text/plain", e.to_string())
+            }
+        },
+        ("GET", "/metrics") => ("200 OK", "text/plain; version=0.0.4", metrics::prometheus()),
+        ("GET", _) => ("404 Not Found", "text/plain", "Not found".into()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed".into(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,307 @@
+//! Filename templates.
+//!
+//! Screenshot filenames are generated from a user-configurable template (see
+//! [`Paths::filename_template`]), in which placeholders, written as `{name}`
+//! or `{name:argument}`, are replaced with details of the capture:
+//!
+//! | Placeholder        | Replaced with                                               |
+//! |--------------------|-------------------------------------------------------------|
+//! | `{date:<format>}`  | The capture time, formatted with a `strftime` format string |
+//! | `{year}`           | The four digit year                                         |
+//! | `{month}`          | The two digit month                                         |
+//! | `{day}`            | The two digit day of the month                              |
+//! | `{hour}`           | The two digit hour (24 hour clock)                          |
+//! | `{minute}`         | The two digit minute                                        |
+//! | `{second}`         | The two digit second                                        |
+//! | `{isoweek}`        | The two digit ISO 8601 week number                          |
+//! | `{isoyear}`        | The year that the ISO 8601 week belongs to                  |
+//! | `{weekday}`        | The name of the day of the week, in the user's language     |
+//! | `{localdate}`      | The date in the user's locale's short date format           |
+//! | `{localdate:long}` | The date in the user's locale's long date format            |
+//! | `{localdate:<pic>}`| The date formatted with a Windows date picture, e.g. `MMMM` |
+//! | `{tz}`             | `Z` for UTC, otherwise the UTC offset, e.g. `+1000`         |
+//! | `{counter}`        | The lowest number (from 1) giving an unused filename        |
+//! | `{counter:<width>}`| As above, zero-padded to `width` digits                     |
+//! | `{resolution}`     | The resolution of the active monitor, e.g. `1920x1080`      |
+//! | `{scale}`          | The display scaling of the active monitor, e.g. `150`       |
+//! | `{window_title}`   | The title of the active window                              |
+//! | `{category}`       | The kind of content, i.e. `ui`, `code`, `photo`, `document` |
+//! | `{title}`          | The first line of text in the capture, if [`Ocr::enabled`]  |
+//!
+//! Times are in local time, or UTC if [`Paths::filename_time_zone`] says so.
+//! `{title}` is empty if no text was recognised, so is best combined with other
+//! placeholders, e.g. `{date}_{title}`.
+//! Unknown placeholders are left as they are. Characters that aren't allowed
+//! in filenames are replaced with underscores.
+//!
+//! As local times repeat when daylight saving time ends, a filename may already
+//! be in use even without a `{counter}` placeholder. In that case, `_2`, `_3`,
+//! etc. is appended, rather than overwriting the existing file.
+//!
+//! [`Paths::filename_template`]: crate::settings::Paths::filename_template
+//! [`Paths::filename_time_zone`]: crate::settings::Paths::filename_time_zone
+//! [`Ocr::enabled`]: crate::settings::Ocr::enabled
+
+use crate::classify::Category;
+use crate::monitors;
+use crate::settings::{FilenameTimeZone, Settings};
+use crate::windows::{get_date_format, get_foreground_window, get_window_text};
+use bindings::Windows::Win32::{
+    Foundation::SYSTEMTIME,
+    Globalization::{DATE_LONGDATE, DATE_SHORTDATE, ENUM_DATE_FORMATS_FLAGS},
+};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, FixedOffset, Local, Utc};
+use log::warn;
+use std::path::PathBuf;
+
+/// The highest `{counter}` value tried, before giving up on finding an unused
+/// filename.
+const MAX_COUNTER: u32 = 100_000;
+
+/// The details of the moment that a capture was taken, which are recorded as
+/// it is captured, rather than when it is saved.
+#[derive(Clone)]
+pub struct Moment {
+    /// When the capture was taken, in the time zone that filenames use.
+    pub time: DateTime<FixedOffset>,
+
+    /// The resolution of the monitor that the active window was on.
+    pub resolution: (i32, i32),
+
+    /// The display scaling (as a percentage) of the monitor that the active
+    /// window was on.
+    pub scale: u32,
+
+    /// The title of the window that was active.
+    pub window_title: String,
+}
+
+impl Moment {
+    /// Records the details of the current moment.
+    pub fn now() -> Self {
+        let window = get_foreground_window();
+        let monitor = monitors::for_window(window);
+
+        let mut time_zone = FilenameTimeZone::Local;
+        Settings::read(|s| time_zone = s.paths.filename_time_zone);
+
+        Self {
+            time: match time_zone {
+                FilenameTimeZone::Local => Local::now().into(),
+                FilenameTimeZone::Utc => Utc::now().into(),
+            },
+            resolution: monitor.map_or((0, 0), |monitor| monitor.resolution()),
+            scale: monitor.map_or(100, |monitor| monitor.dpi * 100 / 96),
+            window_title: get_window_text(window),
+        }
+    }
+}
+
+/// Details of a capture, which are substituted into filename templates.
+pub struct Context {
+    /// When the capture was taken, in the time zone that filenames use.
+    pub time: DateTime<FixedOffset>,
+
+    /// The resolution of the monitor that the active window was on.
+    pub resolution: (i32, i32),
+
+    /// The display scaling (as a percentage) of the monitor that the active
+    /// window was on.
+    pub scale: u32,
+
+    /// The title of the window that was active.
+    pub window_title: String,
+
+    /// The kind of content that the capture shows.
+    pub category: Category,
+
+    /// The first line of text recognised in the capture, if any.
+    pub title: String,
+}
+
+impl Context {
+    /// Creates the details of a capture showing `category` content, which was
+    /// taken at `moment`.
+    pub fn capture(moment: &Moment, category: Category) -> Self {
+        Self {
+            time: moment.time,
+            resolution: moment.resolution,
+            scale: moment.scale,
+            window_title: moment.window_title.clone(),
+            category,
+            title: String::new(),
+        }
+    }
+}
+
+/// Expands `template` into a filename (excluding its extension).
+///
+/// If the template contains a `{counter}` placeholder, `is_used` is called
+/// with candidate filenames, from a counter value of 1 upwards, until it
+/// returns `false`. Otherwise, if the expanded filename is already used, a
+/// numeric suffix is appended in the same way.
+pub fn expand(template: &str, context: &Context, is_used: impl Fn(&str) -> bool) -> String {
+    let has_counter = template.contains("{counter");
+    let mut counter = 1;
+
+    loop {
+        let mut filename = sanitise(&expand_with_counter(template, context, counter));
+
+        if !has_counter && counter > 1 {
+            filename = format!("{}_{}", filename, counter);
+        }
+
+        if !is_used(&filename) || counter >= MAX_COUNTER {
+            return filename;
+        }
+
+        counter += 1;
+    }
+}
+
+/// Formats the capture time with the `strftime` format string `format`, to
+/// give a (possibly nested) subdirectory, e.g. `2024/05`. Each component of
+/// the subdirectory is sanitised in the same way as filenames.
+///
+/// Returns `None` if `format` is invalid.
+pub fn expand_directory(format: &str, context: &Context) -> Option<PathBuf> {
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        warn!("Invalid subdirectory format: {}", format);
+        return None;
+    }
+
+    Some(
+        context
+            .time
+            .format(format)
+            .to_string()
+            .split(&['/', '\\'][..])
+            .map(sanitise)
+            .filter(|component| !component.is_empty())
+            .collect(),
+    )
+}
+
+/// Expands every placeholder in `template`, using `counter` as the value of
+/// `{counter}` placeholders.
+fn expand_with_counter(template: &str, context: &Context, counter: u32) -> String {
+    let mut expanded = String::with_capacity(template.len());
+    let mut remaining = template;
+
+    while let Some(start) = remaining.find('{') {
+        expanded += &remaining[..start];
+
+        let end = match remaining[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        let placeholder = &remaining[start + 1..end];
+
+        let (name, argument) = match placeholder.find(':') {
+            Some(colon) => (&placeholder[..colon], Some(&placeholder[colon + 1..])),
+            None => (placeholder, None),
+        };
+
+        match expand_placeholder(name, argument, context, counter) {
+            Some(value) => expanded += &value,
+            None => expanded += &remaining[start..=end],
+        }
+
+        remaining = &remaining[end + 1..];
+    }
+
+    expanded += remaining;
+    expanded
+}
+
+/// Returns the value of a single placeholder, or `None` if it isn't valid.
+fn expand_placeholder(
+    name: &str,
+    argument: Option<&str>,
+    context: &Context,
+    counter: u32,
+) -> Option<String> {
+    let time = &context.time;
+
+    let value = match name {
+        "date" => {
+            let format = argument.unwrap_or("%Y%m%d_%H%M%S");
+
+            // Formatting with an invalid format string panics, so we check it first
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                warn!("Invalid date format in filename template: {}", format);
+                return None;
+            }
+
+            time.format(format).to_string()
+        }
+        "year" => time.format("%Y").to_string(),
+        "month" => time.format("%m").to_string(),
+        "day" => time.format("%d").to_string(),
+        "hour" => time.format("%H").to_string(),
+        "minute" => time.format("%M").to_string(),
+        "second" => time.format("%S").to_string(),
+        "isoweek" => time.format("%V").to_string(),
+        "isoyear" => time.format("%G").to_string(),
+        "weekday" => format_local_date(time, ENUM_DATE_FORMATS_FLAGS(0), Some("dddd"))
+            .unwrap_or_else(|| time.format("%A").to_string()),
+        "localdate" => match argument {
+            None | Some("short") => format_local_date(time, DATE_SHORTDATE, None)?,
+            Some("long") => format_local_date(time, DATE_LONGDATE, None)?,
+            Some(picture) => format_local_date(time, ENUM_DATE_FORMATS_FLAGS(0), Some(picture))?,
+        },
+        "tz" if time.offset().local_minus_utc() == 0 => "Z".into(),
+        "tz" => time.format("%z").to_string(),
+        "counter" => {
+            let width = argument.and_then(|width| width.parse().ok()).unwrap_or(0);
+
+            format!("{:0width$}", counter, width = width)
+        }
+        "resolution" => format!("{}x{}", context.resolution.0, context.resolution.1),
+        "scale" => context.scale.to_string(),
+        "window_title" => context.window_title.clone(),
+        "category" => context.category.label().into(),
+        "title" => context.title.clone(),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+/// Formats the date of `time` in the user's locale. See [`get_date_format`].
+///
+/// [`get_date_format`]: get_date_format
+fn format_local_date(
+    time: &DateTime<FixedOffset>,
+    flags: ENUM_DATE_FORMATS_FLAGS,
+    picture: Option<&str>,
+) -> Option<String> {
+    let date = SYSTEMTIME {
+        wYear: time.year() as u16,
+        wMonth: time.month() as u16,
+        wDayOfWeek: time.weekday().num_days_from_sunday() as u16,
+        wDay: time.day() as u16,
+        ..Default::default()
+    };
+
+    get_date_format(&date, flags, picture)
+        .map_err(|e| warn!("Failed to format local date: {:#?}", e))
+        .ok()
+}
+
+/// Replaces characters that aren't allowed in filenames with underscores.
+fn sanitise(filename: &str) -> String {
+    let filename: String = filename
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    // Windows silently strips trailing dots and spaces from filenames
+    filename.trim_end_matches(&['.', ' '][..]).to_owned()
+}
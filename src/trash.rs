@@ -0,0 +1,281 @@
+//! Delayed deletion of screenshots.
+//!
+//! Rather than deleting screenshots immediately, they are moved into a `.trash`
+//! subdirectory of the screenshot directory, from which they can be restored,
+//! until they are purged, once they have been in the trash for longer than
+//! [`Trash::retention_days`]. The original location of each trashed screenshot
+//! is kept in a manifest in the config directory.
+//!
+//! [`Trash::retention_days`]: crate::settings::Trash::retention_days
+
+use crate::history;
+use crate::modal::{self, Dialog};
+use crate::notification_area::{self, BalloonLevel};
+use crate::settings::{self, Settings};
+use crate::windows::{open_folder_and_select, task_dialog, TaskDialogIcon};
+use chrono::{DateTime, Duration, Local, Utc};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The subdirectory of the screenshot directory that trashed screenshots are
+/// moved into.
+pub const TRASH_DIR: &str = ".trash";
+
+/// The name of the file within the config directory that lists the trashed
+/// screenshots.
+const MANIFEST_FILE: &str = "trash.json";
+
+/// The number of trashed screenshots listed in the trash dialog.
+const LISTED_FILE_COUNT: usize = 10;
+
+/// A screenshot in the trash.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrashedFile {
+    /// Where the screenshot was before it was trashed.
+    pub original: PathBuf,
+
+    /// Where the screenshot is in the trash.
+    pub trashed: PathBuf,
+
+    /// When the screenshot was trashed.
+    pub trashed_at: DateTime<Utc>,
+}
+
+lazy_static! {
+    /// Serialises access to the manifest.
+    static ref MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Moves `path` into the trash.
+pub fn discard(path: &Path) -> io::Result<()> {
+    let mut screenshot_dir = PathBuf::new();
+    Settings::read(|s| screenshot_dir = s.paths.screenshots.clone());
+
+    let trash_dir = screenshot_dir.join(TRASH_DIR);
+    fs::create_dir_all(&trash_dir)?;
+
+    let now = Utc::now();
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    // Screenshots with the same name (e.g. from different dated subdirectories) can be trashed
+    let trashed = trash_dir.join(format!("{}_{}", now.format("%Y%m%d%H%M%S%3f"), file_name));
+
+    move_file(path, &trashed)?;
+
+    update_manifest(|files| {
+        files.push(TrashedFile {
+            original: path.to_owned(),
+            trashed,
+            trashed_at: now,
+        })
+    })?;
+
+    info!("Moved {} to the trash", path.display());
+
+    Ok(())
+}
+
+/// Returns every screenshot in the trash, oldest first.
+pub fn list() -> Vec<TrashedFile> {
+    let _lock = MANIFEST_LOCK.lock().unwrap();
+
+    read_manifest()
+}
+
+/// Moves every screenshot in the trash back to where it was, returning the
+/// number of screenshots restored. Screenshots that can't be restored (e.g.
+/// because another file has since been saved in their place) stay in the
+/// trash.
+pub fn restore_all() -> io::Result<usize> {
+    let mut restored = 0;
+
+    update_manifest(|files| {
+        files.retain(|file| {
+            if file.original.exists() {
+                warn!(
+                    "Not restoring {}, as it already exists",
+                    file.original.display()
+                );
+                return true;
+            }
+
+            let result = file
+                .original
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| move_file(&file.trashed, &file.original));
+
+            match result {
+                Ok(()) => {
+                    restored += 1;
+                    false
+                }
+                Err(e) => {
+                    warn!("Failed to restore {}: {}", file.original.display(), e);
+                    true
+                }
+            }
+        })
+    })?;
+
+    info!("Restored {} screenshots from the trash", restored);
+
+    Ok(restored)
+}
+
+/// Permanently deletes every screenshot that has been in the trash for longer
+/// than the configured retention period, and removes them from the history.
+pub fn purge_expired() -> io::Result<()> {
+    let mut retention_days = 0;
+    Settings::read(|s| retention_days = s.trash.retention_days);
+
+    let cutoff = Utc::now() - Duration::days(retention_days as i64);
+    let mut purged = Vec::new();
+
+    update_manifest(|files| {
+        files.retain(|file| {
+            if file.trashed_at > cutoff {
+                return true;
+            }
+
+            match fs::remove_file(&file.trashed) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    warn!("Failed to purge {}: {}", file.trashed.display(), e);
+                    true
+                }
+                _ => {
+                    purged.push(file.original.clone());
+                    false
+                }
+            }
+        })
+    })?;
+
+    if !purged.is_empty() {
+        history::update(|entries| entries.retain(|entry| !purged.contains(&entry.path)));
+        info!("Purged {} screenshots from the trash", purged.len());
+    }
+
+    Ok(())
+}
+
+/// Shows the screenshots in the trash in a dialog, on a separate thread, with
+/// actions to restore them, or to open the trash folder.
+pub fn show() {
+    modal::open(Dialog::Trash, || {
+        if let Err(e) = show_dialog() {
+            warn!("Failed to show the trash: {:#?}", e);
+        }
+    });
+}
+
+/// Shows the trash dialog, then performs the action that the user chose.
+fn show_dialog() -> windows::Result<()> {
+    let files = list();
+
+    let content = if files.is_empty() {
+        "The trash is empty.".to_owned()
+    } else {
+        let mut retention_days = 0;
+        Settings::read(|s| retention_days = s.trash.retention_days);
+
+        let newest: Vec<String> = files
+            .iter()
+            .rev()
+            .take(LISTED_FILE_COUNT)
+            .map(|file| {
+                format!(
+                    "{} (deleted {})",
+                    file.original.display(),
+                    file.trashed_at
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                )
+            })
+            .collect();
+
+        format!(
+            "Deleted screenshots are kept for {} days.\n\n{}{}",
+            retention_days,
+            newest.join("\n"),
+            if files.len() > LISTED_FILE_COUNT {
+                format!("\n...and {} more", files.len() - LISTED_FILE_COUNT)
+            } else {
+                String::new()
+            }
+        )
+    };
+
+    let restore_text = format!("Restore all {} screenshots", files.len());
+    let actions = [restore_text.as_str(), "Open the trash folder"];
+
+    let action = task_dialog(
+        "Snip & AutoSave",
+        TaskDialogIcon::Information,
+        "Deleted screenshots",
+        &content,
+        None,
+        if files.is_empty() { &[] } else { &actions },
+    )?;
+
+    match action {
+        Some(0) => match restore_all() {
+            Ok(restored) => {
+                let _ = notification_area::show_balloon(
+                    "Screenshots restored",
+                    &format!("{} of {} screenshots were restored", restored, files.len()),
+                    BalloonLevel::Info,
+                );
+            }
+            Err(e) => warn!("Failed to restore screenshots: {}", e),
+        },
+        Some(1) => {
+            if let Some(file) = files.last() {
+                open_folder_and_select(&file.trashed)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Moves `from` to `to`, copying it, if it can't be renamed (e.g. because the
+/// trash is on another volume).
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the fully qualified path of the manifest file.
+fn manifest_path() -> PathBuf {
+    settings::config_dir().join(MANIFEST_FILE)
+}
+
+/// Reads the manifest, which is empty if it doesn't exist, or can't be parsed.
+fn read_manifest() -> Vec<TrashedFile> {
+    fs::read_to_string(manifest_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Calls `f` with the trashed screenshots, then rewrites the manifest.
+fn update_manifest(f: impl FnOnce(&mut Vec<TrashedFile>)) -> io::Result<()> {
+    let _lock = MANIFEST_LOCK.lock().unwrap();
+
+    let mut files = read_manifest();
+    f(&mut files);
+
+    let contents = serde_json::to_string_pretty(&files)?;
+    fs::write(manifest_path(), contents)
+}
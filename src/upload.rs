@@ -0,0 +1,203 @@
+//! Uploading of saved screenshots to an image host.
+//!
+//! When enabled, each saved screenshot is uploaded to Imgur (or another
+//! endpoint that accepts `multipart/form-data` uploads, and responds with
+//! JSON), and the resulting link is copied to the clipboard. Uploads can be
+//! prohibited by Group Policy, and incognito captures are never uploaded.
+
+use crate::encryption;
+use crate::events::{self, Event};
+use crate::notification_area::{self, BalloonLevel};
+use crate::policy;
+use crate::settings::{NotificationVerbosity, Settings, Upload};
+use crate::windows::{http_request, open_clipboard, set_clipboard_text};
+use log::{info, warn};
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread;
+use thiserror::Error;
+
+/// The boundary between the parts of the request body.
+const BOUNDARY: &str = "----snip-and-autosave-upload-boundary";
+
+/// Errors that can occur whilst uploading a screenshot.
+#[derive(Error, Debug)]
+pub enum UploadError {
+    #[error("Unable to read the screenshot: {0}")]
+    Read(#[from] io::Error),
+    #[error("Unable to send the screenshot: {0}")]
+    Request(#[from] windows::Error),
+    #[error("The server responded with status {0}")]
+    Status(u32),
+    #[error("The server's response didn't contain a link")]
+    MissingLink,
+}
+
+/// Uploads each saved screenshot, on a background thread, if uploading is
+/// enabled.
+pub fn subscribe_to_events() {
+    let events = events::subscribe();
+
+    thread::spawn(move || {
+        for event in events {
+            // Incognito captures are never uploaded, even if incognito mode has
+            // expired by the time they're saved
+            if let Event::Saved {
+                path,
+                incognito: false,
+                ..
+            } = event
+            {
+                on_saved(&path);
+            }
+        }
+    });
+}
+
+/// Uploads the screenshot saved at `path`, if it should be uploaded, then
+/// copies its link to the clipboard.
+fn on_saved(path: &Path) {
+    let mut upload = Upload::default();
+    let mut verbosity = NotificationVerbosity::EverySave;
+
+    Settings::read(|s| {
+        upload = s.upload.clone();
+        verbosity = s.notifications.verbosity;
+    });
+
+    if !upload.enabled {
+        return;
+    }
+
+    if policy::get().disable_uploads {
+        info!(
+            "Not uploading {}, as uploads are disabled by policy",
+            path.display()
+        );
+        return;
+    }
+
+    // Encrypted screenshots are only saved inside the archive
+    if path.file_name() == Some(OsStr::new(encryption::ARCHIVE_FILE)) {
+        return;
+    }
+
+    match send(path, &upload) {
+        Ok(link) => {
+            info!("Uploaded {} to {}", path.display(), link);
+
+            if let Err(e) =
+                open_clipboard(None).and_then(|clipboard| set_clipboard_text(&clipboard, &link))
+            {
+                warn!("Failed to copy upload link to clipboard: {:#?}", e);
+            }
+
+            if verbosity >= NotificationVerbosity::EverySave {
+                let _ = notification_area::show_balloon(
+                    "Screenshot uploaded",
+                    &format!("{} was copied to the clipboard", link),
+                    BalloonLevel::Info,
+                );
+            }
+        }
+        Err(e) => {
+            warn!("Failed to upload {}: {}", path.display(), e);
+
+            if verbosity >= NotificationVerbosity::ErrorsOnly {
+                let _ = notification_area::show_balloon(
+                    "Screenshot not uploaded",
+                    &e.to_string(),
+                    BalloonLevel::Warning,
+                );
+            }
+        }
+    }
+}
+
+/// Uploads the file at `path` to the configured endpoint, returning the link
+/// to the uploaded image.
+fn send(path: &Path, upload: &Upload) -> Result<String, UploadError> {
+    let (mut headers, body) = form_data(&[], &upload.field_name, path)?;
+
+    if !upload.client_id.is_empty() {
+        headers += &format!("\r\nAuthorization: Client-ID {}", upload.client_id);
+    }
+
+    let response = http_request("POST", &upload.endpoint, &headers, &body)?;
+
+    if !(200..300).contains(&response.status) {
+        return Err(UploadError::Status(response.status));
+    }
+
+    serde_json::from_slice::<serde_json::Value>(&response.body)
+        .ok()
+        .and_then(|response| {
+            response
+                .pointer(&upload.link_pointer)
+                .and_then(|link| link.as_str())
+                .map(str::to_owned)
+        })
+        .ok_or(UploadError::MissingLink)
+}
+
+/// Builds a `multipart/form-data` request body, containing the text `fields`
+/// (as name/value pairs), followed by the file at `path`, as `file_field`.
+///
+/// Returns the request's `Content-Type` header, and its body.
+pub fn form_data(
+    fields: &[(&str, &str)],
+    file_field: &str,
+    path: &Path,
+) -> io::Result<(String, Vec<u8>)> {
+    let mut body = Vec::new();
+
+    for (name, value) in fields {
+        body.extend(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                BOUNDARY, name, value
+            )
+            .into_bytes(),
+        );
+    }
+
+    body.extend(
+        format!(
+            "--{}\r\n\
+             Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+             Content-Type: {}\r\n\r\n",
+            BOUNDARY,
+            file_field,
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            content_type(path)
+        )
+        .into_bytes(),
+    );
+
+    body.extend(fs::read(path)?);
+    body.extend(format!("\r\n--{}--\r\n", BOUNDARY).into_bytes());
+
+    Ok((
+        format!("Content-Type: multipart/form-data; boundary={}", BOUNDARY),
+        body,
+    ))
+}
+
+/// Returns the MIME type of the screenshot at `path`, from its extension.
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "webp" => "image/webp",
+        _ => "image/png",
+    }
+}
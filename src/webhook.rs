@@ -0,0 +1,150 @@
+//! Posting of saved screenshots to a user-configured webhook.
+//!
+//! When enabled, each saved screenshot is sent to the configured URL, along
+//! with its metadata, either as a `multipart/form-data` request (with a
+//! `metadata` JSON field, and a `file` field), or as a single JSON object,
+//! with the image base64-encoded in its `image` field. Like uploads, webhooks
+//! can be prohibited by Group Policy, and incognito captures are never sent.
+
+use crate::encryption;
+use crate::events::{self, Event};
+use crate::policy;
+use crate::settings::{Settings, Webhook, WebhookFormat};
+use crate::upload;
+use crate::windows::{base64_encode, http_request};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use thiserror::Error;
+
+/// Errors that can occur whilst posting a screenshot to the webhook.
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Unable to read the screenshot: {0}")]
+    Read(#[from] io::Error),
+    #[error("Unable to read the screenshot's dimensions: {0}")]
+    Dimensions(#[from] image::ImageError),
+    #[error("Unable to send the screenshot: {0}")]
+    Request(#[from] windows::Error),
+    #[error("The webhook responded with status {0}")]
+    Status(u32),
+}
+
+/// The details of a saved screenshot, which are sent with it.
+#[derive(Serialize)]
+struct Metadata {
+    timestamp: DateTime<Utc>,
+    path: PathBuf,
+    filename: String,
+    width: u32,
+    height: u32,
+    content_type: &'static str,
+
+    /// The base64-encoded screenshot, for JSON requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+}
+
+/// Posts each saved screenshot to the webhook, on a background thread, if the
+/// webhook is enabled.
+pub fn subscribe_to_events() {
+    let events = events::subscribe();
+
+    thread::spawn(move || {
+        for event in events {
+            // Incognito captures are never sent, even if incognito mode has
+            // expired by the time they're saved
+            if let Event::Saved {
+                path,
+                incognito: false,
+                ..
+            } = event
+            {
+                on_saved(&path);
+            }
+        }
+    });
+}
+
+/// Posts the screenshot saved at `path` to the webhook, if it should be sent.
+fn on_saved(path: &Path) {
+    let mut webhook = Webhook::default();
+    Settings::read(|s| webhook = s.webhook.clone());
+
+    if !webhook.enabled || webhook.url.is_empty() {
+        return;
+    }
+
+    if policy::get().disable_uploads {
+        info!(
+            "Not posting {} to the webhook, as uploads are disabled by policy",
+            path.display()
+        );
+        return;
+    }
+
+    // Encrypted screenshots are only saved inside the archive
+    if path.file_name() == Some(OsStr::new(encryption::ARCHIVE_FILE)) {
+        return;
+    }
+
+    match send(path, &webhook) {
+        Ok(()) => info!("Posted {} to the webhook", path.display()),
+        Err(e) => warn!("Failed to post {} to the webhook: {}", path.display(), e),
+    }
+}
+
+/// Sends the screenshot at `path` to the webhook, in the configured format.
+fn send(path: &Path, webhook: &Webhook) -> Result<(), WebhookError> {
+    let (width, height) = image::image_dimensions(path)?;
+
+    let mut metadata = Metadata {
+        timestamp: Utc::now(),
+        path: path.to_owned(),
+        filename: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into(),
+        width,
+        height,
+        content_type: upload::content_type(path),
+        image: None,
+    };
+
+    let (content_type, body) = match webhook.format {
+        WebhookFormat::Multipart => {
+            let metadata =
+                serde_json::to_string(&metadata).expect("Failed to serialise webhook metadata");
+
+            upload::form_data(&[("metadata", &metadata)], "file", path)?
+        }
+        WebhookFormat::Json => {
+            metadata.image = Some(base64_encode(&fs::read(path)?)?);
+
+            (
+                "Content-Type: application/json".to_owned(),
+                serde_json::to_vec(&metadata).expect("Failed to serialise webhook metadata"),
+            )
+        }
+    };
+
+    let mut headers = content_type;
+
+    for (name, value) in &webhook.headers {
+        headers += &format!("\r\n{}: {}", name, value);
+    }
+
+    let response = http_request("POST", &webhook.url, &headers, &body)?;
+
+    if (200..300).contains(&response.status) {
+        Ok(())
+    } else {
+        Err(WebhookError::Status(response.status))
+    }
+}
@@ -0,0 +1,141 @@
+//! The "what's new" summary, which is shown once after the program is updated,
+//! so that users of silently updated installs find out about new features and
+//! settings.
+//!
+//! The changelog is embedded in the program, rather than fetched, so that it
+//! always matches the running version.
+
+use crate::modal::{self, Dialog};
+use crate::settings::Settings;
+use crate::windows::{task_dialog, TaskDialogIcon};
+use log::{info, warn};
+
+/// The title of the "what's new" dialog.
+const DIALOG_TITLE: &str = "What's New in Snip & AutoSave";
+
+/// The version of the running program.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The notable changes in a release.
+struct Release {
+    /// The release's version number.
+    version: &'static str,
+
+    /// A one-line summary of each notable change.
+    changes: &'static [&'static str],
+}
+
+/// Every release with notable changes, newest first.
+const RELEASES: &[Release] = &[Release {
+    version: "1.0.0",
+    changes: &[
+        "Screenshots can be saved as JPEG or BMP, set with format in [output].",
+        "Filenames can be customised with a template, set with filename_template in [paths].",
+        "Old screenshots can be cleaned up automatically, configured in [retention].",
+        "Deleted screenshots are kept in a trash for a while, and can be restored.",
+        "Screenshots can be uploaded to an image host, or posted to a webhook.",
+        "The program, window and web page that each screenshot was taken from can be \
+         recorded, by enabling [context].",
+        "Settings can be shared between machines with a signed remote base configuration, \
+         set up in [remote_config].",
+        "\"Test Detection...\" explains why a snip was or wasn't detected, and suggests a fix.",
+        "Auto-start now uses the Run registry key, which survives profile clean-up tools.",
+    ],
+}];
+
+/// Shows the changes made since the previously run version, if the program has
+/// been updated since it was last run, then records the running version.
+///
+/// Nothing is shown on first run, as every feature is new.
+pub fn show_after_update() {
+    let mut last_run_version = String::new();
+    let mut first_run = false;
+
+    Settings::write(|s| {
+        last_run_version = s.program.last_run_version.clone();
+        // Versions before this one didn't record themselves, but did record
+        // whether the import had been offered
+        first_run = last_run_version.is_empty() && !s.program.import_offered;
+        s.program.last_run_version = VERSION.into();
+    });
+
+    if first_run || last_run_version == VERSION {
+        return;
+    }
+
+    let releases: Vec<&Release> = RELEASES
+        .iter()
+        .filter(|release| {
+            last_run_version.is_empty()
+                || parse_version(release.version) > parse_version(&last_run_version)
+        })
+        .collect();
+
+    if releases.is_empty() {
+        return;
+    }
+
+    info!(
+        "Updated from {} to {} - showing what's new",
+        if last_run_version.is_empty() {
+            "an earlier version"
+        } else {
+            &last_run_version
+        },
+        VERSION
+    );
+
+    modal::open(Dialog::WhatsNew, move || {
+        show_releases(
+            &format!("Snip & AutoSave has been updated to version {}", VERSION),
+            &releases,
+        )
+    });
+}
+
+/// Shows the changes in every release.
+pub fn show() {
+    modal::open(Dialog::WhatsNew, || {
+        show_releases(
+            &format!("Snip & AutoSave version {}", VERSION),
+            &RELEASES.iter().collect::<Vec<_>>(),
+        )
+    });
+}
+
+/// Shows the changes in `releases`, under `instruction`.
+fn show_releases(instruction: &str, releases: &[&Release]) {
+    let mut content = String::new();
+
+    for release in releases {
+        if !content.is_empty() {
+            content.push('\n');
+        }
+
+        content += &format!("Version {}\n", release.version);
+
+        for change in release.changes {
+            content += &format!("\u{2022} {}\n", change);
+        }
+    }
+
+    if let Err(e) = task_dialog(
+        DIALOG_TITLE,
+        TaskDialogIcon::Information,
+        instruction,
+        content.trim_end(),
+        None,
+        &[],
+    ) {
+        warn!("Failed to show what's new: {:#?}", e);
+    }
+}
+
+/// Returns the numeric components of `version`, for comparison. Components
+/// that aren't numbers (e.g. pre-release suffixes) are ignored.
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split('.')
+        .map_while(|component| component.parse().ok())
+        .collect()
+}
@@ -7,40 +7,117 @@
 //!
 //! [`windows::Result`]: windows::Result
 
-use crate::extensions::CStringExtensions;
+use crate::extensions::U16CStringExtensions;
 use bindings::Windows::Win32::{
-    Foundation::{CloseHandle, BOOL, HANDLE, HINSTANCE, HWND, LPARAM, PSTR, PWSTR, WPARAM},
-    Graphics::Gdi::BITMAPINFO,
+    Foundation::{
+        CloseHandle, BOOL, FILETIME, HANDLE, HINSTANCE, HWND, LPARAM, POINT, PSTR, PWSTR, RECT,
+        SYSTEMTIME, WPARAM,
+    },
+    Globalization::{GetDateFormatEx, ENUM_DATE_FORMATS_FLAGS},
+    Graphics::Gdi::{
+        BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject,
+        EnumDisplayMonitors, GetDC, GetDIBits, GetMonitorInfoW, GetSysColorBrush, MonitorFromRect,
+        MonitorFromWindow, ReleaseDC, SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB,
+        DIB_RGB_COLORS, HDC, HMONITOR, MONITORINFO, MONITOR_DEFAULTTONEAREST, SRCCOPY,
+    },
+    Networking::{
+        WinHttp::{
+            WinHttpCloseHandle, WinHttpConnect, WinHttpCrackUrl, WinHttpOpen, WinHttpOpenRequest,
+            WinHttpQueryHeaders, WinHttpReadData, WinHttpReceiveResponse, WinHttpSendRequest,
+            INTERNET_PORT, URL_COMPONENTS, WINHTTP_ACCESS_TYPE_DEFAULT_PROXY, WINHTTP_FLAG_SECURE,
+            WINHTTP_OPEN_REQUEST_FLAGS, WINHTTP_QUERY_FLAG_NUMBER, WINHTTP_QUERY_STATUS_CODE,
+        },
+        WinInet::INTERNET_SCHEME_HTTPS,
+    },
+    Security::{
+        CryptProtectData, CryptUnprotectData,
+        Cryptography::Core::{
+            BCryptDestroyKey, BCryptHash, BCryptImportKeyPair, BCryptVerifySignature,
+            CryptBinaryToStringA, CryptStringToBinaryW, BCRYPT_ECCKEY_BLOB,
+            BCRYPT_ECDSA_PUBLIC_P256_MAGIC, CRYPTOAPI_BLOB, CRYPT_STRING, CRYPT_STRING_BASE64,
+            NCRYPT_FLAGS,
+        },
+        CRYPTPROTECT_UI_FORBIDDEN,
+    },
+    Storage::FileSystem::{
+        FindChangeNotificationHandle, FindCloseChangeNotification, FindFirstChangeNotificationW,
+        FindNextChangeNotification, GetFileTime, MoveFileExW, SetFileTime,
+        FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE, MOVEFILE_WRITE_THROUGH,
+    },
     System::{
         Com::{
             CoCreateInstance, CoInitializeEx, CoTaskMemFree, IPersistFile, CLSCTX_INPROC_SERVER,
             COINIT,
         },
-        Console::AttachConsole,
+        Console::{AllocConsole, AttachConsole, FreeConsole, GetConsoleWindow, SetConsoleTitleW},
         DataExchange::{
-            AddClipboardFormatListener, CloseClipboard, GetClipboardData,
-            GetPriorityClipboardFormat, OpenClipboard,
+            AddClipboardFormatListener, CloseClipboard, EmptyClipboard, GetClipboardData,
+            GetClipboardOwner, GetClipboardSequenceNumber, GetPriorityClipboardFormat,
+            IsClipboardFormatAvailable, OpenClipboard, RegisterClipboardFormatW,
+            RemoveClipboardFormatListener, SetClipboardData, COPYDATASTRUCT,
+        },
+        LibraryLoader::GetModuleHandleW,
+        Memory::{
+            GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, LocalFree, GMEM_MOVEABLE,
+        },
+        ProcessStatus::K32GetProcessImageFileNameW,
+        Registry::{
+            RegDeleteKeyValueW, RegDeleteTreeW, RegGetValueW, RegSetKeyValueW, HKEY,
+            HKEY_CURRENT_USER, REG_SZ, RRF_RT_REG_DWORD, RRF_RT_REG_EXPAND_SZ, RRF_RT_REG_SZ,
+        },
+        RemoteDesktop::ProcessIdToSessionId,
+        Shutdown::{ShutdownBlockReasonCreate, ShutdownBlockReasonDestroy},
+        SystemInformation::{GetVersionExW, OSVERSIONINFOW},
+        SystemServices::{CF_DIB, CF_HDROP, CF_UNICODETEXT, CLIPBOARD_FORMATS},
+        Threading::{
+            CreateMutexW, GetCurrentProcessId, GetCurrentThreadId, OpenProcess,
+            WaitForSingleObject, PROCESS_QUERY_LIMITED_INFORMATION, WAIT_OBJECT_0,
         },
-        LibraryLoader::GetModuleHandleA,
-        ProcessStatus::K32GetProcessImageFileNameA,
-        SystemServices::{CF_DIB, CLIPBOARD_FORMATS},
-        Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
     },
     UI::{
-        Shell::{IKnownFolderManager, IShellLinkA, KnownFolderManager, ShellLink},
+        Accessibility::{
+            CUIAutomation, IUIAutomation, IUIAutomationValuePattern, TreeScope_Descendants,
+            UIA_EditControlTypeId, UIA_ValuePatternId,
+        },
+        Controls::{
+            CheckDlgButton, IsDlgButtonChecked, TaskDialogIndirect, BST_CHECKED, BST_UNCHECKED,
+            TASKDIALOGCONFIG, TASKDIALOGCONFIG_0, TASKDIALOGCONFIG_1, TASKDIALOG_BUTTON,
+            TDCBF_CLOSE_BUTTON, TDF_ALLOW_DIALOG_CANCELLATION,
+        },
+        HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI},
+        KeyboardAndMouseInput::{
+            EnableWindow, RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS,
+        },
+        Shell::{
+            DragFinish, DragQueryFileW, IKnownFolderManager, ILCreateFromPathW, ILFree,
+            IShellLinkW, KnownFolderManager, SHFileOperationW, SHOpenFolderAndSelectItems,
+            ShellLink, DROPFILES, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT,
+            FO_DELETE, HDROP, SHFILEOPSTRUCTW,
+        },
         WindowsAndMessaging::{
-            CreateWindowExA, DestroyMenu, DestroyWindow, DispatchMessageA, FindWindowA,
-            GetMessageA, GetWindowThreadProcessId, LoadMenuA, PostQuitMessage, RegisterClassA,
-            SendNotifyMessageA, TranslateMessage, CW_USEDEFAULT, HMENU, MSG, WINDOW_EX_STYLE,
-            WINDOW_STYLE, WNDCLASSA, WNDPROC,
+            AllowSetForegroundWindow, CreateWindowExW, DeleteMenu, DestroyMenu, DestroyWindow,
+            DialogBoxParamW, DispatchMessageW, EndDialog, EnumThreadWindows, FindWindowW,
+            GetDlgItem, GetForegroundWindow, GetMessageW, GetSystemMenu, GetSystemMetrics,
+            GetWindowRect, GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId,
+            IsIconic, IsWindowVisible, LoadCursorW, LoadMenuW, MessageBoxW, PostQuitMessage,
+            RegisterClassW, RegisterWindowMessageW, SendDlgItemMessageW, SendMessageTimeoutW,
+            SendNotifyMessageW, SetDlgItemTextW, SetForegroundWindow, SetWindowPos, ShowWindow,
+            TranslateMessage, CB_ADDSTRING, CB_GETCURSEL, CB_RESETCONTENT, CB_SETCURSEL,
+            COLOR_WINDOW, CW_USEDEFAULT, DLGPROC, HMENU, IDC_ARROW, LB_ADDSTRING, LB_GETCURSEL,
+            LB_RESETCONTENT, MESSAGEBOX_RESULT, MESSAGEBOX_STYLE, MF_BYCOMMAND, MSG, SC_CLOSE,
+            SMTO_ABORTIFHUNG, SM_REMOTESESSION, SWP_NOACTIVATE, SWP_NOZORDER, SW_RESTORE, SW_SHOW,
+            WINDOW_EX_STYLE, WINDOW_STYLE, WM_COPYDATA, WNDCLASSW, WNDPROC,
         },
     },
 };
 use core::ptr;
-use std::ffi::{c_void, CString};
+use std::ffi::{c_void, OsString};
+use std::fs::File;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::os::windows::io::AsRawHandle;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
-use std::{mem, thread};
+use std::{mem, slice, thread};
 use widestring::U16CString;
 use windows::{Guid, Interface, IntoParam, HRESULT};
 
@@ -108,6 +185,49 @@ pub fn attach_console() -> bool {
     unsafe { AttachConsole(ATTACH_PARENT_PROCESS).0 != 0 }
 }
 
+/// Creates a new console window for the current process, titled `title`, and
+/// returns it.
+///
+/// A process can only have a single console, so this fails if the process is
+/// already attached to one.
+pub fn alloc_console(title: &str) -> windows::Result<HWND> {
+    unsafe {
+        if AllocConsole().0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        SetConsoleTitleW(title);
+
+        Ok(GetConsoleWindow())
+    }
+}
+
+/// Safe wrapper around [`GetConsoleWindow`], which returns the window of the
+/// console that the current process is attached to, if any.
+///
+/// [`GetConsoleWindow`]: GetConsoleWindow
+pub fn get_console_window() -> Option<HWND> {
+    match unsafe { GetConsoleWindow() } {
+        HWND(0) => None,
+        window => Some(window),
+    }
+}
+
+/// Detaches the current process from its console. If no other processes are
+/// attached to the console, it is closed.
+pub fn free_console() {
+    unsafe {
+        FreeConsole();
+    }
+}
+
+/// Removes the close button (and "Close" system menu entry) from `window`.
+pub fn remove_close_button(window: HWND) {
+    unsafe {
+        DeleteMenu(GetSystemMenu(window, false), SC_CLOSE, MF_BYCOMMAND);
+    }
+}
+
 /// Safe wrapper around [`CoInitializeEx`].
 ///
 /// [`CoInitializeEx`]: CoInitializeEx
@@ -128,13 +248,13 @@ where
     unsafe { CoCreateInstance(&class_id, None, CLSCTX_INPROC_SERVER) }
 }
 
-/// Safe wrapper around [`GetModuleHandleA`], which gets the module handle for
+/// Safe wrapper around [`GetModuleHandleW`], which gets the module handle for
 /// the current process.
 ///
-/// [`GetModuleHandleA`]: GetModuleHandleA
+/// [`GetModuleHandleW`]: GetModuleHandleW
 pub fn get_instance() -> windows::Result<HINSTANCE> {
     unsafe {
-        let handle = GetModuleHandleA(None);
+        let handle = GetModuleHandleW(None);
 
         if handle.is_null() {
             Err(HRESULT::from_thread().into())
@@ -144,46 +264,77 @@ pub fn get_instance() -> windows::Result<HINSTANCE> {
     }
 }
 
-/// Safe wrapper around [`RegisterClassA`], which registers a new window class.
+/// Safe wrapper around [`RegisterClassW`], which registers a new window class.
 /// On success, returns the name of the class that was registered.
 ///
-/// [`RegisterClassA`]: RegisterClassA
+/// [`RegisterClassW`]: RegisterClassW
 pub fn create_window_class(
     instance: HINSTANCE,
     class_name: &str,
     window_proc: Option<WNDPROC>,
-) -> windows::Result<CString> {
+) -> windows::Result<U16CString> {
+    unsafe {
+        let class_name = to_wide_string(class_name);
+
+        let atom = RegisterClassW(&WNDCLASSW {
+            lpfnWndProc: window_proc,
+            hInstance: instance,
+            lpszClassName: class_name.as_pwstr(),
+            ..Default::default()
+        });
+
+        if atom == 0 {
+            Err(HRESULT::from_thread().into())
+        } else {
+            Ok(class_name)
+        }
+    }
+}
+
+/// Safe wrapper around [`RegisterClassW`], which registers a new window class
+/// for windows that are visible to the user (i.e. with the standard window
+/// background colour and cursor). On success, returns the name of the class
+/// that was registered.
+///
+/// [`RegisterClassW`]: RegisterClassW
+pub fn create_visible_window_class(
+    instance: HINSTANCE,
+    class_name: &str,
+    window_proc: Option<WNDPROC>,
+) -> windows::Result<U16CString> {
     unsafe {
-        let class_name = CString::new(class_name).unwrap();
+        let class_name = to_wide_string(class_name);
 
-        let atom = RegisterClassA(&WNDCLASSA {
+        let atom = RegisterClassW(&WNDCLASSW {
             lpfnWndProc: window_proc,
             hInstance: instance,
-            lpszClassName: class_name.as_pstr(),
+            hCursor: LoadCursorW(None, IDC_ARROW),
+            hbrBackground: GetSysColorBrush(COLOR_WINDOW.0 as i32),
+            lpszClassName: class_name.as_pwstr(),
             ..Default::default()
         });
 
         if atom == 0 {
             Err(HRESULT::from_thread().into())
         } else {
-            Ok(class_name.into())
+            Ok(class_name)
         }
     }
 }
 
-/// Safe wrapper around [`CreateWindowExA`], with most arguments pre-filled
+/// Safe wrapper around [`CreateWindowExW`], with most arguments pre-filled
 /// specifically for creating message-only windows.
 ///
-/// [`CreateWindowExA`]: CreateWindowExA
+/// [`CreateWindowExW`]: CreateWindowExW
 pub fn create_window(
     instance: HINSTANCE,
-    class: &CString,
+    class: &U16CString,
     window_name: &str,
 ) -> windows::Result<HWND> {
     unsafe {
-        let window = CreateWindowExA(
+        let window = CreateWindowExW(
             WINDOW_EX_STYLE(0),
-            class.as_pstr(),
+            class.as_pwstr(),
             window_name,
             WINDOW_STYLE(0),
             CW_USEDEFAULT,
@@ -204,48 +355,1382 @@ pub fn create_window(
     }
 }
 
-/// Safe wrapper around [`DestroyWindow`].
+/// Safe wrapper around [`CreateWindowExW`], which creates a top-level window
+/// that is visible to the user, at the default position for new windows.
 ///
-/// [`DestroyWindow`]: DestroyWindow
-pub fn destroy_window(window: HWND) {
+/// The window is not shown until [`show_window`] is called on it.
+///
+/// [`CreateWindowExW`]: CreateWindowExW
+/// [`show_window`]: show_window
+pub fn create_visible_window(
+    instance: HINSTANCE,
+    class: &U16CString,
+    window_name: &str,
+    (ex_style, style): (WINDOW_EX_STYLE, WINDOW_STYLE),
+    (width, height): (i32, i32),
+) -> windows::Result<HWND> {
     unsafe {
-        DestroyWindow(window);
+        let window = CreateWindowExW(
+            ex_style,
+            class.as_pwstr(),
+            window_name,
+            style,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            width,
+            height,
+            None,
+            None,
+            instance,
+            ptr::null_mut(),
+        );
+
+        if window.is_null() {
+            Err(HRESULT::from_thread().into())
+        } else {
+            Ok(window)
+        }
     }
 }
 
-/// Safe wrapper around [`FindWindowA`], which returns the [`HWND`] of a window
-/// with the specified class and window name, if one exists.
+/// Shows `window`, and brings it to the foreground.
+pub fn show_window(window: HWND) {
+    unsafe {
+        ShowWindow(window, SW_SHOW);
+        SetForegroundWindow(window);
+    }
+}
+
+/// Brings `window` to the foreground, restoring it first, if it is minimised.
+pub fn foreground_window(window: HWND) {
+    unsafe {
+        if IsIconic(window).0 != 0 {
+            ShowWindow(window, SW_RESTORE);
+        }
+
+        SetForegroundWindow(window);
+    }
+}
+
+/// Safe wrapper around [`AllowSetForegroundWindow`], which lets any other
+/// process bring its windows to the foreground, whilst this process is in the
+/// foreground.
 ///
-/// On failure, this function returns `None`.
+/// [`AllowSetForegroundWindow`]: AllowSetForegroundWindow
+pub fn allow_any_set_foreground_window() {
+    // Equivalent to `ASFW_ANY`
+    const ASFW_ANY: u32 = u32::MAX;
+
+    unsafe {
+        AllowSetForegroundWindow(ASFW_ANY);
+    }
+}
+
+/// Returns whether or not the current session is a Remote Desktop session,
+/// using [`GetSystemMetrics`].
 ///
-/// [`FindWindowA`]: FindWindowA
-/// [`HWND`]: HWND
-pub fn find_window(class_name: &str, window_name: &str) -> Option<HWND> {
-    let window = unsafe { FindWindowA(class_name, window_name) };
+/// [`GetSystemMetrics`]: GetSystemMetrics
+pub fn is_remote_session() -> bool {
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
 
-    if window.is_null() {
-        None
+/// Returns the ID of the Remote Desktop Services session that the current
+/// process is running in.
+pub fn get_session_id() -> windows::Result<u32> {
+    let mut session_id = 0;
+
+    if unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session_id).0 != 0 } {
+        Ok(session_id)
     } else {
-        Some(window)
+        Err(HRESULT::from_thread().into())
+    }
+}
+
+/// Safe wrapper around [`CreateMutexW`], which creates (or opens) a named
+/// mutex, initially owned by the calling thread.
+///
+/// Also returns whether or not the mutex already existed, in which case the
+/// calling thread does not own it.
+///
+/// [`CreateMutexW`]: CreateMutexW
+pub fn create_mutex(name: &str) -> windows::Result<(AutoClose<HANDLE>, bool)> {
+    // https://docs.microsoft.com/en-us/windows/win32/debug/system-error-codes--0-499-
+    const ERROR_ALREADY_EXISTS: u32 = 183;
+
+    unsafe {
+        let mutex = CreateMutexW(ptr::null_mut(), true, name);
+
+        if mutex.is_null() {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let already_exists = HRESULT::from_thread() == HRESULT::from_win32(ERROR_ALREADY_EXISTS);
+
+        Ok((
+            AutoClose::new(mutex, |m| {
+                CloseHandle(m);
+            }),
+            already_exists,
+        ))
+    }
+}
+
+/// Safe wrapper around [`GetForegroundWindow`].
+///
+/// [`GetForegroundWindow`]: GetForegroundWindow
+pub fn get_foreground_window() -> HWND {
+    unsafe { GetForegroundWindow() }
+}
+
+/// Safe wrapper around [`GetWindowTextW`], which returns the title of
+/// `window`.
+///
+/// [`GetWindowTextW`]: GetWindowTextW
+pub fn get_window_text(window: HWND) -> String {
+    unsafe {
+        let length = GetWindowTextLengthW(window);
+
+        if length <= 0 {
+            return String::new();
+        }
+
+        let mut text = vec![0u16; length as usize + 1];
+        let length = GetWindowTextW(window, PWSTR(text.as_mut_ptr()), text.len() as i32);
+
+        String::from_utf16_lossy(&text[..length.max(0) as usize])
+    }
+}
+
+/// Copies the creation, last access and last write times of `source` to
+/// `destination`, using [`GetFileTime`] and [`SetFileTime`].
+///
+/// [`GetFileTime`]: GetFileTime
+/// [`SetFileTime`]: SetFileTime
+pub fn copy_file_times(source: &File, destination: &File) -> windows::Result<()> {
+    let source = HANDLE(source.as_raw_handle() as isize);
+    let destination = HANDLE(destination.as_raw_handle() as isize);
+
+    let mut created = FILETIME::default();
+    let mut accessed = FILETIME::default();
+    let mut written = FILETIME::default();
+
+    unsafe {
+        if GetFileTime(source, &mut created, &mut accessed, &mut written).0 == 0
+            || SetFileTime(destination, &created, &accessed, &written).0 == 0
+        {
+            return Err(HRESULT::from_thread().into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Calculates the SHA-256 hash of `data`, using [`BCryptHash`].
+///
+/// [`BCryptHash`]: BCryptHash
+pub fn sha256(data: &[u8]) -> windows::Result<[u8; 32]> {
+    // https://docs.microsoft.com/en-us/windows/win32/seccng/cng-algorithm-pseudo-handles
+    const BCRYPT_SHA256_ALG_HANDLE: usize = 0x41;
+
+    let mut hash = [0u8; 32];
+
+    unsafe {
+        BCryptHash(
+            BCRYPT_SHA256_ALG_HANDLE as *mut c_void,
+            ptr::null_mut(),
+            0,
+            data.as_ptr() as *mut u8,
+            data.len() as u32,
+            hash.as_mut_ptr(),
+            hash.len() as u32,
+        )?;
+    }
+
+    Ok(hash)
+}
+
+/// Encodes `data` as base64, using [`CryptBinaryToStringA`].
+///
+/// [`CryptBinaryToStringA`]: CryptBinaryToStringA
+pub fn base64_encode(data: &[u8]) -> windows::Result<String> {
+    // CRYPT_STRING_NOCRLF isn't in the metadata
+    let flags = CRYPT_STRING_BASE64 | CRYPT_STRING(0x4000_0000);
+    let mut length = 0;
+
+    unsafe {
+        if CryptBinaryToStringA(
+            data.as_ptr(),
+            data.len() as u32,
+            flags,
+            PSTR(ptr::null_mut()),
+            &mut length,
+        )
+        .0 == 0
+        {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        // The length includes the null terminator
+        let mut encoded = vec![0u8; length as usize];
+
+        if CryptBinaryToStringA(
+            data.as_ptr(),
+            data.len() as u32,
+            flags,
+            PSTR(encoded.as_mut_ptr()),
+            &mut length,
+        )
+        .0 == 0
+        {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        encoded.truncate(length as usize);
+
+        Ok(String::from_utf8_lossy(&encoded).into_owned())
+    }
+}
+
+/// Decodes base64 `data`, using [`CryptStringToBinaryW`]. PEM headers and
+/// whitespace are ignored.
+///
+/// [`CryptStringToBinaryW`]: CryptStringToBinaryW
+pub fn base64_decode(data: &str) -> windows::Result<Vec<u8>> {
+    // CRYPT_STRING_BASE64_ANY isn't in the metadata
+    let flags = CRYPT_STRING(6);
+    let wide = to_wide_string(data);
+    let mut length = 0;
+
+    unsafe {
+        if CryptStringToBinaryW(
+            PWSTR(wide.as_ptr() as *mut u16),
+            wide.len() as u32,
+            flags,
+            ptr::null_mut(),
+            &mut length,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+        .0 == 0
+        {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let mut decoded = vec![0u8; length as usize];
+
+        if CryptStringToBinaryW(
+            PWSTR(wide.as_ptr() as *mut u16),
+            wide.len() as u32,
+            flags,
+            decoded.as_mut_ptr(),
+            &mut length,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+        .0 == 0
+        {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        decoded.truncate(length as usize);
+
+        Ok(decoded)
+    }
+}
+
+/// Verifies that `signature` (the concatenated `r` and `s` values) is a valid
+/// ECDSA P-256 signature of the SHA-256 hash of `data`, made with the private
+/// key matching `public_key` (the concatenated `x` and `y` coordinates), using
+/// [`BCryptVerifySignature`]. Returns an error if it isn't.
+///
+/// [`BCryptVerifySignature`]: BCryptVerifySignature
+pub fn verify_ecdsa_p256(
+    public_key: &[u8; 64],
+    data: &[u8],
+    signature: &[u8; 64],
+) -> windows::Result<()> {
+    // https://docs.microsoft.com/en-us/windows/win32/seccng/cng-algorithm-pseudo-handles
+    const BCRYPT_ECDSA_P256_ALG_HANDLE: usize = 0xe1;
+
+    let mut hash = sha256(data)?;
+    let mut signature = *signature;
+
+    let header = BCRYPT_ECCKEY_BLOB {
+        dwMagic: BCRYPT_ECDSA_PUBLIC_P256_MAGIC,
+        cbKey: 32,
+    };
+
+    let mut blob = Vec::with_capacity(mem::size_of_val(&header) + public_key.len());
+    blob.extend_from_slice(&header.dwMagic.to_le_bytes());
+    blob.extend_from_slice(&header.cbKey.to_le_bytes());
+    blob.extend_from_slice(public_key);
+
+    unsafe {
+        let mut key = ptr::null_mut();
+
+        BCryptImportKeyPair(
+            BCRYPT_ECDSA_P256_ALG_HANDLE as *mut c_void,
+            ptr::null_mut(),
+            "ECCPUBLICBLOB",
+            &mut key,
+            blob.as_mut_ptr(),
+            blob.len() as u32,
+            0,
+        )?;
+
+        let key = AutoClose::new(key, |key| {
+            let _ = BCryptDestroyKey(key);
+        });
+
+        BCryptVerifySignature(
+            key.value(),
+            ptr::null_mut(),
+            hash.as_mut_ptr(),
+            hash.len() as u32,
+            signature.as_mut_ptr(),
+            signature.len() as u32,
+            NCRYPT_FLAGS(0),
+        )
+    }
+}
+
+/// The response to an HTTP request.
+pub struct HttpResponse {
+    /// The HTTP status code.
+    pub status: u32,
+
+    /// The response body.
+    pub body: Vec<u8>,
+}
+
+/// Sends an HTTP request to `url` with WinHTTP, using the system's proxy
+/// configuration, and waits for the response. `headers` are CRLF-separated
+/// additional request headers.
+pub fn http_request(
+    verb: &str,
+    url: &str,
+    headers: &str,
+    body: &[u8],
+) -> windows::Result<HttpResponse> {
+    let wide_url = to_wide_string(url);
+
+    // Null component pointers with non-zero lengths make WinHttpCrackUrl point
+    // them into the URL itself
+    let mut components = URL_COMPONENTS {
+        dwStructSize: mem::size_of::<URL_COMPONENTS>() as u32,
+        dwSchemeLength: u32::MAX,
+        dwHostNameLength: u32::MAX,
+        dwUrlPathLength: u32::MAX,
+        dwExtraInfoLength: u32::MAX,
+        ..Default::default()
+    };
+
+    let component = |pointer: PWSTR, length: u32| unsafe {
+        String::from_utf16_lossy(slice::from_raw_parts(pointer.0, length as usize))
+    };
+
+    unsafe {
+        if WinHttpCrackUrl(PWSTR(wide_url.as_ptr() as *mut u16), 0, 0, &mut components).0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+    }
+
+    let host = component(components.lpszHostName, components.dwHostNameLength);
+    let path = component(components.lpszUrlPath, components.dwUrlPathLength)
+        + &component(components.lpszExtraInfo, components.dwExtraInfoLength);
+
+    let open_handle = |handle: *mut c_void| -> windows::Result<AutoClose<*mut c_void>> {
+        if handle.is_null() {
+            Err(HRESULT::from_thread().into())
+        } else {
+            Ok(AutoClose::new(handle, |handle| unsafe {
+                WinHttpCloseHandle(handle);
+            }))
+        }
+    };
+
+    unsafe {
+        let session = open_handle(WinHttpOpen(
+            &*format!("snip-and-autosave/{}", env!("CARGO_PKG_VERSION")),
+            WINHTTP_ACCESS_TYPE_DEFAULT_PROXY,
+            PWSTR(ptr::null_mut()),
+            PWSTR(ptr::null_mut()),
+            0,
+        ))?;
+
+        let connection = open_handle(WinHttpConnect(
+            session.value(),
+            &*host,
+            INTERNET_PORT(components.nPort as u32),
+            0,
+        ))?;
+
+        let request = open_handle(WinHttpOpenRequest(
+            connection.value(),
+            verb,
+            &*path,
+            PWSTR(ptr::null_mut()),
+            PWSTR(ptr::null_mut()),
+            ptr::null_mut(),
+            if components.nScheme == INTERNET_SCHEME_HTTPS {
+                WINHTTP_FLAG_SECURE
+            } else {
+                WINHTTP_OPEN_REQUEST_FLAGS(0)
+            },
+        ))?;
+
+        let headers = to_wide_string(headers);
+
+        if WinHttpSendRequest(
+            request.value(),
+            PWSTR(headers.as_ptr() as *mut u16),
+            headers.len() as u32,
+            body.as_ptr() as *mut c_void,
+            body.len() as u32,
+            body.len() as u32,
+            0,
+        )
+        .0 == 0
+            || WinHttpReceiveResponse(request.value(), ptr::null_mut()).0 == 0
+        {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let mut status = 0u32;
+        let mut status_size = mem::size_of_val(&status) as u32;
+
+        if WinHttpQueryHeaders(
+            request.value(),
+            WINHTTP_QUERY_STATUS_CODE | WINHTTP_QUERY_FLAG_NUMBER,
+            PWSTR(ptr::null_mut()),
+            &mut status as *mut u32 as *mut c_void,
+            &mut status_size,
+            ptr::null_mut(),
+        )
+        .0 == 0
+        {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let mut response_body = Vec::new();
+        let mut buffer = [0u8; 8192];
+
+        loop {
+            let mut read = 0;
+
+            if WinHttpReadData(
+                request.value(),
+                buffer.as_mut_ptr() as *mut c_void,
+                buffer.len() as u32,
+                &mut read,
+            )
+            .0 == 0
+            {
+                return Err(HRESULT::from_thread().into());
+            }
+
+            if read == 0 {
+                break;
+            }
+
+            response_body.extend_from_slice(&buffer[..read as usize]);
+        }
+
+        Ok(HttpResponse {
+            status,
+            body: response_body,
+        })
+    }
+}
+
+/// Safe wrapper around [`GetDateFormatEx`], which formats `date` in the user's
+/// locale, with either one of the locale's standard formats (chosen by
+/// `flags`), or a custom `format` picture (e.g. `dddd` for the weekday name).
+///
+/// [`GetDateFormatEx`]: GetDateFormatEx
+pub fn get_date_format(
+    date: &SYSTEMTIME,
+    flags: ENUM_DATE_FORMATS_FLAGS,
+    format: Option<&str>,
+) -> windows::Result<String> {
+    let format = format.map(to_wide_string);
+    let format_ptr = format.as_ref().map_or(PWSTR(ptr::null_mut()), |format| {
+        PWSTR(format.as_ptr() as *mut u16)
+    });
+
+    unsafe {
+        // A null locale name means the user's default locale
+        let length = GetDateFormatEx(
+            PWSTR(ptr::null_mut()),
+            flags,
+            date,
+            format_ptr,
+            PWSTR(ptr::null_mut()),
+            0,
+            PWSTR(ptr::null_mut()),
+        );
+
+        if length == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let mut buffer = vec![0u16; length as usize];
+
+        let length = GetDateFormatEx(
+            PWSTR(ptr::null_mut()),
+            flags,
+            date,
+            format_ptr,
+            PWSTR(buffer.as_mut_ptr()),
+            buffer.len() as i32,
+            PWSTR(ptr::null_mut()),
+        );
+
+        if length == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        // The returned length includes the null terminator
+        Ok(String::from_utf16_lossy(&buffer[..length as usize - 1]))
+    }
+}
+
+/// Safe wrapper around [`MonitorFromWindow`], which returns the monitor that
+/// `window` is on, or that is nearest to it.
+///
+/// [`MonitorFromWindow`]: MonitorFromWindow
+pub fn monitor_from_window(window: HWND) -> HMONITOR {
+    unsafe { MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST) }
+}
+
+/// Safe wrapper around [`EnumDisplayMonitors`], which returns every monitor
+/// that makes up the desktop.
+///
+/// [`EnumDisplayMonitors`]: EnumDisplayMonitors
+pub fn enum_display_monitors() -> Vec<HMONITOR> {
+    unsafe extern "system" fn enum_proc(
+        monitor: HMONITOR,
+        _: HDC,
+        _: *mut RECT,
+        l_param: LPARAM,
+    ) -> BOOL {
+        (*(l_param.0 as *mut Vec<HMONITOR>)).push(monitor);
+
+        BOOL(1)
+    }
+
+    let mut monitors = Vec::new();
+
+    unsafe {
+        EnumDisplayMonitors(
+            HDC(0),
+            ptr::null_mut(),
+            Some(enum_proc),
+            LPARAM(&mut monitors as *mut _ as isize),
+        );
+    }
+
+    monitors
+}
+
+/// Safe wrapper around [`GetMonitorInfoW`].
+///
+/// [`GetMonitorInfoW`]: GetMonitorInfoW
+pub fn get_monitor_info(monitor: HMONITOR) -> windows::Result<MONITORINFO> {
+    let mut monitor_info = MONITORINFO {
+        cbSize: mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+
+    unsafe {
+        match GetMonitorInfoW(monitor, &mut monitor_info).0 {
+            0 => Err(HRESULT::from_thread().into()),
+            _ => Ok(monitor_info),
+        }
+    }
+}
+
+/// Safe wrapper around [`GetDpiForMonitor`], which returns the effective DPI
+/// of `monitor` (96 being 100% scaling).
+///
+/// [`GetDpiForMonitor`]: GetDpiForMonitor
+pub fn get_dpi_for_monitor(monitor: HMONITOR) -> windows::Result<u32> {
+    let mut dpi_x = 0;
+    let mut dpi_y = 0;
+
+    unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y)? };
+
+    Ok(dpi_x)
+}
+
+/// Safe wrapper around [`GetWindowRect`], which returns the bounds of `window`,
+/// in screen coordinates.
+///
+/// [`GetWindowRect`]: GetWindowRect
+pub fn get_window_rect(window: HWND) -> windows::Result<RECT> {
+    let mut rect = RECT::default();
+
+    unsafe {
+        match GetWindowRect(window, &mut rect).0 {
+            0 => Err(HRESULT::from_thread().into()),
+            _ => Ok(rect),
+        }
+    }
+}
+
+/// Moves and resizes `window` to `rect`, in screen coordinates, without
+/// changing its Z order or activating it.
+pub fn set_window_rect(window: HWND, rect: &RECT) -> windows::Result<()> {
+    unsafe {
+        match SetWindowPos(
+            window,
+            HWND(0),
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        )
+        .0
+        {
+            0 => Err(HRESULT::from_thread().into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the work area (i.e. excluding the taskbar) of the monitor that
+/// `rect` overlaps the most, or the nearest monitor, if `rect` is off-screen.
+pub fn get_work_area(rect: &RECT) -> windows::Result<RECT> {
+    let mut rect = *rect;
+    let mut monitor_info = MONITORINFO {
+        cbSize: mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+
+    unsafe {
+        let monitor = MonitorFromRect(&mut rect, MONITOR_DEFAULTTONEAREST);
+
+        if GetMonitorInfoW(monitor, &mut monitor_info).0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+    }
+
+    Ok(monitor_info.rcWork)
+}
+
+/// Copies the pixels within `bounds` (in virtual screen coordinates) from the
+/// screen, returning them as top-down, 32-bpp BGRX pixel data.
+pub fn capture_screen(bounds: &RECT) -> windows::Result<Vec<u8>> {
+    let width = bounds.right - bounds.left;
+    let height = bounds.bottom - bounds.top;
+
+    unsafe {
+        let screen = GetDC(HWND(0));
+
+        if screen.0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let screen = AutoClose::new(screen, |dc| {
+            ReleaseDC(HWND(0), dc);
+        });
+
+        let memory = CreateCompatibleDC(screen.value());
+
+        if memory.0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let memory = AutoClose::new(memory, |dc| {
+            DeleteDC(dc);
+        });
+
+        let bitmap = CreateCompatibleBitmap(screen.value(), width, height);
+
+        if bitmap.0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let bitmap = AutoClose::new(bitmap, |bitmap| {
+            DeleteObject(bitmap);
+        });
+
+        let previous = SelectObject(memory.value(), bitmap.value());
+        let copied = BitBlt(
+            memory.value(),
+            0,
+            0,
+            width,
+            height,
+            screen.value(),
+            bounds.left,
+            bounds.top,
+            SRCCOPY,
+        );
+        SelectObject(memory.value(), previous);
+
+        if copied.0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let mut bitmap_info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                // A negative height requests top-down rows
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB as u32,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+
+        if GetDIBits(
+            screen.value(),
+            bitmap.value(),
+            0,
+            height as u32,
+            pixels.as_mut_ptr() as *mut c_void,
+            &mut bitmap_info,
+            DIB_RGB_COLORS,
+        ) == 0
+        {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        Ok(pixels)
+    }
+}
+
+/// Calls `draw` with a memory device context, holding a `width` by `height`
+/// bitmap, then returns what was drawn as top-down, 32-bpp BGRX pixel data.
+pub fn draw_offscreen(width: i32, height: i32, draw: impl FnOnce(HDC)) -> windows::Result<Vec<u8>> {
+    unsafe {
+        let screen = GetDC(HWND(0));
+
+        if screen.0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let screen = AutoClose::new(screen, |dc| {
+            ReleaseDC(HWND(0), dc);
+        });
+
+        let memory = CreateCompatibleDC(screen.value());
+
+        if memory.0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let memory = AutoClose::new(memory, |dc| {
+            DeleteDC(dc);
+        });
+
+        let bitmap = CreateCompatibleBitmap(screen.value(), width, height);
+
+        if bitmap.0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let bitmap = AutoClose::new(bitmap, |bitmap| {
+            DeleteObject(bitmap);
+        });
+
+        // The bitmap can't be read whilst it is selected into the device context
+        let previous = SelectObject(memory.value(), bitmap.value());
+        draw(HDC(memory.value().0));
+        SelectObject(memory.value(), previous);
+
+        let mut bitmap_info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                // A negative height requests top-down rows
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB as u32,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+
+        if GetDIBits(
+            memory.value(),
+            bitmap.value(),
+            0,
+            height as u32,
+            pixels.as_mut_ptr() as *mut c_void,
+            &mut bitmap_info,
+            DIB_RGB_COLORS,
+        ) == 0
+        {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        Ok(pixels)
+    }
+}
+
+/// Safe wrapper around [`GetCurrentThreadId`].
+///
+/// [`GetCurrentThreadId`]: GetCurrentThreadId
+pub fn get_current_thread_id() -> u32 {
+    unsafe { GetCurrentThreadId() }
+}
+
+/// Returns the first visible top-level window owned by the thread with ID
+/// `thread_id`, if it has one.
+pub fn find_thread_window(thread_id: u32) -> Option<HWND> {
+    unsafe extern "system" fn enum_proc(window: HWND, l_param: LPARAM) -> BOOL {
+        if IsWindowVisible(window).0 != 0 {
+            *(l_param.0 as *mut HWND) = window;
+
+            // Stop enumerating
+            BOOL(0)
+        } else {
+            BOOL(1)
+        }
+    }
+
+    let mut window = HWND(0);
+
+    unsafe {
+        EnumThreadWindows(
+            thread_id,
+            Some(enum_proc),
+            LPARAM(&mut window as *mut _ as isize),
+        );
+    }
+
+    if window.is_null() {
+        None
+    } else {
+        Some(window)
+    }
+}
+
+/// Safe wrapper around [`MessageBoxW`], which shows a modal message box, and
+/// returns the button that the user clicked.
+///
+/// [`MessageBoxW`]: MessageBoxW
+pub fn message_box(title: &str, text: &str, style: MESSAGEBOX_STYLE) -> MESSAGEBOX_RESULT {
+    unsafe { MessageBoxW(None, text, title, style) }
+}
+
+/// Safe wrapper around [`DialogBoxParamW`], which shows the modal dialog box
+/// with the resource ID `template_id`, and returns the value that it was ended
+/// with.
+///
+/// [`DialogBoxParamW`]: DialogBoxParamW
+pub fn dialog_box(template_id: u16, dialog_proc: DLGPROC) -> windows::Result<isize> {
+    let instance = get_instance()?;
+
+    // Equivalent to `MAKEINTRESOURCEW(template_id)`
+    let template = PWSTR(template_id as usize as *mut u16);
+
+    match unsafe { DialogBoxParamW(instance, template, None, Some(dialog_proc), LPARAM(0)) } {
+        -1 => Err(HRESULT::from_thread().into()),
+        result => Ok(result),
+    }
+}
+
+/// Safe wrapper around [`EndDialog`], which closes a modal dialog box, making
+/// [`dialog_box`] return `result`.
+///
+/// [`EndDialog`]: EndDialog
+/// [`dialog_box`]: dialog_box
+pub fn end_dialog(dialog: HWND, result: isize) {
+    unsafe {
+        EndDialog(dialog, result);
+    }
+}
+
+/// Returns the text of the control `id` within `dialog`.
+pub fn get_dialog_item_text(dialog: HWND, id: i32) -> String {
+    get_window_text(unsafe { GetDlgItem(dialog, id) })
+}
+
+/// Safe wrapper around [`SetDlgItemTextW`], which sets the text of the control
+/// `id` within `dialog`.
+///
+/// [`SetDlgItemTextW`]: SetDlgItemTextW
+pub fn set_dialog_item_text(dialog: HWND, id: i32, text: &str) {
+    let text = to_wide_string(text);
+
+    unsafe {
+        SetDlgItemTextW(dialog, id, PWSTR(text.as_ptr() as *mut u16));
+    }
+}
+
+/// Safe wrapper around [`EnableWindow`], which enables or disables the control
+/// `id` within `dialog`.
+///
+/// [`EnableWindow`]: EnableWindow
+pub fn enable_dialog_item(dialog: HWND, id: i32, enabled: bool) {
+    unsafe {
+        EnableWindow(GetDlgItem(dialog, id), enabled);
+    }
+}
+
+/// Returns whether or not the check box `id` within `dialog` is checked.
+pub fn is_dialog_button_checked(dialog: HWND, id: i32) -> bool {
+    unsafe { IsDlgButtonChecked(dialog, id) == BST_CHECKED.0 }
+}
+
+/// Checks or unchecks the check box `id` within `dialog`.
+pub fn check_dialog_button(dialog: HWND, id: i32, checked: bool) {
+    let state = if checked { BST_CHECKED } else { BST_UNCHECKED };
+
+    unsafe {
+        CheckDlgButton(dialog, id, state);
+    }
+}
+
+/// Replaces the items of the combo box `id` within `dialog` with `items`, and
+/// selects the item at `selected`.
+pub fn set_combo_box_items(dialog: HWND, id: i32, items: &[&str], selected: usize) {
+    unsafe {
+        SendDlgItemMessageW(dialog, id, CB_RESETCONTENT, WPARAM(0), LPARAM(0));
+
+        for item in items {
+            let item = to_wide_string(item);
+
+            SendDlgItemMessageW(
+                dialog,
+                id,
+                CB_ADDSTRING,
+                WPARAM(0),
+                LPARAM(item.as_ptr() as isize),
+            );
+        }
+
+        SendDlgItemMessageW(dialog, id, CB_SETCURSEL, WPARAM(selected), LPARAM(0));
+    }
+}
+
+/// Returns the index of the selected item of the combo box `id` within
+/// `dialog`, if an item is selected.
+pub fn get_combo_box_selection(dialog: HWND, id: i32) -> Option<usize> {
+    match unsafe { SendDlgItemMessageW(dialog, id, CB_GETCURSEL, WPARAM(0), LPARAM(0)).0 } {
+        selected if selected < 0 => None,
+        selected => Some(selected as usize),
+    }
+}
+
+/// Replaces the items of the list box `id` within `dialog` with `items`.
+pub fn set_list_box_items(dialog: HWND, id: i32, items: &[String]) {
+    unsafe {
+        SendDlgItemMessageW(dialog, id, LB_RESETCONTENT, WPARAM(0), LPARAM(0));
+
+        for item in items {
+            let item = to_wide_string(item);
+
+            SendDlgItemMessageW(
+                dialog,
+                id,
+                LB_ADDSTRING,
+                WPARAM(0),
+                LPARAM(item.as_ptr() as isize),
+            );
+        }
+    }
+}
+
+/// Returns the index of the selected item of the list box `id` within
+/// `dialog`, if an item is selected.
+pub fn get_list_box_selection(dialog: HWND, id: i32) -> Option<usize> {
+    match unsafe { SendDlgItemMessageW(dialog, id, LB_GETCURSEL, WPARAM(0), LPARAM(0)).0 } {
+        selected if selected < 0 => None,
+        selected => Some(selected as usize),
+    }
+}
+
+/// The icon shown at the top of a task dialog.
+#[derive(Clone, Copy)]
+pub enum TaskDialogIcon {
+    Information,
+    Error,
+}
+
+/// Shows an error task dialog, with a "Close" button, and a single additional
+/// button labelled `button_text`. `details` is shown in a collapsible section
+/// beneath `content`.
+///
+/// Returns whether or not the additional button was clicked.
+pub fn error_task_dialog(
+    title: &str,
+    instruction: &str,
+    content: &str,
+    details: &str,
+    button_text: &str,
+) -> windows::Result<bool> {
+    let clicked_button = task_dialog(
+        title,
+        TaskDialogIcon::Error,
+        instruction,
+        content,
+        Some(details),
+        &[button_text],
+    )?;
+
+    Ok(clicked_button == Some(0))
+}
+
+/// Shows a task dialog, with a "Close" button, and an additional button for
+/// each of `buttons`. If given, `details` is shown in a collapsible section
+/// beneath `content`.
+///
+/// Returns the index of the additional button that was clicked, or `None` if
+/// the dialog was closed.
+pub fn task_dialog(
+    title: &str,
+    icon: TaskDialogIcon,
+    instruction: &str,
+    content: &str,
+    details: Option<&str>,
+    buttons: &[&str],
+) -> windows::Result<Option<usize>> {
+    // Equivalent to `MAKEINTRESOURCEW(-2)` and `MAKEINTRESOURCEW(-3)`
+    const TD_ERROR_ICON: *mut u16 = 0xFFFE as *mut u16;
+    const TD_INFORMATION_ICON: *mut u16 = 0xFFFD as *mut u16;
+    const FIRST_BUTTON_ID: i32 = 100;
+
+    let title = to_wide_string(title);
+    let instruction = to_wide_string(instruction);
+    let content = to_wide_string(content);
+    let details = details.map(to_wide_string);
+    let button_texts: Vec<U16CString> = buttons.iter().map(|text| to_wide_string(text)).collect();
+
+    let mut buttons: Vec<TASKDIALOG_BUTTON> = button_texts
+        .iter()
+        .zip(FIRST_BUTTON_ID..)
+        .map(|(text, id)| TASKDIALOG_BUTTON {
+            nButtonID: id,
+            pszButtonText: PWSTR(text.as_ptr() as *mut u16),
+        })
+        .collect();
+
+    let config = TASKDIALOGCONFIG {
+        cbSize: mem::size_of::<TASKDIALOGCONFIG>() as u32,
+        hwndParent: HWND(0),
+        hInstance: HINSTANCE(0),
+        dwFlags: TDF_ALLOW_DIALOG_CANCELLATION.0,
+        dwCommonButtons: TDCBF_CLOSE_BUTTON.0,
+        pszWindowTitle: PWSTR(title.as_ptr() as *mut u16),
+        Anonymous1: TASKDIALOGCONFIG_0 {
+            pszMainIcon: PWSTR(match icon {
+                TaskDialogIcon::Information => TD_INFORMATION_ICON,
+                TaskDialogIcon::Error => TD_ERROR_ICON,
+            }),
+        },
+        pszMainInstruction: PWSTR(instruction.as_ptr() as *mut u16),
+        pszContent: PWSTR(content.as_ptr() as *mut u16),
+        cButtons: buttons.len() as u32,
+        pButtons: buttons.as_mut_ptr(),
+        nDefaultButton: FIRST_BUTTON_ID,
+        cRadioButtons: 0,
+        pRadioButtons: ptr::null_mut(),
+        nDefaultRadioButton: 0,
+        pszVerificationText: PWSTR(ptr::null_mut()),
+        pszExpandedInformation: details.as_ref().map_or(PWSTR(ptr::null_mut()), |details| {
+            PWSTR(details.as_ptr() as *mut u16)
+        }),
+        pszExpandedControlText: PWSTR(ptr::null_mut()),
+        pszCollapsedControlText: PWSTR(ptr::null_mut()),
+        Anonymous2: TASKDIALOGCONFIG_1 {
+            pszFooterIcon: PWSTR(ptr::null_mut()),
+        },
+        pszFooter: PWSTR(ptr::null_mut()),
+        pfCallback: None,
+        lpCallbackData: 0,
+        cxWidth: 0,
+    };
+
+    let mut clicked_button = 0;
+
+    unsafe {
+        TaskDialogIndirect(
+            &config,
+            &mut clicked_button,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )?;
+    }
+
+    Ok(clicked_button
+        .checked_sub(FIRST_BUTTON_ID)
+        .map(|index| index as usize)
+        .filter(|&index| index < buttons.len()))
+}
+
+/// Moves `paths` to the Recycle Bin, using [`SHFileOperationW`], without
+/// showing any confirmation or progress UI.
+///
+/// [`SHFileOperationW`]: SHFileOperationW
+pub fn move_to_recycle_bin(paths: &[PathBuf]) -> windows::Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    // `pFrom` is a list of null-terminated paths, terminated by an additional null
+    let mut from: Vec<u16> = Vec::new();
+
+    for path in paths {
+        from.extend(path.as_os_str().encode_wide());
+        from.push(0);
+    }
+
+    from.push(0);
+
+    let mut operation = SHFILEOPSTRUCTW {
+        wFunc: FO_DELETE,
+        pFrom: PWSTR(from.as_mut_ptr()),
+        fFlags: (FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_NOERRORUI | FOF_SILENT) as u16,
+        ..Default::default()
+    };
+
+    match unsafe { SHFileOperationW(&mut operation) } {
+        0 => Ok(()),
+        error => Err(HRESULT::from_win32(error as u32).into()),
+    }
+}
+
+/// Converts `string` to a null-terminated UTF-16 string, dropping any interior
+/// null characters.
+pub fn to_wide_string(string: &str) -> U16CString {
+    U16CString::from_str(string.replace('\0', "")).unwrap()
+}
+
+/// Converts a UTF-16 `buffer`, filled in by a Win32 function, to a [`String`],
+/// stopping at the first null character, if there is one. Invalid UTF-16 (e.g.
+/// unpaired surrogates) is replaced with U+FFFD.
+///
+/// [`String`]: String
+pub fn from_wide_buffer(buffer: &[u16]) -> String {
+    let length = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+
+    String::from_utf16_lossy(&buffer[..length])
+}
+
+/// Safe wrapper around [`DestroyWindow`].
+///
+/// [`DestroyWindow`]: DestroyWindow
+pub fn destroy_window(window: HWND) {
+    unsafe {
+        DestroyWindow(window);
+    }
+}
+
+/// Renames `from` to `to` with [`MoveFileExW`], unless `to` already exists, in
+/// which case `Ok(false)` is returned. Unlike [`fs::rename`], this never
+/// replaces a file that another process (possibly on another machine) has
+/// just written.
+///
+/// [`MoveFileExW`]: MoveFileExW
+/// [`fs::rename`]: std::fs::rename
+pub fn rename_without_replacing(from: &Path, to: &Path) -> windows::Result<bool> {
+    /// The error codes set when the destination already exists.
+    const ERROR_FILE_EXISTS: u32 = 80;
+    const ERROR_ALREADY_EXISTS: u32 = 183;
+
+    let from = U16CString::from_os_str(from.as_os_str()).unwrap();
+    let to = U16CString::from_os_str(to.as_os_str()).unwrap();
+
+    unsafe {
+        if MoveFileExW(
+            PWSTR(from.as_ptr() as *mut u16),
+            PWSTR(to.as_ptr() as *mut u16),
+            MOVEFILE_WRITE_THROUGH,
+        )
+        .as_bool()
+        {
+            return Ok(true);
+        }
+    }
+
+    let error = HRESULT::from_thread();
+
+    if error == HRESULT::from_win32(ERROR_FILE_EXISTS)
+        || error == HRESULT::from_win32(ERROR_ALREADY_EXISTS)
+    {
+        Ok(false)
+    } else {
+        Err(error.into())
+    }
+}
+
+/// Safe wrapper around [`FindFirstChangeNotificationW`], which starts watching
+/// `directory` (but not its subdirectories) for files being created, renamed,
+/// deleted or written to. Wait for a change with
+/// [`wait_for_directory_change`].
+///
+/// [`FindFirstChangeNotificationW`]: FindFirstChangeNotificationW
+/// [`wait_for_directory_change`]: wait_for_directory_change
+pub fn watch_directory(directory: &Path) -> windows::Result<AutoClose<HANDLE>> {
+    /// Returned by `FindFirstChangeNotificationW` on failure.
+    const INVALID_HANDLE_VALUE: isize = -1;
+
+    let directory = U16CString::from_os_str(directory.as_os_str()).unwrap();
+
+    unsafe {
+        let handle = FindFirstChangeNotificationW(
+            PWSTR(directory.as_ptr() as *mut u16),
+            false,
+            FILE_NOTIFY_CHANGE_FILE_NAME | FILE_NOTIFY_CHANGE_LAST_WRITE,
+        );
+
+        if handle.0 == INVALID_HANDLE_VALUE {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        Ok(AutoClose::new(HANDLE(handle.0), |h| {
+            FindCloseChangeNotification(FindChangeNotificationHandle(h.0));
+        }))
+    }
+}
+
+/// Blocks until something changes in the directory watched by `watch`, which
+/// was returned by [`watch_directory`].
+///
+/// [`watch_directory`]: watch_directory
+pub fn wait_for_directory_change(watch: &AutoClose<HANDLE>) -> windows::Result<()> {
+    /// Waits without a timeout.
+    const INFINITE: u32 = u32::MAX;
+
+    unsafe {
+        if WaitForSingleObject(watch.value(), INFINITE) != WAIT_OBJECT_0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        // Re-arms the notification, for the next call
+        if !FindNextChangeNotification(FindChangeNotificationHandle(watch.value().0)).as_bool() {
+            return Err(HRESULT::from_thread().into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Safe wrapper around [`ShutdownBlockReasonCreate`], which shows `reason` to
+/// the user if they shut down or log off whilst `window` is busy.
+///
+/// [`ShutdownBlockReasonCreate`]: ShutdownBlockReasonCreate
+pub fn block_shutdown(window: HWND, reason: &str) -> windows::Result<()> {
+    unsafe {
+        if ShutdownBlockReasonCreate(window, reason).as_bool() {
+            Ok(())
+        } else {
+            Err(HRESULT::from_thread().into())
+        }
+    }
+}
+
+/// Safe wrapper around [`ShutdownBlockReasonDestroy`], which undoes
+/// [`block_shutdown`].
+///
+/// [`ShutdownBlockReasonDestroy`]: ShutdownBlockReasonDestroy
+/// [`block_shutdown`]: block_shutdown
+pub fn unblock_shutdown(window: HWND) {
+    unsafe {
+        ShutdownBlockReasonDestroy(window);
+    }
+}
+
+/// Safe wrapper around [`FindWindowW`], which returns the [`HWND`] of a window
+/// with the specified class and window name, if one exists.
+///
+/// On failure, this function returns `None`.
+///
+/// [`FindWindowW`]: FindWindowW
+/// [`HWND`]: HWND
+pub fn find_window(class_name: &str, window_name: &str) -> Option<HWND> {
+    let window = unsafe { FindWindowW(class_name, window_name) };
+
+    if window.is_null() {
+        None
+    } else {
+        Some(window)
+    }
+}
+
+/// Safe wrapper around [`RegisterWindowMessageW`], which returns the ID of the
+/// message named `name`, which is unique to the message name system-wide.
+///
+/// [`RegisterWindowMessageW`]: RegisterWindowMessageW
+pub fn register_window_message(name: &str) -> windows::Result<u32> {
+    match unsafe { RegisterWindowMessageW(name) } {
+        0 => Err(HRESULT::from_thread().into()),
+        message => Ok(message),
     }
 }
 
-/// Safe wrapper around [`SendNotifyMessageA`].
+/// Safe wrapper around [`SendNotifyMessageW`].
 ///
-/// [`SendNotifyMessageA`]: SendNotifyMessageA
+/// [`SendNotifyMessageW`]: SendNotifyMessageW
 pub fn send_notify_message(
     window: HWND,
     message: u32,
     w_param: WPARAM,
     l_param: LPARAM,
 ) -> windows::Result<()> {
-    if unsafe { SendNotifyMessageA(window, message, w_param, l_param).0 != 0 } {
+    if unsafe { SendNotifyMessageW(window, message, w_param, l_param).0 != 0 } {
         Ok(())
     } else {
         Err(HRESULT::from_thread().into())
     }
 }
 
+/// Sends a [`WM_COPYDATA`] message to `window`, which is how data is passed to
+/// windows owned by other processes. `id` is an application-defined value,
+/// which identifies the kind of `data` being sent.
+///
+/// Returns whether or not the receiving window processed the message, or
+/// `None` if it didn't respond within `timeout` (or is hung).
+///
+/// [`WM_COPYDATA`]: WM_COPYDATA
+pub fn send_copy_data(window: HWND, id: usize, data: &[u8], timeout: Duration) -> Option<bool> {
+    /// The error code set when the receiving window doesn't respond in time.
+    const ERROR_TIMEOUT: u32 = 1460;
+
+    let copy_data = COPYDATASTRUCT {
+        dwData: id,
+        cbData: data.len() as u32,
+        lpData: data.as_ptr() as *mut c_void,
+    };
+
+    let mut result = 0;
+
+    let sent = unsafe {
+        SendMessageTimeoutW(
+            window,
+            WM_COPYDATA,
+            WPARAM(0),
+            LPARAM(&copy_data as *const _ as isize),
+            SMTO_ABORTIFHUNG,
+            timeout.as_millis() as u32,
+            &mut result,
+        )
+        .0 != 0
+    };
+
+    if sent {
+        Some(result != 0)
+    } else if HRESULT::from_thread() == HRESULT::from_win32(ERROR_TIMEOUT) {
+        None
+    } else {
+        // e.g. the window has been destroyed
+        Some(false)
+    }
+}
+
 /// Safe wrapper around [`AddClipboardFormatListener`], which registers a
 /// [`HWND`] for receiving [`WM_CLIPBOARDUPDATE`] messages.
 ///
@@ -261,6 +1746,82 @@ pub fn add_clipboard_listener(window: HWND) -> windows::Result<()> {
     }
 }
 
+/// Safe wrapper around [`RemoveClipboardFormatListener`], which undoes
+/// [`add_clipboard_listener`].
+///
+/// [`RemoveClipboardFormatListener`]: RemoveClipboardFormatListener
+/// [`add_clipboard_listener`]: add_clipboard_listener
+pub fn remove_clipboard_listener(window: HWND) -> windows::Result<()> {
+    unsafe {
+        match RemoveClipboardFormatListener(window).0 {
+            0 => Err(HRESULT::from_thread().into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Safe wrapper around [`RegisterHotKey`], which registers a system-wide
+/// hotkey that posts `WM_HOTKEY` messages to `window`.
+///
+/// [`RegisterHotKey`]: RegisterHotKey
+pub fn register_hot_key(
+    window: HWND,
+    id: i32,
+    modifiers: HOT_KEY_MODIFIERS,
+    key: u32,
+) -> windows::Result<()> {
+    unsafe {
+        match RegisterHotKey(window, id, modifiers, key).0 {
+            0 => Err(HRESULT::from_thread().into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Safe wrapper around [`UnregisterHotKey`].
+///
+/// [`UnregisterHotKey`]: UnregisterHotKey
+pub fn unregister_hot_key(window: HWND, id: i32) -> windows::Result<()> {
+    unsafe {
+        match UnregisterHotKey(window, id).0 {
+            0 => Err(HRESULT::from_thread().into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Finds the first edit control within `window`, using UI Automation, and
+/// returns its value, if it has one. In a browser window, this is the address
+/// bar.
+///
+/// Note that [`com_initialize`] must be called on the current thread first.
+///
+/// [`com_initialize`]: com_initialize
+pub fn get_first_edit_value(window: HWND) -> windows::Result<Option<String>> {
+    let automation: IUIAutomation = com_create_instance(CUIAutomation)?;
+
+    unsafe {
+        let root = automation.ElementFromHandle(window.0 as *mut c_void)?;
+        let elements = root.FindAll(TreeScope_Descendants, automation.CreateTrueCondition()?)?;
+
+        for i in 0..elements.get_Length()? {
+            let element = elements.GetElement(i)?;
+
+            if element.get_CurrentControlType()? != UIA_EditControlTypeId {
+                continue;
+            }
+
+            let value = element
+                .GetCurrentPatternAs::<IUIAutomationValuePattern>(UIA_ValuePatternId)
+                .and_then(|pattern| pattern.get_CurrentValue());
+
+            return Ok(value.ok().map(|value| value.to_string()));
+        }
+    }
+
+    Ok(None)
+}
+
 /// Safe wrapper around [`GetWindowThreadProcessId`], which obtains the process
 /// and thread IDs of the owner of a [`HWND`].
 ///
@@ -296,7 +1857,7 @@ pub fn open_process(process_id: u32) -> windows::Result<AutoClose<HANDLE>> {
     }
 }
 
-/// Safe wrapper around [`K32GetProcessImageFileNameA`], which gets the path to
+/// Safe wrapper around [`K32GetProcessImageFileNameW`], which gets the path to
 /// the process image (i.e. the executable file for the process).
 ///
 /// Note that the returned path is actually an NT path, not a Windows path.
@@ -304,32 +1865,29 @@ pub fn open_process(process_id: u32) -> windows::Result<AutoClose<HANDLE>> {
 /// `"C:\Windows\System32\svchost.exe"`, you'll get a result more like
 /// `"\Device\HarddiskVolume1\Windows\System32\svchost.exe"`.
 ///
-/// The image name is copied into a fixed length C-string buffer before
+/// The image name is copied into a fixed length UTF-16 buffer before
 /// conversion to a Rust [`String`]. Currently, this function can handle image
-/// names up to 256-bytes in length.
+/// names up to 1024 characters in length.
 ///
-/// [`K32GetProcessImageFileNameA`]: K32GetProcessImageFileNameA
+/// [`K32GetProcessImageFileNameW`]: K32GetProcessImageFileNameW
 /// [`String`]: String
 pub fn get_process_image_file_name(process_handle: HANDLE) -> windows::Result<String> {
-    const FILENAME_MAX_BYTES: usize = 256;
+    const FILENAME_MAX_CHARS: usize = 1024;
 
-    let mut filename_raw = vec![0; FILENAME_MAX_BYTES + 1];
+    let mut filename_raw = vec![0; FILENAME_MAX_CHARS + 1];
 
     let filename_length = unsafe {
-        K32GetProcessImageFileNameA(
+        K32GetProcessImageFileNameW(
             process_handle,
-            PSTR(filename_raw.as_mut_ptr()),
-            FILENAME_MAX_BYTES as u32,
+            PWSTR(filename_raw.as_mut_ptr()),
+            FILENAME_MAX_CHARS as u32,
         )
     };
 
     if filename_length == 0 {
         Err(HRESULT::from_thread().into())
     } else {
-        filename_raw.truncate(filename_length as usize);
-
-        Ok(String::from_utf8(filename_raw)
-            .expect("Invalid UTF-8 returned by GetProcessImageFileNameA"))
+        Ok(from_wide_buffer(&filename_raw[..filename_length as usize]))
     }
 }
 
@@ -378,6 +1936,22 @@ pub fn open_clipboard(window: Option<HWND>) -> windows::Result<Clipboard> {
     result
 }
 
+/// Safe wrapper around [`GetClipboardOwner`], which returns the window that
+/// owns the current clipboard data, if any.
+///
+/// [`GetClipboardOwner`]: GetClipboardOwner
+pub fn get_clipboard_owner() -> HWND {
+    unsafe { GetClipboardOwner() }
+}
+
+/// Safe wrapper around [`GetClipboardSequenceNumber`], which returns a number
+/// that is incremented every time that the clipboard contents change.
+///
+/// [`GetClipboardSequenceNumber`]: GetClipboardSequenceNumber
+pub fn get_clipboard_sequence_number() -> u32 {
+    unsafe { GetClipboardSequenceNumber() }
+}
+
 /// Safe wrapper around [`GetPriorityClipboardFormat`], which returns the first
 /// clipboard format in `formats` that the current data on the clipboard is
 /// either in, or can be converted to by the operating system.
@@ -442,20 +2016,202 @@ pub fn get_clipboard_dib(_clipboard: &Clipboard) -> windows::Result<*const BITMA
     unsafe { get_clipboard_data::<BITMAPINFO>(CF_DIB) }
 }
 
-/// Unsafe wrapper around [`LoadMenuA`], which loads a menu from a Windows
+/// Safe wrapper around [`GlobalSize`], which returns the size in bytes of
+/// `data`, as returned by [`get_clipboard_data`], or `0` if it can't be
+/// determined.
+///
+/// [`GlobalSize`]: GlobalSize
+/// [`get_clipboard_data`]: get_clipboard_data
+pub fn get_clipboard_data_size<T>(_clipboard: &Clipboard, data: *const T) -> usize {
+    unsafe { GlobalSize(data as isize) }
+}
+
+/// Returns a copy of the clipboard data in the registered clipboard format
+/// named `format_name` (e.g. `GIF`), if the clipboard contains data in that
+/// format.
+pub fn get_clipboard_bytes(_clipboard: &Clipboard, format_name: &str) -> Option<Vec<u8>> {
+    unsafe {
+        let format = RegisterClipboardFormatW(format_name);
+
+        if format == 0 || IsClipboardFormatAvailable(format).0 == 0 {
+            return None;
+        }
+
+        let memory = GetClipboardData(format);
+
+        if memory.is_null() {
+            return None;
+        }
+
+        let data = GlobalLock(memory.0) as *const u8;
+
+        if data.is_null() {
+            return None;
+        }
+
+        let bytes = slice::from_raw_parts(data, GlobalSize(memory.0)).to_vec();
+        GlobalUnlock(memory.0);
+
+        Some(bytes)
+    }
+}
+
+/// Empties the clipboard.
+///
+/// The clipboard must have been opened with [`open_clipboard`].
+///
+/// [`open_clipboard`]: open_clipboard
+pub fn empty_clipboard(_clipboard: &Clipboard) -> windows::Result<()> {
+    unsafe {
+        if EmptyClipboard().0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the contents of the clipboard with `text`.
+///
+/// The clipboard must have been opened with [`open_clipboard`].
+///
+/// [`open_clipboard`]: open_clipboard
+pub fn set_clipboard_text(_clipboard: &Clipboard, text: &str) -> windows::Result<()> {
+    let text = to_wide_string(text);
+    let text = text.as_slice_with_nul();
+
+    unsafe {
+        if EmptyClipboard().0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let memory = GlobalAlloc(GMEM_MOVEABLE, mem::size_of_val(text));
+
+        if memory == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        ptr::copy_nonoverlapping(text.as_ptr(), GlobalLock(memory) as *mut u16, text.len());
+        GlobalUnlock(memory);
+
+        // On success, the clipboard takes ownership of the memory
+        if SetClipboardData(CF_UNICODETEXT.0, HANDLE(memory)).is_null() {
+            GlobalFree(memory);
+
+            return Err(HRESULT::from_thread().into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the contents of the clipboard with a file drop list of `paths`
+/// (i.e. [`CF_HDROP`]), so that the files themselves can be pasted into
+/// Explorer, or attached to an email, etc.
+///
+/// The clipboard must have been opened with [`open_clipboard`].
+///
+/// [`CF_HDROP`]: CF_HDROP
+/// [`open_clipboard`]: open_clipboard
+pub fn set_clipboard_files(_clipboard: &Clipboard, paths: &[&Path]) -> windows::Result<()> {
+    // The file list is a sequence of null-terminated paths, terminated by an
+    // additional null character, which directly follows a DROPFILES header
+    let mut file_list: Vec<u16> = Vec::new();
+
+    for path in paths {
+        file_list.extend(path.as_os_str().encode_wide());
+        file_list.push(0);
+    }
+
+    file_list.push(0);
+
+    let header_size = mem::size_of::<DROPFILES>();
+    let list_size = mem::size_of_val(file_list.as_slice());
+
+    unsafe {
+        if EmptyClipboard().0 == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let memory = GlobalAlloc(GMEM_MOVEABLE, header_size + list_size);
+
+        if memory == 0 {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        let data = GlobalLock(memory) as *mut u8;
+
+        ptr::write(
+            data as *mut DROPFILES,
+            DROPFILES {
+                pFiles: header_size as u32,
+                pt: POINT { x: 0, y: 0 },
+                fNC: false.into(),
+                fWide: true.into(),
+            },
+        );
+        ptr::copy_nonoverlapping(
+            file_list.as_ptr(),
+            data.add(header_size) as *mut u16,
+            file_list.len(),
+        );
+        GlobalUnlock(memory);
+
+        // On success, the clipboard takes ownership of the memory
+        if SetClipboardData(CF_HDROP.0, HANDLE(memory)).is_null() {
+            GlobalFree(memory);
+
+            return Err(HRESULT::from_thread().into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Safe wrapper around [`DragQueryFileW`], which gets the paths of the files
+/// dropped onto a window, from the `HDROP` handle passed with a
+/// [`WM_DROPFILES`] message. The handle is released with [`DragFinish`], once
+/// the paths have been read.
+///
+/// [`DragQueryFileW`]: DragQueryFileW
+/// [`WM_DROPFILES`]: bindings::Windows::Win32::UI::WindowsAndMessaging::WM_DROPFILES
+/// [`DragFinish`]: DragFinish
+pub fn take_dropped_files(drop: HDROP) -> Vec<PathBuf> {
+    unsafe {
+        let file_count = DragQueryFileW(drop, 0xFFFFFFFF, PWSTR(ptr::null_mut()), 0);
+
+        let paths = (0..file_count)
+            .map(|i| {
+                let length = DragQueryFileW(drop, i, PWSTR(ptr::null_mut()), 0);
+                let mut path = vec![0u16; length as usize + 1];
+
+                DragQueryFileW(drop, i, PWSTR(path.as_mut_ptr()), length + 1);
+                path.truncate(length as usize);
+
+                PathBuf::from(OsString::from_wide(&path))
+            })
+            .collect();
+
+        DragFinish(drop);
+
+        paths
+    }
+}
+
+/// Unsafe wrapper around [`LoadMenuW`], which loads a menu from a Windows
 /// resource file, that has been compiled into the executable file.
 ///
 /// # Safety
 ///
-/// `menu_name` must either point to a valid C-string, or have its pointer value
-/// set to the resource ID of a `MENU` structure.
+/// `menu_name` must either point to a valid UTF-16 string, or have its pointer
+/// value set to the resource ID of a `MENU` structure.
 ///
-/// [`LoadMenuA`]: LoadMenuA
+/// [`LoadMenuW`]: LoadMenuW
 pub unsafe fn load_menu<'a>(
     instance: HINSTANCE,
-    menu_name: impl IntoParam<'a, PSTR>,
+    menu_name: impl IntoParam<'a, PWSTR>,
 ) -> AutoClose<HMENU> {
-    let menu = LoadMenuA(instance, menu_name);
+    let menu = LoadMenuW(instance, menu_name);
 
     AutoClose::new(menu, |m| {
         DestroyMenu(m);
@@ -482,23 +2238,291 @@ pub fn get_known_folder_path(folder_id: Guid) -> windows::Result<PathBuf> {
     Ok(path)
 }
 
-/// Creates a .lnk shortcut file at `link_location`, that points to `target`.
-pub fn create_link(link_location: &Path, target: &Path) -> windows::Result<()> {
+/// Creates a .lnk shortcut file at `link_location`, that runs `target` with
+/// `arguments`.
+pub fn create_link(link_location: &Path, target: &Path, arguments: &str) -> windows::Result<()> {
     let link_path = U16CString::from_os_str(link_location.as_os_str()).unwrap();
-    let target_path = CString::new(target.to_string_lossy().to_string()).unwrap();
+    let target_path = U16CString::from_os_str(target.as_os_str()).unwrap();
 
-    let shell_link: IShellLinkA = com_create_instance(ShellLink)?;
+    let shell_link: IShellLinkW = com_create_instance(ShellLink)?;
 
     unsafe {
-        shell_link.SetPath(target_path.as_pstr())?;
+        shell_link.SetPath(target_path.as_pwstr())?;
+        shell_link.SetArguments(arguments)?;
 
         let persist_file = shell_link.cast::<IPersistFile>().unwrap();
-        persist_file.Save(PWSTR(link_path.as_ptr() as *mut u16), BOOL::from(true))?;
+        persist_file.Save(link_path.as_pwstr(), BOOL::from(true))?;
     }
 
     Ok(())
 }
 
+/// Copies the contents of a [`CRYPTOAPI_BLOB`] allocated by a DPAPI function
+/// into a [`Vec`], then frees the blob's buffer with [`LocalFree`].
+///
+/// [`CRYPTOAPI_BLOB`]: CRYPTOAPI_BLOB
+/// [`Vec`]: Vec
+/// [`LocalFree`]: LocalFree
+unsafe fn take_dpapi_blob(blob: CRYPTOAPI_BLOB) -> Vec<u8> {
+    let data = std::slice::from_raw_parts(blob.pbData, blob.cbData as usize).to_vec();
+
+    LocalFree(blob.pbData as isize);
+
+    data
+}
+
+/// Safe wrapper around [`CryptProtectData`], which encrypts `data` with a key
+/// tied to the current user's Windows account.
+///
+/// [`CryptProtectData`]: CryptProtectData
+pub fn dpapi_protect(data: &[u8]) -> windows::Result<Vec<u8>> {
+    let mut data_in = CRYPTOAPI_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    };
+    let mut data_out = CRYPTOAPI_BLOB::default();
+
+    unsafe {
+        if CryptProtectData(
+            &mut data_in,
+            PWSTR(ptr::null_mut()),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut data_out,
+        )
+        .0 == 0
+        {
+            Err(HRESULT::from_thread().into())
+        } else {
+            Ok(take_dpapi_blob(data_out))
+        }
+    }
+}
+
+/// Safe wrapper around [`CryptUnprotectData`], which decrypts `data` that was
+/// previously encrypted with [`dpapi_protect`], by the same Windows user.
+///
+/// [`CryptUnprotectData`]: CryptUnprotectData
+/// [`dpapi_protect`]: dpapi_protect
+pub fn dpapi_unprotect(data: &[u8]) -> windows::Result<Vec<u8>> {
+    let mut data_in = CRYPTOAPI_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    };
+    let mut data_out = CRYPTOAPI_BLOB::default();
+
+    unsafe {
+        if CryptUnprotectData(
+            &mut data_in,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut data_out,
+        )
+        .0 == 0
+        {
+            Err(HRESULT::from_thread().into())
+        } else {
+            Ok(take_dpapi_blob(data_out))
+        }
+    }
+}
+
+/// Safe wrapper around [`RegGetValueW`], which reads a `REG_DWORD` value from
+/// `sub_key` of `key`.
+///
+/// Returns `None` if the value doesn't exist, or isn't a `REG_DWORD`.
+///
+/// [`RegGetValueW`]: RegGetValueW
+pub fn get_registry_dword(key: HKEY, sub_key: &str, value_name: &str) -> Option<u32> {
+    let sub_key = U16CString::from_str(sub_key).unwrap();
+    let value_name = U16CString::from_str(value_name).unwrap();
+
+    let mut value = 0u32;
+    let mut value_size = mem::size_of::<u32>() as u32;
+
+    let status = unsafe {
+        RegGetValueW(
+            key,
+            PWSTR(sub_key.as_ptr() as *mut u16),
+            PWSTR(value_name.as_ptr() as *mut u16),
+            RRF_RT_REG_DWORD,
+            ptr::null_mut(),
+            &mut value as *mut u32 as *mut c_void,
+            &mut value_size,
+        )
+    };
+
+    if status.0 == 0 {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Safe wrapper around [`RegGetValueW`], which reads a `REG_SZ` or
+/// `REG_EXPAND_SZ` value from `sub_key` of `key`. Environment variables in
+/// `REG_EXPAND_SZ` values are expanded.
+///
+/// Returns `None` if the value doesn't exist, or isn't a string.
+///
+/// [`RegGetValueW`]: RegGetValueW
+pub fn get_registry_string(key: HKEY, sub_key: &str, value_name: &str) -> Option<String> {
+    let sub_key = U16CString::from_str(sub_key).unwrap();
+    let value_name = U16CString::from_str(value_name).unwrap();
+
+    let get_value = |data: *mut u16, data_size: &mut u32| unsafe {
+        RegGetValueW(
+            key,
+            PWSTR(sub_key.as_ptr() as *mut u16),
+            PWSTR(value_name.as_ptr() as *mut u16),
+            RRF_RT_REG_SZ | RRF_RT_REG_EXPAND_SZ,
+            ptr::null_mut(),
+            data as *mut c_void,
+            data_size,
+        )
+    };
+
+    // Query the size of the value (in bytes, including the null terminator) first
+    let mut data_size = 0;
+
+    if get_value(ptr::null_mut(), &mut data_size).0 != 0 {
+        return None;
+    }
+
+    let mut data = vec![0u16; data_size as usize / 2 + 1];
+
+    if get_value(data.as_mut_ptr(), &mut data_size).0 != 0 {
+        return None;
+    }
+
+    data.truncate(data_size as usize / 2);
+
+    U16CString::from_vec_with_nul(data)
+        .ok()
+        .map(|value| value.to_string_lossy())
+}
+
+/// Safe wrapper around [`RegSetKeyValueW`], which writes a string value to a
+/// registry key under `HKEY_CURRENT_USER`, creating the key if it doesn't exist.
+///
+/// If `value_name` is `None`, the key's default value is written.
+///
+/// [`RegSetKeyValueW`]: RegSetKeyValueW
+pub fn set_user_registry_string(
+    sub_key: &str,
+    value_name: Option<&str>,
+    value: &str,
+) -> windows::Result<()> {
+    let sub_key = U16CString::from_str(sub_key).unwrap();
+    let value_name = value_name.map(|name| U16CString::from_str(name).unwrap());
+    let value = U16CString::from_str(value).unwrap();
+
+    let status = unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            PWSTR(sub_key.as_ptr() as *mut u16),
+            PWSTR(
+                value_name
+                    .as_ref()
+                    .map_or(ptr::null_mut(), |name| name.as_ptr() as *mut u16),
+            ),
+            REG_SZ.0,
+            value.as_ptr() as *const c_void,
+            (value.len() as u32 + 1) * 2,
+        )
+    };
+
+    if status.0 == 0 {
+        Ok(())
+    } else {
+        Err(HRESULT::from_win32(status.0 as u32).into())
+    }
+}
+
+/// Safe wrapper around [`RegDeleteTreeW`], which recursively deletes a registry
+/// key under `HKEY_CURRENT_USER`, along with all of its values and subkeys.
+///
+/// [`RegDeleteTreeW`]: RegDeleteTreeW
+pub fn delete_user_registry_tree(sub_key: &str) -> windows::Result<()> {
+    let sub_key = U16CString::from_str(sub_key).unwrap();
+
+    let status = unsafe { RegDeleteTreeW(HKEY_CURRENT_USER, PWSTR(sub_key.as_ptr() as *mut u16)) };
+
+    if status.0 == 0 {
+        Ok(())
+    } else {
+        Err(HRESULT::from_win32(status.0 as u32).into())
+    }
+}
+
+/// Safe wrapper around [`RegDeleteKeyValueW`], which deletes a value from a
+/// registry key under `HKEY_CURRENT_USER`.
+///
+/// [`RegDeleteKeyValueW`]: RegDeleteKeyValueW
+pub fn delete_user_registry_value(sub_key: &str, value_name: &str) -> windows::Result<()> {
+    let sub_key = U16CString::from_str(sub_key).unwrap();
+    let value_name = U16CString::from_str(value_name).unwrap();
+
+    let status =
+        unsafe { RegDeleteKeyValueW(HKEY_CURRENT_USER, sub_key.as_pwstr(), value_name.as_pwstr()) };
+
+    if status.0 == 0 {
+        Ok(())
+    } else {
+        Err(HRESULT::from_win32(status.0 as u32).into())
+    }
+}
+
+/// Opens an Explorer window to the folder containing `path`, with `path`
+/// pre-selected, via [`SHOpenFolderAndSelectItems`].
+///
+/// [`SHOpenFolderAndSelectItems`]: SHOpenFolderAndSelectItems
+pub fn open_folder_and_select(path: &Path) -> windows::Result<()> {
+    let path = U16CString::from_os_str(path.as_os_str()).unwrap();
+
+    unsafe {
+        let item = ILCreateFromPathW(PWSTR(path.as_ptr() as *mut u16));
+
+        if item.is_null() {
+            return Err(HRESULT::from_thread().into());
+        }
+
+        // Passing a fully qualified item, without any children, selects the item within its
+        // parent folder
+        let result = SHOpenFolderAndSelectItems(item, 0, ptr::null_mut(), 0);
+
+        ILFree(item);
+
+        result
+    }
+}
+
+/// Returns the major version, minor version, and build number of Windows.
+///
+/// The returned version is only accurate up to the newest version of Windows
+/// declared as supported in the application manifest.
+pub fn get_windows_version() -> (u32, u32, u32) {
+    let mut version_info = OSVERSIONINFOW {
+        dwOSVersionInfoSize: mem::size_of::<OSVERSIONINFOW>() as u32,
+        ..Default::default()
+    };
+
+    unsafe {
+        GetVersionExW(&mut version_info);
+    }
+
+    (
+        version_info.dwMajorVersion,
+        version_info.dwMinorVersion,
+        version_info.dwBuildNumber,
+    )
+}
+
 /// Safe wrapper around [`PostQuitMessage`], which posts a [`WM_QUIT`] message
 /// to the current thread's message queue.
 ///
@@ -524,9 +2548,9 @@ pub fn message_loop(window: HWND) {
     let mut message = MSG::default();
 
     unsafe {
-        while GetMessageA(&mut message, window, 0, 0).0 != 0 {
+        while GetMessageW(&mut message, window, 0, 0).0 != 0 {
             TranslateMessage(&message);
-            DispatchMessageA(&message);
+            DispatchMessageW(&message);
         }
     }
 }
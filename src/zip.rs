@@ -0,0 +1,196 @@
+//! Reading and writing of uncompressed zip files.
+//!
+//! Only the subset of the format that the program writes itself is supported:
+//! stored (uncompressed) entries, without data descriptors, encryption, or
+//! zip64 extensions.
+
+use chrono::{DateTime, Datelike, Local, Timelike};
+use crc32fast::Hasher;
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+/// The signature of each entry's local header.
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+
+/// The signature of each entry's central directory header.
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+
+/// The signature of the end of central directory record.
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+
+/// Errors that can occur whilst reading or writing a zip file.
+#[derive(Error, Debug)]
+pub enum ZipError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("The files are too large to zip")]
+    TooLarge,
+    #[error("The zip file is corrupt, or wasn't created by this program")]
+    Invalid,
+}
+
+/// A file within a zip file.
+pub struct Entry {
+    /// The file's path within the zip file, with `/` separators.
+    pub name: String,
+
+    /// The file's contents.
+    pub data: Vec<u8>,
+
+    /// When the file was last modified.
+    pub modified: DateTime<Local>,
+}
+
+/// Writes a zip file to `path`, containing each of `entries`, without
+/// compression.
+pub fn write(path: &Path, entries: &[Entry]) -> Result<(), ZipError> {
+    /// Bit 11 of the general purpose flags marks file names as UTF-8.
+    const UTF8_NAMES: u16 = 1 << 11;
+
+    /// The zip specification version needed to extract stored files.
+    const VERSION: u16 = 20;
+
+    let mut zip = Vec::new();
+    let mut central_directory = Vec::new();
+
+    for entry in entries {
+        let offset: u32 = zip.len().try_into().map_err(|_| ZipError::TooLarge)?;
+        let size: u32 = entry
+            .data
+            .len()
+            .try_into()
+            .map_err(|_| ZipError::TooLarge)?;
+        let (time, date) = dos_date_time(&entry.modified);
+
+        let mut header = Vec::new();
+        put_u16(&mut header, VERSION);
+        put_u16(&mut header, UTF8_NAMES);
+        put_u16(&mut header, 0); // Stored
+        put_u16(&mut header, time);
+        put_u16(&mut header, date);
+        put_u32(&mut header, crc32(&entry.data));
+        put_u32(&mut header, size);
+        put_u32(&mut header, size);
+        put_u16(&mut header, entry.name.len() as u16);
+        put_u16(&mut header, 0); // Extra field length
+
+        put_u32(&mut zip, LOCAL_HEADER_SIGNATURE);
+        zip.extend_from_slice(&header);
+        zip.extend_from_slice(entry.name.as_bytes());
+        zip.extend_from_slice(&entry.data);
+
+        put_u32(&mut central_directory, CENTRAL_DIRECTORY_SIGNATURE);
+        put_u16(&mut central_directory, VERSION); // Version made by
+        central_directory.extend_from_slice(&header);
+        put_u16(&mut central_directory, 0); // Comment length
+        put_u16(&mut central_directory, 0); // Disk number
+        put_u16(&mut central_directory, 0); // Internal attributes
+        put_u32(&mut central_directory, 0); // External attributes
+        put_u32(&mut central_directory, offset);
+        central_directory.extend_from_slice(entry.name.as_bytes());
+    }
+
+    let offset: u32 = zip.len().try_into().map_err(|_| ZipError::TooLarge)?;
+    let count: u16 = entries.len().try_into().map_err(|_| ZipError::TooLarge)?;
+
+    zip.extend_from_slice(&central_directory);
+
+    put_u32(&mut zip, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+    put_u16(&mut zip, 0); // Disk number
+    put_u16(&mut zip, 0); // Disk with the central directory
+    put_u16(&mut zip, count);
+    put_u16(&mut zip, count);
+    put_u32(&mut zip, central_directory.len() as u32);
+    put_u32(&mut zip, offset);
+    put_u16(&mut zip, 0); // Comment length
+
+    fs::write(path, zip)?;
+
+    Ok(())
+}
+
+/// Reads the name and contents of each entry in the zip file at `path`, by
+/// walking its local headers, checking each entry's CRC.
+pub fn read(path: &Path) -> Result<Vec<(String, Vec<u8>)>, ZipError> {
+    let zip = fs::read(path)?;
+    let mut entries = Vec::new();
+    let mut offset = 0;
+
+    while get_u32(&zip, offset)? == LOCAL_HEADER_SIGNATURE {
+        let method = get_u16(&zip, offset + 8)?;
+        let crc = get_u32(&zip, offset + 14)?;
+        let compressed_size = get_u32(&zip, offset + 18)? as usize;
+        let size = get_u32(&zip, offset + 22)? as usize;
+        let name_length = get_u16(&zip, offset + 26)? as usize;
+        let extra_length = get_u16(&zip, offset + 28)? as usize;
+
+        if method != 0 || compressed_size != size {
+            return Err(ZipError::Invalid);
+        }
+
+        let name_start = offset + 30;
+        let data_start = name_start + name_length + extra_length;
+
+        let name = zip
+            .get(name_start..name_start + name_length)
+            .ok_or(ZipError::Invalid)?;
+        let data = zip
+            .get(data_start..data_start + size)
+            .ok_or(ZipError::Invalid)?;
+
+        if crc32(data) != crc {
+            return Err(ZipError::Invalid);
+        }
+
+        entries.push((String::from_utf8_lossy(name).into_owned(), data.to_vec()));
+
+        offset = data_start + size;
+    }
+
+    Ok(entries)
+}
+
+/// Returns the CRC-32 checksum of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Hasher::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+/// Converts `time` into the MS-DOS time and date format used by zip files.
+fn dos_date_time(time: &DateTime<Local>) -> (u16, u16) {
+    // MS-DOS dates start in 1980
+    let year = (time.year().max(1980) - 1980) as u16;
+
+    (
+        ((time.hour() << 11) | (time.minute() << 5) | (time.second() / 2)) as u16,
+        (year << 9) | ((time.month() << 5) | time.day()) as u16,
+    )
+}
+
+/// Appends `value` to `buffer`, in little-endian byte order.
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Appends `value` to `buffer`, in little-endian byte order.
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Reads a little-endian value from `buffer` at `offset`.
+fn get_u16(buffer: &[u8], offset: usize) -> Result<u16, ZipError> {
+    let bytes = buffer.get(offset..offset + 2).ok_or(ZipError::Invalid)?;
+
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a little-endian value from `buffer` at `offset`.
+fn get_u32(buffer: &[u8], offset: usize) -> Result<u32, ZipError> {
+    let bytes = buffer.get(offset..offset + 4).ok_or(ZipError::Invalid)?;
+
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
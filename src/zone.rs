@@ -0,0 +1,43 @@
+//! Windows "Mark of the Web" (zone identifier) handling for saved files.
+//!
+//! Windows records the security zone that a file originated from in a
+//! `Zone.Identifier` alternate data stream. Some corporate environments gate
+//! files by zone (e.g. blocking files marked as coming from the internet), so
+//! the stream can optionally be added to, or stripped from, saved screenshots.
+
+use crate::settings::{Settings, ZoneMode};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Returns the path to the `Zone.Identifier` alternate data stream of `path`.
+fn zone_identifier_stream(path: &Path) -> PathBuf {
+    let mut stream = OsString::from(path.as_os_str());
+    stream.push(":Zone.Identifier");
+
+    stream.into()
+}
+
+/// Applies the configured zone identifier handling to a newly saved file.
+pub fn apply(path: &Path) -> io::Result<()> {
+    let mut mode = ZoneMode::Keep;
+    let mut zone_id = 0;
+
+    Settings::read(|s| {
+        mode = s.zone.mode;
+        zone_id = s.zone.zone_id;
+    });
+
+    match mode {
+        ZoneMode::Keep => Ok(()),
+        ZoneMode::Set => fs::write(
+            zone_identifier_stream(path),
+            format!("[ZoneTransfer]\r\nZoneId={}\r\n", zone_id),
+        ),
+        ZoneMode::Strip => match fs::remove_file(zone_identifier_stream(path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}